@@ -36,8 +36,8 @@
           openssl
           glib
           gtk3
-          libsoup
-          webkitgtk
+          libsoup_3
+          webkitgtk_4_1
           librsvg
           mesa
           libglvnd
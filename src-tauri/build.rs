@@ -1,3 +1,36 @@
+use std::fs;
+
+/// Dependencies whose versions are reported by `get_engine_info`.
+const REPORTED_CRATES: &[&str] = &[
+    "cpal",
+    "hound",
+    "nnnoiseless",
+    "samplerate",
+    "voice_activity_detector",
+];
+
 fn main() {
+    export_dependency_versions();
     tauri_build::build()
 }
+
+/// Exposes the locked version of each of `REPORTED_CRATES` to the app as a
+/// `RECORDR_VERSION_<CRATE>` environment variable at compile time.
+fn export_dependency_versions() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let mut name = None;
+    for line in lock.lines() {
+        if let Some(value) = line.strip_prefix("name = ") {
+            name = Some(value.trim_matches('"'));
+        } else if let Some(version) = line.strip_prefix("version = ") {
+            if let Some(name) = name.take().filter(|name| REPORTED_CRATES.contains(name)) {
+                println!(
+                    "cargo:rustc-env=RECORDR_VERSION_{}={}",
+                    name.to_uppercase(),
+                    version.trim_matches('"')
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::audio::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use crate::errors::RecordrError;
+use crate::file_utils::app_data_file_path;
+use crate::jobs::run_blocking;
+use crate::storage;
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Sessions kept in the store; the oldest are dropped beyond this.
+const MAX_SESSIONS: usize = 10_000;
+
+/// Serializes the read-modify-write of the store.
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Figures of one auto-record session. Only counts and timings are kept,
+/// never prompt text or audio, and nothing leaves the machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub session_id: String,
+    /// Directory of the project the session recorded into.
+    pub project_directory: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    /// Time the session ran for, pauses included.
+    pub duration_ms: u64,
+    /// Time spent paused, on rest breaks or suspended.
+    pub paused_ms: u64,
+    /// Length of the takes kept.
+    pub take_ms: u64,
+    /// Takes kept, retakes of a sentence included.
+    pub takes: usize,
+    /// Distinct sentences that got a take.
+    pub sentences_recorded: usize,
+    /// Takes deleted as too short or without voice.
+    pub rejected_takes: usize,
+    pub take_timeouts: usize,
+    pub skipped: usize,
+    pub duplicate_takes: usize,
+    pub device_errors: usize,
+    pub low_disk_space: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AnalyticsStore {
+    sessions: Vec<SessionRecord>,
+}
+
+/// Totals over a set of sessions.
+#[derive(Debug, Default, Serialize)]
+pub struct SessionTotals {
+    pub sessions: usize,
+    pub duration_ms: u64,
+    /// Time spent recording, pauses left out.
+    pub active_ms: u64,
+    pub take_ms: u64,
+    pub takes: usize,
+    pub sentences_recorded: usize,
+    pub rejected_takes: usize,
+    /// Share of kept takes that re-recorded a sentence, from 0.0 to 1.0.
+    pub retake_rate: f32,
+    /// Share of all takes that were rejected, from 0.0 to 1.0.
+    pub rejection_rate: f32,
+    pub average_session_ms: u64,
+    /// Sentences recorded per hour of active recording.
+    pub sentences_per_hour: f32,
+    /// Device losses, low disk space pauses and take timeouts.
+    pub errors: usize,
+}
+
+impl SessionTotals {
+    fn from_sessions<'a>(sessions: impl IntoIterator<Item = &'a SessionRecord>) -> Self {
+        let mut totals = Self::default();
+        for session in sessions {
+            totals.sessions += 1;
+            totals.duration_ms += session.duration_ms;
+            totals.active_ms += session.duration_ms.saturating_sub(session.paused_ms);
+            totals.take_ms += session.take_ms;
+            totals.takes += session.takes;
+            totals.sentences_recorded += session.sentences_recorded;
+            totals.rejected_takes += session.rejected_takes;
+            totals.errors += session.device_errors + session.low_disk_space + session.take_timeouts;
+        }
+        if totals.takes > 0 {
+            totals.retake_rate =
+                (totals.takes - totals.sentences_recorded) as f32 / totals.takes as f32;
+        }
+        let attempts = totals.takes + totals.rejected_takes;
+        if attempts > 0 {
+            totals.rejection_rate = totals.rejected_takes as f32 / attempts as f32;
+        }
+        if totals.sessions > 0 {
+            totals.average_session_ms = totals.duration_ms / totals.sessions as u64;
+        }
+        if totals.active_ms > 0 {
+            totals.sentences_per_hour =
+                totals.sentences_recorded as f32 * 3_600_000.0 / totals.active_ms as f32;
+        }
+        totals
+    }
+}
+
+/// Recording efficiency over the sessions asked for.
+#[derive(Debug, Serialize)]
+pub struct SessionAnalytics {
+    pub totals: SessionTotals,
+    /// Totals of each project, keyed by project directory.
+    pub projects: BTreeMap<String, SessionTotals>,
+    /// The sessions themselves, most recent first.
+    pub sessions: Vec<SessionRecord>,
+}
+
+fn get_store_path() -> PathBuf {
+    app_data_file_path("analytics.json")
+}
+
+fn load_store() -> AnalyticsStore {
+    let path = get_store_path();
+    [path.clone(), storage::previous_path(&path)]
+        .iter()
+        .find_map(|path| {
+            let contents = fs::read(path).ok()?;
+            serde_json::from_slice(&contents)
+                .map_err(|e| warn!("Invalid session analytics in {:?}: {}", path, e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn save_store(store: &AnalyticsStore) -> Result<(), RecordrError> {
+    let json = serde_json::to_string(store)?;
+    storage::write_checked(&get_store_path(), json.as_bytes(), |written| {
+        serde_json::from_slice::<AnalyticsStore>(written)?;
+        Ok(())
+    })
+}
+
+fn append_session(record: SessionRecord) {
+    let _guard = STORE_LOCK.lock().unwrap();
+    let mut store = load_store();
+    store.sessions.push(record);
+    let excess = store.sessions.len().saturating_sub(MAX_SESSIONS);
+    store.sessions.drain(..excess);
+    if let Err(e) = save_store(&store) {
+        warn!("Failed to save session analytics: {}", e);
+    }
+}
+
+/// Running counts of a session, turned into its record once it completes.
+struct SessionTally {
+    session_id: Option<String>,
+    started_at: DateTime<Utc>,
+    paused_since: Option<DateTime<Utc>>,
+    paused_ms: u64,
+    take_ms: u64,
+    takes: usize,
+    sentences: HashSet<usize>,
+    rejected_takes: usize,
+    take_timeouts: usize,
+    skipped: usize,
+    duplicate_takes: usize,
+    device_errors: usize,
+    low_disk_space: usize,
+}
+
+impl SessionTally {
+    fn new() -> Self {
+        Self {
+            session_id: None,
+            started_at: Utc::now(),
+            paused_since: None,
+            paused_ms: 0,
+            take_ms: 0,
+            takes: 0,
+            sentences: HashSet::new(),
+            rejected_takes: 0,
+            take_timeouts: 0,
+            skipped: 0,
+            duplicate_takes: 0,
+            device_errors: 0,
+            low_disk_space: 0,
+        }
+    }
+
+    fn pause(&mut self, at: DateTime<Utc>) {
+        self.paused_since.get_or_insert(at);
+    }
+
+    fn resume(&mut self, at: DateTime<Utc>) {
+        if let Some(since) = self.paused_since.take() {
+            self.paused_ms += (at - since).num_milliseconds().max(0) as u64;
+        }
+    }
+
+    fn count(&mut self, event: &AutoRecordEvent) {
+        self.session_id
+            .get_or_insert_with(|| event.session_id.clone());
+        match event.kind {
+            AutoRecordEventKind::SentenceFinished => {
+                self.takes += 1;
+                self.take_ms += event.duration_ms.unwrap_or(0);
+                self.sentences.extend(event.sentence_id);
+            }
+            AutoRecordEventKind::TakeRejected | AutoRecordEventKind::EmptyTake => {
+                self.rejected_takes += 1
+            }
+            AutoRecordEventKind::TakeTimeout => self.take_timeouts += 1,
+            AutoRecordEventKind::SentenceSkipped => self.skipped += 1,
+            AutoRecordEventKind::DuplicateSpeech => self.duplicate_takes += 1,
+            AutoRecordEventKind::DeviceLost => {
+                self.device_errors += 1;
+                self.pause(event.timestamp);
+            }
+            AutoRecordEventKind::LowDiskSpace => {
+                self.low_disk_space += 1;
+                self.pause(event.timestamp);
+            }
+            AutoRecordEventKind::Paused
+            | AutoRecordEventKind::RestBreak
+            | AutoRecordEventKind::Suspended => self.pause(event.timestamp),
+            AutoRecordEventKind::Resumed => self.resume(event.timestamp),
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self, project_directory: &str, ended_at: DateTime<Utc>) -> SessionRecord {
+        self.resume(ended_at);
+        SessionRecord {
+            session_id: self.session_id.clone().unwrap_or_default(),
+            project_directory: project_directory.to_string(),
+            started_at: self.started_at,
+            ended_at,
+            duration_ms: (ended_at - self.started_at).num_milliseconds().max(0) as u64,
+            paused_ms: self.paused_ms,
+            take_ms: self.take_ms,
+            takes: self.takes,
+            sentences_recorded: self.sentences.len(),
+            rejected_takes: self.rejected_takes,
+            take_timeouts: self.take_timeouts,
+            skipped: self.skipped,
+            duplicate_takes: self.duplicate_takes,
+            device_errors: self.device_errors,
+            low_disk_space: self.low_disk_space,
+        }
+    }
+}
+
+/**
+ * Passes a session's events on to `events` while counting them, and adds
+ * the session to the local analytics store when it completes. Used only
+ * when the user opted in with the `session_analytics` app setting.
+ */
+pub struct SessionAnalyticsRecorder {
+    events: Arc<dyn RecorderEvents>,
+    project_directory: String,
+    tally: Mutex<SessionTally>,
+}
+
+impl SessionAnalyticsRecorder {
+    /// Wraps `events` to record the session when `enabled`, and returns
+    /// them unchanged otherwise.
+    pub fn wrap(
+        events: Arc<dyn RecorderEvents>,
+        project_directory: &str,
+        enabled: bool,
+    ) -> Arc<dyn RecorderEvents> {
+        if !enabled {
+            return events;
+        }
+        Arc::new(Self {
+            events,
+            project_directory: project_directory.to_string(),
+            tally: Mutex::new(SessionTally::new()),
+        })
+    }
+}
+
+impl RecorderEvents for SessionAnalyticsRecorder {
+    fn send(&self, event: AutoRecordEvent) {
+        let record = {
+            let mut tally = self.tally.lock().unwrap();
+            tally.count(&event);
+            matches!(event.kind, AutoRecordEventKind::Complete)
+                .then(|| tally.finish(&self.project_directory, event.timestamp))
+        };
+        self.events.send(event);
+        if let Some(record) = record {
+            debug!("Recording analytics of session {}", record.session_id);
+            std::thread::spawn(move || append_session(record));
+        }
+    }
+}
+
+/// Summarizes the recorded sessions, optionally only those of the project
+/// in `project_directory` or those started at or after `since`.
+pub fn summarize_sessions(
+    project_directory: Option<&str>,
+    since: Option<DateTime<Utc>>,
+) -> SessionAnalytics {
+    let store = {
+        let _guard = STORE_LOCK.lock().unwrap();
+        load_store()
+    };
+    let mut sessions: Vec<SessionRecord> = store
+        .sessions
+        .into_iter()
+        .filter(|s| project_directory.is_none_or(|dir| s.project_directory == dir))
+        .filter(|s| since.is_none_or(|since| s.started_at >= since))
+        .collect();
+    sessions.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+
+    let mut by_project: BTreeMap<&str, Vec<&SessionRecord>> = BTreeMap::new();
+    for session in &sessions {
+        by_project
+            .entry(&session.project_directory)
+            .or_default()
+            .push(session);
+    }
+    let projects = by_project
+        .into_iter()
+        .map(|(dir, sessions)| (dir.to_string(), SessionTotals::from_sessions(sessions)))
+        .collect();
+    SessionAnalytics {
+        totals: SessionTotals::from_sessions(&sessions),
+        projects,
+        sessions,
+    }
+}
+
+/// Deletes every recorded session.
+pub fn clear_sessions() -> Result<(), RecordrError> {
+    let _guard = STORE_LOCK.lock().unwrap();
+    save_store(&AnalyticsStore::default())
+}
+
+/// Returns recording efficiency over the locally recorded sessions,
+/// optionally only for one project directory or since a point in time.
+/// Sessions are only recorded with the `session_analytics` setting on.
+#[tauri::command]
+pub async fn get_session_analytics(
+    project_directory: Option<String>,
+    since: Option<DateTime<Utc>>,
+) -> Result<SessionAnalytics, RecordrError> {
+    run_blocking(move || Ok(summarize_sessions(project_directory.as_deref(), since))).await
+}
+
+/// Deletes the locally recorded session analytics.
+#[tauri::command]
+pub async fn clear_session_analytics() -> Result<(), RecordrError> {
+    run_blocking(clear_sessions).await
+}
@@ -0,0 +1,121 @@
+use crate::audio::take_file_stem;
+use crate::errors::RecordrError;
+use crate::models::{Project, Sentence, SentenceStatus};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory within the project directory holding the approved takes.
+const APPROVED_DIRECTORY: &str = "approved";
+
+/// Name of a sentence's entry in the approved tree. It depends only on the
+/// sentence, so it stays the same when another take is approved.
+fn entry_name(sentence: &Sentence, take: &Path) -> String {
+    let extension = take
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "wav".to_string());
+    format!(
+        "{:04}_{}.{}",
+        sentence.id,
+        take_file_stem(sentence),
+        extension
+    )
+}
+
+/// Whether the entry at `link` already points at `target`. Symlinks are
+/// compared by target; hard links share their metadata with the file.
+fn is_current(link: &Path, target: &Path) -> bool {
+    let Ok(link_metadata) = fs::symlink_metadata(link) else {
+        return false;
+    };
+    if link_metadata.file_type().is_symlink() {
+        return fs::read_link(link).is_ok_and(|current| current == target);
+    }
+    let Ok(target_metadata) = fs::metadata(target) else {
+        return false;
+    };
+    link_metadata.len() == target_metadata.len()
+        && link_metadata.modified().ok() == target_metadata.modified().ok()
+}
+
+/// Links `link` to `target`: a symlink where that needs no privileges,
+/// otherwise a hard link.
+fn link(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::hard_link(target, link)
+    }
+}
+
+/// Removes the project's `approved/` directory, if it has one. Hard links
+/// in it would keep the plain contents of takes that are encrypted in
+/// place.
+pub fn remove_approved_tree(project_directory: &Path) -> Result<(), RecordrError> {
+    let directory = project_directory.join(APPROVED_DIRECTORY);
+    match fs::remove_dir_all(&directory) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(RecordrError::from(e).with_context(directory.display().to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Brings the project's `approved/` directory up to date: one link per
+/// approved sentence to its take, named after the sentence, with links of
+/// sentences no longer approved removed. Entries already pointing at the
+/// right take are left alone, so tools watching the directory only see
+/// what changed. Encrypted projects get no tree, as their takes can only be
+/// read through the app.
+pub fn refresh_approved_tree(project: &Project) -> Result<(), RecordrError> {
+    if !project.metadata.approved_links || project.metadata.encrypted {
+        return Ok(());
+    }
+    let directory = Path::new(&project.metadata.directory).join(APPROVED_DIRECTORY);
+    fs::create_dir_all(&directory)
+        .map_err(|e| RecordrError::from(e).with_context(directory.display().to_string()))?;
+
+    let wanted: HashMap<String, PathBuf> = project
+        .sentences
+        .iter()
+        .filter(|s| s.status == SentenceStatus::Approved && s.recorded)
+        .filter_map(|s| {
+            let take = PathBuf::from(s.audio_file_path.as_ref()?);
+            Some((entry_name(s, &take), take))
+        })
+        .filter(|(_, take)| take.exists())
+        .collect();
+
+    let entries = fs::read_dir(&directory)
+        .map_err(|e| RecordrError::from(e).with_context(directory.display().to_string()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        if wanted
+            .get(&name)
+            .is_some_and(|take| is_current(&path, take))
+        {
+            continue;
+        }
+        debug!("Removing {} from the approved tree", name);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+
+    for (name, take) in &wanted {
+        let path = directory.join(name);
+        if fs::symlink_metadata(&path).is_ok() {
+            continue;
+        }
+        link(take, &path)
+            .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use crate::audio::{play_reference, stop_reference};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::write_project;
+use crate::jobs::run_blocking;
+use crate::models::Project;
+use crate::storage;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of a project that attached assets are copied into.
+const ASSETS_DIR: &str = "assets";
+/// Largest asset that can be attached, so a wrong file can't bloat the
+/// project or be pulled whole into the webview.
+const MAX_ASSET_SIZE: u64 = 50 * 1024 * 1024;
+
+fn assets_dir(project: &Project) -> PathBuf {
+    Path::new(&project.metadata.directory).join(ASSETS_DIR)
+}
+
+/// Path of an attached asset. Assets are referenced by file name only, so a
+/// name that reaches outside the assets directory is refused.
+fn asset_path(project: &Project, asset: &str) -> Result<PathBuf, RecordrError> {
+    let path = Path::new(asset);
+    if path.file_name() != Some(path.as_os_str()) {
+        return Err(RecordrError::invalid_input("Invalid asset name").with_context(asset));
+    }
+    Ok(assets_dir(project).join(path))
+}
+
+/// File name in the assets directory for a copy of `source`, numbered if an
+/// asset of the same name is already attached.
+fn unique_asset_name(directory: &Path, source: &Path) -> Result<String, RecordrError> {
+    let stem = source.file_stem().and_then(OsStr::to_str).ok_or_else(|| {
+        RecordrError::invalid_input("Invalid asset file name")
+            .with_context(source.display().to_string())
+    })?;
+    let extension = source
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(|extension| format!(".{}", extension))
+        .unwrap_or_default();
+    let mut name = format!("{}{}", stem, extension);
+    let mut copy = 1;
+    while directory.join(&name).exists() {
+        copy += 1;
+        name = format!("{}_{}{}", stem, copy, extension);
+    }
+    Ok(name)
+}
+
+/// Copies a file, such as an image of the scene or a screenshot of its
+/// context, into the project's assets and attaches it to a sentence. The
+/// project is saved and returned.
+#[tauri::command]
+pub async fn attach_sentence_asset(
+    mut project: Project,
+    sentence_id: usize,
+    file_path: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let sentence = project
+            .sentences
+            .iter_mut()
+            .find(|s| s.id == sentence_id)
+            .ok_or_else(|| {
+                RecordrError::not_found(format!("Sentence {} not found", sentence_id))
+            })?;
+        let source = Path::new(&file_path);
+        let size = fs::metadata(source)
+            .map_err(|e| RecordrError::from(e).with_context(file_path.as_str()))?
+            .len();
+        if size > MAX_ASSET_SIZE {
+            return Err(RecordrError::new(
+                ErrorKind::FileTooLarge,
+                format!("Assets are limited to {} bytes", MAX_ASSET_SIZE),
+            )
+            .with_context(file_path));
+        }
+
+        let directory = Path::new(&project.metadata.directory).join(ASSETS_DIR);
+        fs::create_dir_all(&directory)?;
+        let name = unique_asset_name(&directory, source)?;
+        // Assets of encrypted projects are encrypted like their takes.
+        let data = fs::read(source).map_err(|e| RecordrError::from(e).with_context(file_path))?;
+        storage::write(&directory.join(&name), &data)?;
+        sentence.assets.push(name);
+
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/// Detaches an asset from a sentence and saves the project. The file is
+/// deleted once no sentence refers to it.
+#[tauri::command]
+pub async fn remove_sentence_asset(
+    mut project: Project,
+    sentence_id: usize,
+    asset: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let path = asset_path(&project, &asset)?;
+        let sentence = project
+            .sentences
+            .iter_mut()
+            .find(|s| s.id == sentence_id)
+            .ok_or_else(|| {
+                RecordrError::not_found(format!("Sentence {} not found", sentence_id))
+            })?;
+        sentence.assets.retain(|attached| *attached != asset);
+
+        write_project(&project)?;
+        let in_use = project.sentences.iter().any(|s| s.assets.contains(&asset));
+        if !in_use && path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+        }
+        Ok(project)
+    })
+    .await
+}
+
+/// Reads an attached asset for display in the frontend, decrypting it if
+/// the project is encrypted.
+#[tauri::command]
+pub async fn load_sentence_asset(project: Project, asset: String) -> Result<Vec<u8>, RecordrError> {
+    run_blocking(move || storage::read(&asset_path(&project, &asset)?)).await
+}
+
+/// Plays an attached WAV asset, such as a reference read of the line, on
+/// the default output device. A clip recorded at another sample rate than
+/// the device's is resampled as it plays. Any clip already playing is
+/// stopped.
+#[tauri::command]
+pub async fn play_sentence_asset(project: Project, asset: String) -> Result<(), RecordrError> {
+    run_blocking(move || Ok(play_reference(&asset_path(&project, &asset)?)?)).await
+}
+
+/// Stops the asset being played by `play_sentence_asset`.
+#[tauri::command]
+pub fn stop_sentence_asset() {
+    stop_reference();
+}
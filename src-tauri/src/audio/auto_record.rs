@@ -1,29 +1,348 @@
-use super::config::{AudioConfig, RecordingState};
-use crate::models::Sentence;
-use std::sync::{Arc, Mutex};
+use super::board_tape::BoardTape;
+use super::config::{AudioChunkWithVAD, RecordingState};
+use super::events::SessionProgress;
+use super::fingerprint::SpeechFingerprint;
+use super::order::SentenceOrder;
+use super::raw_take::RawTap;
+use super::session_log::{SessionLog, VadSummary};
+use super::silence::SilenceGate;
+use super::source::AudioSource;
+use super::stream::VAD_VOICE_PROBABILITY;
+use crate::jobs::CancellationToken;
+use crate::models::{
+    ChannelMode, CueSettings, DuplicateSpeechSettings, FilterSettings, NoiseReductionSettings,
+    OutputFormat, PausePolicy, ProjectLayout, RecordingOrder, Sentence, SentenceStats,
+    TakeTimeoutAction, TransientSettings, VadPreprocessingSettings,
+};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// What to do with a take when auto-advance is off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TakeDecision {
+    Confirm,
+    Retry,
+}
+
+/// Request to abandon the take being recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TakeInterrupt {
+    /// Move on to the next sentence, marking this one skipped.
+    Skip,
+    /// Record the sentence again from the start.
+    Retry,
+}
+
+/// Separates the take number from the stem in the name of a numbered take.
+/// Stems never contain it, so a numbered take can't also be read as the
+/// unnumbered take of another sentence.
+const TAKE_SEPARATOR: &str = "~take";
+
+/// Separator of takes numbered by earlier versions, still recognised when
+/// reading. A stem can end in it, so only the new one is written.
+const LEGACY_TAKE_SEPARATOR: &str = "_take";
+
+/// Name shared by the WAV files of every take of `sentence`, before any
+/// take number and the extension.
+pub fn take_file_stem(sentence: &Sentence) -> String {
+    sentence
+        .file_stem
+        .clone()
+        .unwrap_or_else(|| sentence.text.trim().replace(" ", "_"))
+        .replace('~', "-")
+}
+
+/// Name of the WAV file of take `take` of the sentence with `stem`, or of
+/// its unnumbered take.
+pub fn take_wav_name(stem: &str, take: Option<u32>) -> String {
+    match take {
+        Some(take) => format!("{}{}{}.wav", stem, TAKE_SEPARATOR, take),
+        None => format!("{}.wav", stem),
+    }
+}
+
+/// Splits a take's file name, without the extension, into its stem and
+/// take number, if it is numbered.
+pub fn split_take_number(name: &str) -> Option<(&str, u32)> {
+    [TAKE_SEPARATOR, LEGACY_TAKE_SEPARATOR]
+        .into_iter()
+        .find_map(|separator| {
+            let (stem, take) = name.rsplit_once(separator)?;
+            Some((stem, take.parse().ok()?))
+        })
+}
+
+/// Audio of a take interrupted by a pause, kept to continue the take on
+/// resume.
+pub struct PartialTake {
+    pub sentence_index: usize,
+    pub chunks: Vec<AudioChunkWithVAD>,
+    /// What the raw tap collected of the take, if raw takes are kept.
+    pub raw: Vec<i16>,
+}
+
+impl fmt::Debug for PartialTake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialTake")
+            .field("sentence_index", &self.sentence_index)
+            .field("chunks", &self.chunks.len())
+            .field("raw", &self.raw.len())
+            .finish()
+    }
+}
+
+/// Takes whose average speech level becomes the baseline when none was
+/// measured at the soundcheck.
+const LEVEL_BASELINE_TAKES: usize = 3;
+/// Takes averaged into the rolling speech level.
+const LEVEL_WINDOW_TAKES: usize = 5;
+
+fn mean(levels: &[f32]) -> f32 {
+    levels.iter().sum::<f32>() / levels.len().max(1) as f32
+}
+
+/// Follows the speech level of a session's takes to notice the narrator
+/// moving on or off the mic.
+#[derive(Debug, Default)]
+pub struct LevelTracker {
+    baseline: Option<f32>,
+    /// Zero disables the check.
+    threshold: f32,
+    levels: Vec<f32>,
+    drifted: bool,
+}
+
+impl LevelTracker {
+    pub fn new(baseline: Option<f32>, threshold: f32) -> Self {
+        Self {
+            baseline,
+            threshold,
+            ..Self::default()
+        }
+    }
+
+    /// Adds the RMS level of a kept take. Returns how far the rolling level
+    /// is from the baseline, positive when louder, when it has just moved
+    /// beyond the threshold. Drift is reported again only after the level
+    /// has come back.
+    pub fn add_take(&mut self, rms_dbfs: f32) -> Option<f32> {
+        if self.threshold <= 0.0 {
+            return None;
+        }
+        self.levels.push(rms_dbfs);
+        if self.baseline.is_none() && self.levels.len() >= LEVEL_BASELINE_TAKES {
+            self.baseline = Some(mean(&self.levels[..LEVEL_BASELINE_TAKES]));
+        }
+        let baseline = self.baseline?;
+        let recent = &self.levels[self.levels.len().saturating_sub(LEVEL_WINDOW_TAKES)..];
+        let drift = mean(recent) - baseline;
+        let was_drifted = self.drifted;
+        self.drifted = drift.abs() > self.threshold;
+        (self.drifted && !was_drifted).then_some(drift)
+    }
+}
 
 // Main AutoRecordState struct
 #[derive(Debug)]
 pub struct AutoRecordState {
     pub sentences: Vec<Sentence>,
     pub project_directory: String,
+    pub layout: ProjectLayout,
+    /// VAD speech probability at or above which audio counts as voice.
+    pub voice_probability: f32,
+    /// Level, in dBFS, below which audio counts as silence even if the VAD
+    /// hears voice in it.
     pub silence_threshold: f32,
     pub silence_duration: Duration,
     pub silence_padding: Duration,
+    pub silence_confirmation: Duration,
+    /// Voice probability, threshold and end-of-take silence as the audio
+    /// callback sees them, kept in step with the fields above.
+    pub silence_gate: Arc<SilenceGate>,
+    pub pre_sentence_delay: Duration,
+    pub output_format: OutputFormat,
+    pub channel_mode: ChannelMode,
+    pub emit_prompt_text: bool,
+    pub noise_reduction: NoiseReductionSettings,
+    pub filters: FilterSettings,
+    pub transients: TransientSettings,
+    pub duplicate_speech: DuplicateSpeechSettings,
+    pub vad_preprocessing: VadPreprocessingSettings,
+    pub write_sidecar: bool,
+    /// Collects the input before the gain when raw takes are kept.
+    pub raw_tap: Option<Arc<RawTap>>,
+    pub auto_advance: bool,
+    pub requeue_skipped: bool,
+    pub takes_per_sentence: u32,
+    pub min_take_duration: Duration,
+    /// Takes of the current sentence discarded in a row for being shorter
+    /// than the minimum take duration.
+    pub short_takes: u32,
+    pub min_voiced_duration: Duration,
+    /// Zero means takes may run indefinitely.
+    pub max_take_duration: Duration,
+    pub take_timeout_action: TakeTimeoutAction,
+    pub pause_policy: PausePolicy,
+    /// Whether the take interrupted by the last pause had speech. Under
+    /// `PausePolicy::Discard` only such a take skips its sentence.
+    pub paused_speaking: bool,
+    /// Zero disables sprints.
+    pub sprint_length: Duration,
+    pub rest_length: Duration,
+    /// When the current sprint started. Every resume starts a new sprint,
+    /// so any pause counts as a rest.
+    pub sprint_started: Instant,
+    /// When the session started, for its time quota.
+    pub session_started: Instant,
+    pub max_session_length: Duration,
+    pub max_session_sentences: usize,
+    pub level_tracker: LevelTracker,
+    pub cues: CueSettings,
+    /// Take interrupted by the last pause, under `PausePolicy::KeepPartial`.
+    pub partial_take: Option<PartialTake>,
+    /// Number of the take being recorded of the current sentence, from 1.
+    pub current_take: u32,
+    /// Indices of skipped sentences to revisit once the script is done.
+    pub skipped: VecDeque<usize>,
+    /// Set once the session has moved on to the skipped sentences.
+    pub cleanup_pass: bool,
+    /// Set while a finished take waits for a decision.
+    pub awaiting_confirmation: bool,
+    /// Set when the finished take must be confirmed even with auto-advance,
+    /// as it sounds like another sentence's take.
+    pub confirm_take: bool,
+    pub take_decision: Option<TakeDecision>,
+    /// Set while the recorder is paused to abandon the take in progress.
+    pub take_interrupt: Option<TakeInterrupt>,
+    /// Order the sentences are recorded in, up to the end of the script.
+    pub order: SentenceOrder,
     pub current_sentence_index: usize,
-    pub audio_config: AudioConfig,
+    /// Identifies this session in `auto-record-event` payloads.
+    pub session_id: String,
+    /// Measurements of the most recently written take.
+    pub last_take: Option<SentenceStats>,
+    /// Total length of the chunks of the most recently written take in
+    /// which voice was detected.
+    pub last_voiced: Duration,
+    /// Fingerprint of the most recently written take, with duplicate-speech
+    /// detection enabled.
+    pub last_fingerprint: Option<SpeechFingerprint>,
+    /// Fingerprints of the recorded takes, by sentence ID, to compare new
+    /// takes against.
+    pub speech_fingerprints: HashMap<usize, Arc<SpeechFingerprint>>,
+    /// Number of takes recorded in this session, by sentence ID.
+    pub take_counts: HashMap<usize, u32>,
+    /// When the first take of the current sentence was started.
+    pub sentence_started: Option<Instant>,
+    /// Time spent on each sentence finished in this session.
+    pub sentence_times: Vec<Duration>,
+    /// Length of each take kept in this session.
+    pub take_durations: Vec<Duration>,
+    /// Audit trail of the session, unless turned off.
+    pub session_log: Option<Arc<SessionLog>>,
+    /// What the VAD made of the most recent take, copied from its capture
+    /// once the take ends.
+    pub vad_summary: VadSummary,
+    /// Recording of the whole session, when one is being taped.
+    pub board_tape: Option<BoardTape>,
+    pub audio_source: Box<dyn AudioSource>,
     pub state: RecordingState,
-    pub is_speaking: Arc<Mutex<bool>>,
-    pub last_active_time: Arc<Mutex<Instant>>,
+    /// Cancelled when the session is stopped so in-flight waits end promptly.
+    pub cancel_token: CancellationToken,
+    /// Set when the recorder paused itself because the system was suspended.
+    pub suspended: bool,
+    /// Set when the recorder paused itself because the audio input failed.
+    pub device_lost: bool,
+    /// Set when the recorder paused itself because the disk is nearly full.
+    pub low_disk_space: bool,
 }
 
 impl AutoRecordState {
+    /// Number of channels written to each take's WAV file.
+    pub fn output_channels(&self) -> u16 {
+        match self.channel_mode {
+            ChannelMode::Input => self.audio_source.channels(),
+            ChannelMode::Mono => 1,
+            ChannelMode::Stereo => 2,
+        }
+    }
+
+    /// Number of the current take, reported in events only when several are
+    /// recorded per sentence.
+    pub fn take_number(&self) -> Option<u32> {
+        (self.takes_per_sentence > 1).then_some(self.current_take)
+    }
+
+    /// Directory the session's takes and recordings are written to.
+    pub fn recordings_directory(&self) -> PathBuf {
+        self.layout
+            .recordings_path(Path::new(&self.project_directory))
+    }
+
+    /// Name of the WAV file for the current take of `sentence`. Takes are
+    /// numbered when more than one is recorded per sentence.
+    pub fn take_file_name(&self, sentence: &Sentence) -> String {
+        let take = (self.takes_per_sentence > 1).then_some(self.current_take);
+        take_wav_name(&take_file_stem(sentence), take)
+    }
+
+    /// Whether the current sprint has run its full length.
+    pub fn sprint_over(&self) -> bool {
+        !self.sprint_length.is_zero() && self.sprint_started.elapsed() >= self.sprint_length
+    }
+
+    /// Whether the session has used up its time or sentence quota.
+    pub fn quota_reached(&self) -> bool {
+        let out_of_time = !self.max_session_length.is_zero()
+            && self.session_started.elapsed() >= self.max_session_length;
+        let out_of_sentences = self.max_session_sentences > 0
+            && self.sentence_times.len() >= self.max_session_sentences;
+        out_of_time || out_of_sentences
+    }
+
+    /// Silence that ends a take, including the confirmation window.
+    pub fn end_of_take_silence(&self) -> Duration {
+        self.silence_duration + self.silence_confirmation
+    }
+
+    /// Changes the voice and silence settings given, the voice probability,
+    /// threshold and duration taking effect on the next chunk of audio and
+    /// the padding on the next take written.
+    pub fn set_silence(
+        &mut self,
+        voice_probability: Option<f32>,
+        threshold: Option<f32>,
+        duration: Option<Duration>,
+        padding: Option<Duration>,
+    ) {
+        if let Some(voice_probability) = voice_probability {
+            self.voice_probability = voice_probability;
+            self.silence_gate.set_voice_probability(voice_probability);
+        }
+        if let Some(threshold) = threshold {
+            self.silence_threshold = threshold;
+            self.silence_gate.set_threshold(threshold);
+        }
+        if let Some(duration) = duration {
+            self.silence_duration = duration;
+            self.silence_gate
+                .set_end_of_take_silence(self.end_of_take_silence());
+        }
+        if let Some(padding) = padding {
+            self.silence_padding = padding;
+        }
+    }
+
     // State transition methods
     pub fn start_recording(&mut self) -> Result<(), &'static str> {
         match self.state {
             RecordingState::Idle => {
                 self.state = RecordingState::Recording;
+                self.sprint_started = Instant::now();
                 Ok(())
             }
             _ => Err("Can only start recording from Idle state"),
@@ -44,16 +363,211 @@ impl AutoRecordState {
         match self.state {
             RecordingState::Paused => {
                 self.state = RecordingState::Recording;
+                self.suspended = false;
+                self.device_lost = false;
+                self.low_disk_space = false;
+                self.sprint_started = Instant::now();
                 Ok(())
             }
             _ => Err("Can only resume from Paused state"),
         }
     }
 
+    /// Moves the session pointer to another sentence. Only allowed while
+    /// paused, so the in-flight take is never redirected. Sentences passed
+    /// over without a take are marked skipped; their IDs are returned.
+    pub fn set_current_sentence(&mut self, index: usize) -> Result<Vec<usize>, &'static str> {
+        if self.state != RecordingState::Paused {
+            return Err("Can only change the current sentence from Paused state");
+        }
+        if index >= self.sentences.len() {
+            return Err("Sentence index out of range");
+        }
+
+        // Jumping ahead in the order passes over everything in between;
+        // jumping back, or around the cleanup pass, only leaves the current
+        // sentence behind.
+        let current = self.current_sentence_index;
+        let mut passed_over = if self.cleanup_pass {
+            Vec::new()
+        } else {
+            self.order.seek(index)
+        };
+        if passed_over.is_empty() && index != current && current < self.sentences.len() {
+            passed_over.push(current);
+        }
+        let mut skipped_ids = Vec::new();
+        for i in passed_over {
+            let sentence = &mut self.sentences[i];
+            if sentence.recorded || sentence.skipped || self.take_counts.contains_key(&sentence.id)
+            {
+                continue;
+            }
+            sentence.skipped = true;
+            skipped_ids.push(sentence.id);
+            self.skipped.push_back(i);
+        }
+
+        self.current_sentence_index = index;
+        self.current_take = 1;
+        self.sentence_started = None;
+        Ok(skipped_ids)
+    }
+
+    /// Leaves the current sentence without a take and moves on. The
+    /// sentence is marked skipped unless it was already recorded; its ID is
+    /// returned if so.
+    pub fn skip_current(&mut self) -> Option<usize> {
+        let index = self.current_sentence_index;
+        let sentence = self.sentences.get_mut(index)?;
+        let skipped_id =
+            if sentence.recorded || sentence.skipped || self.take_counts.contains_key(&sentence.id)
+            {
+                None
+            } else {
+                sentence.skipped = true;
+                self.skipped.push_back(index);
+                Some(sentence.id)
+            };
+        self.advance();
+        skipped_id
+    }
+
+    /// Moves on from a kept take: to the next take of the same sentence
+    /// until `takes_per_sentence` have been recorded, then to the next
+    /// sentence.
+    pub fn next_take(&mut self) {
+        if self.current_take < self.takes_per_sentence {
+            self.current_take += 1;
+        } else {
+            self.advance();
+        }
+    }
+
+    /// Moves on to the next sentence in the recording order. After the last
+    /// one, the skipped sentences are revisited if `requeue_skipped` is set.
+    pub fn advance(&mut self) {
+        self.current_take = 1;
+        self.short_takes = 0;
+        if !self.cleanup_pass {
+            let next = self.order.next();
+            self.current_sentence_index = next.unwrap_or(self.sentences.len());
+            if next.is_some() || !self.requeue_skipped {
+                return;
+            }
+            self.cleanup_pass = true;
+        }
+        self.current_sentence_index = self.next_skipped().unwrap_or(self.sentences.len());
+    }
+
+    /// Next skipped sentence that has not been recorded since it was
+    /// skipped.
+    fn next_skipped(&mut self) -> Option<usize> {
+        while let Some(index) = self.skipped.pop_front() {
+            if !self.take_counts.contains_key(&self.sentences[index].id) {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Sentences left to record, including skipped sentences that will be
+    /// revisited.
+    pub fn remaining_sentences(&self) -> usize {
+        let requeued = if self.cleanup_pass || self.requeue_skipped {
+            self.skipped.len()
+        } else {
+            0
+        };
+        let pending = if self.cleanup_pass {
+            usize::from(self.current_sentence_index < self.sentences.len())
+        } else {
+            self.order.remaining()
+        };
+        pending + requeued
+    }
+
+    /// Rearranges the sentences not yet reached by `strategy`. The current
+    /// sentence is kept, so a take in progress is never redirected.
+    pub fn set_recording_order(&mut self, strategy: RecordingOrder) {
+        self.order.reorder(&self.sentences, strategy);
+    }
+
+    /// Stops the clock on the sentence just finished and reports the
+    /// session's pacing.
+    pub fn finish_sentence_timing(&mut self) -> SessionProgress {
+        if let Some(started) = self.sentence_started.take() {
+            self.sentence_times.push(started.elapsed());
+        }
+        let average = |durations: &[Duration]| {
+            if durations.is_empty() {
+                Duration::ZERO
+            } else {
+                durations.iter().sum::<Duration>() / durations.len() as u32
+            }
+        };
+        let average_sentence = average(&self.sentence_times);
+        let remaining = self.remaining_sentences();
+        SessionProgress {
+            completed: self.sentence_times.len(),
+            remaining,
+            average_take_ms: average(&self.take_durations).as_millis() as u64,
+            average_sentence_ms: average_sentence.as_millis() as u64,
+            eta_ms: (average_sentence * remaining as u32).as_millis() as u64,
+        }
+    }
+
+    /// Records the decision on a take waiting for confirmation.
+    pub fn decide_take(&mut self, decision: TakeDecision) -> Result<(), &'static str> {
+        if !self.awaiting_confirmation {
+            return Err("No take is waiting for confirmation");
+        }
+        self.take_decision = Some(decision);
+        Ok(())
+    }
+
+    /// Abandons the take in progress. While recording, the session pauses
+    /// and the recording thread applies the interrupt before carrying on.
+    /// While paused it applies at once. A take waiting for confirmation is
+    /// retried, or kept when skipping. Returns the ID of a sentence skipped
+    /// at once.
+    pub fn interrupt_take(
+        &mut self,
+        interrupt: TakeInterrupt,
+    ) -> Result<Option<usize>, &'static str> {
+        if self.awaiting_confirmation {
+            let decision = match interrupt {
+                TakeInterrupt::Skip => TakeDecision::Confirm,
+                TakeInterrupt::Retry => TakeDecision::Retry,
+            };
+            self.decide_take(decision)?;
+            return Ok(None);
+        }
+        match self.state {
+            RecordingState::Recording => {
+                self.take_interrupt = Some(interrupt);
+                self.pause_recording()?;
+                Ok(None)
+            }
+            RecordingState::Paused => {
+                self.partial_take = None;
+                Ok(match interrupt {
+                    TakeInterrupt::Skip => {
+                        self.sentence_started = None;
+                        self.skip_current()
+                    }
+                    TakeInterrupt::Retry => None,
+                })
+            }
+            RecordingState::Idle => Err("No take in progress"),
+        }
+    }
+
     pub fn stop_recording(&mut self) -> Result<(), &'static str> {
         match self.state {
             RecordingState::Recording | RecordingState::Paused => {
                 self.state = RecordingState::Idle;
+                self.cancel_token.cancel();
                 Ok(())
             }
             _ => Err("Can only stop from Recording or Paused state"),
@@ -65,10 +579,43 @@ impl AutoRecordState {
 pub struct AutoRecordStateBuilder {
     sentences: Option<Vec<Sentence>>,
     project_directory: Option<String>,
+    layout: ProjectLayout,
+    voice_probability: f32,
     silence_threshold: Option<f32>,
     silence_duration: Option<Duration>,
     silence_padding: Option<Duration>,
-    audio_config: Option<AudioConfig>,
+    silence_confirmation: Duration,
+    pre_sentence_delay: Duration,
+    output_format: OutputFormat,
+    channel_mode: ChannelMode,
+    emit_prompt_text: bool,
+    noise_reduction: NoiseReductionSettings,
+    filters: FilterSettings,
+    transients: TransientSettings,
+    duplicate_speech: DuplicateSpeechSettings,
+    vad_preprocessing: VadPreprocessingSettings,
+    write_sidecar: bool,
+    raw_tap: Option<Arc<RawTap>>,
+    auto_advance: bool,
+    requeue_skipped: bool,
+    recording_order: RecordingOrder,
+    overwrite_recorded: bool,
+    start_index: Option<usize>,
+    session_id: Option<String>,
+    takes_per_sentence: u32,
+    min_take_duration: Duration,
+    min_voiced_duration: Duration,
+    max_take_duration: Duration,
+    take_timeout_action: TakeTimeoutAction,
+    pause_policy: PausePolicy,
+    sprint_length: Duration,
+    rest_length: Duration,
+    max_session_length: Duration,
+    max_session_sentences: usize,
+    level_baseline: Option<f32>,
+    level_drift_threshold: f32,
+    cues: CueSettings,
+    audio_source: Option<Box<dyn AudioSource>>,
 }
 
 impl AutoRecordStateBuilder {
@@ -76,10 +623,43 @@ impl AutoRecordStateBuilder {
         Self {
             sentences: None,
             project_directory: None,
+            layout: ProjectLayout::default(),
+            voice_probability: VAD_VOICE_PROBABILITY,
             silence_threshold: None,
             silence_duration: None,
             silence_padding: None,
-            audio_config: None,
+            silence_confirmation: Duration::ZERO,
+            pre_sentence_delay: Duration::ZERO,
+            output_format: OutputFormat::default(),
+            channel_mode: ChannelMode::default(),
+            emit_prompt_text: false,
+            noise_reduction: NoiseReductionSettings::default(),
+            filters: FilterSettings::default(),
+            transients: TransientSettings::default(),
+            duplicate_speech: DuplicateSpeechSettings::default(),
+            vad_preprocessing: VadPreprocessingSettings::default(),
+            write_sidecar: false,
+            raw_tap: None,
+            auto_advance: true,
+            requeue_skipped: false,
+            recording_order: RecordingOrder::default(),
+            overwrite_recorded: false,
+            start_index: None,
+            session_id: None,
+            takes_per_sentence: 1,
+            min_take_duration: Duration::ZERO,
+            min_voiced_duration: Duration::ZERO,
+            max_take_duration: Duration::ZERO,
+            take_timeout_action: TakeTimeoutAction::default(),
+            pause_policy: PausePolicy::default(),
+            sprint_length: Duration::ZERO,
+            rest_length: Duration::ZERO,
+            max_session_length: Duration::ZERO,
+            max_session_sentences: 0,
+            level_baseline: None,
+            level_drift_threshold: 0.0,
+            cues: CueSettings::default(),
+            audio_source: None,
         }
     }
 
@@ -93,6 +673,16 @@ impl AutoRecordStateBuilder {
         self
     }
 
+    pub fn layout(mut self, layout: ProjectLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn voice_probability(mut self, voice_probability: f32) -> Self {
+        self.voice_probability = voice_probability;
+        self
+    }
+
     pub fn silence_threshold(mut self, silence_threshold: f32) -> Self {
         self.silence_threshold = Some(silence_threshold);
         self
@@ -108,23 +698,274 @@ impl AutoRecordStateBuilder {
         self
     }
 
-    pub fn audio_config(mut self, audio_config: AudioConfig) -> Self {
-        self.audio_config = Some(audio_config);
+    pub fn silence_confirmation(mut self, silence_confirmation_ms: u64) -> Self {
+        self.silence_confirmation = Duration::from_millis(silence_confirmation_ms);
+        self
+    }
+
+    pub fn pre_sentence_delay(mut self, pre_sentence_delay_ms: u64) -> Self {
+        self.pre_sentence_delay = Duration::from_millis(pre_sentence_delay_ms);
+        self
+    }
+
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    pub fn channel_mode(mut self, channel_mode: ChannelMode) -> Self {
+        self.channel_mode = channel_mode;
+        self
+    }
+
+    pub fn emit_prompt_text(mut self, emit_prompt_text: bool) -> Self {
+        self.emit_prompt_text = emit_prompt_text;
+        self
+    }
+
+    pub fn noise_reduction(mut self, noise_reduction: NoiseReductionSettings) -> Self {
+        self.noise_reduction = noise_reduction;
+        self
+    }
+
+    pub fn filters(mut self, filters: FilterSettings) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    pub fn transients(mut self, transients: TransientSettings) -> Self {
+        self.transients = transients;
+        self
+    }
+
+    pub fn duplicate_speech(mut self, duplicate_speech: DuplicateSpeechSettings) -> Self {
+        self.duplicate_speech = duplicate_speech;
+        self
+    }
+
+    pub fn vad_preprocessing(mut self, vad_preprocessing: VadPreprocessingSettings) -> Self {
+        self.vad_preprocessing = vad_preprocessing;
+        self
+    }
+
+    pub fn write_sidecar(mut self, write_sidecar: bool) -> Self {
+        self.write_sidecar = write_sidecar;
+        self
+    }
+
+    pub fn raw_tap(mut self, raw_tap: Option<Arc<RawTap>>) -> Self {
+        self.raw_tap = raw_tap;
+        self
+    }
+
+    pub fn auto_advance(mut self, auto_advance: bool) -> Self {
+        self.auto_advance = auto_advance;
+        self
+    }
+
+    pub fn requeue_skipped(mut self, requeue_skipped: bool) -> Self {
+        self.requeue_skipped = requeue_skipped;
+        self
+    }
+
+    pub fn recording_order(mut self, recording_order: RecordingOrder) -> Self {
+        self.recording_order = recording_order;
+        self
+    }
+
+    pub fn overwrite_recorded(mut self, overwrite_recorded: bool) -> Self {
+        self.overwrite_recorded = overwrite_recorded;
+        self
+    }
+
+    /// Index of the sentence to start the session at instead of the first
+    /// in the recording order.
+    pub fn start_index(mut self, start_index: Option<usize>) -> Self {
+        self.start_index = start_index;
+        self
+    }
+
+    /// ID for the session's events, or `None` to generate one.
+    pub fn session_id(mut self, session_id: Option<String>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    pub fn takes_per_sentence(mut self, takes_per_sentence: u32) -> Self {
+        self.takes_per_sentence = takes_per_sentence;
+        self
+    }
+
+    pub fn min_take_duration(mut self, min_take_duration_ms: u64) -> Self {
+        self.min_take_duration = Duration::from_millis(min_take_duration_ms);
+        self
+    }
+
+    pub fn min_voiced_duration(mut self, min_voiced_ms: u64) -> Self {
+        self.min_voiced_duration = Duration::from_millis(min_voiced_ms);
+        self
+    }
+
+    pub fn max_take_duration(mut self, max_take_duration_ms: u64) -> Self {
+        self.max_take_duration = Duration::from_millis(max_take_duration_ms);
+        self
+    }
+
+    pub fn take_timeout_action(mut self, take_timeout_action: TakeTimeoutAction) -> Self {
+        self.take_timeout_action = take_timeout_action;
+        self
+    }
+
+    pub fn pause_policy(mut self, pause_policy: PausePolicy) -> Self {
+        self.pause_policy = pause_policy;
+        self
+    }
+
+    pub fn sprint_length(mut self, sprint_minutes: u32) -> Self {
+        self.sprint_length = Duration::from_secs(u64::from(sprint_minutes) * 60);
+        self
+    }
+
+    pub fn rest_length(mut self, rest_minutes: u32) -> Self {
+        self.rest_length = Duration::from_secs(u64::from(rest_minutes) * 60);
+        self
+    }
+
+    pub fn max_session_length(mut self, max_session_minutes: u32) -> Self {
+        self.max_session_length = Duration::from_secs(u64::from(max_session_minutes) * 60);
+        self
+    }
+
+    pub fn max_session_sentences(mut self, max_session_sentences: usize) -> Self {
+        self.max_session_sentences = max_session_sentences;
+        self
+    }
+
+    pub fn level_baseline(mut self, level_baseline_dbfs: Option<f32>) -> Self {
+        self.level_baseline = level_baseline_dbfs;
+        self
+    }
+
+    pub fn level_drift_threshold(mut self, level_drift_threshold_db: f32) -> Self {
+        self.level_drift_threshold = level_drift_threshold_db;
+        self
+    }
+
+    pub fn cues(mut self, cues: CueSettings) -> Self {
+        self.cues = cues;
+        self
+    }
+
+    pub fn audio_source(mut self, audio_source: Box<dyn AudioSource>) -> Self {
+        self.audio_source = Some(audio_source);
         self
     }
 
     pub fn build(self) -> Result<AutoRecordState, String> {
+        let sentences = self.sentences.ok_or("Sentences not set")?;
+        if self.takes_per_sentence == 0 {
+            return Err("Takes per sentence must be at least 1".into());
+        }
+        if self.session_id.as_deref().is_some_and(str::is_empty) {
+            return Err("Session ID must not be empty".into());
+        }
+        self.layout.validate()?;
+        // Skips from earlier sessions, and sentences queued to be recorded
+        // again, join the cleanup pass too.
+        let skipped = sentences
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                (s.skipped && !s.recorded) || s.rerecord.as_ref().is_some_and(|f| f.queued)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        let mut order =
+            SentenceOrder::new(&sentences, self.recording_order, self.overwrite_recorded);
+        if let Some(index) = self.start_index {
+            if index >= sentences.len() {
+                return Err("Start sentence index out of range".into());
+            }
+            // Sentences before the start are left for another session
+            // rather than marked skipped.
+            order.start_at(index);
+        }
+        let current_sentence_index = order.current().unwrap_or(sentences.len());
+        let silence_threshold = self.silence_threshold.ok_or("Silence threshold not set")?;
+        let silence_duration = self.silence_duration.ok_or("Silence duration not set")?;
         Ok(AutoRecordState {
-            sentences: self.sentences.ok_or("Sentences not set")?,
+            sentences,
             project_directory: self.project_directory.ok_or("Project directory not set")?,
-            silence_threshold: self.silence_threshold.ok_or("Silence threshold not set")?,
-            silence_duration: self.silence_duration.ok_or("Silence duration not set")?,
+            layout: self.layout,
+            voice_probability: self.voice_probability,
+            silence_threshold,
+            silence_duration,
             silence_padding: self.silence_padding.ok_or("Silence padding not set")?,
-            current_sentence_index: 0,
-            audio_config: self.audio_config.ok_or("Audio config not set")?,
+            silence_confirmation: self.silence_confirmation,
+            silence_gate: Arc::new(SilenceGate::new(
+                self.voice_probability,
+                silence_threshold,
+                silence_duration + self.silence_confirmation,
+            )),
+            pre_sentence_delay: self.pre_sentence_delay,
+            output_format: self.output_format,
+            channel_mode: self.channel_mode,
+            emit_prompt_text: self.emit_prompt_text,
+            noise_reduction: self.noise_reduction,
+            filters: self.filters,
+            transients: self.transients,
+            duplicate_speech: self.duplicate_speech,
+            vad_preprocessing: self.vad_preprocessing,
+            write_sidecar: self.write_sidecar,
+            raw_tap: self.raw_tap,
+            auto_advance: self.auto_advance,
+            requeue_skipped: self.requeue_skipped,
+            takes_per_sentence: self.takes_per_sentence,
+            min_take_duration: self.min_take_duration,
+            min_voiced_duration: self.min_voiced_duration,
+            max_take_duration: self.max_take_duration,
+            take_timeout_action: self.take_timeout_action,
+            pause_policy: self.pause_policy,
+            sprint_length: self.sprint_length,
+            rest_length: self.rest_length,
+            sprint_started: Instant::now(),
+            session_started: Instant::now(),
+            max_session_length: self.max_session_length,
+            max_session_sentences: self.max_session_sentences,
+            level_tracker: LevelTracker::new(self.level_baseline, self.level_drift_threshold),
+            cues: self.cues,
+            partial_take: None,
+            current_take: 1,
+            skipped,
+            cleanup_pass: false,
+            awaiting_confirmation: false,
+            confirm_take: false,
+            take_decision: None,
+            take_interrupt: None,
+            order,
+            current_sentence_index,
+            session_id: self
+                .session_id
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            last_take: None,
+            last_voiced: Duration::ZERO,
+            short_takes: 0,
+            paused_speaking: false,
+            last_fingerprint: None,
+            speech_fingerprints: HashMap::new(),
+            take_counts: HashMap::new(),
+            sentence_started: None,
+            sentence_times: Vec::new(),
+            take_durations: Vec::new(),
+            session_log: None,
+            vad_summary: VadSummary::default(),
+            board_tape: None,
+            audio_source: self.audio_source.ok_or("Audio source not set")?,
             state: RecordingState::Idle,
-            is_speaking: Arc::new(Mutex::new(false)),
-            last_active_time: Arc::new(Mutex::new(Instant::now())),
+            cancel_token: CancellationToken::new(),
+            suspended: false,
+            device_lost: false,
+            low_disk_space: false,
         })
     }
 }
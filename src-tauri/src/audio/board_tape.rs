@@ -0,0 +1,284 @@
+use super::cues::{beep, Cue};
+use super::errors::RecorderError;
+use super::review::codec_error;
+use super::source::{AudioSource, SourceStream};
+use super::utils::downmix_to_mono;
+use crate::jobs::CancellationToken;
+use crate::models::BoardTapeSettings;
+use crate::paths::long_path;
+use crate::storage::{self, FileSink};
+use chrono::Local;
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use log::{debug, error, warn};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Encoder};
+use samplerate::{ConverterType, Samplerate};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Folder in the project directory that board tapes are written to, apart
+/// from the takes.
+pub const TAPE_DIRECTORY: &str = "board_tape";
+/// Rate the tape is encoded at. Wideband is plenty for speech.
+const TAPE_SAMPLE_RATE: u32 = 16_000;
+/// Samples in each 20 ms Opus frame at the tape rate.
+const FRAME_SAMPLES: usize = TAPE_SAMPLE_RATE as usize / 50;
+/// Ogg Opus granule positions always count 48 kHz samples.
+const GRANULES_PER_SAMPLE: u64 = 48_000 / TAPE_SAMPLE_RATE as u64;
+/// Largest Opus packet the encoder is allowed to produce.
+const MAX_PACKET: usize = 4000;
+/// How often the tape checks whether it has been stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Serial number of the tape's only logical stream.
+const STREAM_SERIAL: u32 = 1;
+
+enum TapeInput {
+    /// Interleaved input as delivered by the source.
+    Audio(Vec<i16>),
+    /// A cue's beep at the tape rate, to be mixed in.
+    Cue(Vec<f32>),
+}
+
+/**
+ * Low-bitrate Opus recording of a whole session, kept apart from the
+ * takes: everything the microphone picks up between takes as well as
+ * during them, with the session's cues mixed in. Meant to settle later
+ * what was said or directed, not for use as audio.
+ */
+#[derive(Debug)]
+pub struct BoardTape {
+    pub path: PathBuf,
+    inputs: Sender<TapeInput>,
+    cancel: CancellationToken,
+    thread: JoinHandle<()>,
+}
+
+impl BoardTape {
+    /// Mixes the cue's beep into the tape at `volume`, as the narrator
+    /// hears it.
+    pub fn cue(&self, cue: Cue, volume: f32) {
+        let _ = self
+            .inputs
+            .send(TapeInput::Cue(beep(cue, TAPE_SAMPLE_RATE, volume)));
+    }
+
+    /// Stops recording and waits for the tape to be finished on disk.
+    pub fn stop(self) {
+        self.cancel.cancel();
+        if self.thread.join().is_err() {
+            error!("Board tape thread panicked");
+        }
+    }
+}
+
+/// Encodes the tape as Ogg Opus into `path`, held in memory until it is
+/// finished if the project is encrypted.
+struct TapeWriter {
+    encoder: Encoder,
+    packets: PacketWriter<'static, FileSink>,
+    converter: Samplerate,
+    channels: u16,
+    pending: Vec<f32>,
+    cue: Vec<f32>,
+    granule: u64,
+}
+
+impl TapeWriter {
+    fn create(
+        path: &Path,
+        settings: BoardTapeSettings,
+        source_rate: u32,
+        channels: u16,
+        session_id: &str,
+    ) -> Result<Self, RecorderError> {
+        let mut encoder = Encoder::new(TAPE_SAMPLE_RATE, Channels::Mono, Application::Voip)
+            .map_err(codec_error)?;
+        encoder
+            .set_bitrate(Bitrate::Bits(settings.bitrate_kbps.max(6) as i32 * 1000))
+            .map_err(codec_error)?;
+        // Samples the decoder drops from the start, covering the encoder's
+        // lookahead.
+        let pre_skip = encoder.get_lookahead().map_err(codec_error)? as u64 * GRANULES_PER_SAMPLE;
+        let converter =
+            Samplerate::new(ConverterType::SincFastest, source_rate, TAPE_SAMPLE_RATE, 1)
+                .map_err(|e| RecorderError::Other(format!("Failed to create resampler: {}", e)))?;
+
+        let file = storage::create(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+        let mut packets = PacketWriter::new(file);
+        let mut head = b"OpusHead".to_vec();
+        head.push(1); // Version
+        head.push(1); // Channels
+        head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+        head.extend_from_slice(&source_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+        head.push(0); // Mono or stereo, no channel mapping table
+        packets
+            .write_packet(head, STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)
+            .map_err(codec_error)?;
+
+        let vendor = concat!("recordr ", env!("CARGO_PKG_VERSION"));
+        let comment = format!("SESSION_ID={}", session_id);
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        tags.extend_from_slice(vendor.as_bytes());
+        tags.extend_from_slice(&1u32.to_le_bytes());
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+        packets
+            .write_packet(tags, STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)
+            .map_err(codec_error)?;
+
+        Ok(Self {
+            encoder,
+            packets,
+            converter,
+            channels,
+            pending: Vec::new(),
+            cue: Vec::new(),
+            granule: pre_skip,
+        })
+    }
+
+    fn add_cue(&mut self, beep: Vec<f32>) {
+        for (i, sample) in beep.into_iter().enumerate() {
+            match self.cue.get_mut(i) {
+                Some(mixed) => *mixed += sample,
+                None => self.cue.push(sample),
+            }
+        }
+    }
+
+    fn add_audio(&mut self, data: &[i16]) -> Result<(), RecorderError> {
+        let mono = downmix_to_mono(data, self.channels);
+        let mut samples = self
+            .converter
+            .process(&mono)
+            .map_err(|e| RecorderError::Other(format!("Failed to resample: {}", e)))?;
+        let mixed = samples.len().min(self.cue.len());
+        for (sample, cue) in samples.iter_mut().zip(self.cue.drain(..mixed)) {
+            *sample = (*sample + cue).clamp(-1.0, 1.0);
+        }
+        self.pending.extend(samples);
+        while self.pending.len() >= FRAME_SAMPLES {
+            let frame: Vec<f32> = self.pending.drain(..FRAME_SAMPLES).collect();
+            self.write_frame(&frame, PacketWriteEndInfo::NormalPacket)?;
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &[f32], end: PacketWriteEndInfo) -> Result<(), RecorderError> {
+        let packet = self
+            .encoder
+            .encode_vec_float(frame, MAX_PACKET)
+            .map_err(codec_error)?;
+        self.granule += FRAME_SAMPLES as u64 * GRANULES_PER_SAMPLE;
+        self.packets
+            .write_packet(packet, STREAM_SERIAL, end, self.granule)
+            .map_err(codec_error)?;
+        Ok(())
+    }
+
+    /// Pads the last frame with silence and ends the stream. Flushing the
+    /// file writes it out, encrypted if need be.
+    fn finish(mut self) -> Result<(), RecorderError> {
+        let mut frame = std::mem::take(&mut self.pending);
+        frame.resize(FRAME_SAMPLES, 0.0);
+        self.write_frame(&frame, PacketWriteEndInfo::EndStream)?;
+        let mut file = self.packets.into_inner();
+        std::io::Write::flush(&mut file)?;
+        Ok(())
+    }
+}
+
+fn record_tape(
+    inputs: &Receiver<TapeInput>,
+    mut writer: TapeWriter,
+    cancel: &CancellationToken,
+) -> Result<(), RecorderError> {
+    while !cancel.is_cancelled() {
+        match inputs.recv_timeout(POLL_INTERVAL) {
+            Ok(TapeInput::Audio(data)) => writer.add_audio(&data)?,
+            Ok(TapeInput::Cue(beep)) => writer.add_cue(beep),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    writer.finish()
+}
+
+/**
+ * Starts a board tape of session `session_id` in the project's
+ * `board_tape` folder, listening to `audio_source` alongside the session.
+ * The source gets a stream of its own that stays open for the whole
+ * session, so the tape carries on between takes.
+ */
+pub fn start_board_tape(
+    audio_source: Box<dyn AudioSource>,
+    project_directory: &Path,
+    session_id: &str,
+    settings: BoardTapeSettings,
+) -> Result<BoardTape, RecorderError> {
+    let directory = project_directory.join(TAPE_DIRECTORY);
+    fs::create_dir_all(long_path(&directory))?;
+    let path = directory.join(format!(
+        "session_{}_{}.opus",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        session_id
+    ));
+
+    let (inputs_tx, inputs_rx) = unbounded();
+    let (started_tx, started_rx) = bounded(1);
+    let cancel = CancellationToken::new();
+    let thread = {
+        let cancel = cancel.clone();
+        let inputs_tx = inputs_tx.clone();
+        let path = path.clone();
+        let session_id = session_id.to_string();
+        std::thread::spawn(move || {
+            // Neither the stream nor the resampler can move between
+            // threads, so both are set up on the thread that uses them.
+            let started = TapeWriter::create(
+                &path,
+                settings,
+                audio_source.sample_rate() as u32,
+                audio_source.channels(),
+                &session_id,
+            )
+            .and_then(|writer| {
+                let stream = audio_source.start(
+                    Box::new(move |data| {
+                        let _ = inputs_tx.send(TapeInput::Audio(data.to_vec()));
+                    }),
+                    Box::new(|e| warn!("Board tape input failed: {}", e)),
+                )?;
+                Ok((writer, stream))
+            });
+            let (writer, stream): (TapeWriter, SourceStream) = match started {
+                Ok(started) => started,
+                Err(e) => {
+                    let _ = fs::remove_file(long_path(&path));
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = started_tx.send(Ok(()));
+            debug!("Recording board tape to {:?}", path);
+            if let Err(e) = record_tape(&inputs_rx, writer, &cancel) {
+                error!("Failed to record board tape {:?}: {}", path, e);
+            }
+            drop(stream);
+        })
+    };
+
+    started_rx
+        .recv()
+        .map_err(|_| RecorderError::Other("Board tape ended before it started".to_string()))??;
+    Ok(BoardTape {
+        path,
+        inputs: inputs_tx,
+        cancel,
+        thread,
+    })
+}
@@ -1,62 +1,237 @@
+use super::auto_record::{TakeDecision, TakeInterrupt};
+use super::config::InputLatency;
+use super::continuous::split_session;
+use super::convert::{
+    convert_file, import_file, in_project_format, to_project_format, ConversionTarget,
+    ORIGINALS_DIRECTORY,
+};
+use super::disk_space::DEFAULT_MIN_FREE_SPACE_MB;
+use super::editing;
+use super::engine::{engine_info, EngineInfo};
+use super::events::{ProgressEvents, WindowEvents};
+use super::external_edit::send_take_for_editing;
+use super::metadata::{embed_metadata, read_metadata, AudioMetadata};
+use super::probe::{decode_file, probe_file, AudioProbe};
 use super::recorder::Recorder;
-use crate::models::Sentence;
-use std::fs::File;
-use std::io::Read;
+use super::retrim::retrim_take;
+use super::review::{encode_review_copy, ReviewFormat};
+use super::segments::stitch_segments;
+use super::takes::{compare_takes, next_take_path, TakeMetrics};
+use super::transients::find_transients;
+use crate::analytics::SessionAnalyticsRecorder;
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::write_project;
+use crate::jobs::{run_batch, run_blocking, run_job, CancellationToken, JobRegistry};
+use crate::models::{
+    Project, ProjectLayout, RecordingOrder, RecordingSettings, Sentence, SentenceStatus,
+};
+use crate::paths::long_path;
+use crate::presets::{find_preset, AudioFormat};
+use crate::settings::{AppSettings, SettingsState};
+use crate::storage;
+use chrono::Local;
+use hound::WavReader;
+use log::error;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::State;
 
-/// Starts a standard recording and writes to a WAV file.
+/// Overlap between recording segments when none is given.
+pub(crate) const DEFAULT_SEGMENT_OVERLAP_MS: u64 = 500;
+
+/// Starts a standard recording and writes to a WAV file. Long captures can
+/// pass `segment_length_secs` to be written as overlapping segment files
+/// instead, so a crash loses at most one segment. Recording is refused with
+/// a `disk_full` error if the volume has less than `min_free_space_mb` free.
+/// A take recorded for `sentence` gets its metadata once it is stopped.
 #[tauri::command]
 pub fn start_recording(
     filename: String,
+    segment_length_secs: Option<u64>,
+    segment_overlap_ms: Option<u64>,
+    min_free_space_mb: Option<u64>,
+    sentence: Option<Sentence>,
+    project_directory: Option<String>,
     state: State<Arc<Mutex<Recorder>>>,
-) -> Result<String, String> {
+) -> Result<String, RecordrError> {
     let recorder_state = Arc::clone(state.inner());
     let mut recorder = recorder_state.lock().unwrap();
-    recorder.start_recording(filename)
+    recorder.start_recording(
+        filename,
+        segment_length_secs.map(Duration::from_secs),
+        Duration::from_millis(segment_overlap_ms.unwrap_or(DEFAULT_SEGMENT_OVERLAP_MS)),
+        min_free_space_mb.unwrap_or(DEFAULT_MIN_FREE_SPACE_MB),
+        sentence,
+        project_directory,
+    )
+}
+
+/// Joins the segments of a segmented recording, listed in its
+/// `.segments.json` manifest, into a single WAV file.
+#[tauri::command]
+pub async fn stitch_recording_segments(
+    manifest_path: String,
+    output_path: String,
+) -> Result<String, RecordrError> {
+    run_blocking(move || {
+        stitch_segments(Path::new(&manifest_path), Path::new(&output_path))
+            .map_err(|e| RecordrError::from(e).with_context(manifest_path))?;
+        Ok(output_path)
+    })
+    .await
 }
 
 /// Stops the current recording and finalizes the WAV file.
 #[tauri::command]
-pub fn stop_recording(state: State<Arc<Mutex<Recorder>>>) -> Result<String, String> {
+pub fn stop_recording(state: State<Arc<Mutex<Recorder>>>) -> Result<String, RecordrError> {
     let recorder_state = Arc::clone(state.inner());
     let mut recorder = recorder_state.lock().unwrap();
     recorder.stop_recording()
 }
 
 /// Starts the auto-recording process with sentence detection and silence
-/// handling.
+/// handling. Settings default to the project's stored recording settings;
+/// any silence parameter passed explicitly overrides the stored value.
+/// Takes go in the recordings folder of `layout`, or next to the project
+/// file without one. Sentences that already have a take are passed over
+/// unless `overwrite` is set. The session starts from the sentence at
+/// `start_index`, or the one with `start_sentence_id`, if either is given,
+/// leaving the sentences before it for another day. The session's
+/// `auto-record-event`s carry `session_id` if one is given, so events sent
+/// before this returns can be matched, or else a new ID. Returns the ID.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn start_auto_record(
     sentences: Vec<Sentence>,
+    start_index: Option<usize>,
+    start_sentence_id: Option<usize>,
+    session_id: Option<String>,
     project_directory: String,
-    silence_threshold: f32,
-    silence_duration: u64,
-    silence_padding: u64,
+    layout: Option<ProjectLayout>,
+    recording_settings: Option<RecordingSettings>,
+    silence_threshold: Option<f32>,
+    silence_duration: Option<u64>,
+    silence_padding: Option<u64>,
+    overwrite: Option<bool>,
     window: tauri::Window,
     state: State<Arc<Mutex<Recorder>>>,
-) -> Result<(), String> {
-    let recorder_state = Arc::clone(state.inner());
-    {
-        let mut recorder = recorder_state.lock().unwrap();
-        recorder
-            .start_auto_record(
-                sentences.clone(),
-                project_directory.clone(),
-                silence_threshold,
-                silence_duration,
-                silence_padding,
-                window.clone(),
-            )
-            .map_err(|e| e.to_string())?;
+    app_settings: State<SettingsState>,
+) -> Result<String, RecordrError> {
+    let app_settings = app_settings.lock().unwrap().clone();
+    let mut settings = auto_record_settings(
+        recording_settings,
+        silence_threshold,
+        silence_duration,
+        silence_padding,
+        &app_settings,
+    );
+    if let Some(overwrite) = overwrite {
+        settings.overwrite_recorded = overwrite;
+    }
+    let events = SessionAnalyticsRecorder::wrap(
+        Arc::new(WindowEvents::new(window)),
+        &project_directory,
+        app_settings.session_analytics,
+    );
+    let start_index = start_sentence_index(&sentences, start_index, start_sentence_id)?;
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.start_auto_record(
+        sentences,
+        start_index,
+        session_id,
+        project_directory,
+        layout.unwrap_or_default(),
+        settings,
+        events,
+    )
+}
+
+/// Starts monitor mode: the auto-record voice detection runs on the input
+/// and reports `voice_detected`, `silence_detected` and `level` events as
+/// `auto-record-event`s, without writing any files. Settings are resolved
+/// as for `start_auto_record`. Returns the ID carried by the events.
+#[tauri::command]
+pub fn start_monitor_mode(
+    recording_settings: Option<RecordingSettings>,
+    silence_threshold: Option<f32>,
+    silence_duration: Option<u64>,
+    silence_padding: Option<u64>,
+    window: tauri::Window,
+    state: State<Arc<Mutex<Recorder>>>,
+    app_settings: State<SettingsState>,
+) -> Result<String, RecordrError> {
+    let settings = auto_record_settings(
+        recording_settings,
+        silence_threshold,
+        silence_duration,
+        silence_padding,
+        &app_settings.lock().unwrap(),
+    );
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.start_monitor_mode(settings, Arc::new(WindowEvents::new(window)))
+}
+
+/// Stops monitor mode. Returns `false` if it wasn't running.
+#[tauri::command]
+pub fn stop_monitor_mode(state: State<Arc<Mutex<Recorder>>>) -> bool {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.stop_monitor_mode()
+}
+
+/// Index of the sentence a session starts from, given by its index or its
+/// ID.
+pub(crate) fn start_sentence_index(
+    sentences: &[Sentence],
+    start_index: Option<usize>,
+    start_sentence_id: Option<usize>,
+) -> Result<Option<usize>, RecordrError> {
+    match (start_index, start_sentence_id) {
+        (Some(index), _) => Ok(Some(index)),
+        (None, Some(id)) => sentences
+            .iter()
+            .position(|s| s.id == id)
+            .map(Some)
+            .ok_or_else(|| RecordrError::not_found(format!("Sentence not found: {}", id))),
+        (None, None) => Ok(None),
     }
+}
 
-    Ok(())
+/// Settings of a new auto-record session: the given recording settings or
+/// the app defaults, with explicitly passed silence parameters on top.
+pub(crate) fn auto_record_settings(
+    recording_settings: Option<RecordingSettings>,
+    silence_threshold: Option<f32>,
+    silence_duration: Option<u64>,
+    silence_padding: Option<u64>,
+    app_settings: &AppSettings,
+) -> RecordingSettings {
+    let mut settings = recording_settings.unwrap_or_else(|| app_settings.recording_defaults());
+    if settings.device_name.is_none() {
+        settings.device_name = app_settings.default_device.clone();
+    }
+    if let Some(silence_threshold) = silence_threshold {
+        settings.silence_threshold = silence_threshold;
+    }
+    if let Some(silence_duration) = silence_duration {
+        settings.silence_duration_ms = silence_duration;
+    }
+    if let Some(silence_padding) = silence_padding {
+        settings.silence_padding_ms = silence_padding;
+    }
+    settings
 }
 
 /// Stops the auto-recording process.
 #[tauri::command]
-pub fn stop_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), String> {
+pub fn stop_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
     let recorder_state = Arc::clone(state.inner());
     let mut recorder = recorder_state.lock().unwrap();
     recorder.stop_auto_record()
@@ -64,7 +239,7 @@ pub fn stop_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), String
 
 /// Pauses the auto-recording process.
 #[tauri::command]
-pub fn pause_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), String> {
+pub fn pause_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
     let recorder_state = Arc::clone(state.inner());
     let mut recorder = recorder_state.lock().unwrap();
     recorder.pause_auto_record()
@@ -72,16 +247,941 @@ pub fn pause_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), Strin
 
 /// Resumes the auto-recording process.
 #[tauri::command]
-pub fn resume_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), String> {
+pub fn resume_auto_record(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
     let recorder_state = Arc::clone(state.inner());
     let mut recorder = recorder_state.lock().unwrap();
     recorder.resume_auto_record()
 }
 
+/// Jumps a paused auto-record session to another sentence, identified by
+/// its index in the session or by its ID.
+#[tauri::command]
+pub fn set_current_sentence(
+    index: Option<usize>,
+    sentence_id: Option<usize>,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.set_current_sentence(index, sentence_id)
+}
+
+/// Rebuilds the audio input after the device was lost and resumes the
+/// session. `device_name` switches to another device.
+#[tauri::command]
+pub fn reconnect_audio_device(
+    device_name: Option<String>,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.reconnect_audio_device(device_name)
+}
+
+/// Accepts the take waiting for confirmation and moves on to the next
+/// sentence. Only used when auto-advance is off.
+#[tauri::command]
+pub fn confirm_sentence(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.decide_take(TakeDecision::Confirm)
+}
+
+/// Records the current sentence again, discarding the take in progress or
+/// the take waiting for confirmation.
+#[tauri::command]
+pub fn retry_sentence(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.interrupt_take(TakeInterrupt::Retry)
+}
+
+/// Abandons the take in progress and moves on, marking the sentence
+/// skipped.
+#[tauri::command]
+pub fn skip_sentence(state: State<Arc<Mutex<Recorder>>>) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.interrupt_take(TakeInterrupt::Skip)
+}
+
+/// Enables or disables noise reduction on recorded takes.
+#[tauri::command]
+pub fn set_noise_reduction(
+    enabled: bool,
+    strength: f32,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.set_noise_reduction(enabled, strength)
+}
+
+/// Changes the VAD voice probability, the silence threshold, in dBFS, and
+/// the silence duration and padding, in ms, of the running auto-record
+/// session or monitor without stopping it. Values left out are kept.
+#[tauri::command]
+pub fn update_auto_record_settings(
+    voice_probability: Option<f32>,
+    silence_threshold: Option<f32>,
+    silence_duration: Option<u64>,
+    silence_padding: Option<u64>,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.update_auto_record_settings(
+        voice_probability,
+        silence_threshold,
+        silence_duration,
+        silence_padding,
+    )
+}
+
+/// Sets the order an auto-record session works through its sentences:
+/// sequential, shuffled with a seed, shortest first or unrecorded first.
+/// It applies to the sentences after the current one, for the running
+/// session only.
+#[tauri::command]
+pub fn set_recording_order(
+    strategy: RecordingOrder,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.set_recording_order(strategy)
+}
+
+/// Sets the software input gain in dB, applied before voice detection and
+/// writing, and optionally turns the slow AGC on or off. Takes effect at
+/// once in a running session.
+#[tauri::command]
+pub fn set_input_gain(
+    gain_db: f32,
+    agc: Option<bool>,
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<(), RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let mut recorder = recorder_state.lock().unwrap();
+    recorder.set_input_gain(gain_db, agc)
+}
+
+/// Reports the input latency of the running auto-record session, including
+/// whether low-latency mode could be used.
+#[tauri::command]
+pub fn get_input_latency(
+    state: State<Arc<Mutex<Recorder>>>,
+) -> Result<Option<InputLatency>, RecordrError> {
+    let recorder_state = Arc::clone(state.inner());
+    let recorder = recorder_state.lock().unwrap();
+    recorder.input_latency()
+}
+
+/// Largest read `load_audio_file` performs unless the caller passes a
+/// different `max_size`.
+const DEFAULT_MAX_LOAD_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Reads the sentence, project, recording time and device embedded in a
+/// WAV file, so a take can be identified without its project.
+#[tauri::command]
+pub async fn read_audio_metadata(path: String) -> Result<AudioMetadata, RecordrError> {
+    run_blocking(move || {
+        read_metadata(Path::new(&path)).map_err(|e| RecordrError::from(e).with_context(path))
+    })
+    .await
+}
+
+/// Reads the encoding, sample rate, channels, bit depth, length and tags
+/// of a WAV, FLAC or MP3 file, e.g. to check outside audio before it is
+/// linked to a sentence.
+#[tauri::command]
+pub async fn probe_audio(path: String) -> Result<AudioProbe, RecordrError> {
+    run_blocking(move || probe_file(Path::new(&path))).await
+}
+
+/// Reads an audio file, or the byte range `offset..offset + length` of it.
+/// The app plays takes through the `audio` URI scheme, which streams them
+/// in ranges; this serves JSON-RPC clients and analysis. Reads larger than
+/// `max_size` bytes are refused with a `file_too_large` error so a wrong
+/// path can't pull a huge file into the webview.
+#[tauri::command]
+pub async fn load_audio_file(
+    file_path: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, RecordrError> {
+    run_blocking(move || {
+        let path = Path::new(&file_path);
+        let max_size = max_size.unwrap_or(DEFAULT_MAX_LOAD_SIZE);
+        let too_large = |size: u64| {
+            RecordrError::new(
+                ErrorKind::FileTooLarge,
+                format!("Read of {} bytes exceeds the {} byte limit", size, max_size),
+            )
+            .with_context(file_path.clone())
+        };
+        // Encrypted files can only be decrypted whole, so they are read into
+        // memory before the requested range is taken, and must fit the limit
+        // as they are.
+        let disk_size = fs::metadata(long_path(path))
+            .map_err(|e| RecordrError::from(e).with_context(file_path.clone()))?
+            .len();
+        if disk_size > max_size && storage::is_encrypted(path)? {
+            return Err(too_large(disk_size));
+        }
+        let mut file = storage::open(path)?;
+        let file_size = file.seek(SeekFrom::End(0))?;
+        let offset = offset.unwrap_or(0);
+        if offset > file_size {
+            return Err(RecordrError::invalid_input(format!(
+                "Offset {} is past the end of the file ({} bytes)",
+                offset, file_size
+            ))
+            .with_context(file_path));
+        }
+
+        let available = file_size - offset;
+        let read_size = length.map_or(available, |length| length.min(available));
+        if read_size > max_size {
+            return Err(too_large(read_size));
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buffer = Vec::with_capacity(read_size as usize);
+        file.take(read_size).read_to_end(&mut buffer)?;
+        Ok(buffer)
+    })
+    .await
+}
+
+pub(crate) fn emit_file_updated(events: &dyn ProgressEvents, file_path: &str) {
+    events.notify("file-updated", serde_json::json!({ "path": file_path }));
+}
+
+/// Trims a recorded file to the given range. The original is kept as a
+/// `.bak` file so the edit can be undone.
+#[tauri::command]
+pub async fn trim_audio(
+    file_path: String,
+    start_ms: u64,
+    end_ms: u64,
+    window: tauri::Window,
+) -> Result<(), RecordrError> {
+    run_blocking(move || {
+        editing::trim_audio(Path::new(&file_path), start_ms, end_ms)?;
+        emit_file_updated(&window, &file_path);
+        Ok(())
+    })
+    .await
+}
+
+/// Applies a fade in and fade out to a recorded file. The original is kept
+/// as a `.bak` file so the edit can be undone.
+#[tauri::command]
+pub async fn apply_fade(
+    file_path: String,
+    fade_in_ms: u64,
+    fade_out_ms: u64,
+    window: tauri::Window,
+) -> Result<(), RecordrError> {
+    run_blocking(move || {
+        editing::apply_fade(Path::new(&file_path), fade_in_ms, fade_out_ms)?;
+        emit_file_updated(&window, &file_path);
+        Ok(())
+    })
+    .await
+}
+
+/// Restores a recorded file to its state before any edits.
+#[tauri::command]
+pub async fn undo_audio_edit(file_path: String, window: tauri::Window) -> Result<(), RecordrError> {
+    run_blocking(move || {
+        editing::undo_audio_edit(Path::new(&file_path))?;
+        emit_file_updated(&window, &file_path);
+        Ok(())
+    })
+    .await
+}
+
+/// Re-trims the silence around the speech of a sentence's take to
+/// `silence_padding_ms`, defaulting to the project's padding, and saves the
+/// project with the take's new stats. The original is kept as a `.bak` file
+/// so the edit can be undone.
+#[tauri::command]
+pub async fn retrim_sentence(
+    project: Project,
+    sentence_id: usize,
+    silence_padding_ms: Option<u64>,
+    window: tauri::Window,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        retrim_project_sentence(
+            project,
+            sentence_id,
+            silence_padding_ms,
+            &WindowEvents::new(window),
+        )
+    })
+    .await
+}
+
+pub(crate) fn retrim_project_sentence(
+    mut project: Project,
+    sentence_id: usize,
+    silence_padding_ms: Option<u64>,
+    events: &dyn ProgressEvents,
+) -> Result<Project, RecordrError> {
+    let settings = &project.metadata.recording_settings;
+    let padding = Duration::from_millis(silence_padding_ms.unwrap_or(settings.silence_padding_ms));
+    let vad_preprocessing = settings.vad_preprocessing;
+    let sentence = project
+        .sentences
+        .iter_mut()
+        .find(|s| s.id == sentence_id)
+        .ok_or_else(|| RecordrError::not_found(format!("Sentence {} not found", sentence_id)))?;
+    let path = match (&sentence.audio_file_path, sentence.recorded) {
+        (Some(path), true) => path.clone(),
+        _ => {
+            return Err(RecordrError::invalid_state(format!(
+                "Sentence {} has no take",
+                sentence_id
+            )))
+        }
+    };
+    let stats = retrim_take(Path::new(&path), padding, vad_preprocessing)?.ok_or_else(|| {
+        RecordrError::new(
+            ErrorKind::Audio,
+            format!("No speech found in the take of sentence {}", sentence_id),
+        )
+    })?;
+    sentence.stats = Some(stats);
+    write_project(&project)?;
+    emit_file_updated(events, &path);
+    Ok(project)
+}
+
+/// Re-trims the silence around the speech of every recorded take in the
+/// project, as `retrim_sentence` does, several at once. Emits a
+/// `retrim-progress` event as each take is done and saves the project with
+/// the new stats. Takes that fail, or in which no speech is found, are
+/// reported but do not abort the batch.
+/// Passing a `job_id` allows the batch to be cancelled with `cancel_job`;
+/// takes re-trimmed before cancellation are kept.
+#[tauri::command]
+pub async fn retrim_project(
+    project: Project,
+    silence_padding_ms: Option<u64>,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Project, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        retrim_project_takes(
+            project,
+            silence_padding_ms,
+            &WindowEvents::new(window),
+            &cancel,
+        )
+    })
+    .await
+}
+
+pub(crate) fn retrim_project_takes(
+    mut project: Project,
+    silence_padding_ms: Option<u64>,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Project, RecordrError> {
+    let settings = &project.metadata.recording_settings;
+    let padding = Duration::from_millis(silence_padding_ms.unwrap_or(settings.silence_padding_ms));
+    let vad_preprocessing = settings.vad_preprocessing;
+    let takes = recorded_takes(&project);
+
+    run_batch(
+        &takes,
+        cancel,
+        |(_, path)| {
+            retrim_take(Path::new(path), padding, vad_preprocessing)?
+                .ok_or_else(|| RecordrError::new(ErrorKind::Audio, "No speech found"))
+        },
+        |index, result, progress| {
+            let (position, path) = &takes[index];
+            let sentence = &mut project.sentences[*position];
+            let error = match result {
+                Ok(stats) => {
+                    sentence.stats = Some(stats);
+                    emit_file_updated(events, path);
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to re-trim {}: {}", path, e);
+                    Some(e.to_string())
+                }
+            };
+
+            events.notify(
+                "retrim-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": sentence.id,
+                    "stats": sentence.stats,
+                    "error": error,
+                }),
+            );
+        },
+    );
+
+    // Persist the stats of the takes re-trimmed so far, even when cancelled.
+    write_project(&project)?;
+    cancel.check()?;
+    Ok(project)
+}
+
+/// Looks for breaths, clicks and pops in every recorded take in the project,
+/// as is done when a take is written, for takes recorded before the check
+/// existed or edited since. Emits a `transients-progress` event as each take
+/// is done and saves the project with the findings in the takes' stats.
+/// Takes without stats, such as imported ones, are left out.
+/// Passing a `job_id` allows the batch to be cancelled with `cancel_job`.
+#[tauri::command]
+pub async fn detect_project_transients(
+    project: Project,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Project, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        detect_transients_in_takes(project, &WindowEvents::new(window), &cancel)
+    })
+    .await
+}
+
+pub(crate) fn detect_transients_in_takes(
+    mut project: Project,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Project, RecordrError> {
+    let takes: Vec<(usize, String)> = recorded_takes(&project)
+        .into_iter()
+        .filter(|(position, _)| project.sentences[*position].stats.is_some())
+        .collect();
+
+    run_batch(
+        &takes,
+        cancel,
+        |(_, path)| Ok(find_transients(Path::new(path))?),
+        |index, result, progress| {
+            let (position, path) = &takes[index];
+            let sentence = &mut project.sentences[*position];
+            let error = match result {
+                Ok(transients) => {
+                    if let Some(stats) = &mut sentence.stats {
+                        stats.transients = transients;
+                    }
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to analyse {}: {}", path, e);
+                    Some(e.to_string())
+                }
+            };
+
+            events.notify(
+                "transients-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": sentence.id,
+                    "stats": sentence.stats,
+                    "error": error,
+                }),
+            );
+        },
+    );
+
+    // Persist the findings for the takes analysed so far, even when cancelled.
+    write_project(&project)?;
+    cancel.check()?;
+    Ok(project)
+}
+
+/// Re-encodes every recorded sentence in the project to a new sample rate
+/// and bit depth, either given explicitly or taken from a named conversion
+/// preset, several files at once. Only WAV targets are accepted, and each
+/// original is kept as a `.bak`. Emits a `convert-progress` event as each
+/// file is done and saves the project with the updated audio paths. Failed
+/// files are reported but do not abort the batch. Passing a `job_id` allows
+/// the conversion to be cancelled with `cancel_job`; files converted before
+/// cancellation are kept.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn convert_project_audio(
+    project: Project,
+    target_sample_rate: Option<u32>,
+    target_format: Option<AudioFormat>,
+    bit_depth: Option<u16>,
+    preset: Option<String>,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+    app_settings: State<'_, SettingsState>,
+) -> Result<Project, RecordrError> {
+    let target = conversion_target(
+        target_sample_rate,
+        target_format,
+        bit_depth,
+        preset,
+        &app_settings.lock().unwrap(),
+    )?;
+    run_job(&jobs, job_id, move |cancel| {
+        convert_project(project, target, &WindowEvents::new(window), &cancel)
+    })
+    .await
+}
+
+/// Target of a conversion. Values not given are taken from the preset, then
+/// the default preset. Files are mixed to the preset's channels, and keep
+/// their own when there is no preset.
+pub(crate) fn conversion_target(
+    target_sample_rate: Option<u32>,
+    target_format: Option<AudioFormat>,
+    bit_depth: Option<u16>,
+    preset: Option<String>,
+    app_settings: &AppSettings,
+) -> Result<ConversionTarget, RecordrError> {
+    let preset = preset
+        .or_else(|| app_settings.default_export_preset.clone())
+        .map(|name| find_preset(&name))
+        .transpose()?;
+    let format = target_format
+        .or(preset.as_ref().map(|p| p.format))
+        .unwrap_or(AudioFormat::Wav);
+    // Takes stay WAV files, so only WAV targets can replace them.
+    if format != AudioFormat::Wav {
+        return Err(RecordrError::invalid_input(format!(
+            "Project audio can only be converted to WAV, not {:?}",
+            format
+        )));
+    }
+    Ok(ConversionTarget {
+        sample_rate: target_sample_rate
+            .or(preset.as_ref().map(|p| p.sample_rate))
+            .ok_or_else(|| RecordrError::invalid_input("Target sample rate not set"))?,
+        format,
+        bit_depth: bit_depth
+            .or(preset.as_ref().and_then(|p| p.bit_depth))
+            .unwrap_or(16),
+        channels: preset.as_ref().map(|p| p.channels),
+    })
+}
+
+/// Position in the project's sentences and path of each recorded take.
+fn recorded_takes(project: &Project) -> Vec<(usize, String)> {
+    project
+        .sentences
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.recorded)
+        .filter_map(|(position, s)| Some((position, s.audio_file_path.clone()?)))
+        .collect()
+}
+
+pub(crate) fn convert_project(
+    mut project: Project,
+    target: ConversionTarget,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Project, RecordrError> {
+    let takes = recorded_takes(&project);
+
+    run_batch(
+        &takes,
+        cancel,
+        |(_, path)| Ok(convert_file(Path::new(path), target)?),
+        |index, result, progress| {
+            let (position, path) = &takes[index];
+            let sentence = &mut project.sentences[*position];
+            let error = match result {
+                Ok(output_path) => {
+                    sentence.audio_file_path = Some(output_path.to_string_lossy().to_string());
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to convert {}: {}", path, e);
+                    Some(e.to_string())
+                }
+            };
+
+            events.notify(
+                "convert-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": sentence.id,
+                    "audioFilePath": sentence.audio_file_path,
+                    "error": error,
+                }),
+            );
+        },
+    );
+
+    // Persist the paths of the files converted so far, even when cancelled.
+    write_project(&project)?;
+    cancel.check()?;
+    Ok(project)
+}
+
+/// Existing recording to import as a sentence's take.
+#[derive(Debug, Deserialize)]
+pub struct AudioImport {
+    pub sentence_id: usize,
+    pub file_path: String,
+}
+
+/// Imports WAV files recorded outside the app as the takes of their
+/// sentences, converted to the project's sample rate, channels and sample
+/// format. Each is added as a new take, numbered after the sentence's last
+/// one, so takes already recorded are kept. Originals are copied unchanged to `originals/` in the project
+/// directory. Emits an `import-progress` event per file and saves the
+/// project; failed files are reported but do not abort the batch. Passing a
+/// `job_id` allows the import to be cancelled with `cancel_job`.
+#[tauri::command]
+pub async fn import_sentence_audio(
+    project: Project,
+    imports: Vec<AudioImport>,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Project, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        import_project_audio(project, imports, &WindowEvents::new(window), &cancel)
+    })
+    .await
+}
+
+pub(crate) fn import_project_audio(
+    mut project: Project,
+    imports: Vec<AudioImport>,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Project, RecordrError> {
+    let directory = PathBuf::from(&project.metadata.directory);
+    let originals_dir = directory.join(ORIGINALS_DIRECTORY);
+    let recordings_dir = project.metadata.layout.recordings_path(&directory);
+    fs::create_dir_all(long_path(&recordings_dir))?;
+    let settings = project.metadata.recording_settings.clone();
+    let total = imports.len();
+
+    for (current, import) in imports.iter().enumerate() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let result = project
+            .sentences
+            .iter_mut()
+            .find(|s| s.id == import.sentence_id)
+            .ok_or_else(|| format!("Sentence {} not found", import.sentence_id))
+            .and_then(|sentence| {
+                let take_path =
+                    next_take_path(sentence, &recordings_dir).map_err(|e| e.to_string())?;
+                import_file(
+                    Path::new(&import.file_path),
+                    &take_path,
+                    &originals_dir,
+                    &settings,
+                )
+                .map_err(|e| e.to_string())?;
+                let metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+                    .project_directory(&project.metadata.directory);
+                embed_metadata(&take_path, &metadata).map_err(|e| e.to_string())?;
+                sentence.audio_file_path = Some(take_path.to_string_lossy().to_string());
+                sentence.recorded = true;
+                sentence.skipped = false;
+                sentence.status = SentenceStatus::Recorded;
+                sentence.stats = None;
+                Ok(())
+            });
+        let error = result.err();
+        if let Some(error) = &error {
+            error!("Failed to import {}: {}", import.file_path, error);
+        }
+
+        events.notify(
+            "import-progress",
+            serde_json::json!({
+                "current": current + 1,
+                "total": total,
+                "id": import.sentence_id,
+                "filePath": import.file_path,
+                "error": error,
+            }),
+        );
+    }
+
+    write_project(&project)?;
+    cancel.check()?;
+    Ok(project)
+}
+
+/// Links audio recorded outside the app, e.g. in a studio DAW, to a
+/// sentence as its take and marks the sentence recorded. The WAV, FLAC or
+/// MP3 file is checked first. With `copy`, it is converted to the project's
+/// sample rate, channels and sample format and written to the recordings
+/// folder as the sentence's next take, leaving the original and any earlier
+/// takes alone.
+/// Without, the file is linked where it is, which requires a WAV file
+/// already in the project's format. Saves and returns the project.
+#[tauri::command]
+pub async fn attach_external_audio(
+    project: Project,
+    sentence_id: usize,
+    path: String,
+    copy: bool,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || attach_audio(project, sentence_id, Path::new(&path), copy)).await
+}
+
+pub(crate) fn attach_audio(
+    mut project: Project,
+    sentence_id: usize,
+    source: &Path,
+    copy: bool,
+) -> Result<Project, RecordrError> {
+    let context = source.display().to_string();
+    let probe = probe_file(source)?;
+    if probe.duration_ms == Some(0) {
+        return Err(RecordrError::invalid_input("File holds no audio").with_context(context));
+    }
+    let position = project
+        .sentences
+        .iter()
+        .position(|s| s.id == sentence_id)
+        .ok_or_else(|| RecordrError::not_found(format!("Sentence not found: {}", sentence_id)))?;
+    let settings = &project.metadata.recording_settings;
+    let is_wav = source
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"));
+
+    let take_path = if copy {
+        let buffer = if is_wav {
+            editing::read_wav(source)?
+        } else {
+            decode_file(source)?
+        };
+        let sentence = &project.sentences[position];
+        let directory = project
+            .metadata
+            .layout
+            .recordings_path(Path::new(&project.metadata.directory));
+        fs::create_dir_all(long_path(&directory))?;
+        let take_path = next_take_path(sentence, &directory)?;
+        editing::write_wav(&take_path, &to_project_format(&buffer, settings)?)?;
+        let metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+            .project_directory(&project.metadata.directory);
+        embed_metadata(&take_path, &metadata)?;
+        take_path
+    } else {
+        let spec = is_wav
+            .then(|| storage::open(source))
+            .transpose()?
+            .map(WavReader::new)
+            .transpose()?
+            .map(|reader| reader.spec());
+        if !spec.is_some_and(|spec| in_project_format(spec, settings)) {
+            return Err(RecordrError::invalid_input(
+                "File is not a WAV file in the project's format; attach a copy to convert it",
+            )
+            .with_context(context));
+        }
+        source.to_path_buf()
+    };
+
+    let sentence = &mut project.sentences[position];
+    sentence.audio_file_path = Some(take_path.to_string_lossy().to_string());
+    sentence.recorded = true;
+    sentence.skipped = false;
+    sentence.rerecord = None;
+    sentence.status = SentenceStatus::Recorded;
+    sentence.stats = None;
+    write_project(&project)?;
+    Ok(project)
+}
+
+/// Copies the take of a sentence to the project's `external_edit/` folder
+/// for editing in a DAW and returns the copy's path. When the edited file
+/// is saved back, it is imported as a new take of the sentence and an
+/// `external-edit` event is emitted; see `send_take_for_editing`.
+#[tauri::command]
+pub async fn send_to_external_editor(
+    project: Project,
+    sentence_id: usize,
+    window: tauri::Window,
+) -> Result<String, RecordrError> {
+    run_blocking(move || {
+        let events: Arc<dyn ProgressEvents> = Arc::new(WindowEvents::new(window));
+        send_take_for_editing(&project, sentence_id, events)
+            .map(|path| path.to_string_lossy().to_string())
+    })
+    .await
+}
+
+/// Encodes compressed copies of every recorded sentence into `review/` in
+/// the project directory, for reviewers who stream or download them. The
+/// WAV masters are left untouched. `bitrate_kbps` is the target bitrate.
+/// Files are encoded several at once, with a `review-export-progress` event
+/// as each is done. Returns the paths of the copies written; failed files
+/// are reported but do not abort the batch. Passing a `job_id` allows the
+/// export to be cancelled with `cancel_job`. Encrypted projects are refused,
+/// since their copies would be sealed.
+#[tauri::command]
+pub async fn export_review_audio(
+    project: Project,
+    format: ReviewFormat,
+    bitrate_kbps: u32,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Vec<String>, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        export_review_copies(
+            &project,
+            format,
+            bitrate_kbps,
+            &WindowEvents::new(window),
+            &cancel,
+        )
+    })
+    .await
+}
+
+pub(crate) fn export_review_copies(
+    project: &Project,
+    format: ReviewFormat,
+    bitrate_kbps: u32,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Vec<String>, RecordrError> {
+    if bitrate_kbps == 0 {
+        return Err(RecordrError::invalid_input("Bitrate must be non-zero"));
+    }
+    // Copies inside the project would be sealed and of no use to reviewers.
+    if project.metadata.encrypted {
+        return Err(RecordrError::invalid_state(
+            "Review copies of encrypted projects can't be exported",
+        ));
+    }
+    let review_dir = PathBuf::from(&project.metadata.directory).join("review");
+    fs::create_dir_all(&review_dir)
+        .map_err(|e| RecordrError::from(e).with_context(review_dir.display().to_string()))?;
+    let takes = recorded_takes(project);
+    let mut written = Vec::new();
+
+    run_batch(
+        &takes,
+        cancel,
+        |(_, path)| {
+            let source = Path::new(path);
+            let output = review_dir
+                .join(source.file_stem().unwrap_or_default())
+                .with_extension(format.extension());
+            encode_review_copy(source, &output, format, bitrate_kbps)?;
+            Ok(output.to_string_lossy().to_string())
+        },
+        |index, result, progress| {
+            let (position, path) = &takes[index];
+            let (review_file_path, error) = match result {
+                Ok(review_file_path) => {
+                    written.push(review_file_path.clone());
+                    (Some(review_file_path), None)
+                }
+                Err(e) => {
+                    error!("Failed to encode review copy of {}: {}", path, e);
+                    (None, Some(e.to_string()))
+                }
+            };
+
+            events.notify(
+                "review-export-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": project.sentences[*position].id,
+                    "audioFilePath": path,
+                    "reviewFilePath": review_file_path,
+                    "error": error,
+                }),
+            );
+        },
+    );
+
+    cancel.check()?;
+    Ok(written)
+}
+
+/// Cuts the takes of a continuous session recording, listed in its
+/// `.markers.json` file, into a WAV file per take and points each sentence
+/// at its take. The project is saved and returned.
+#[tauri::command]
+pub async fn split_session_recording(
+    mut project: Project,
+    markers_path: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        split_session(&mut project, Path::new(&markers_path))
+            .map_err(|e| RecordrError::from(e).with_context(markers_path))?;
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/// Lists every take of a sentence with its duration, loudness and clipping,
+/// so the best take can be chosen from data. `transcripts` maps take file
+/// paths to speech recognizer output, adding a word error rate to those
+/// takes.
+#[tauri::command]
+pub async fn compare_takes_metrics(
+    project: Project,
+    sentence_id: usize,
+    transcripts: Option<HashMap<String, String>>,
+) -> Result<Vec<TakeMetrics>, RecordrError> {
+    run_blocking(move || {
+        let sentence = project
+            .sentences
+            .iter()
+            .find(|s| s.id == sentence_id)
+            .ok_or_else(|| {
+                RecordrError::not_found(format!("Sentence {} not found", sentence_id))
+            })?;
+        // Takes are written next to each other, so the current take shows
+        // where to look.
+        let recordings_dir = project
+            .metadata
+            .layout
+            .recordings_path(Path::new(&project.metadata.directory));
+        let directory = sentence
+            .audio_file_path
+            .as_deref()
+            .and_then(|path| Path::new(path).parent())
+            .unwrap_or(&recordings_dir);
+        Ok(compare_takes(
+            sentence,
+            directory,
+            &transcripts.unwrap_or_default(),
+        )?)
+    })
+    .await
+}
+
+/// Reports the library versions and processing parameters used for audio,
+/// so a dataset can record exactly how it was produced.
 #[tauri::command]
-pub fn load_audio_file(file_path: String) -> Result<Vec<u8>, String> {
-    let mut file = File::open(&file_path).map_err(|e| e.to_string())?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-    Ok(buffer)
+pub fn get_engine_info() -> EngineInfo {
+    engine_info()
 }
@@ -1,6 +1,9 @@
 use cpal::traits::DeviceTrait;
 use cpal::{Device, StreamConfig, SupportedStreamConfig};
+use serde::Serialize;
 use std::fmt;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
 
 pub struct DeviceWrapper(pub Device);
 
@@ -16,6 +19,24 @@ pub struct AudioConfig {
     pub supported_config: SupportedStreamConfig,
     pub config: StreamConfig,
     pub sample_rate: usize,
+    /// Whether low latency was requested and the device accepted it.
+    pub low_latency: bool,
+    /// Latency between capture and the data callback, in microseconds, as
+    /// last reported by the host. Zero until the host reports one.
+    pub capture_latency_us: Arc<AtomicU32>,
+}
+
+/// Input latency of a running session.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct InputLatency {
+    /// Whether the device runs with the low-latency buffer.
+    pub low_latency: bool,
+    /// Frames per buffer; `None` when the host picks the size.
+    pub buffer_frames: Option<u32>,
+    pub buffer_latency_ms: Option<f64>,
+    /// Measured delay between capture and delivery, when the host reports
+    /// timestamps.
+    pub capture_latency_ms: Option<f64>,
 }
 
 // Enum for audio events
@@ -0,0 +1,533 @@
+use super::auto_record::{take_file_stem, take_wav_name, AutoRecordState, TakeInterrupt};
+use super::config::RecordingState;
+use super::editing::{write_wav, AudioBuffer};
+use super::errors::RecorderError;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use super::filters::FilterChain;
+use super::metadata::{embed_metadata, AudioMetadata};
+use super::source::SourceStream;
+use super::stats::measure_take;
+use super::stream::{
+    create_recordings_directory, get_chunk_size, mark_device_lost, VAD_RESAMPLER, VAD_SAMPLE_RATE,
+};
+use super::utils::{
+    create_wav, downmix_to_mono, remix_channels, wav_spec, write_pcm_sample, WavFileWriter,
+};
+use super::vad_filter::VadFilter;
+use crate::jobs::CancellationToken;
+use crate::models::{Project, SentenceStatus};
+use crate::storage;
+use chrono::Local;
+use hound::{SampleFormat as HoundSampleFormat, WavReader};
+use log::{debug, error, warn};
+use samplerate::Samplerate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use voice_activity_detector::VoiceActivityDetector;
+
+/// Where a take lies in a continuous session recording, in frames from the
+/// start of the recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionMarker {
+    pub sentence_id: usize,
+    /// Number of the take of the sentence, from 1.
+    pub take: u32,
+    /// Span to cut, including the silence padding.
+    pub start_frame: u64,
+    pub end_frame: u64,
+    /// Span in which speech was detected.
+    pub speech_start_frame: u64,
+    pub speech_end_frame: u64,
+}
+
+/// Marker file written next to a continuous session recording, rewritten
+/// as each take is detected so a crash loses at most the take in progress.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionMarkers {
+    /// File name of the session recording, in the marker file's directory.
+    pub recording: String,
+    pub sample_rate: u32,
+    pub markers: Vec<SessionMarker>,
+}
+
+/// Session recording being written, shared with the audio callback.
+struct Capture {
+    /// Taken to finalize the recording once the session ends.
+    writer: Option<WavFileWriter>,
+    /// Frames written so far.
+    frames: u64,
+    /// First frame of the speech being captured, if any.
+    segment_start: Option<u64>,
+    /// Frame after the last chunk in which speech was detected.
+    last_voice: u64,
+    /// Speech spans ended by silence, waiting to be assigned to sentences.
+    segments: Vec<(u64, u64)>,
+}
+
+fn frames_for(duration: Duration, sample_rate: usize) -> u64 {
+    (duration.as_secs_f64() * sample_rate as f64) as u64
+}
+
+/**
+ * Starts the session's audio source, writing everything it captures while
+ * the session is recording to the session recording and splitting the
+ * speech into segments at each silence that would end a take.
+ */
+fn start_capture(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    capture: &Arc<Mutex<Capture>>,
+) -> Result<SourceStream, RecorderError> {
+    let state = state_arc.lock().unwrap();
+    let sample_rate = state.audio_source.sample_rate();
+    let channels = state.audio_source.channels();
+    let output_channels = state.output_channels();
+    let gate = Arc::clone(&state.silence_gate);
+    let chunk_len = get_chunk_size(sample_rate)? * channels.max(1) as usize;
+    let mut vad = VoiceActivityDetector::builder()
+        .sample_rate(VAD_SAMPLE_RATE as i64)
+        .chunk_size(get_chunk_size(VAD_SAMPLE_RATE)?)
+        .build()
+        .expect("Failed to build VAD");
+    let mut vad_filter = VadFilter::new(state.vad_preprocessing, sample_rate as u32);
+    let mut filters = FilterChain::new(state.filters, output_channels, sample_rate as u32);
+    let mut buffer = Vec::new();
+
+    let on_data = {
+        let state_arc = Arc::clone(state_arc);
+        let capture = Arc::clone(capture);
+        move |data: &[i16]| {
+            let mut converter =
+                Samplerate::new(VAD_RESAMPLER, sample_rate as u32, VAD_SAMPLE_RATE as u32, 1)
+                    .expect("Failed to create Samplerate converter");
+            buffer.extend_from_slice(data);
+            while buffer.len() >= chunk_len {
+                let chunk: Vec<i16> = buffer.drain(..chunk_len).collect();
+                // The session is never locked while holding the capture.
+                let recording = state_arc.lock().unwrap().state == RecordingState::Recording;
+                let mut capture = capture.lock().unwrap();
+                // Nothing is kept while paused, and speech cut off by the
+                // pause is dropped.
+                if !recording {
+                    capture.segment_start = None;
+                    continue;
+                }
+                let capture = &mut *capture;
+                let Some(writer) = capture.writer.as_mut() else {
+                    return;
+                };
+
+                let mut mono = downmix_to_mono(&chunk, channels);
+                vad_filter.process(&mut mono);
+                let downsampled = converter.process(&mono).expect("Failed to downsample");
+                let is_voice = gate.is_voice(vad.predict(downsampled), &chunk);
+
+                let start = capture.frames;
+                let mut output = remix_channels(&chunk, channels, output_channels);
+                filters.process(&mut output);
+                for sample in output {
+                    if let Err(e) = write_pcm_sample(writer, sample) {
+                        error!("Error writing session recording: {}", e);
+                        return;
+                    }
+                }
+                capture.frames += (chunk.len() / channels.max(1) as usize) as u64;
+
+                if is_voice {
+                    capture.segment_start.get_or_insert(start);
+                    capture.last_voice = capture.frames;
+                } else if let Some(segment_start) = capture.segment_start {
+                    let end_of_take_silence = frames_for(gate.end_of_take_silence(), sample_rate);
+                    if capture.frames - capture.last_voice >= end_of_take_silence {
+                        let segment = (segment_start, capture.last_voice);
+                        capture.segments.push(segment);
+                        capture.segment_start = None;
+                    }
+                }
+            }
+        }
+    };
+
+    let on_error = {
+        let state_arc = Arc::clone(state_arc);
+        move |e: RecorderError| {
+            error!("Audio input failed: {}", e);
+            mark_device_lost(&state_arc);
+        }
+    };
+
+    state
+        .audio_source
+        .start(Box::new(on_data), Box::new(on_error))
+}
+
+/**
+ * Handles a pause of a continuous session. A skip or retry is applied and
+ * recording carries on; otherwise this waits until the session is resumed,
+ * restarting the capture if the device was reconnected meanwhile. Returns
+ * `false` if the session ended.
+ */
+fn wait_while_paused(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    capture: &Arc<Mutex<Capture>>,
+    stream: &mut Option<SourceStream>,
+    events: &dyn RecorderEvents,
+    cancel: &CancellationToken,
+) -> Result<bool, RecorderError> {
+    let (sample_rate, device_lost) = {
+        let mut state = state_arc.lock().unwrap();
+        match state.take_interrupt.take() {
+            Some(interrupt) => {
+                if interrupt == TakeInterrupt::Skip {
+                    state.sentence_started = None;
+                    if let Some(skipped_id) = state.skip_current() {
+                        AutoRecordEvent::new(
+                            AutoRecordEventKind::SentenceSkipped,
+                            &state.session_id,
+                        )
+                        .sentence(skipped_id)
+                        .emit(events);
+                    }
+                }
+                if state.resume_recording().is_ok() {
+                    AutoRecordEvent::new(AutoRecordEventKind::Resumed, &state.session_id)
+                        .emit(events);
+                }
+                return Ok(true);
+            }
+            None => (state.audio_source.sample_rate(), state.device_lost),
+        }
+    };
+    if device_lost {
+        *stream = None;
+        let session_id = state_arc.lock().unwrap().session_id.clone();
+        AutoRecordEvent::new(AutoRecordEventKind::DeviceLost, &session_id).emit(events);
+    }
+
+    loop {
+        if cancel.is_cancelled() {
+            return Ok(false);
+        }
+        match state_arc.lock().unwrap().state {
+            RecordingState::Idle => return Ok(false),
+            RecordingState::Recording => break,
+            RecordingState::Paused => {}
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    if stream.is_none() {
+        // A reconnected device has to keep the session recording's rate.
+        if state_arc.lock().unwrap().audio_source.sample_rate() != sample_rate {
+            return Err(RecorderError::Other(
+                "The reconnected device's sample rate differs from the session recording's"
+                    .to_string(),
+            ));
+        }
+        *stream = Some(start_capture(state_arc, capture)?);
+    }
+    Ok(true)
+}
+
+/**
+ * Records a whole auto-record session into one continuous WAV file instead
+ * of a file per take. Speech is still detected and assigned to sentences as
+ * the session goes, but each take is only recorded as a marker, in a
+ * `.markers.json` file next to the recording, to be cut out afterwards with
+ * `split_session`. Nothing is lost between takes, and a crash loses at most
+ * the take in progress. Noise reduction is not applied to the recording.
+ * Encrypted projects can't record sessions this way, as their files are
+ * only written once complete.
+ */
+pub fn record_continuous(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    cancel: &CancellationToken,
+) -> Result<(), RecorderError> {
+    let (recording_path, sample_rate, padding, min_take_duration, session_id) = {
+        let state = state_arc.lock().unwrap();
+        let recordings_dir = create_recordings_directory(&state)?;
+        let file_name = format!("session_{}.wav", Local::now().format("%Y%m%d_%H%M%S"));
+        (
+            recordings_dir.join(file_name),
+            state.audio_source.sample_rate(),
+            state.silence_padding,
+            state.min_take_duration,
+            state.session_id.clone(),
+        )
+    };
+    let markers_path = recording_path.with_extension("markers.json");
+    debug!("Recording session to {}", recording_path.display());
+
+    let spec = {
+        let state = state_arc.lock().unwrap();
+        wav_spec(
+            state.output_channels(),
+            sample_rate as u32,
+            state.output_format,
+        )
+    };
+    let capture = Arc::new(Mutex::new(Capture {
+        writer: Some(create_wav(&recording_path, spec)?),
+        frames: 0,
+        segment_start: None,
+        last_voice: 0,
+        segments: Vec::new(),
+    }));
+    let mut markers = SessionMarkers {
+        recording: recording_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        sample_rate: sample_rate as u32,
+        markers: Vec::new(),
+    };
+    let padding_frames = frames_for(padding, sample_rate);
+    let min_take_frames = frames_for(min_take_duration, sample_rate);
+    let mut stream = Some(start_capture(state_arc, &capture)?);
+    let mut announced = None;
+
+    let result = loop {
+        if cancel.is_cancelled() {
+            break Ok(());
+        }
+        let recording_state = state_arc.lock().unwrap().state;
+        match recording_state {
+            RecordingState::Idle => break Ok(()),
+            RecordingState::Paused => {
+                match wait_while_paused(state_arc, &capture, &mut stream, events, cancel) {
+                    Ok(true) => continue,
+                    Ok(false) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            }
+            RecordingState::Recording => {}
+        }
+
+        let (segments, frames) = {
+            let mut capture = capture.lock().unwrap();
+            (std::mem::take(&mut capture.segments), capture.frames)
+        };
+        let mut state = state_arc.lock().unwrap();
+        let mut quota_reached = false;
+        for (speech_start, speech_end) in segments {
+            let Some(sentence) = state.sentences.get(state.current_sentence_index).cloned() else {
+                break;
+            };
+            if speech_end - speech_start < min_take_frames {
+                debug!("Ignoring {} frames of speech", speech_end - speech_start);
+                AutoRecordEvent::new(AutoRecordEventKind::TakeRejected, &session_id)
+                    .sentence(sentence.id)
+                    .take_number(state.take_number())
+                    .emit(events);
+                continue;
+            }
+            markers.markers.push(SessionMarker {
+                sentence_id: sentence.id,
+                take: state.current_take,
+                start_frame: speech_start.saturating_sub(padding_frames),
+                end_frame: (speech_end + padding_frames).min(frames),
+                speech_start_frame: speech_start,
+                speech_end_frame: speech_end,
+            });
+            // The recording's header is brought up to date with each marker,
+            // so both survive a crash.
+            if let Some(writer) = capture.lock().unwrap().writer.as_mut() {
+                if let Err(e) = writer.flush() {
+                    warn!("Failed to flush session recording: {}", e);
+                }
+            }
+            if let Err(e) = write_markers(&markers_path, &markers) {
+                error!("Failed to write {}: {}", markers_path.display(), e);
+            }
+
+            *state.take_counts.entry(sentence.id).or_insert(0) += 1;
+            let index = state.current_sentence_index;
+            let sentence = &mut state.sentences[index];
+            sentence.skipped = false;
+            sentence.rerecord = None;
+            sentence.status = SentenceStatus::Recorded;
+            let sentence = sentence.clone();
+            let duration =
+                Duration::from_secs_f64((speech_end - speech_start) as f64 / sample_rate as f64);
+            state.take_durations.push(duration);
+            AutoRecordEvent::new(AutoRecordEventKind::SentenceFinished, &session_id)
+                .sentence(sentence.id)
+                .take_number(state.take_number())
+                .duration(duration)
+                .review(&sentence)
+                .emit(events);
+            state.next_take();
+            if state.current_sentence_index != index {
+                let progress = state.finish_sentence_timing();
+                AutoRecordEvent::new(AutoRecordEventKind::SessionProgress, &session_id)
+                    .progress(progress)
+                    .emit(events);
+                if state.quota_reached() && state.remaining_sentences() > 0 {
+                    debug!("Session quota reached");
+                    AutoRecordEvent::new(AutoRecordEventKind::SessionQuotaReached, &session_id)
+                        .progress(progress)
+                        .emit(events);
+                    quota_reached = true;
+                    break;
+                }
+            }
+        }
+        if quota_reached {
+            break Ok(());
+        }
+
+        let Some(sentence) = state.sentences.get(state.current_sentence_index).cloned() else {
+            break Ok(());
+        };
+        let current = (state.current_sentence_index, state.current_take);
+        if announced != Some(current) {
+            announced = Some(current);
+            state.sentence_started.get_or_insert_with(Instant::now);
+            let mut event = AutoRecordEvent::new(AutoRecordEventKind::SentenceStarted, &session_id)
+                .sentence(sentence.id)
+                .take_number(state.take_number());
+            if state.emit_prompt_text {
+                event = event.text(sentence.text.clone());
+            }
+            event.emit(events);
+        }
+        drop(state);
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    drop(stream);
+    if let Some(writer) = capture.lock().unwrap().writer.take() {
+        writer.finalize()?;
+    }
+    debug!(
+        "Session recording finished with {} markers",
+        markers.markers.len()
+    );
+    result
+}
+
+fn write_markers(path: &Path, markers: &SessionMarkers) -> Result<(), RecorderError> {
+    let data =
+        serde_json::to_vec_pretty(markers).map_err(|e| RecorderError::Other(e.to_string()))?;
+    storage::write(path, &data).map_err(|e| RecorderError::Other(e.to_string()))
+}
+
+/// Reads a continuous session's marker file.
+fn read_markers(markers_path: &Path) -> Result<SessionMarkers, RecorderError> {
+    let data = storage::read(markers_path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    serde_json::from_slice(&data).map_err(|e| RecorderError::Other(e.to_string()))
+}
+
+/// Reads frames `start..end` of a WAV file, normalized like `read_wav`, so
+/// a long recording is never read whole.
+fn read_span<R: Read + Seek>(
+    reader: &mut WavReader<R>,
+    start: u64,
+    end: u64,
+) -> Result<Vec<f32>, RecorderError> {
+    let spec = reader.spec();
+    let count = (end - start) as usize * spec.channels.max(1) as usize;
+    let start = u32::try_from(start)
+        .map_err(|_| RecorderError::Other("Recording is too long to read from".to_string()))?;
+    reader.seek(start)?;
+    let samples = match spec.sample_format {
+        HoundSampleFormat::Float => reader
+            .samples::<f32>()
+            .take(count)
+            .collect::<Result<Vec<_>, _>>()?,
+        HoundSampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .take(count)
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(samples)
+}
+
+/// Cuts the takes listed in a marker file out of its session recording into
+/// a WAV file per take, named as auto-record would have named them, and
+/// points each sentence of `project` at its last take. A sentence with
+/// more than one marker gets a numbered file for each, in marker order, so
+/// none overwrites another. Returns the files written.
+pub fn split_session(
+    project: &mut Project,
+    markers_path: &Path,
+) -> Result<Vec<PathBuf>, RecorderError> {
+    let markers = read_markers(markers_path)?;
+    let directory = markers_path.parent().unwrap_or(Path::new(""));
+    let recording_path = directory.join(&markers.recording);
+    let file = storage::open(&recording_path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    let mut recording = WavReader::new(file)?;
+    let spec = recording.spec();
+    let takes_per_sentence = project.metadata.recording_settings.takes_per_sentence;
+    let recordings_dir = project
+        .metadata
+        .layout
+        .recordings_path(Path::new(&project.metadata.directory));
+    let mut written = Vec::new();
+    let mut marker_counts: HashMap<usize, u32> = HashMap::new();
+    for marker in &markers.markers {
+        *marker_counts.entry(marker.sentence_id).or_insert(0) += 1;
+    }
+    let mut take_numbers: HashMap<usize, u32> = HashMap::new();
+
+    for marker in &markers.markers {
+        let Some(sentence) = project
+            .sentences
+            .iter_mut()
+            .find(|s| s.id == marker.sentence_id)
+        else {
+            warn!("Marker for unknown sentence {}", marker.sentence_id);
+            continue;
+        };
+        let frames = u64::from(recording.duration());
+        let (start, end) = (marker.start_frame.min(frames), marker.end_frame.min(frames));
+        let samples = read_span(&mut recording, start, end)?;
+
+        let stem = take_file_stem(sentence);
+        let take_number = take_numbers.entry(sentence.id).or_insert(0);
+        *take_number += 1;
+        let numbered = takes_per_sentence > 1 || marker_counts[&sentence.id] > 1;
+        let file_name = take_wav_name(&stem, numbered.then_some(*take_number));
+        let path = recordings_dir.join(file_name);
+        let pcm: Vec<i16> = samples
+            .iter()
+            .map(|&s| (s * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+            .collect();
+        let frames_to_duration = |frames: u64| {
+            Duration::from_secs_f64(frames as f64 / markers.sample_rate.max(1) as f64)
+        };
+        let stats = measure_take(
+            &pcm,
+            spec.channels,
+            markers.sample_rate,
+            frames_to_duration(marker.speech_start_frame.saturating_sub(start)),
+            frames_to_duration(end.saturating_sub(marker.speech_end_frame)),
+        );
+        write_wav(&path, &AudioBuffer { spec, samples })?;
+        let metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+            .project_directory(&project.metadata.directory);
+        if let Err(e) = embed_metadata(&path, &metadata) {
+            error!("Failed to embed metadata in {}: {}", path.display(), e);
+        }
+
+        sentence.audio_file_path = Some(path.to_string_lossy().to_string());
+        sentence.recorded = true;
+        sentence.skipped = false;
+        sentence.rerecord = None;
+        sentence.stats = Some(stats);
+        if sentence.status == SentenceStatus::Unrecorded {
+            sentence.status = SentenceStatus::Recorded;
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,201 @@
+use super::editing::{ensure_backup, read_wav, write_wav, AudioBuffer};
+use super::errors::RecorderError;
+use super::utils::wav_spec;
+use crate::models::{ChannelMode, RecordingSettings};
+use crate::presets::AudioFormat;
+use crate::storage;
+use hound::{SampleFormat as HoundSampleFormat, WavSpec};
+use log::debug;
+use samplerate::ConverterType;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder in the project directory that imported files are copied to
+/// unchanged.
+pub const ORIGINALS_DIRECTORY: &str = "originals";
+
+/// Resampler used when converting recorded audio.
+pub(super) const CONVERSION_RESAMPLER: ConverterType = ConverterType::SincBestQuality;
+
+/// Target parameters for re-encoding recorded audio.
+#[derive(Debug, Clone, Copy)]
+pub struct ConversionTarget {
+    pub sample_rate: u32,
+    pub format: AudioFormat,
+    pub bit_depth: u16,
+    /// Channels to mix down or up to, or `None` to keep each file's own.
+    pub channels: Option<u16>,
+}
+
+impl ConversionTarget {
+    fn validate(&self) -> Result<(), RecorderError> {
+        if self.format != AudioFormat::Wav {
+            return Err(RecorderError::Other(format!(
+                "Conversion to {:?} is not supported",
+                self.format
+            )));
+        }
+        if ![16, 24, 32].contains(&self.bit_depth) {
+            return Err(RecorderError::Other(format!(
+                "Unsupported bit depth: {}",
+                self.bit_depth
+            )));
+        }
+        if self.sample_rate == 0 {
+            return Err(RecorderError::Other("Sample rate must be non-zero".into()));
+        }
+        if self.channels == Some(0) {
+            return Err(RecorderError::Other("Channels must be non-zero".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Resamples an audio buffer to `sample_rate`, keeping its channel layout.
+pub fn resample(buffer: &AudioBuffer, sample_rate: u32) -> Result<Vec<f32>, RecorderError> {
+    if buffer.spec.sample_rate == sample_rate {
+        return Ok(buffer.samples.clone());
+    }
+    samplerate::convert(
+        buffer.spec.sample_rate,
+        sample_rate,
+        buffer.spec.channels as usize,
+        CONVERSION_RESAMPLER,
+        &buffer.samples,
+    )
+    .map_err(|e| RecorderError::Other(format!("Failed to resample: {}", e)))
+}
+
+/// Re-encodes a single file to the target spec and returns the path of the
+/// converted file. The original is kept beside it as a `.bak`, and the
+/// converted audio replaces it only once fully written.
+pub fn convert_file(path: &Path, target: ConversionTarget) -> Result<PathBuf, RecorderError> {
+    target.validate()?;
+    let buffer = read_wav(path)?;
+    let channels = target.channels.unwrap_or(buffer.spec.channels);
+    debug!(
+        "Converting {:?} from {} Hz/{} ch/{} bit to {} Hz/{} ch/{} bit",
+        path,
+        buffer.spec.sample_rate,
+        buffer.spec.channels,
+        buffer.spec.bits_per_sample,
+        target.sample_rate,
+        channels,
+        target.bit_depth
+    );
+
+    let samples = resample(&buffer, target.sample_rate)?;
+    let samples = remix(&samples, buffer.spec.channels, channels);
+    let converted = AudioBuffer {
+        spec: WavSpec {
+            channels,
+            sample_rate: target.sample_rate,
+            bits_per_sample: target.bit_depth,
+            sample_format: HoundSampleFormat::Int,
+        },
+        samples,
+    };
+
+    let output_path = path.with_extension("wav");
+    ensure_backup(path)?;
+    write_wav(&output_path, &converted)?;
+    Ok(output_path)
+}
+
+/// Spec of the project's own takes for audio recorded elsewhere with
+/// `source`. Settings the project leaves open keep the source's value.
+fn project_spec(source: WavSpec, settings: &RecordingSettings) -> WavSpec {
+    let channels = match settings.channel_mode {
+        ChannelMode::Input => source.channels,
+        ChannelMode::Mono => 1,
+        ChannelMode::Stereo => 2,
+    };
+    let sample_rate = settings.sample_rate.unwrap_or(source.sample_rate);
+    wav_spec(channels, sample_rate, settings.output_format)
+}
+
+/// Converts interleaved audio between channel counts the same way
+/// `remix_channels` does for recorded audio.
+pub(super) fn remix(samples: &[f32], from: u16, to: u16) -> Vec<f32> {
+    let (from, to) = (from.max(1) as usize, to.max(1) as usize);
+    if from == to {
+        return samples.to_vec();
+    }
+    let mut output = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            output.push(frame.iter().sum::<f32>() / from as f32);
+        } else if from == 1 {
+            output.extend(std::iter::repeat(frame[0]).take(to));
+        } else {
+            output.extend((0..to).map(|c| frame.get(c).copied().unwrap_or(0.0)));
+        }
+    }
+    output
+}
+
+/// Path in `originals_dir` for a copy of `source`, numbered if an original
+/// of the same name was imported before, e.g. from another folder.
+fn original_path(originals_dir: &Path, source: &Path) -> Result<PathBuf, RecorderError> {
+    let stem = source
+        .file_stem()
+        .ok_or_else(|| RecorderError::Other(format!("Not a file: {}", source.display())))?
+        .to_string_lossy();
+    let extension = source
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut path = originals_dir.join(format!("{}{}", stem, extension));
+    let mut copy = 1;
+    while path.exists() {
+        copy += 1;
+        path = originals_dir.join(format!("{}_{}{}", stem, copy, extension));
+    }
+    Ok(path)
+}
+
+/// Imports a WAV file recorded outside the app as the take at `take_path`,
+/// converted to the project's sample rate, channels and sample format. The
+/// file is first copied unchanged into `originals_dir`, without replacing
+/// an earlier original of the same name.
+pub fn import_file(
+    source: &Path,
+    take_path: &Path,
+    originals_dir: &Path,
+    settings: &RecordingSettings,
+) -> Result<(), RecorderError> {
+    fs::create_dir_all(originals_dir)?;
+    storage::write(&original_path(originals_dir, source)?, &fs::read(source)?)
+        .map_err(|e| RecorderError::Other(e.to_string()))?;
+
+    let buffer = read_wav(source)?;
+    debug!("Importing {:?}", source);
+    write_wav(take_path, &to_project_format(&buffer, settings)?)
+}
+
+/// Whether audio with `spec` is already in the format of the project's own
+/// takes.
+pub fn in_project_format(spec: WavSpec, settings: &RecordingSettings) -> bool {
+    project_spec(spec, settings) == spec
+}
+
+/// Converts audio recorded elsewhere to the project's sample rate, channels
+/// and sample format.
+pub fn to_project_format(
+    buffer: &AudioBuffer,
+    settings: &RecordingSettings,
+) -> Result<AudioBuffer, RecorderError> {
+    let spec = project_spec(buffer.spec, settings);
+    debug!(
+        "Converting from {} Hz/{} ch/{} bit to {} Hz/{} ch/{} bit",
+        buffer.spec.sample_rate,
+        buffer.spec.channels,
+        buffer.spec.bits_per_sample,
+        spec.sample_rate,
+        spec.channels,
+        spec.bits_per_sample
+    );
+    let samples = resample(buffer, spec.sample_rate)?;
+    let samples = remix(&samples, buffer.spec.channels, spec.channels);
+    Ok(AudioBuffer { spec, samples })
+}
@@ -0,0 +1,115 @@
+use super::errors::RecorderError;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat, StreamConfig};
+use log::error;
+use serde::Serialize;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Length of a cue beep.
+const BEEP_DURATION: Duration = Duration::from_millis(150);
+/// Fade at either end of a beep, so it doesn't click.
+const BEEP_FADE: Duration = Duration::from_millis(10);
+/// How often the end of a beep is checked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Longest a beep is waited for, in case the device stops pulling samples.
+const BEEP_TIMEOUT: Duration = Duration::from_secs(1);
+/// Allowance after the last sample is handed to the device, for its buffer
+/// and the room's echo to die away.
+const BEEP_TAIL: Duration = Duration::from_millis(150);
+
+/// Moment in a session the narrator is cued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cue {
+    /// The recorder is listening for the sentence; start speaking.
+    Armed,
+    /// The take has been captured.
+    Captured,
+}
+
+impl Cue {
+    /// Pitch of the cue's beep: high to start, low to finish.
+    fn frequency(self) -> f32 {
+        match self {
+            Cue::Armed => 880.0,
+            Cue::Captured => 440.0,
+        }
+    }
+}
+
+/// Samples of the cue's beep at `volume`, as a mono signal.
+pub(super) fn beep(cue: Cue, sample_rate: u32, volume: f32) -> Vec<f32> {
+    let length = (BEEP_DURATION.as_secs_f32() * sample_rate as f32) as usize;
+    let fade = (BEEP_FADE.as_secs_f32() * sample_rate as f32).max(1.0);
+    (0..length)
+        .map(|i| {
+            let envelope = (i as f32 / fade).min((length - i) as f32 / fade).min(1.0);
+            let phase = 2.0 * PI * cue.frequency() * i as f32 / sample_rate as f32;
+            phase.sin() * envelope * volume.clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+fn build_output_stream<T: Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    samples: Vec<f32>,
+    finished: Arc<AtomicBool>,
+) -> Result<cpal::Stream, RecorderError> {
+    let channels = config.channels.max(1) as usize;
+    let mut position = 0;
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                let sample = samples.get(position).copied().unwrap_or(0.0);
+                position += 1;
+                frame.fill(T::from(&sample));
+            }
+            if position >= samples.len() {
+                finished.store(true, Ordering::Relaxed);
+            }
+        },
+        |e| error!("Cue playback failed: {}", e),
+    )?;
+    Ok(stream)
+}
+
+/// Plays the cue's beep on the default output device, returning once the
+/// device has played it and it has died away, so a take started afterwards
+/// doesn't capture it.
+pub fn play_cue(cue: Cue, volume: f32) -> Result<(), RecorderError> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| RecorderError::Other("No output device available".to_string()))?;
+    let supported_config = device.default_output_config()?;
+    let config = supported_config.config();
+    let samples = beep(cue, config.sample_rate.0, volume);
+    let finished = Arc::new(AtomicBool::new(false));
+    let stream = match supported_config.sample_format() {
+        SampleFormat::F32 => {
+            build_output_stream::<f32>(&device, &config, samples, Arc::clone(&finished))?
+        }
+        SampleFormat::I16 => {
+            build_output_stream::<i16>(&device, &config, samples, Arc::clone(&finished))?
+        }
+        SampleFormat::U16 => {
+            build_output_stream::<u16>(&device, &config, samples, Arc::clone(&finished))?
+        }
+        _ => {
+            return Err(RecorderError::Other(
+                "Unsupported output sample format".to_string(),
+            ))
+        }
+    };
+    stream.play()?;
+    let started = Instant::now();
+    while !finished.load(Ordering::Relaxed) && started.elapsed() < BEEP_DURATION + BEEP_TIMEOUT {
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    std::thread::sleep(BEEP_TAIL);
+    Ok(())
+}
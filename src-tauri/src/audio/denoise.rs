@@ -0,0 +1,90 @@
+use log::{error, trace};
+use nnnoiseless::DenoiseState;
+use samplerate::ConverterType;
+
+/// The denoiser model only operates on 48 kHz audio.
+pub(super) const DENOISE_SAMPLE_RATE: u32 = 48000;
+/// Resampler used to convert to and from `DENOISE_SAMPLE_RATE`.
+pub(super) const DENOISE_RESAMPLER: ConverterType = ConverterType::SincMediumQuality;
+
+/// Suppresses background noise in interleaved 16-bit audio using an
+/// RNNoise-style denoiser. `strength` blends the denoised signal with the
+/// original, from 0.0 (untouched) to 1.0 (fully denoised).
+pub fn reduce_noise(samples: &[i16], channels: u16, sample_rate: u32, strength: f32) -> Vec<i16> {
+    let strength = strength.clamp(0.0, 1.0);
+    let channels = channels.max(1) as usize;
+    if strength == 0.0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let mut output = samples.to_vec();
+    for channel in 0..channels {
+        let dry: Vec<f32> = samples
+            .iter()
+            .skip(channel)
+            .step_by(channels)
+            .map(|&s| s as f32)
+            .collect();
+
+        let wet = match denoise_channel(&dry, sample_rate) {
+            Ok(wet) => wet,
+            Err(e) => {
+                error!("Noise reduction failed, keeping original audio: {}", e);
+                return samples.to_vec();
+            }
+        };
+
+        for (i, sample) in output
+            .iter_mut()
+            .skip(channel)
+            .step_by(channels)
+            .enumerate()
+        {
+            let mixed = dry[i] * (1.0 - strength) + wet[i] * strength;
+            *sample = mixed.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+    output
+}
+
+/// Denoises a single channel of samples in the i16 range, resampling to and
+/// from the denoiser's rate if needed. The result has the same length as
+/// the input.
+fn denoise_channel(samples: &[f32], sample_rate: u32) -> Result<Vec<f32>, samplerate::Error> {
+    let input = if sample_rate == DENOISE_SAMPLE_RATE {
+        samples.to_vec()
+    } else {
+        samplerate::convert(
+            sample_rate,
+            DENOISE_SAMPLE_RATE,
+            1,
+            DENOISE_RESAMPLER,
+            samples,
+        )?
+    };
+
+    let mut state = DenoiseState::new();
+    let mut denoised = Vec::with_capacity(input.len());
+    let mut frame_out = [0.0f32; DenoiseState::FRAME_SIZE];
+    for frame in input.chunks(DenoiseState::FRAME_SIZE) {
+        let mut frame_in = [0.0f32; DenoiseState::FRAME_SIZE];
+        frame_in[..frame.len()].copy_from_slice(frame);
+        state.process_frame(&mut frame_out, &frame_in);
+        denoised.extend_from_slice(&frame_out[..frame.len()]);
+    }
+    trace!("Denoised {} samples", denoised.len());
+
+    let mut output = if sample_rate == DENOISE_SAMPLE_RATE {
+        denoised
+    } else {
+        samplerate::convert(
+            DENOISE_SAMPLE_RATE,
+            sample_rate,
+            1,
+            DENOISE_RESAMPLER,
+            &denoised,
+        )?
+    };
+    output.resize(samples.len(), 0.0);
+    Ok(output)
+}
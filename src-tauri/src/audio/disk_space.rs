@@ -0,0 +1,110 @@
+use super::auto_record::AutoRecordState;
+use super::config::RecordingState;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use crate::errors::{ErrorKind, RecordrError};
+use log::{debug, warn};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Free space below which manual recordings are refused.
+pub const DEFAULT_MIN_FREE_SPACE_MB: u64 = 500;
+
+/// How often a session checks the free space on its volume.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Free space, in MB, on the volume that holds `path`. The path need not
+/// exist yet; its nearest existing ancestor is checked.
+fn available_space_mb(path: &Path) -> io::Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    Ok(fs2::available_space(existing)? / BYTES_PER_MB)
+}
+
+/// Refuses to start recording to `path` with less than `min_free_space_mb`
+/// free on its volume. Zero disables the check, and a volume whose free
+/// space can't be read is not refused.
+pub fn check_free_space(path: &Path, min_free_space_mb: u64) -> Result<(), RecordrError> {
+    if min_free_space_mb == 0 {
+        return Ok(());
+    }
+    match available_space_mb(path) {
+        Ok(free_mb) if free_mb < min_free_space_mb => Err(RecordrError::new(
+            ErrorKind::DiskFull,
+            format!(
+                "Only {} MB free on the recording volume, below the {} MB required",
+                free_mb, min_free_space_mb
+            ),
+        )
+        .with_context(path.display().to_string())),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            warn!("Failed to check free space for {}: {}", path.display(), e);
+            Ok(())
+        }
+    }
+}
+
+/**
+ * Watches the free space on the session's volume until the session ends,
+ * pausing it with a `low_disk_space` event once the space drops below
+ * `min_free_space_mb`, so a take is never cut off by the disk filling up.
+ * The paused take is kept, as after a lost device, and the check applies
+ * again once the session is resumed.
+ */
+pub fn watch_disk_space(
+    state_arc: Arc<Mutex<AutoRecordState>>,
+    events: Arc<dyn RecorderEvents>,
+    min_free_space_mb: u64,
+) {
+    if min_free_space_mb == 0 {
+        return;
+    }
+    let (directory, cancel) = {
+        let state = state_arc.lock().unwrap();
+        (
+            PathBuf::from(&state.project_directory),
+            state.cancel_token.clone(),
+        )
+    };
+    std::thread::spawn(move || {
+        let mut last_check: Option<Instant> = None;
+        while !cancel.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(100));
+            if last_check.is_some_and(|checked| checked.elapsed() < CHECK_INTERVAL) {
+                continue;
+            }
+            last_check = Some(Instant::now());
+
+            let recording = match state_arc.lock().unwrap().state {
+                RecordingState::Idle => break,
+                RecordingState::Paused => false,
+                RecordingState::Recording => true,
+            };
+            if !recording {
+                continue;
+            }
+            let free_mb = match available_space_mb(&directory) {
+                Ok(free_mb) if free_mb < min_free_space_mb => free_mb,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Failed to check free space: {}", e);
+                    continue;
+                }
+            };
+            let mut state = state_arc.lock().unwrap();
+            if state.pause_recording().is_ok() {
+                debug!("{} MB free, pausing auto-record", free_mb);
+                state.low_disk_space = true;
+                AutoRecordEvent::new(AutoRecordEventKind::LowDiskSpace, &state.session_id)
+                    .free_space(free_mb)
+                    .emit(events.as_ref());
+            }
+        }
+    });
+}
@@ -0,0 +1,154 @@
+use super::errors::RecorderError;
+use super::metadata::carry_metadata;
+use crate::storage;
+use hound::{SampleFormat as HoundSampleFormat, WavReader, WavSpec, WavWriter};
+use log::debug;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// Samples of a WAV file, normalized to `[-1.0, 1.0]` and interleaved by
+/// channel.
+pub struct AudioBuffer {
+    pub spec: WavSpec,
+    pub samples: Vec<f32>,
+}
+
+impl AudioBuffer {
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.spec.channels.max(1) as usize
+    }
+
+    fn ms_to_frames(&self, ms: u64) -> usize {
+        (ms as u128 * self.spec.sample_rate as u128 / 1000) as usize
+    }
+}
+
+/// Reads a WAV file of any integer or float sample format, decrypting it if
+/// it belongs to an encrypted project.
+pub fn read_wav(path: &Path) -> Result<AudioBuffer, RecorderError> {
+    let file = storage::open(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    let mut reader = WavReader::new(file)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        HoundSampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        HoundSampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(AudioBuffer { spec, samples })
+}
+
+/// Writes the buffer to `path` using the buffer's spec, encrypted if the
+/// file belongs to an encrypted project. Metadata embedded in the file
+/// being replaced is kept.
+pub fn write_wav(path: &Path, buffer: &AudioBuffer) -> Result<(), RecorderError> {
+    let mut data = Cursor::new(Vec::new());
+    {
+        let mut writer = WavWriter::new(&mut data, buffer.spec)?;
+        match buffer.spec.sample_format {
+            HoundSampleFormat::Float => {
+                for &sample in &buffer.samples {
+                    writer.write_sample(sample)?;
+                }
+            }
+            HoundSampleFormat::Int => {
+                let max = ((1i64 << (buffer.spec.bits_per_sample - 1)) - 1) as f32;
+                for &sample in &buffer.samples {
+                    let value = (sample * (max + 1.0)).round().clamp(-max - 1.0, max);
+                    writer.write_sample(value as i32)?;
+                }
+            }
+        }
+        writer.finalize()?;
+    }
+    let mut data = data.into_inner();
+    if path.exists() {
+        let existing = storage::read(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+        if let Ok(carried) = carry_metadata(&existing, &data) {
+            data = carried;
+        }
+    }
+    storage::write(path, &data).map_err(|e| RecorderError::Other(e.to_string()))
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Keeps a copy of the original file so edits can be undone. An existing
+/// backup is kept, so undo always restores the unedited recording.
+pub(super) fn ensure_backup(path: &Path) -> Result<(), RecorderError> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        debug!("Creating backup {:?}", backup);
+        fs::copy(path, &backup)?;
+    }
+    Ok(())
+}
+
+/// Trims the file to the range between `start_ms` and `end_ms`.
+pub fn trim_audio(path: &Path, start_ms: u64, end_ms: u64) -> Result<(), RecorderError> {
+    if end_ms <= start_ms {
+        return Err(RecorderError::Other(
+            "End of trim range must be after the start".into(),
+        ));
+    }
+
+    let mut buffer = read_wav(path)?;
+    let frames = buffer.frame_count();
+    let start = buffer.ms_to_frames(start_ms).min(frames);
+    let end = buffer.ms_to_frames(end_ms).min(frames);
+    if start >= end {
+        return Err(RecorderError::Other(
+            "Trim range is outside the audio".into(),
+        ));
+    }
+
+    ensure_backup(path)?;
+    let channels = buffer.spec.channels as usize;
+    buffer.samples = buffer.samples[start * channels..end * channels].to_vec();
+    write_wav(path, &buffer)
+}
+
+/// Applies a linear fade in and fade out of the given lengths.
+pub fn apply_fade(path: &Path, fade_in_ms: u64, fade_out_ms: u64) -> Result<(), RecorderError> {
+    let mut buffer = read_wav(path)?;
+    let frames = buffer.frame_count();
+    let fade_in = buffer.ms_to_frames(fade_in_ms).min(frames);
+    let fade_out = buffer.ms_to_frames(fade_out_ms).min(frames);
+    let channels = buffer.spec.channels as usize;
+
+    ensure_backup(path)?;
+    for frame in 0..frames {
+        let mut gain = 1.0;
+        if frame < fade_in {
+            gain *= frame as f32 / fade_in as f32;
+        }
+        if frame >= frames - fade_out {
+            gain *= (frames - frame) as f32 / fade_out as f32;
+        }
+        if gain < 1.0 {
+            for sample in &mut buffer.samples[frame * channels..(frame + 1) * channels] {
+                *sample *= gain;
+            }
+        }
+    }
+    write_wav(path, &buffer)
+}
+
+/// Restores the original recording from its backup, discarding all edits.
+pub fn undo_audio_edit(path: &Path) -> Result<(), RecorderError> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(RecorderError::Other("No edits to undo".into()));
+    }
+    fs::rename(&backup, path)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use super::convert::CONVERSION_RESAMPLER;
+use super::denoise::{DENOISE_RESAMPLER, DENOISE_SAMPLE_RATE};
+use super::playback::PLAYBACK_RESAMPLER;
+use super::stream::{get_chunk_size, VAD_RESAMPLER, VAD_SAMPLE_RATE, VAD_VOICE_PROBABILITY};
+use samplerate::ConverterType;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How recorded audio is processed, so datasets can document it.
+#[derive(Debug, Serialize)]
+pub struct EngineInfo {
+    pub app_version: &'static str,
+    /// Versions of the audio libraries the app depends on directly. `None`
+    /// when the version was not known at build time.
+    pub libraries: BTreeMap<&'static str, Option<&'static str>>,
+    /// Voice activity model bundled with `voice_activity_detector`.
+    pub vad_model: &'static str,
+    pub vad_sample_rate: usize,
+    /// Samples the VAD analyses at a time.
+    pub vad_chunk_size: usize,
+    pub vad_voice_probability: f32,
+    /// libsamplerate converter used for each resampling step.
+    pub vad_resampler: &'static str,
+    pub conversion_resampler: &'static str,
+    pub denoise_resampler: &'static str,
+    pub denoise_sample_rate: u32,
+    /// Used when a reference clip doesn't match the output device's rate.
+    pub playback_resampler: &'static str,
+}
+
+fn converter_name(converter: ConverterType) -> &'static str {
+    match converter {
+        ConverterType::SincBestQuality => "sinc_best_quality",
+        ConverterType::SincMediumQuality => "sinc_medium_quality",
+        ConverterType::SincFastest => "sinc_fastest",
+        ConverterType::ZeroOrderHold => "zero_order_hold",
+        ConverterType::Linear => "linear",
+    }
+}
+
+pub fn engine_info() -> EngineInfo {
+    let libraries = BTreeMap::from([
+        ("cpal", option_env!("RECORDR_VERSION_CPAL")),
+        ("hound", option_env!("RECORDR_VERSION_HOUND")),
+        ("nnnoiseless", option_env!("RECORDR_VERSION_NNNOISELESS")),
+        ("samplerate", option_env!("RECORDR_VERSION_SAMPLERATE")),
+        (
+            "voice_activity_detector",
+            option_env!("RECORDR_VERSION_VOICE_ACTIVITY_DETECTOR"),
+        ),
+    ]);
+    EngineInfo {
+        app_version: env!("CARGO_PKG_VERSION"),
+        libraries,
+        vad_model: "silero_vad",
+        vad_sample_rate: VAD_SAMPLE_RATE,
+        vad_chunk_size: get_chunk_size(VAD_SAMPLE_RATE).unwrap_or_default(),
+        vad_voice_probability: VAD_VOICE_PROBABILITY,
+        vad_resampler: converter_name(VAD_RESAMPLER),
+        conversion_resampler: converter_name(CONVERSION_RESAMPLER),
+        denoise_resampler: converter_name(DENOISE_RESAMPLER),
+        denoise_sample_rate: DENOISE_SAMPLE_RATE,
+        playback_resampler: converter_name(PLAYBACK_RESAMPLER),
+    }
+}
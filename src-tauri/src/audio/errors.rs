@@ -4,6 +4,7 @@ use std::fmt;
 pub enum RecorderError {
     RecordingPaused,
     RecordingStopped,
+    Cancelled,
     IoError(std::io::Error),
     CpalStreamError(cpal::StreamError),
     CpalBuildStreamError(cpal::BuildStreamError),
@@ -11,6 +12,14 @@ pub enum RecorderError {
     CpalDefaultStreamConfigError(cpal::DefaultStreamConfigError),
     HoundError(hound::Error),
     StreamPlayError(String),
+    /// The take's speech lasted this many milliseconds, less than the
+    /// minimum take duration.
+    TakeTooShort(u64),
+    /// Voice was detected in the take for this many milliseconds in total,
+    /// too little for it to hold the sentence.
+    EmptyTake(u64),
+    /// Silence was not reached within the maximum take duration.
+    TakeTimedOut,
     Other(String),
 }
 
@@ -19,6 +28,7 @@ impl fmt::Display for RecorderError {
         match self {
             RecorderError::RecordingPaused => write!(f, "Recording paused"),
             RecorderError::RecordingStopped => write!(f, "Recording stopped"),
+            RecorderError::Cancelled => write!(f, "Recording cancelled"),
             RecorderError::IoError(e) => write!(f, "I/O error: {}", e),
             RecorderError::CpalStreamError(e) => write!(f, "Audio stream error: {}", e),
             RecorderError::CpalBuildStreamError(e) => {
@@ -32,6 +42,9 @@ impl fmt::Display for RecorderError {
             }
             RecorderError::HoundError(e) => write!(f, "Audio processing error: {}", e),
             RecorderError::StreamPlayError(e) => write!(f, "Failed to play stream: {}", e),
+            RecorderError::TakeTooShort(ms) => write!(f, "Take too short ({} ms of speech)", ms),
+            RecorderError::EmptyTake(ms) => write!(f, "Take has no speech ({} ms of voice)", ms),
+            RecorderError::TakeTimedOut => write!(f, "Take exceeded the maximum duration"),
             RecorderError::Other(msg) => write!(f, "{}", msg),
         }
     }
@@ -0,0 +1,412 @@
+use super::cues::Cue;
+use crate::models::{Sentence, SentenceStats, SentenceStatus};
+use chrono::{DateTime, Utc};
+use crossbeam_channel::Sender;
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// Window event carrying every auto-record notification.
+pub const AUTO_RECORD_EVENT: &str = "auto-record-event";
+
+/// How often coalesced events are delivered while the window is hidden.
+const HIDDEN_FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// How long the window's visibility is trusted before it is checked again.
+const VISIBILITY_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoRecordEventKind {
+    SentenceStarted,
+    CountdownTick,
+    SentenceFinished,
+    SentenceSkipped,
+    TakeRejected,
+    /// The take had little or no voice in it and was deleted; the same
+    /// sentence is armed again.
+    EmptyTake,
+    TakeTimeout,
+    SessionProgress,
+    AwaitingConfirmation,
+    Paused,
+    /// The session paused itself at the end of a sprint.
+    RestBreak,
+    /// The session used up its time or sentence quota and is ending.
+    SessionQuotaReached,
+    Resumed,
+    Suspended,
+    DeviceLost,
+    /// The session paused itself as the project volume is nearly full.
+    LowDiskSpace,
+    /// The narrator is cued to start speaking or that the take is done.
+    Cue,
+    /// The speech level of recent takes moved away from the baseline.
+    LevelDrift,
+    /// The take just recorded sounds like another sentence's take, so it
+    /// waits for confirmation.
+    DuplicateSpeech,
+    /// Monitor mode heard speech start.
+    VoiceDetected,
+    /// Monitor mode heard the end of a segment that would have been a take.
+    SilenceDetected,
+    /// Monitor mode's input level, reported several times a second.
+    Level,
+    Complete,
+}
+
+/// Payload of `auto-record-event`. Every event carries the ID of the session
+/// that emitted it, so the frontend can ignore late events from a session it
+/// has already stopped.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoRecordEvent {
+    pub kind: AutoRecordEventKind,
+    pub session_id: String,
+    pub sentence_id: Option<usize>,
+    /// Prompt text, only included when `emit_prompt_text` is enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    /// Time left before the recorder starts listening, on countdown ticks,
+    /// or the suggested rest, on rest breaks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_ms: Option<u64>,
+    /// Number of the take of the sentence, when several are recorded per
+    /// sentence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub take_number: Option<u32>,
+    pub audio_file_path: Option<String>,
+    pub duration_ms: Option<u64>,
+    /// Peak absolute sample value of the take, from 0.0 to 1.0.
+    pub peak_level: Option<f32>,
+    pub stats: Option<SentenceStats>,
+    /// How far the rolling speech level is from the baseline in dB,
+    /// positive when louder, on level drift events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level_drift_db: Option<f32>,
+    /// Sentence whose take the take sounds like, and how alike they are
+    /// from 0.0 to 1.0, on duplicate speech events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f32>,
+    /// What the narrator is cued for, on cue events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cue: Option<Cue>,
+    /// Free space left on the project volume in MB, on low disk space
+    /// events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_space_mb: Option<u64>,
+    /// Pacing of the session, on progress events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<SessionProgress>,
+    /// Review status, notes and tags of the sentence, on finish events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<SentenceStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// How far a session has got, reported after each sentence.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionProgress {
+    /// Sentences finished in this session.
+    pub completed: usize,
+    pub remaining: usize,
+    /// Average length of the takes kept so far.
+    pub average_take_ms: u64,
+    /// Average time spent per sentence, including reading the prompt,
+    /// retakes and pauses.
+    pub average_sentence_ms: u64,
+    /// Estimated time until the last sentence is done.
+    pub eta_ms: u64,
+}
+
+impl AutoRecordEvent {
+    pub fn new(kind: AutoRecordEventKind, session_id: &str) -> Self {
+        Self {
+            kind,
+            session_id: session_id.to_string(),
+            sentence_id: None,
+            text: None,
+            timestamp: Utc::now(),
+            remaining_ms: None,
+            take_number: None,
+            audio_file_path: None,
+            duration_ms: None,
+            peak_level: None,
+            stats: None,
+            level_drift_db: None,
+            duplicate_of: None,
+            similarity: None,
+            cue: None,
+            free_space_mb: None,
+            progress: None,
+            status: None,
+            notes: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn sentence(mut self, sentence_id: usize) -> Self {
+        self.sentence_id = Some(sentence_id);
+        self
+    }
+
+    pub fn text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn remaining(mut self, remaining: Duration) -> Self {
+        self.remaining_ms = Some(remaining.as_millis() as u64);
+        self
+    }
+
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis() as u64);
+        self
+    }
+
+    pub fn peak_level(mut self, peak_level: f32) -> Self {
+        self.peak_level = Some(peak_level);
+        self
+    }
+
+    pub fn take_number(mut self, take_number: Option<u32>) -> Self {
+        self.take_number = take_number;
+        self
+    }
+
+    pub fn audio_file_path(mut self, audio_file_path: String) -> Self {
+        self.audio_file_path = Some(audio_file_path);
+        self
+    }
+
+    pub fn stats(mut self, stats: SentenceStats) -> Self {
+        self.duration_ms = Some(stats.duration_ms);
+        self.peak_level = Some(10f32.powf(stats.peak_dbfs / 20.0));
+        self.stats = Some(stats);
+        self
+    }
+
+    pub fn level_drift(mut self, drift_db: f32) -> Self {
+        self.level_drift_db = Some(drift_db);
+        self
+    }
+
+    pub fn duplicate_of(mut self, sentence_id: usize, similarity: f32) -> Self {
+        self.duplicate_of = Some(sentence_id);
+        self.similarity = Some(similarity);
+        self
+    }
+
+    pub fn cue(mut self, cue: Cue) -> Self {
+        self.cue = Some(cue);
+        self
+    }
+
+    pub fn free_space(mut self, free_space_mb: u64) -> Self {
+        self.free_space_mb = Some(free_space_mb);
+        self
+    }
+
+    pub fn progress(mut self, progress: SessionProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn review(mut self, sentence: &Sentence) -> Self {
+        self.status = Some(sentence.status);
+        self.notes = sentence.notes.clone();
+        self.tags = sentence.tags.clone();
+        self
+    }
+
+    pub fn emit(self, events: &dyn RecorderEvents) {
+        events.send(self);
+    }
+}
+
+/// Receives the notifications of an auto-record session, so the recorder
+/// does not depend on where they are delivered.
+pub trait RecorderEvents: Send + Sync {
+    fn send(&self, event: AutoRecordEvent);
+}
+
+/// Forwards events over a channel, so a session can be observed without a
+/// window, e.g. when driving the auto-record loop from tests.
+impl RecorderEvents for Sender<AutoRecordEvent> {
+    fn send(&self, event: AutoRecordEvent) {
+        // A dropped receiver only means nobody is listening any more.
+        let _ = Sender::send(self, event);
+    }
+}
+
+/// Delivers events to the frontend as `auto-record-event`.
+impl RecorderEvents for tauri::Window {
+    fn send(&self, event: AutoRecordEvent) {
+        Emitter::emit(self, AUTO_RECORD_EVENT, event)
+            .unwrap_or_else(|e| warn!("Failed to emit event: {}", e));
+    }
+}
+
+/// Receives the progress of batch jobs and notices of edited files, such as
+/// `convert-progress` and `file-updated`, so jobs do not depend on where
+/// they are delivered.
+pub trait ProgressEvents: Send + Sync {
+    fn notify(&self, event: &str, payload: serde_json::Value);
+}
+
+/// Delivers progress to the frontend as a window event named after it.
+impl ProgressEvents for tauri::Window {
+    fn notify(&self, event: &str, payload: serde_json::Value) {
+        Emitter::emit(self, event, payload)
+            .unwrap_or_else(|e| warn!("Failed to emit event: {}", e));
+    }
+}
+
+/// Events held back while the window is hidden.
+struct Throttle {
+    window: tauri::Window,
+    visible: bool,
+    checked: Option<Instant>,
+    last_flush: Instant,
+    /// Latest payload of each coalesced event, keyed by what it replaces.
+    pending: HashMap<String, (String, serde_json::Value)>,
+}
+
+impl Throttle {
+    fn is_visible(&mut self) -> bool {
+        if self
+            .checked
+            .is_none_or(|checked| checked.elapsed() >= VISIBILITY_CHECK_INTERVAL)
+        {
+            self.visible = self.window.is_visible().unwrap_or(true)
+                && !self.window.is_minimized().unwrap_or(false);
+            self.checked = Some(Instant::now());
+        }
+        self.visible
+    }
+
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        if let Err(e) = Emitter::emit(&self.window, event, payload) {
+            warn!("Failed to emit {}: {}", event, e);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        for (_, (event, payload)) in std::mem::take(&mut self.pending) {
+            self.emit(&event, payload);
+        }
+    }
+}
+
+/// Delivers events to a window, easing off while it is hidden or minimized
+/// so a long session doesn't back up events nobody can see. Meanwhile
+/// progress is coalesced to its latest value, delivered every few seconds,
+/// and the per-chunk countdown and level events are dropped. Other events
+/// are delivered as they happen, after any progress held back, so their
+/// order is kept. A timer delivers what is held back every few seconds
+/// while hidden and as soon as the window is visible again, even if no
+/// further events come, and the rest is delivered when the events are
+/// dropped at the end of a job.
+pub struct WindowEvents {
+    throttle: Arc<Mutex<Throttle>>,
+}
+
+impl WindowEvents {
+    pub fn new(window: tauri::Window) -> Self {
+        let throttle = Arc::new(Mutex::new(Throttle {
+            window,
+            visible: true,
+            checked: None,
+            last_flush: Instant::now(),
+            pending: HashMap::new(),
+        }));
+        // The timer ends once the events are dropped.
+        let timer = Arc::downgrade(&throttle);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(VISIBILITY_CHECK_INTERVAL);
+            let Some(throttle) = timer.upgrade() else {
+                return;
+            };
+            let mut throttle = throttle.lock().unwrap();
+            if !throttle.pending.is_empty()
+                && (throttle.is_visible() || throttle.last_flush.elapsed() >= HIDDEN_FLUSH_INTERVAL)
+            {
+                throttle.flush();
+            }
+        });
+        Self { throttle }
+    }
+
+    /// Delivers `payload` now, or holds it back as the latest under `key`
+    /// while the window is hidden. Without a key it is always delivered,
+    /// and with `droppable` set it is dropped while hidden.
+    fn deliver(
+        &self,
+        event: &str,
+        key: Option<String>,
+        droppable: bool,
+        payload: serde_json::Value,
+    ) {
+        let mut throttle = self.throttle.lock().unwrap();
+        if throttle.is_visible() {
+            throttle.flush();
+            throttle.emit(event, payload);
+            return;
+        }
+        if droppable {
+            return;
+        }
+        match key {
+            Some(key) => {
+                throttle.pending.insert(key, (event.to_string(), payload));
+                if throttle.last_flush.elapsed() >= HIDDEN_FLUSH_INTERVAL {
+                    throttle.flush();
+                }
+            }
+            None => {
+                throttle.flush();
+                throttle.emit(event, payload);
+            }
+        }
+    }
+}
+
+impl Drop for WindowEvents {
+    fn drop(&mut self) {
+        self.throttle.lock().unwrap().flush();
+    }
+}
+
+impl RecorderEvents for WindowEvents {
+    fn send(&self, event: AutoRecordEvent) {
+        let (key, droppable) = match event.kind {
+            AutoRecordEventKind::SessionProgress => (Some(format!("{:?}", event.kind)), false),
+            AutoRecordEventKind::CountdownTick | AutoRecordEventKind::Level => (None, true),
+            _ => (None, false),
+        };
+        match serde_json::to_value(&event) {
+            Ok(payload) => self.deliver(AUTO_RECORD_EVENT, key, droppable, payload),
+            Err(e) => warn!("Failed to serialize {:?} event: {}", event.kind, e),
+        }
+    }
+}
+
+impl ProgressEvents for WindowEvents {
+    fn notify(&self, event: &str, payload: serde_json::Value) {
+        // Progress reporting a failure is never coalesced away.
+        let failed = payload.get("error").is_some_and(|error| !error.is_null());
+        let key = (event.ends_with("-progress") && !failed).then(|| event.to_string());
+        self.deliver(event, key, false, payload);
+    }
+}
@@ -0,0 +1,296 @@
+use super::editing::read_wav;
+use super::events::ProgressEvents;
+use super::metadata::{embed_metadata, AudioMetadata};
+use super::sidecar::sidecar_path;
+use super::takes::next_take_path;
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::{project_file_path, read_project, write_project};
+use crate::models::{Project, SentenceStatus};
+use crate::paths::long_path;
+use crate::storage;
+use chrono::{DateTime, Local, Utc};
+use hound::WavSpec;
+use log::{debug, error, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Folder in the project directory that takes are sent to for editing.
+const WATCH_DIRECTORY: &str = "external_edit";
+
+/// How often the watch folder is checked for the edited file.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long an edit is waited for before the watch is given up.
+const WATCH_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Watches of copies sent out for editing, by copy path, each stopped by
+/// setting its flag. There is one watch per copy, so sending the same take
+/// again replaces its watch rather than importing the edit twice.
+static WATCHES: Mutex<Vec<(PathBuf, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+/// Stops the watch of `copy`, if there is one.
+fn stop_watch(watches: &mut Vec<(PathBuf, Arc<AtomicBool>)>, copy: &Path) {
+    watches.retain(|(path, previous)| {
+        if path != copy {
+            return true;
+        }
+        debug!("Stopping the external edit watch of {:?}", copy);
+        previous.store(true, Ordering::SeqCst);
+        false
+    });
+}
+
+/// Registers a watch of `copy`, stopping any earlier one.
+fn start_watch(copy: &Path) -> Arc<AtomicBool> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut watches = WATCHES.lock().unwrap();
+    stop_watch(&mut watches, copy);
+    watches.push((copy.to_path_buf(), stopped.clone()));
+    stopped
+}
+
+/// Removes a watch that has ended, unless it was already replaced.
+fn end_watch(stopped: &Arc<AtomicBool>) {
+    WATCHES
+        .lock()
+        .unwrap()
+        .retain(|(_, watch)| !Arc::ptr_eq(watch, stopped));
+}
+
+/// Where a take edited outside the app came from, written next to it as
+/// its sidecar.
+#[derive(Debug, Serialize)]
+struct EditProvenance {
+    sentence_id: usize,
+    source: &'static str,
+    /// Take that was sent out for editing.
+    edited_from: String,
+    /// File the editor saved, in the watch folder.
+    editor_file: String,
+    sent_at: DateTime<Utc>,
+    imported_at: DateTime<Utc>,
+    app_version: &'static str,
+}
+
+/// A take sent out for editing, waiting for the edited file.
+struct PendingEdit {
+    project_file: PathBuf,
+    sentence_id: usize,
+    original: PathBuf,
+    /// Copy in the watch folder that the editor saves over.
+    copy: PathBuf,
+    spec: WavSpec,
+    sent_at: DateTime<Utc>,
+    sent_modified: SystemTime,
+}
+
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/**
+ * Copies the take of `sentence_id` to the project's `external_edit/`
+ * folder for editing in a DAW, returning the copy's path. Once the editor
+ * saves over the copy, the edited file is checked against the take's
+ * sample rate, channels and sample format and imported as a new take of
+ * the sentence, with a sidecar recording where it came from. Each import,
+ * or rejected file, is reported with an `external-edit` event. Encrypted
+ * projects are refused, as the copy would be in plain form.
+ */
+pub(crate) fn send_take_for_editing(
+    project: &Project,
+    sentence_id: usize,
+    events: Arc<dyn ProgressEvents>,
+) -> Result<PathBuf, RecordrError> {
+    if project.metadata.encrypted {
+        return Err(RecordrError::invalid_state(
+            "Takes of encrypted projects can't be edited externally",
+        ));
+    }
+    let sentence = project
+        .sentences
+        .iter()
+        .find(|s| s.id == sentence_id)
+        .ok_or_else(|| RecordrError::not_found(format!("Sentence {} not found", sentence_id)))?;
+    let original = match (&sentence.audio_file_path, sentence.recorded) {
+        (Some(path), true) => PathBuf::from(path),
+        _ => {
+            return Err(RecordrError::invalid_state(format!(
+                "Sentence {} has no take",
+                sentence_id
+            )))
+        }
+    };
+
+    let watch_dir = Path::new(&project.metadata.directory).join(WATCH_DIRECTORY);
+    fs::create_dir_all(&watch_dir)?;
+    let file_name = original
+        .file_name()
+        .ok_or_else(|| RecordrError::invalid_input("Take has no file name"))?;
+    let copy = watch_dir.join(file_name);
+    // Stopped before the copy is written again, so the earlier watch
+    // doesn't take the fresh copy for an edit.
+    stop_watch(&mut WATCHES.lock().unwrap(), &copy);
+    fs::copy(&original, &copy)
+        .map_err(|e| RecordrError::from(e).with_context(original.display().to_string()))?;
+    let spec = read_wav(&original)?.spec;
+    let (sent_modified, _) =
+        modified(&copy).ok_or_else(|| RecordrError::not_found("Copy for editing disappeared"))?;
+    debug!("Sent {:?} for external editing as {:?}", original, copy);
+
+    let pending = PendingEdit {
+        project_file: project_file_path(project),
+        sentence_id,
+        original,
+        copy: copy.clone(),
+        spec,
+        sent_at: Utc::now(),
+        sent_modified,
+    };
+    let stopped = start_watch(&copy);
+    std::thread::spawn(move || {
+        watch_edit(pending, &stopped, events.as_ref());
+        end_watch(&stopped);
+    });
+    Ok(copy)
+}
+
+/**
+ * Waits for the editor to save over the copy, importing the edit once the
+ * file has stopped changing. A file that fails validation is reported and
+ * the watch carries on, so it can be exported again. The watch ends once
+ * an edit is imported, when the copy is deleted, when the take is sent
+ * again or after `WATCH_TIMEOUT`.
+ */
+fn watch_edit(pending: PendingEdit, stopped: &AtomicBool, events: &dyn ProgressEvents) {
+    let started = SystemTime::now();
+    let mut last_seen = None;
+    let mut rejected = None;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        if started
+            .elapsed()
+            .is_ok_and(|elapsed| elapsed > WATCH_TIMEOUT)
+        {
+            debug!("Gave up waiting for an edit of {:?}", pending.copy);
+            return;
+        }
+        let Some(seen) = modified(&pending.copy) else {
+            debug!("{:?} was removed, ending the external edit", pending.copy);
+            return;
+        };
+        // The editor may still be writing until the file holds still for a
+        // whole interval.
+        if seen.0 == pending.sent_modified
+            || rejected == Some(seen)
+            || last_seen.replace(seen) != Some(seen)
+        {
+            continue;
+        }
+
+        let result = import_edit(&pending);
+        events.notify(
+            "external-edit",
+            serde_json::json!({
+                "id": pending.sentence_id,
+                "audioFilePath": result.as_ref().ok().map(|path| path.to_string_lossy()),
+                "error": result.as_ref().err().map(|e| e.to_string()),
+            }),
+        );
+        match result {
+            Ok(path) => {
+                debug!("Imported external edit as {:?}", path);
+                if let Err(e) = fs::remove_file(&pending.copy) {
+                    warn!("Failed to remove {:?}: {}", pending.copy, e);
+                }
+                return;
+            }
+            Err(e) => {
+                error!("Rejected external edit {:?}: {}", pending.copy, e);
+                rejected = Some(seen);
+            }
+        }
+    }
+}
+
+/// Checks the edited file and imports it as the sentence's new take,
+/// returning the take's path.
+fn import_edit(pending: &PendingEdit) -> Result<PathBuf, RecordrError> {
+    let edited = read_wav(&pending.copy)?;
+    let expected = pending.spec;
+    if (
+        edited.spec.sample_rate,
+        edited.spec.channels,
+        edited.spec.bits_per_sample,
+    ) != (
+        expected.sample_rate,
+        expected.channels,
+        expected.bits_per_sample,
+    ) || edited.spec.sample_format != expected.sample_format
+    {
+        return Err(RecordrError::new(
+            ErrorKind::UnsupportedFormat,
+            format!(
+                "Edited file is {} Hz/{} ch/{} bit, expected {} Hz/{} ch/{} bit",
+                edited.spec.sample_rate,
+                edited.spec.channels,
+                edited.spec.bits_per_sample,
+                expected.sample_rate,
+                expected.channels,
+                expected.bits_per_sample
+            ),
+        ));
+    }
+    if edited.frame_count() == 0 {
+        return Err(RecordrError::invalid_input("Edited file is empty"));
+    }
+
+    let mut project = read_project(&pending.project_file.to_string_lossy())?;
+    let directory = project
+        .metadata
+        .layout
+        .recordings_path(Path::new(&project.metadata.directory));
+    fs::create_dir_all(long_path(&directory))?;
+    let sentence = project
+        .sentences
+        .iter_mut()
+        .find(|s| s.id == pending.sentence_id)
+        .ok_or_else(|| {
+            RecordrError::not_found(format!("Sentence {} not found", pending.sentence_id))
+        })?;
+
+    let take_path = next_take_path(sentence, &directory)?;
+    storage::write(&take_path, &fs::read(&pending.copy)?)?;
+    let metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+        .project_directory(&project.metadata.directory);
+    embed_metadata(&take_path, &metadata)?;
+    let provenance = EditProvenance {
+        sentence_id: sentence.id,
+        source: "external_edit",
+        edited_from: pending.original.to_string_lossy().to_string(),
+        editor_file: pending.copy.to_string_lossy().to_string(),
+        sent_at: pending.sent_at,
+        imported_at: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION"),
+    };
+    storage::write(
+        &sidecar_path(&take_path),
+        serde_json::to_string_pretty(&provenance)?.as_bytes(),
+    )?;
+
+    sentence.audio_file_path = Some(take_path.to_string_lossy().to_string());
+    sentence.recorded = true;
+    sentence.skipped = false;
+    sentence.status = SentenceStatus::Recorded;
+    sentence.stats = None;
+    write_project(&project)?;
+    Ok(take_path)
+}
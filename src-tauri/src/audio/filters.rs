@@ -0,0 +1,92 @@
+use super::vad_filter::HighPass;
+use crate::models::FilterSettings;
+use std::f32::consts::PI;
+
+/// Range the high-pass cutoff is kept in: high enough to take out rumble
+/// and handling noise, low enough to leave voices alone.
+const MIN_HIGH_PASS_HZ: f32 = 80.0;
+const MAX_HIGH_PASS_HZ: f32 = 120.0;
+
+/// Cutoff of the DC blocker, well below anything audible.
+const DC_BLOCKER_HZ: f32 = 5.0;
+
+/// One-pole DC blocker of one channel.
+#[derive(Debug, Clone, Copy)]
+struct DcBlocker {
+    pole: f32,
+    /// Previous input, or `None` before the first sample. Starting from the
+    /// first sample rather than zero avoids a step at the start of a take
+    /// from an interface with a large offset.
+    x1: Option<f32>,
+    y1: f32,
+}
+
+impl DcBlocker {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            pole: 1.0 - 2.0 * PI * DC_BLOCKER_HZ / sample_rate as f32,
+            x1: None,
+            y1: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = x - self.x1.unwrap_or(x) + self.pole * self.y1;
+        self.x1 = Some(x);
+        self.y1 = y;
+        y
+    }
+}
+
+/**
+ * DC offset removal and high-pass filtering of interleaved 16-bit audio,
+ * cleaning up the bias of cheap interfaces and low-frequency rumble before
+ * the audio is written. The filters keep their state between calls, so a
+ * stream can be processed block by block; a take is processed with a
+ * chain of its own.
+ */
+#[derive(Debug, Clone)]
+pub struct FilterChain {
+    channels: usize,
+    dc_blockers: Option<Vec<DcBlocker>>,
+    high_passes: Option<Vec<HighPass>>,
+}
+
+impl FilterChain {
+    pub fn new(settings: FilterSettings, channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        let cutoff = settings
+            .high_pass_hz
+            .clamp(MIN_HIGH_PASS_HZ, MAX_HIGH_PASS_HZ);
+        Self {
+            channels,
+            dc_blockers: settings
+                .remove_dc_offset
+                .then(|| vec![DcBlocker::new(sample_rate); channels]),
+            high_passes: settings
+                .high_pass
+                .then(|| vec![HighPass::new(cutoff, sample_rate); channels]),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.dc_blockers.is_some() || self.high_passes.is_some()
+    }
+
+    pub fn process(&mut self, samples: &mut [i16]) {
+        if !self.is_active() {
+            return;
+        }
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let channel = i % self.channels;
+            let mut value = f32::from(*sample);
+            if let Some(dc_blockers) = &mut self.dc_blockers {
+                value = dc_blockers[channel].process(value);
+            }
+            if let Some(high_passes) = &mut self.high_passes {
+                value = high_passes[channel].process(value);
+            }
+            *sample = value.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use super::auto_record::AutoRecordState;
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use log::{debug, warn};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Length of each analysed frame, and the step between frames, in seconds.
+const FRAME_SECS: f32 = 0.032;
+const HOP_SECS: f32 = 0.016;
+/// Mel-spaced bands covering the voice range.
+const BANDS: usize = 20;
+const LOW_HZ: f32 = 100.0;
+const HIGH_HZ: f32 = 4000.0;
+/// Frames this far below the loudest one are taken as pauses and left out,
+/// so differences in pacing don't count.
+const SPEECH_RANGE_DB: f32 = 40.0;
+/// Fewest speech frames worth comparing, about a third of a second.
+const MIN_FRAMES: usize = 20;
+/// Takes whose speech differs in length by more than this ratio are never
+/// the same line.
+const MAX_LENGTH_RATIO: f32 = 1.6;
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/**
+ * Spectral outline of the speech in a take, compact enough to keep for
+ * every sentence in a session. Two readings of the same line have similar
+ * outlines even with different pacing, while different lines don't, which
+ * catches a narrator reading the wrong prompt.
+ */
+#[derive(Debug, Clone)]
+pub struct SpeechFingerprint {
+    /// Log band energies of each speech frame, with each band's mean
+    /// removed to cancel out the mic and room, scaled to unit length.
+    frames: Vec<[f32; BANDS]>,
+}
+
+impl SpeechFingerprint {
+    /// Fingerprints mono audio in the -1.0..1.0 range. Returns `None` if it
+    /// holds too little speech to compare.
+    pub fn new(samples: &[f32], sample_rate: u32) -> Option<Self> {
+        let frame_len = ((sample_rate as f32 * FRAME_SECS) as usize).max(2);
+        let hop = ((sample_rate as f32 * HOP_SECS) as usize).max(1);
+        if samples.len() < frame_len {
+            return None;
+        }
+
+        let window: Vec<f32> = (0..frame_len)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (frame_len - 1) as f32).cos())
+            .collect();
+        let bin_hz = sample_rate as f32 / frame_len as f32;
+        let high_hz = HIGH_HZ.min(sample_rate as f32 / 2.0);
+        let (low_mel, high_mel) = (hz_to_mel(LOW_HZ), hz_to_mel(high_hz));
+        let edges: Vec<usize> = (0..=BANDS)
+            .map(|band| {
+                let mel = low_mel + (high_mel - low_mel) * band as f32 / BANDS as f32;
+                (mel_to_hz(mel) / bin_hz).round() as usize
+            })
+            .collect();
+
+        let fft = FftPlanner::new().plan_fft_forward(frame_len);
+        let mut buffer = vec![Complex::default(); frame_len];
+        let mut frames = Vec::new();
+        let mut loudness = Vec::new();
+        for start in (0..=samples.len() - frame_len).step_by(hop) {
+            for (i, value) in buffer.iter_mut().enumerate() {
+                *value = Complex::new(samples[start + i] * window[i], 0.0);
+            }
+            fft.process(&mut buffer);
+
+            let mut bands = [0.0; BANDS];
+            let mut total = 0.0;
+            for (band, energy) in bands.iter_mut().enumerate() {
+                let (low, high) = (edges[band], edges[band + 1].max(edges[band] + 1));
+                let power: f32 = buffer[low..high].iter().map(|bin| bin.norm_sqr()).sum();
+                total += power;
+                *energy = 10.0 * power.max(1e-10).log10();
+            }
+            frames.push(bands);
+            loudness.push(10.0 * total.max(1e-10).log10());
+        }
+
+        let loudest = loudness.iter().copied().fold(f32::MIN, f32::max);
+        let mut frames: Vec<[f32; BANDS]> = frames
+            .into_iter()
+            .zip(loudness)
+            .filter(|&(_, db)| db > loudest - SPEECH_RANGE_DB)
+            .map(|(bands, _)| bands)
+            .collect();
+        if frames.len() < MIN_FRAMES {
+            return None;
+        }
+
+        let mut means = [0.0; BANDS];
+        for bands in &frames {
+            for (mean, energy) in means.iter_mut().zip(bands) {
+                *mean += energy / frames.len() as f32;
+            }
+        }
+        for bands in &mut frames {
+            for (energy, mean) in bands.iter_mut().zip(means) {
+                *energy -= mean;
+            }
+            let norm = bands.iter().map(|e| e * e).sum::<f32>().sqrt().max(1e-6);
+            bands.iter_mut().for_each(|energy| *energy /= norm);
+        }
+        Some(Self { frames })
+    }
+
+    /// Fingerprints a take's WAV file, mixed down to mono.
+    pub fn from_file(path: &Path) -> Result<Option<Self>, RecorderError> {
+        let buffer = read_wav(path)?;
+        let channels = buffer.spec.channels.max(1) as usize;
+        let mono: Vec<f32> = buffer
+            .samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Ok(Self::new(&mono, buffer.spec.sample_rate))
+    }
+
+    /**
+     * How alike two takes sound, from 0.0 to 1.0. The frames are aligned
+     * with dynamic time warping, within a band around the diagonal, and the
+     * similarity is the mean cosine similarity of the aligned frames.
+     */
+    pub fn similarity(&self, other: &Self) -> f32 {
+        let (n, m) = (self.frames.len(), other.frames.len());
+        let ratio = n.max(m) as f32 / n.min(m).max(1) as f32;
+        if ratio > MAX_LENGTH_RATIO {
+            return 0.0;
+        }
+        let band = n.abs_diff(m) + n.max(m) / 10 + 2;
+        let distance = |i: usize, j: usize| {
+            let dot: f32 = self.frames[i]
+                .iter()
+                .zip(&other.frames[j])
+                .map(|(a, b)| a * b)
+                .sum();
+            1.0 - dot
+        };
+
+        // Cost and path length of the best alignment ending at each cell,
+        // a row at a time.
+        let mut previous = vec![(f32::INFINITY, 0u32); m + 1];
+        let mut current = vec![(f32::INFINITY, 0u32); m + 1];
+        previous[0] = (0.0, 0);
+        for i in 1..=n {
+            current.fill((f32::INFINITY, 0));
+            let expected = i * m / n;
+            let low = expected.saturating_sub(band).max(1);
+            let high = (expected + band).min(m);
+            for j in low..=high {
+                let best = [previous[j - 1], previous[j], current[j - 1]]
+                    .into_iter()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap_or((f32::INFINITY, 0));
+                if best.0.is_finite() {
+                    current[j] = (best.0 + distance(i - 1, j - 1), best.1 + 1);
+                }
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+
+        let (cost, steps) = previous[m];
+        if !cost.is_finite() || steps == 0 {
+            return 0.0;
+        }
+        (1.0 - cost / steps as f32).clamp(0.0, 1.0)
+    }
+}
+
+/**
+ * Fingerprints the takes recorded before the session on a thread of its
+ * own, so new takes can be compared against them. Takes recorded in the
+ * session are fingerprinted as they are written, and their fingerprints
+ * are kept over those read here.
+ */
+pub fn index_recorded_takes(state_arc: Arc<Mutex<AutoRecordState>>) {
+    let (takes, cancel) = {
+        let state = state_arc.lock().unwrap();
+        if !state.duplicate_speech.enabled {
+            return;
+        }
+        let takes: Vec<(usize, PathBuf)> = state
+            .sentences
+            .iter()
+            .filter(|sentence| sentence.recorded)
+            .filter_map(|sentence| Some((sentence.id, sentence.audio_file_path.as_ref()?.into())))
+            .collect();
+        (takes, state.cancel_token.clone())
+    };
+    std::thread::spawn(move || {
+        for (sentence_id, path) in takes {
+            if cancel.is_cancelled() {
+                return;
+            }
+            let fingerprint = match SpeechFingerprint::from_file(&path) {
+                Ok(Some(fingerprint)) => fingerprint,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Failed to fingerprint {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            state_arc
+                .lock()
+                .unwrap()
+                .speech_fingerprints
+                .entry(sentence_id)
+                .or_insert_with(|| Arc::new(fingerprint));
+        }
+        debug!("Fingerprinted the takes recorded before the session");
+    });
+}
+
+/**
+ * Compares the take just written for `sentence_id` with the takes of every
+ * other sentence, emitting a `duplicate_speech` event and holding the take
+ * for confirmation if it sounds like one of them. The take's fingerprint
+ * then replaces the sentence's earlier one.
+ */
+pub fn check_duplicate_speech(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    sentence_id: usize,
+) {
+    let (fingerprint, others, threshold) = {
+        let mut state = state_arc.lock().unwrap();
+        let Some(fingerprint) = state.last_fingerprint.take() else {
+            return;
+        };
+        let others: Vec<(usize, Arc<SpeechFingerprint>)> = state
+            .speech_fingerprints
+            .iter()
+            .filter(|(&id, _)| id != sentence_id)
+            .map(|(&id, other)| (id, Arc::clone(other)))
+            .collect();
+        (fingerprint, others, state.duplicate_speech.threshold)
+    };
+
+    // Compared without the session locked, as a long script takes a while.
+    let closest = others
+        .iter()
+        .map(|(id, other)| (*id, fingerprint.similarity(other)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    let mut state = state_arc.lock().unwrap();
+    state
+        .speech_fingerprints
+        .insert(sentence_id, Arc::new(fingerprint));
+    if let Some((duplicate_of, similarity)) = closest.filter(|&(_, s)| s >= threshold) {
+        debug!(
+            "Take of sentence {} sounds like sentence {} ({:.2})",
+            sentence_id, duplicate_of, similarity
+        );
+        state.confirm_take = true;
+        AutoRecordEvent::new(AutoRecordEventKind::DuplicateSpeech, &state.session_id)
+            .sentence(sentence_id)
+            .duplicate_of(duplicate_of, similarity)
+            .emit(events);
+    }
+}
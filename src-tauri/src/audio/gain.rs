@@ -0,0 +1,168 @@
+use super::config::InputLatency;
+use super::errors::RecorderError;
+use super::raw_take::RawTap;
+use super::source::{AudioSource, DataCallback, ErrorCallback, SourceStream};
+use crate::models::InputGainSettings;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// Largest gain, in dB, that can be applied to the input either way.
+pub const MAX_INPUT_GAIN_DB: f32 = 24.0;
+
+/// Level the AGC brings speech towards, in dBFS.
+const AGC_TARGET_DBFS: f32 = -20.0;
+/// Blocks quieter than this, in dBFS, are treated as silence and leave the
+/// AGC alone, so room noise isn't brought up between sentences.
+const AGC_GATE_DBFS: f32 = -50.0;
+/// Furthest the AGC moves the level either way, in dB.
+const AGC_RANGE_DB: f32 = 12.0;
+/// How fast the AGC raises or lowers the gain, in dB per second. Slow
+/// enough not to pump within a sentence.
+const AGC_RATE_DB_PER_SEC: f32 = 1.0;
+
+/// Input gain shared with the sources it is applied to, so a change made
+/// while recording takes effect on the next block of audio.
+#[derive(Debug, Default)]
+pub struct InputGain {
+    /// Bits of the `f32` gain in dB.
+    gain_db: AtomicU32,
+    agc: AtomicBool,
+}
+
+impl InputGain {
+    pub fn set(&self, settings: InputGainSettings) {
+        self.gain_db
+            .store(settings.gain_db.to_bits(), Ordering::Relaxed);
+        self.agc.store(settings.agc, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> InputGainSettings {
+        InputGainSettings {
+            gain_db: f32::from_bits(self.gain_db.load(Ordering::Relaxed)),
+            agc: self.agc.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Slow automatic gain control state of one stream.
+struct Agc {
+    channels: usize,
+    sample_rate: f32,
+    /// Gain the AGC currently adds, in dB.
+    gain_db: f32,
+}
+
+impl Agc {
+    /// Applies the fixed gain and, if enabled, the AGC to a block of
+    /// interleaved samples, saturating rather than wrapping on overload.
+    fn apply(&mut self, samples: &mut [i16], settings: InputGainSettings) {
+        if !settings.agc {
+            self.gain_db = 0.0;
+        } else if !samples.is_empty() {
+            let sum_squares: f64 = samples.iter().map(|&s| f64::from(s).powi(2)).sum();
+            let rms = (sum_squares / samples.len() as f64).sqrt() as f32 / 32768.0;
+            // Level after the fixed gain but before the AGC's own
+            let level_dbfs = 20.0 * rms.max(1e-9).log10() + settings.gain_db;
+            if level_dbfs > AGC_GATE_DBFS {
+                let block_secs =
+                    samples.len() as f32 / (self.channels.max(1) as f32 * self.sample_rate);
+                let step = AGC_RATE_DB_PER_SEC * block_secs;
+                let wanted_db = (AGC_TARGET_DBFS - level_dbfs).clamp(-AGC_RANGE_DB, AGC_RANGE_DB);
+                self.gain_db += (wanted_db - self.gain_db).clamp(-step, step);
+            }
+            // Overload is pulled back at once rather than at the slow rate.
+            let peak = samples
+                .iter()
+                .map(|&s| f32::from(s).abs())
+                .fold(0.0, f32::max)
+                / 32768.0;
+            let peak_dbfs = 20.0 * peak.max(1e-9).log10() + settings.gain_db + self.gain_db;
+            if peak_dbfs > 0.0 {
+                self.gain_db = (self.gain_db - peak_dbfs).max(-AGC_RANGE_DB);
+            }
+        }
+
+        let factor = 10f32.powf((settings.gain_db + self.gain_db) / 20.0);
+        for sample in samples.iter_mut() {
+            *sample = (f32::from(*sample) * factor)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        }
+    }
+}
+
+/// Applies the software input gain, and optionally a slow AGC, to another
+/// source's audio before anything else sees it, so the VAD and the takes
+/// both get the adjusted level. Helps when the system's microphone gain
+/// can't easily be raised. The audio is passed to the raw tap, if any,
+/// before the gain is applied.
+#[derive(Debug)]
+pub struct GainSource {
+    inner: Box<dyn AudioSource>,
+    gain: Arc<InputGain>,
+    raw_tap: Option<Arc<RawTap>>,
+}
+
+impl GainSource {
+    pub fn new(inner: Box<dyn AudioSource>, gain: Arc<InputGain>) -> Self {
+        Self {
+            inner,
+            gain,
+            raw_tap: None,
+        }
+    }
+
+    pub fn raw_tap(mut self, raw_tap: Option<Arc<RawTap>>) -> Self {
+        self.raw_tap = raw_tap;
+        self
+    }
+}
+
+impl AudioSource for GainSource {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.inner.sample_rate()
+    }
+
+    fn start(
+        &self,
+        mut on_data: DataCallback,
+        on_error: ErrorCallback,
+    ) -> Result<SourceStream, RecorderError> {
+        let gain = Arc::clone(&self.gain);
+        let raw_tap = self.raw_tap.clone();
+        let mut agc = Agc {
+            channels: self.channels() as usize,
+            sample_rate: self.sample_rate() as f32,
+            gain_db: 0.0,
+        };
+        let mut buffer = Vec::new();
+        let on_data = move |data: &[i16]| {
+            if let Some(raw_tap) = &raw_tap {
+                raw_tap.push(data);
+            }
+            let settings = gain.get();
+            if settings.gain_db == 0.0 && !settings.agc {
+                agc.gain_db = 0.0;
+                on_data(data);
+                return;
+            }
+            buffer.clear();
+            buffer.extend_from_slice(data);
+            agc.apply(&mut buffer, settings);
+            on_data(&buffer);
+        };
+        self.inner.start(Box::new(on_data), on_error)
+    }
+
+    fn latency(&self) -> Option<InputLatency> {
+        self.inner.latency()
+    }
+}
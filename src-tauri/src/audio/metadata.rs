@@ -0,0 +1,318 @@
+use super::errors::RecorderError;
+use crate::storage;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::ops::Range;
+use std::path::Path;
+
+/// Length of the fixed part of a version 1 `bext` chunk (EBU Tech 3285).
+const BEXT_LEN: usize = 602;
+const BEXT_DESCRIPTION: Range<usize> = 0..256;
+const BEXT_ORIGINATOR: Range<usize> = 256..288;
+const BEXT_ORIGINATOR_REFERENCE: Range<usize> = 288..320;
+const BEXT_DATE: Range<usize> = 320..330;
+const BEXT_TIME: Range<usize> = 330..338;
+const BEXT_VERSION: usize = 346;
+const ORIGINATOR: &str = "recordr";
+
+/// Description of a take embedded in its WAV file, so the file stays
+/// self-describing when separated from the project JSON. Written both as a
+/// Broadcast Wave `bext` chunk and as `LIST`/`INFO` tags, since tools tend
+/// to read only one of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AudioMetadata {
+    pub sentence_id: Option<usize>,
+    pub text: Option<String>,
+    pub project_name: Option<String>,
+    /// As stored in the file; RFC 3339 for files written by this app.
+    pub recorded_at: Option<String>,
+    pub device: Option<String>,
+    pub software: Option<String>,
+}
+
+impl AudioMetadata {
+    /// Metadata for a take of `sentence_id` recorded at `recorded_at`.
+    pub fn new(sentence_id: usize, text: &str, recorded_at: DateTime<Local>) -> Self {
+        Self {
+            sentence_id: Some(sentence_id),
+            text: Some(text.to_string()),
+            recorded_at: Some(recorded_at.to_rfc3339()),
+            software: Some(format!("{} {}", ORIGINATOR, env!("CARGO_PKG_VERSION"))),
+            ..Self::default()
+        }
+    }
+
+    /// Project name taken from the project directory, which is named after
+    /// the project.
+    pub fn project_directory(mut self, directory: &str) -> Self {
+        self.project_name = Path::new(directory)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        self
+    }
+
+    pub fn device(mut self, device: Option<String>) -> Self {
+        self.device = device;
+        self
+    }
+
+    fn info_tags(&self) -> Vec<([u8; 4], String)> {
+        [
+            (*b"INAM", self.text.clone()),
+            (*b"ITRK", self.sentence_id.map(|id| id.to_string())),
+            (*b"IPRD", self.project_name.clone()),
+            (*b"ICRD", self.recorded_at.clone()),
+            (*b"ISRF", self.device.clone()),
+            (*b"ISFT", self.software.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(id, value)| Some((id, value?)))
+        .collect()
+    }
+
+    fn set_info_tag(&mut self, id: &[u8], value: String) {
+        match id {
+            b"INAM" => self.text = Some(value),
+            b"ITRK" => self.sentence_id = value.parse().ok(),
+            b"IPRD" => self.project_name = Some(value),
+            b"ICRD" => self.recorded_at = Some(value),
+            b"ISRF" => self.device = Some(value),
+            b"ISFT" => self.software = Some(value),
+            _ => {}
+        }
+    }
+}
+
+/// Chunk of a RIFF file.
+struct Chunk<'a> {
+    id: [u8; 4],
+    body: &'a [u8],
+}
+
+impl Chunk<'_> {
+    fn is_metadata(&self) -> bool {
+        &self.id == b"bext" || (&self.id == b"LIST" && self.body.starts_with(b"INFO"))
+    }
+}
+
+fn invalid_wav() -> RecorderError {
+    RecorderError::Other("Not a valid WAV file".into())
+}
+
+/// Splits a WAV file into its chunks.
+fn parse_chunks(data: &[u8]) -> Result<Vec<Chunk>, RecorderError> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(invalid_wav());
+    }
+    Ok(read_chunks(&data[12..]))
+}
+
+/// Splits a sequence of chunks, such as the contents of a `LIST` chunk.
+fn read_chunks(data: &[u8]) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let id = data[offset..offset + 4].try_into().unwrap();
+        let len = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let start = offset + 8;
+        // Tolerate a data chunk cut short by an interrupted write.
+        let end = (start + len).min(data.len());
+        chunks.push(Chunk {
+            id,
+            body: &data[start..end],
+        });
+        offset = end + len % 2;
+    }
+    chunks
+}
+
+fn push_chunk(output: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    output.extend_from_slice(id);
+    output.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    output.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// Rebuilds a WAV file with its metadata chunks replaced by `metadata`,
+/// which go before the data chunk as the Broadcast Wave format expects.
+fn replace_metadata(data: &[u8], metadata: &[Chunk]) -> Result<Vec<u8>, RecorderError> {
+    let chunks = parse_chunks(data)?;
+    let mut output = Vec::with_capacity(data.len() + 1024);
+    output.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+    for chunk in chunks.iter().filter(|chunk| !chunk.is_metadata()) {
+        if &chunk.id == b"data" {
+            for metadata in metadata {
+                push_chunk(&mut output, &metadata.id, metadata.body);
+            }
+        }
+        push_chunk(&mut output, &chunk.id, chunk.body);
+    }
+    let riff_len = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    Ok(output)
+}
+
+/// Copies `text` into a fixed-width, NUL-padded field, cutting it at a
+/// character boundary if it does not fit.
+fn write_field(field: &mut [u8], text: &str) {
+    let mut len = text.len().min(field.len());
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+}
+
+fn read_field(field: &[u8]) -> Option<String> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    let text = String::from_utf8_lossy(&field[..len]).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn bext_chunk(metadata: &AudioMetadata, format: Option<&[u8]>) -> Vec<u8> {
+    let mut body = vec![0u8; BEXT_LEN];
+    write_field(
+        &mut body[BEXT_DESCRIPTION],
+        metadata.text.as_deref().unwrap_or(""),
+    );
+    write_field(&mut body[BEXT_ORIGINATOR], ORIGINATOR);
+    if let Some(id) = metadata.sentence_id {
+        write_field(&mut body[BEXT_ORIGINATOR_REFERENCE], &id.to_string());
+    }
+    let recorded_at = metadata
+        .recorded_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    if let Some(recorded_at) = recorded_at {
+        write_field(
+            &mut body[BEXT_DATE],
+            &recorded_at.format("%Y-%m-%d").to_string(),
+        );
+        write_field(
+            &mut body[BEXT_TIME],
+            &recorded_at.format("%H:%M:%S").to_string(),
+        );
+    }
+    body[BEXT_VERSION..BEXT_VERSION + 2].copy_from_slice(&1u16.to_le_bytes());
+
+    // Coding history, e.g. "A=PCM,F=48000,W=16,M=mono,T=USB Microphone".
+    if let Some(format) = format.filter(|f| f.len() >= 16) {
+        let channels = u16::from_le_bytes([format[2], format[3]]);
+        let sample_rate = u32::from_le_bytes(format[4..8].try_into().unwrap());
+        let bits = u16::from_le_bytes([format[14], format[15]]);
+        let mode = match channels {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            n => format!("{}ch", n),
+        };
+        let mut history = format!("A=PCM,F={},W={},M={}", sample_rate, bits, mode);
+        if let Some(device) = &metadata.device {
+            history.push_str(",T=");
+            history.push_str(&device.replace(['\r', '\n', ','], " "));
+        }
+        history.push_str("\r\n");
+        body.extend_from_slice(history.as_bytes());
+    }
+    body
+}
+
+fn info_chunk(metadata: &AudioMetadata) -> Vec<u8> {
+    let mut body = b"INFO".to_vec();
+    for (id, value) in metadata.info_tags() {
+        let mut value = value.into_bytes();
+        value.push(0);
+        push_chunk(&mut body, &id, &value);
+    }
+    body
+}
+
+/// Writes `metadata` into the WAV file at `path`, replacing any metadata it
+/// already has. Encrypted files stay encrypted.
+pub fn embed_metadata(path: &Path, metadata: &AudioMetadata) -> Result<(), RecorderError> {
+    let data = storage::read(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    let format = parse_chunks(&data)?
+        .into_iter()
+        .find(|chunk| &chunk.id == b"fmt ")
+        .map(|chunk| chunk.body);
+    let bext = bext_chunk(metadata, format);
+    let info = info_chunk(metadata);
+    let output = replace_metadata(
+        &data,
+        &[
+            Chunk {
+                id: *b"bext",
+                body: &bext,
+            },
+            Chunk {
+                id: *b"LIST",
+                body: &info,
+            },
+        ],
+    )?;
+    storage::write(path, &output).map_err(|e| RecorderError::Other(e.to_string()))
+}
+
+/// Copies the metadata chunks of the WAV file `source` into `data`, the
+/// contents of a WAV file about to replace it. Used by edits, which rewrite
+/// the file from its samples.
+pub(super) fn carry_metadata(source: &[u8], data: &[u8]) -> Result<Vec<u8>, RecorderError> {
+    let metadata: Vec<Chunk> = parse_chunks(source)?
+        .into_iter()
+        .filter(Chunk::is_metadata)
+        .collect();
+    replace_metadata(data, &metadata)
+}
+
+/// Reads the metadata embedded in a WAV file. `LIST`/`INFO` tags take
+/// precedence; fields they lack are taken from the `bext` chunk.
+pub fn read_metadata(path: &Path) -> Result<AudioMetadata, RecorderError> {
+    let data = storage::read(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    let mut metadata = AudioMetadata::default();
+    let chunks = parse_chunks(&data)?;
+
+    if let Some(info) = chunks
+        .iter()
+        .find(|c| &c.id == b"LIST" && c.body.starts_with(b"INFO"))
+    {
+        for tag in read_chunks(&info.body[4..]) {
+            if let Some(value) = read_field(tag.body) {
+                metadata.set_info_tag(&tag.id, value);
+            }
+        }
+    }
+
+    if let Some(bext) = chunks
+        .iter()
+        .find(|c| &c.id == b"bext" && c.body.len() >= BEXT_LEN)
+    {
+        let body = bext.body;
+        if metadata.text.is_none() {
+            metadata.text = read_field(&body[BEXT_DESCRIPTION]);
+        }
+        if metadata.sentence_id.is_none() {
+            metadata.sentence_id =
+                read_field(&body[BEXT_ORIGINATOR_REFERENCE]).and_then(|r| r.parse().ok());
+        }
+        if metadata.recorded_at.is_none() {
+            metadata.recorded_at =
+                read_field(&body[BEXT_DATE]).map(|date| match read_field(&body[BEXT_TIME]) {
+                    Some(time) => format!("{}T{}", date, time),
+                    None => date,
+                });
+        }
+        if metadata.device.is_none() {
+            let history = String::from_utf8_lossy(&body[BEXT_LEN..]);
+            // The last line of the coding history describes this file.
+            metadata.device = history.lines().rev().find_map(|line| {
+                line.split(',')
+                    .find_map(|field| field.strip_prefix("T="))
+                    .map(|device| device.trim_end_matches('\0').to_string())
+            });
+        }
+        if metadata.software.is_none() {
+            metadata.software = read_field(&body[BEXT_ORIGINATOR]);
+        }
+    }
+    Ok(metadata)
+}
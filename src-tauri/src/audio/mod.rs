@@ -1,11 +1,63 @@
 mod auto_record;
+mod board_tape;
 mod commands;
 mod config;
+mod continuous;
+mod convert;
+mod cues;
+mod denoise;
+mod disk_space;
+pub(crate) mod editing;
+mod engine;
 mod errors;
+mod events;
+mod external_edit;
+mod filters;
+mod fingerprint;
+mod gain;
+mod metadata;
+mod monitor;
+mod order;
+mod playback;
+mod power;
+mod probe;
+mod raw_take;
 mod recorder;
 mod recording_session;
+mod retrim;
+mod review;
+mod rtp;
+mod segments;
+mod session_log;
+mod sidecar;
+mod silence;
+mod source;
+mod spectrogram;
+mod stats;
 mod stream;
+mod takes;
+mod transients;
 mod utils;
+mod vad_filter;
 
+pub(crate) use auto_record::TakeDecision;
+pub use auto_record::{split_take_number, take_file_stem, TakeInterrupt};
+pub(crate) use board_tape::TAPE_DIRECTORY;
 pub use commands::*;
+pub(crate) use convert::ORIGINALS_DIRECTORY;
+pub(crate) use disk_space::DEFAULT_MIN_FREE_SPACE_MB;
+pub use errors::RecorderError;
+pub use events::{
+    AutoRecordEvent, AutoRecordEventKind, ProgressEvents, RecorderEvents, WindowEvents,
+    AUTO_RECORD_EVENT,
+};
+pub(crate) use external_edit::send_take_for_editing;
+pub(crate) use playback::{play_reference, stop_reference};
+pub(crate) use raw_take::RAW_DIRECTORY;
 pub use recorder::Recorder;
+pub(crate) use review::ReviewFormat;
+pub(crate) use sidecar::sidecar_path;
+pub(crate) use silence::check_silence_threshold;
+pub use source::BufferSource;
+pub(crate) use spectrogram::spectrogram_png;
+pub(crate) use takes::{move_takes, rename_takes};
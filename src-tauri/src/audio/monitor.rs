@@ -0,0 +1,280 @@
+use super::errors::RecorderError;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use super::silence::SilenceGate;
+use super::source::{AudioSource, SourceStream};
+use super::stats::to_dbfs;
+use super::stream::{get_chunk_size, VAD_RESAMPLER, VAD_SAMPLE_RATE};
+use super::utils::downmix_to_mono;
+use super::vad_filter::VadFilter;
+use crate::jobs::CancellationToken;
+use crate::models::RecordingSettings;
+use crossbeam_channel::bounded;
+use log::{debug, error, trace};
+use samplerate::Samplerate;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use voice_activity_detector::VoiceActivityDetector;
+
+/// How often monitor mode reports the input level.
+const LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A running monitor session: the auto-record VAD pipeline listening to the
+/// input without a project, sentences or files.
+pub struct MonitorSession {
+    pub session_id: String,
+    gate: Arc<SilenceGate>,
+    silence_confirmation: Duration,
+    /// RMS level in dBFS of each segment spoken so far.
+    levels: Arc<Mutex<Vec<f32>>>,
+    cancel: CancellationToken,
+    thread: JoinHandle<()>,
+}
+
+impl MonitorSession {
+    /// Changes the voice probability, silence threshold and duration,
+    /// taking effect on the next chunk of audio.
+    pub fn set_silence(
+        &self,
+        voice_probability: Option<f32>,
+        threshold: Option<f32>,
+        duration: Option<Duration>,
+    ) {
+        if let Some(voice_probability) = voice_probability {
+            self.gate.set_voice_probability(voice_probability);
+        }
+        if let Some(threshold) = threshold {
+            self.gate.set_threshold(threshold);
+        }
+        if let Some(duration) = duration {
+            self.gate
+                .set_end_of_take_silence(duration + self.silence_confirmation);
+        }
+    }
+
+    /// Stops listening and waits for the session to finish. Returns the
+    /// average level of the segments spoken, in dBFS, if there were any.
+    pub fn stop(self) -> Option<f32> {
+        self.cancel.cancel();
+        if self.thread.join().is_err() {
+            error!("Monitor thread panicked");
+        }
+        let levels = self.levels.lock().unwrap();
+        (!levels.is_empty()).then(|| levels.iter().sum::<f32>() / levels.len() as f32)
+    }
+}
+
+/// Splits the VAD decisions into segments the way a session splits takes:
+/// a segment starts at the first voice and ends once the silence has lasted
+/// the silence duration plus the confirmation window. The level of each
+/// segment, up to its last voice, is measured like that of a take, so it
+/// can serve as the baseline of the next session.
+struct Segmenter {
+    gate: Arc<SilenceGate>,
+    segment_started: Option<Instant>,
+    last_voice: Instant,
+    last_level: Instant,
+    peak: f32,
+    /// Sum of squares and number of samples of the latest chunk.
+    chunk_energy: (f64, usize),
+    /// Sum of squares and number of samples of the segment so far, and up
+    /// to its last voice.
+    segment_energy: (f64, usize),
+    voiced_energy: (f64, usize),
+    levels: Arc<Mutex<Vec<f32>>>,
+}
+
+impl Segmenter {
+    fn new(gate: Arc<SilenceGate>, levels: Arc<Mutex<Vec<f32>>>) -> Self {
+        Self {
+            gate,
+            segment_started: None,
+            last_voice: Instant::now(),
+            last_level: Instant::now(),
+            peak: 0.0,
+            chunk_energy: (0.0, 0),
+            segment_energy: (0.0, 0),
+            voiced_energy: (0.0, 0),
+            levels,
+        }
+    }
+
+    /// Measures a chunk of the input before the VAD filters it.
+    fn measure(&mut self, mono: &[f32]) {
+        self.peak = mono.iter().fold(self.peak, |peak, s| peak.max(s.abs()));
+        let sum_squares = mono.iter().map(|&s| f64::from(s).powi(2)).sum();
+        self.chunk_energy = (sum_squares, mono.len());
+    }
+
+    fn process(&mut self, is_voice: bool, session_id: &str, events: &dyn RecorderEvents) {
+        let now = Instant::now();
+        if now.duration_since(self.last_level) >= LEVEL_INTERVAL {
+            AutoRecordEvent::new(AutoRecordEventKind::Level, session_id)
+                .peak_level(self.peak.min(1.0))
+                .emit(events);
+            self.last_level = now;
+            self.peak = 0.0;
+        }
+
+        if is_voice {
+            self.last_voice = now;
+            if self.segment_started.is_none() {
+                trace!("Monitor: voice detected");
+                self.segment_started = Some(now);
+                self.segment_energy = (0.0, 0);
+                AutoRecordEvent::new(AutoRecordEventKind::VoiceDetected, session_id).emit(events);
+            }
+            self.add_chunk_energy();
+            self.voiced_energy = self.segment_energy;
+        } else if let Some(started) = self.segment_started {
+            self.add_chunk_energy();
+            if now.duration_since(self.last_voice) >= self.gate.end_of_take_silence() {
+                trace!("Monitor: silence detected");
+                self.segment_started = None;
+                let (sum_squares, len) = self.voiced_energy;
+                if len > 0 {
+                    let level = to_dbfs((sum_squares / len as f64).sqrt() as f32);
+                    self.levels.lock().unwrap().push(level);
+                }
+                AutoRecordEvent::new(AutoRecordEventKind::SilenceDetected, session_id)
+                    .duration(self.last_voice.duration_since(started))
+                    .emit(events);
+            }
+        }
+    }
+
+    fn add_chunk_energy(&mut self) {
+        self.segment_energy.0 += self.chunk_energy.0;
+        self.segment_energy.1 += self.chunk_energy.1;
+    }
+}
+
+/**
+ * Starts monitor mode: runs the input through the same VAD filtering and
+ * silence handling as auto-recording, emitting `voice_detected`,
+ * `silence_detected` and `level` events, but never writes a file. Lets a
+ * narrator rehearse a script and check how their silence settings segment
+ * it before recording for real.
+ */
+pub fn start_monitor(
+    audio_source: Box<dyn AudioSource>,
+    settings: &RecordingSettings,
+    session_id: String,
+    events: Arc<dyn RecorderEvents>,
+) -> Result<MonitorSession, RecorderError> {
+    let cancel = CancellationToken::new();
+    let silence_confirmation = Duration::from_millis(settings.silence_confirmation_ms);
+    let gate = Arc::new(SilenceGate::new(
+        settings.voice_probability,
+        settings.silence_threshold,
+        Duration::from_millis(settings.silence_duration_ms) + silence_confirmation,
+    ));
+    let levels = Arc::new(Mutex::new(Vec::new()));
+    let (started_tx, started_rx) = bounded(1);
+    let thread = {
+        let cancel = cancel.clone();
+        let gate = Arc::clone(&gate);
+        let levels = Arc::clone(&levels);
+        let session_id = session_id.clone();
+        let settings = settings.clone();
+        std::thread::spawn(move || {
+            // Streams can't move between threads, so the source is started
+            // on the thread that keeps it alive.
+            let started = start_source(
+                &*audio_source,
+                &settings,
+                gate,
+                levels,
+                &session_id,
+                &events,
+                &cancel,
+            );
+            let stream = match started {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = started_tx.send(Ok(()));
+            debug!("Monitor mode started");
+            while !cancel.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            drop(stream);
+            debug!("Monitor mode stopped");
+            AutoRecordEvent::new(AutoRecordEventKind::Complete, &session_id).emit(events.as_ref());
+        })
+    };
+
+    started_rx
+        .recv()
+        .unwrap_or_else(|_| Err(RecorderError::Other("Monitor thread exited".to_string())))?;
+    Ok(MonitorSession {
+        session_id,
+        gate,
+        silence_confirmation,
+        levels,
+        cancel,
+        thread,
+    })
+}
+
+fn start_source(
+    audio_source: &dyn AudioSource,
+    settings: &RecordingSettings,
+    gate: Arc<SilenceGate>,
+    levels: Arc<Mutex<Vec<f32>>>,
+    session_id: &str,
+    events: &Arc<dyn RecorderEvents>,
+    cancel: &CancellationToken,
+) -> Result<SourceStream, RecorderError> {
+    let sample_rate = audio_source.sample_rate();
+    let channels = audio_source.channels();
+    let chunk_len = get_chunk_size(sample_rate)? * channels.max(1) as usize;
+    let mut vad = VoiceActivityDetector::builder()
+        .sample_rate(VAD_SAMPLE_RATE as i64)
+        .chunk_size(get_chunk_size(VAD_SAMPLE_RATE)?)
+        .build()
+        .expect("Failed to build VAD");
+    let mut vad_filter = VadFilter::new(settings.vad_preprocessing, sample_rate as u32);
+    let mut segmenter = Segmenter::new(Arc::clone(&gate), levels);
+    let mut buffer = Vec::new();
+
+    let on_data = {
+        let session_id = session_id.to_string();
+        let events = Arc::clone(events);
+        move |data: &[i16]| {
+            let mut converter =
+                Samplerate::new(VAD_RESAMPLER, sample_rate as u32, VAD_SAMPLE_RATE as u32, 1)
+                    .expect("Failed to create Samplerate converter");
+            buffer.extend_from_slice(data);
+            while buffer.len() >= chunk_len {
+                let chunk: Vec<i16> = buffer.drain(..chunk_len).collect();
+                let mut mono = downmix_to_mono(&chunk, channels);
+                // Levels are of the input, not of the VAD's filtered copy.
+                segmenter.measure(&mono);
+                vad_filter.process(&mut mono);
+                let downsampled = converter.process(&mono).expect("Failed to downsample");
+                let is_voice = gate.is_voice(vad.predict(downsampled), &chunk);
+                segmenter.process(is_voice, &session_id, events.as_ref());
+            }
+        }
+    };
+
+    // A failing device ends monitoring, as nothing is lost by starting it
+    // again.
+    let on_error = {
+        let session_id = session_id.to_string();
+        let events = Arc::clone(events);
+        let cancel = cancel.clone();
+        move |e: RecorderError| {
+            error!("Audio input failed while monitoring: {}", e);
+            AutoRecordEvent::new(AutoRecordEventKind::DeviceLost, &session_id)
+                .emit(events.as_ref());
+            cancel.cancel();
+        }
+    };
+
+    audio_source.start(Box::new(on_data), Box::new(on_error))
+}
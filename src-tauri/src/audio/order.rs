@@ -0,0 +1,122 @@
+use crate::models::{RecordingOrder, Sentence};
+
+/// Next value of a SplitMix64 generator, enough to shuffle a script
+/// reproducibly.
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Whether a session records `sentence`: always when overwriting, else only
+/// if it has no take yet or is queued to be recorded again.
+fn is_pending(sentence: &Sentence, overwrite: bool) -> bool {
+    overwrite || !sentence.recorded || sentence.rerecord.as_ref().is_some_and(|f| f.queued)
+}
+
+/// Puts the sentence `indices` in the order of `strategy`.
+fn arrange(indices: &mut Vec<usize>, sentences: &[Sentence], strategy: RecordingOrder) {
+    match strategy {
+        RecordingOrder::Sequential => indices.sort_unstable(),
+        RecordingOrder::Shuffled { seed } => {
+            indices.sort_unstable();
+            let mut state = seed;
+            for i in (1..indices.len()).rev() {
+                let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+                indices.swap(i, j);
+            }
+        }
+        RecordingOrder::ShortestFirst => {
+            indices.sort_by_key(|&i| (sentences[i].text.trim().chars().count(), i))
+        }
+        RecordingOrder::UnrecordedFirst => indices.sort_by_key(|&i| (sentences[i].recorded, i)),
+    }
+}
+
+/**
+ * Indices of a session's sentences in the order they are recorded, and how
+ * far the session has got through them. Iterating moves on to the next
+ * sentence and yields its index.
+ */
+#[derive(Debug, Default)]
+pub struct SentenceOrder {
+    indices: Vec<usize>,
+    position: usize,
+}
+
+impl SentenceOrder {
+    /// Orders the sentences the session records, leaving out those that
+    /// already have a take unless `overwrite` is set.
+    pub fn new(sentences: &[Sentence], strategy: RecordingOrder, overwrite: bool) -> Self {
+        let mut indices = (0..sentences.len())
+            .filter(|&i| is_pending(&sentences[i], overwrite))
+            .collect();
+        arrange(&mut indices, sentences, strategy);
+        Self {
+            indices,
+            position: 0,
+        }
+    }
+
+    /// Index of the sentence being recorded, or `None` once every sentence
+    /// has been reached.
+    pub fn current(&self) -> Option<usize> {
+        self.indices.get(self.position).copied()
+    }
+
+    /// Sentences not yet reached, the current one included.
+    pub fn remaining(&self) -> usize {
+        self.indices.len().saturating_sub(self.position)
+    }
+
+    /// Starts at the sentence at `index` rather than the first, leaving
+    /// those before it for another session. A sentence the order left out
+    /// is placed first, so no pending sentence is passed over.
+    pub fn start_at(&mut self, index: usize) {
+        match self.indices.iter().position(|&i| i == index) {
+            Some(target) => self.position = target,
+            None => self.indices.insert(self.position, index),
+        }
+    }
+
+    /// Moves to the sentence at `index`, returning the indices passed over
+    /// on the way when it lies ahead. A sentence the order left out is
+    /// placed right after the current one first.
+    pub fn seek(&mut self, index: usize) -> Vec<usize> {
+        let target = match self.indices.iter().position(|&i| i == index) {
+            Some(target) => target,
+            None => {
+                let at = (self.position + 1).min(self.indices.len());
+                self.indices.insert(at, index);
+                at
+            }
+        };
+        let passed_over = if target > self.position {
+            self.indices[self.position..target].to_vec()
+        } else {
+            Vec::new()
+        };
+        self.position = target;
+        passed_over
+    }
+
+    /// Rearranges the sentences after the current one by `strategy`,
+    /// leaving those already reached where they are.
+    pub fn reorder(&mut self, sentences: &[Sentence], strategy: RecordingOrder) {
+        let start = (self.position + 1).min(self.indices.len());
+        let mut rest = self.indices.split_off(start);
+        arrange(&mut rest, sentences, strategy);
+        self.indices.extend(rest);
+    }
+}
+
+impl Iterator for SentenceOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.position = (self.position + 1).min(self.indices.len());
+        self.current()
+    }
+}
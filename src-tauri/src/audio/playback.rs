@@ -0,0 +1,185 @@
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Sample, SampleFormat, StreamConfig};
+use crossbeam_channel::{bounded, select, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use log::{debug, error};
+use samplerate::{ConverterType, Samplerate};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Resampler used when a clip's sample rate differs from the output
+/// device's. Fast enough to keep up with playback.
+pub(super) const PLAYBACK_RESAMPLER: ConverterType = ConverterType::SincMediumQuality;
+
+/// Frames of the clip resampled at a time.
+const BLOCK_FRAMES: usize = 2048;
+/// Resampled blocks queued ahead of the device.
+const QUEUED_BLOCKS: usize = 4;
+/// How often the end of playback is checked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Allowance for the device's buffer before the stream is dropped.
+const OUTPUT_DRAIN: Duration = Duration::from_millis(100);
+
+/// Stops the clip being played when dropped, or sent to.
+static PLAYING: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+/// Spreads interleaved samples over `to` channels: mono is copied to every
+/// channel, anything mixed down to mono is averaged and otherwise channels
+/// are matched up in order.
+fn remix(samples: &[f32], from: u16, to: u16) -> Vec<f32> {
+    let (from, to) = (from.max(1) as usize, to.max(1) as usize);
+    if from == to {
+        return samples.to_vec();
+    }
+    let mut output = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            output.push(frame.iter().sum::<f32>() / from as f32);
+        } else if from == 1 {
+            output.extend(std::iter::repeat(frame[0]).take(to));
+        } else {
+            output.extend((0..to).map(|c| frame.get(c).copied().unwrap_or(0.0)));
+        }
+    }
+    output
+}
+
+fn build_output_stream<T: Sample>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    blocks: Receiver<Vec<f32>>,
+    finished: Arc<AtomicBool>,
+) -> Result<cpal::Stream, RecorderError> {
+    let mut block = Vec::new();
+    let mut position = 0;
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for sample in data.iter_mut() {
+                if position >= block.len() {
+                    match blocks.try_recv() {
+                        Ok(next) => (block, position) = (next, 0),
+                        Err(TryRecvError::Disconnected) => finished.store(true, Ordering::Relaxed),
+                        Err(TryRecvError::Empty) => {}
+                    }
+                }
+                let value = block.get(position).copied().unwrap_or(0.0);
+                position = (position + 1).min(block.len());
+                *sample = T::from(&value);
+            }
+        },
+        |e| error!("Reference playback failed: {}", e),
+    )?;
+    Ok(stream)
+}
+
+/**
+ * Plays a WAV clip on the default output device, reporting to `ready` once
+ * it has started. A clip whose sample rate or channels differ from the
+ * device's is converted a block at a time as it plays, so it never plays at
+ * the wrong pitch. Returns once the clip has finished or `stop` fires.
+ */
+fn play_clip(
+    path: &Path,
+    stop: &Receiver<()>,
+    ready: &Sender<Result<(), RecorderError>>,
+) -> Result<(), RecorderError> {
+    let buffer = read_wav(path)?;
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| RecorderError::Other("No output device available".to_string()))?;
+    let supported_config = device.default_output_config()?;
+    let config = supported_config.config();
+    let channels = config.channels.max(1);
+    let sample_rate = config.sample_rate.0;
+    let converter = (buffer.spec.sample_rate != sample_rate)
+        .then(|| {
+            Samplerate::new(
+                PLAYBACK_RESAMPLER,
+                buffer.spec.sample_rate,
+                sample_rate,
+                channels as usize,
+            )
+        })
+        .transpose()
+        .map_err(|e| RecorderError::Other(format!("Failed to create resampler: {}", e)))?;
+
+    let (block_tx, block_rx) = bounded(QUEUED_BLOCKS);
+    let finished = Arc::new(AtomicBool::new(false));
+    let stream = match supported_config.sample_format() {
+        SampleFormat::F32 => {
+            build_output_stream::<f32>(&device, &config, block_rx, Arc::clone(&finished))?
+        }
+        SampleFormat::I16 => {
+            build_output_stream::<i16>(&device, &config, block_rx, Arc::clone(&finished))?
+        }
+        SampleFormat::U16 => {
+            build_output_stream::<u16>(&device, &config, block_rx, Arc::clone(&finished))?
+        }
+        _ => {
+            return Err(RecorderError::Other(
+                "Unsupported output sample format".to_string(),
+            ))
+        }
+    };
+    stream.play()?;
+    let _ = ready.send(Ok(()));
+    debug!(
+        "Playing {:?} at {} Hz on a {} Hz device",
+        path, buffer.spec.sample_rate, sample_rate
+    );
+
+    let samples = remix(&buffer.samples, buffer.spec.channels, channels);
+    let mut chunks = samples.chunks(BLOCK_FRAMES * channels as usize).peekable();
+    while let Some(chunk) = chunks.next() {
+        let block = match &converter {
+            Some(converter) if chunks.peek().is_none() => converter.process_last(chunk),
+            Some(converter) => converter.process(chunk),
+            None => Ok(chunk.to_vec()),
+        }
+        .map_err(|e| RecorderError::Other(format!("Failed to resample: {}", e)))?;
+        select! {
+            send(block_tx, block) -> sent => if sent.is_err() {
+                return Ok(());
+            },
+            recv(stop) -> _ => return Ok(()),
+        }
+    }
+    drop(block_tx);
+
+    while !finished.load(Ordering::Relaxed) {
+        match stop.recv_timeout(POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return Ok(()),
+        }
+    }
+    std::thread::sleep(OUTPUT_DRAIN);
+    Ok(())
+}
+
+/// Starts playing a reference clip, stopping any clip already playing.
+/// Returns once playback has started.
+pub fn play_reference(path: &Path) -> Result<(), RecorderError> {
+    let (stop_tx, stop_rx) = bounded(1);
+    let (ready_tx, ready_rx) = bounded(1);
+    // Dropping the previous sender stops its clip.
+    *PLAYING.lock().unwrap() = Some(stop_tx);
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        if let Err(e) = play_clip(&path, &stop_rx, &ready_tx) {
+            error!("Failed to play {:?}: {}", path, e);
+            let _ = ready_tx.send(Err(e));
+        }
+    });
+    ready_rx
+        .recv()
+        .map_err(|_| RecorderError::Other("Playback ended before it started".to_string()))?
+}
+
+/// Stops the reference clip being played, if any.
+pub fn stop_reference() {
+    PLAYING.lock().unwrap().take();
+}
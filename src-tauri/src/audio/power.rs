@@ -0,0 +1,120 @@
+use log::{debug, warn};
+use std::time::{Duration, SystemTime};
+
+/// Keeps the system from sleeping (and the screen from locking) while a
+/// recording is in progress. The inhibition is released when the value is
+/// dropped.
+pub struct SleepInhibitor {
+    #[cfg(not(target_os = "windows"))]
+    child: Option<std::process::Child>,
+    #[cfg(target_os = "windows")]
+    release_tx: Option<crossbeam_channel::Sender<()>>,
+}
+
+impl SleepInhibitor {
+    /// Acquires a sleep inhibition. Failure to inhibit is logged but never
+    /// prevents recording.
+    #[cfg(target_os = "macos")]
+    pub fn acquire(reason: &str) -> Self {
+        debug!("Inhibiting sleep: {}", reason);
+        let child = std::process::Command::new("caffeinate")
+            .args(["-d", "-i", "-w", &std::process::id().to_string()])
+            .spawn()
+            .map_err(|e| warn!("Failed to inhibit sleep: {}", e))
+            .ok();
+        Self { child }
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn acquire(reason: &str) -> Self {
+        debug!("Inhibiting sleep: {}", reason);
+        let child = std::process::Command::new("systemd-inhibit")
+            .args([
+                "--what=sleep:idle",
+                "--who=Recordr",
+                &format!("--why={}", reason),
+                "--mode=block",
+                "sleep",
+                "infinity",
+            ])
+            .spawn()
+            .map_err(|e| warn!("Failed to inhibit sleep: {}", e))
+            .ok();
+        Self { child }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn acquire(reason: &str) -> Self {
+        debug!("Inhibiting sleep: {}", reason);
+        // The execution state is tracked per thread, so hold it on a dedicated
+        // thread that resets it once the inhibitor is dropped.
+        let (release_tx, release_rx) = crossbeam_channel::bounded::<()>(0);
+        std::thread::spawn(move || {
+            let flags = ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED;
+            unsafe { SetThreadExecutionState(flags) };
+            let _ = release_rx.recv();
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        });
+        Self {
+            release_tx: Some(release_tx),
+        }
+    }
+
+    #[cfg(not(any(unix, target_os = "windows")))]
+    pub fn acquire(_reason: &str) -> Self {
+        warn!("Sleep inhibition is not supported on this platform");
+        Self { child: None }
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        debug!("Releasing sleep inhibition");
+        #[cfg(not(target_os = "windows"))]
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        #[cfg(target_os = "windows")]
+        drop(self.release_tx.take());
+    }
+}
+
+#[cfg(target_os = "windows")]
+const ES_CONTINUOUS: u32 = 0x8000_0000;
+#[cfg(target_os = "windows")]
+const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+#[cfg(target_os = "windows")]
+const ES_DISPLAY_REQUIRED: u32 = 0x0000_0002;
+
+#[cfg(target_os = "windows")]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetThreadExecutionState(flags: u32) -> u32;
+}
+
+/// Detects that the system was suspended by watching for wall-clock jumps
+/// between polls. The monotonic clock stops during suspend on most
+/// platforms, so the wall clock is used instead.
+pub struct SuspendDetector {
+    last_tick: SystemTime,
+    threshold: Duration,
+}
+
+impl SuspendDetector {
+    pub fn new() -> Self {
+        Self {
+            last_tick: SystemTime::now(),
+            threshold: Duration::from_secs(5),
+        }
+    }
+
+    /// Records a poll and returns `true` if the time since the previous poll
+    /// indicates the system was suspended in between.
+    pub fn tick(&mut self) -> bool {
+        let now = SystemTime::now();
+        let gap = now.duration_since(self.last_tick).unwrap_or(Duration::ZERO);
+        self.last_tick = now;
+        gap >= self.threshold
+    }
+}
@@ -0,0 +1,167 @@
+use super::editing::AudioBuffer;
+use crate::errors::{ErrorKind, RecordrError};
+use crate::paths::long_path;
+use crate::storage;
+use hound::{SampleFormat as HoundSampleFormat, WavSpec};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Cursor, ErrorKind as IoErrorKind};
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, Track};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision};
+use symphonia::core::probe::{Hint, ProbeResult};
+
+/// What an audio file holds, read from its headers without decoding it.
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioProbe {
+    /// Codec of the audio, e.g. `pcm_s16le`, `flac` or `mp3`.
+    pub encoding: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub bits_per_sample: Option<u32>,
+    /// Unknown for files that don't state their length, such as some MP3s.
+    pub duration_ms: Option<u64>,
+    /// Tags embedded in the file, such as RIFF INFO, Vorbis comments or
+    /// ID3, by name.
+    pub tags: BTreeMap<String, String>,
+}
+
+fn probe_error(path: &Path, error: SymphoniaError) -> RecordrError {
+    let kind = match error {
+        SymphoniaError::Unsupported(_) => ErrorKind::UnsupportedFormat,
+        SymphoniaError::IoError(_) => ErrorKind::Io,
+        _ => ErrorKind::Parse,
+    };
+    RecordrError::new(kind, error.to_string()).with_context(path.display().to_string())
+}
+
+/// Adds the tags of `revision` to `tags`, named by their standard key where
+/// one is known.
+fn add_tags(tags: &mut BTreeMap<String, String>, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let name = tag
+            .std_key
+            .map(|key| format!("{:?}", key))
+            .unwrap_or_else(|| tag.key.clone());
+        tags.insert(name, tag.value.to_string());
+    }
+}
+
+/// Opens the file at `path` with the demuxer for its format, decrypting it
+/// first if it belongs to an encrypted project.
+fn open_format(path: &Path) -> Result<ProbeResult, RecordrError> {
+    let source: Box<dyn MediaSource> = if storage::is_encrypted(path)? {
+        Box::new(Cursor::new(storage::read(path)?))
+    } else {
+        Box::new(
+            File::open(long_path(path))
+                .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?,
+        )
+    };
+    let stream = MediaSourceStream::new(source, Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| probe_error(path, e))
+}
+
+fn audio_track<'a>(path: &Path, tracks: &'a [Track]) -> Result<&'a Track, RecordrError> {
+    tracks
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| {
+            RecordrError::new(ErrorKind::UnsupportedFormat, "File has no audio track")
+                .with_context(path.display().to_string())
+        })
+}
+
+/// Reads the format, length and tags of the WAV, FLAC or MP3 file at
+/// `path`. Files of encrypted projects are decrypted first.
+pub fn probe_file(path: &Path) -> Result<AudioProbe, RecordrError> {
+    let mut probed = open_format(path)?;
+    let track = audio_track(path, probed.format.tracks())?;
+    let params = &track.codec_params;
+    let encoding = symphonia::default::get_codecs()
+        .get_codec(params.codec)
+        .map(|codec| codec.short_name.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    let duration_ms = params
+        .n_frames
+        .zip(params.sample_rate)
+        .map(|(frames, rate)| frames * 1000 / u64::from(rate.max(1)));
+
+    let mut probe = AudioProbe {
+        encoding,
+        sample_rate: params.sample_rate,
+        channels: params.channels.map(|channels| channels.count() as u16),
+        bits_per_sample: params.bits_per_sample,
+        duration_ms,
+        tags: BTreeMap::new(),
+    };
+    // Tags found before the container, such as ID3v2, then the
+    // container's own.
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        add_tags(&mut probe.tags, revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        add_tags(&mut probe.tags, revision);
+    }
+    Ok(probe)
+}
+
+/// Decodes the WAV, FLAC or MP3 file at `path` into 32-bit float samples.
+/// Packets that fail to decode are dropped, as players do.
+pub fn decode_file(path: &Path) -> Result<AudioBuffer, RecordrError> {
+    let mut format = open_format(path)?.format;
+    let track = audio_track(path, format.tracks())?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| probe_error(path, e))?;
+
+    let mut spec = None;
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == IoErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(probe_error(path, e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(probe_error(path, e)),
+        };
+        let signal = *decoded.spec();
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, signal);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+        spec.get_or_insert(WavSpec {
+            channels: signal.channels.count() as u16,
+            sample_rate: signal.rate,
+            bits_per_sample: 32,
+            sample_format: HoundSampleFormat::Float,
+        });
+    }
+    let spec = spec.ok_or_else(|| {
+        RecordrError::new(ErrorKind::Audio, "File holds no audio")
+            .with_context(path.display().to_string())
+    })?;
+    Ok(AudioBuffer { spec, samples })
+}
@@ -0,0 +1,71 @@
+use super::errors::RecorderError;
+use super::utils::{create_wav, wav_spec, write_pcm_sample};
+use crate::models::OutputFormat;
+use crate::paths::long_path;
+use crate::temp_files::TempFile;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Folder in the project directory that raw takes are written to, apart
+/// from the takes.
+pub const RAW_DIRECTORY: &str = "raw";
+
+/// Input audio as the device delivered it, before the input gain and AGC,
+/// collected while a take is recorded. Fed by the `GainSource` of a session
+/// keeping raw takes.
+#[derive(Debug, Default)]
+pub struct RawTap {
+    samples: Mutex<Option<Vec<i16>>>,
+}
+
+impl RawTap {
+    /// Starts collecting, after `samples` already kept of the take.
+    pub fn start(&self, samples: Vec<i16>) {
+        *self.samples.lock().unwrap() = Some(samples);
+    }
+
+    /// Adds a block of interleaved samples if collecting.
+    pub fn push(&self, data: &[i16]) {
+        if let Some(samples) = self.samples.lock().unwrap().as_mut() {
+            samples.extend_from_slice(data);
+        }
+    }
+
+    /// Stops collecting, returning what was collected.
+    pub fn finish(&self) -> Vec<i16> {
+        self.samples.lock().unwrap().take().unwrap_or_default()
+    }
+}
+
+/**
+ * Writes the take just kept at `take_path` as it was captured: everything
+ * heard from the start of the take, before the input gain, trimming,
+ * remixing, filtering and noise reduction, at the input's sample rate and
+ * channels. It goes to the project's `raw` folder under the take's file
+ * name, so a take spoiled by trimming, processing or a later conversion
+ * can be recovered from it. Returns the raw take's path.
+ */
+pub fn write_raw_take(
+    project_directory: &str,
+    channels: u16,
+    sample_rate: u32,
+    take_path: &Path,
+    samples: &[i16],
+) -> Result<PathBuf, RecorderError> {
+    let directory = Path::new(project_directory).join(RAW_DIRECTORY);
+    fs::create_dir_all(long_path(&directory))?;
+    let file_name = take_path
+        .file_name()
+        .ok_or_else(|| RecorderError::Other("Take has no file name".to_string()))?;
+    let raw = TempFile::new(directory.join(file_name));
+
+    // The input is 16-bit, so nothing is lost at that depth.
+    let spec = wav_spec(channels, sample_rate, OutputFormat::Wav16);
+    let mut writer = create_wav(raw.path(), spec)?;
+    for &sample in samples {
+        write_pcm_sample(&mut writer, sample)?;
+    }
+    writer.finalize()?;
+    raw.keep().map_err(|e| RecorderError::Other(e.to_string()))
+}
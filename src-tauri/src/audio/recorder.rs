@@ -1,34 +1,95 @@
-use super::auto_record::{AutoRecordState, AutoRecordStateBuilder};
-use super::config::{AudioConfig, DeviceWrapper, RecordingState};
+use super::auto_record::{AutoRecordState, AutoRecordStateBuilder, TakeDecision, TakeInterrupt};
+use super::board_tape::{start_board_tape, BoardTape};
+use super::config::{AudioConfig, DeviceWrapper, InputLatency, RecordingState};
+use super::continuous::record_continuous;
+use super::cues::{play_cue, Cue};
+use super::disk_space::{check_free_space, watch_disk_space};
 use super::errors::RecorderError;
-use super::stream::record_sentence;
-use super::utils::{find_supported_config, write_input_data};
-use crate::models::Sentence;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{BufferSize, SampleFormat, Stream, StreamConfig};
-use hound::{SampleFormat as HoundSampleFormat, WavSpec, WavWriter};
-use log::{debug, error, trace};
-use serde_json::json;
-use std::cell::RefCell;
-use std::fs::File;
-use std::io::BufWriter;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use super::fingerprint::{check_duplicate_speech, index_recorded_takes};
+use super::gain::{GainSource, InputGain, MAX_INPUT_GAIN_DB};
+use super::metadata::{embed_metadata, AudioMetadata};
+use super::monitor::{start_monitor, MonitorSession};
+use super::power::SleepInhibitor;
+use super::raw_take::RawTap;
+use super::rtp::RtpSource;
+use super::segments::SegmentedWriter;
+use super::session_log::{LoggedEvents, SessionLog};
+use super::silence::{check_silence_threshold, check_voice_probability};
+use super::source::{AudioSource, BufferSource};
+use super::stream::{mark_device_lost, record_sentence, wait_pre_sentence_delay};
+use super::utils::{
+    create_wav, find_supported_config, low_latency_config, write_input_data, SampleSink,
+};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::jobs::CancellationToken;
+use crate::models::{
+    InputGainSettings, InputSource, NoiseReductionSettings, PausePolicy, ProjectLayout,
+    RecordingOrder, RecordingSettings, Sentence, SentenceStatus, TakeTimeoutAction,
+};
+use crate::paths::long_path;
+use crate::storage;
+use chrono::Local;
+use cpal::traits::{DeviceTrait, HostTrait};
+use crossbeam_channel::{bounded, Sender};
+use hound::{SampleFormat as HoundSampleFormat, WavSpec};
+use log::{debug, error, trace, warn};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::info_span;
+use uuid::Uuid;
 
 // Shared state for the recorder.
 pub struct Recorder {
     auto_record_state: Option<Arc<Mutex<AutoRecordState>>>,
-    writer: Option<Arc<Mutex<WavWriter<BufWriter<File>>>>>,
+    auto_record_thread: Option<JoinHandle<()>>,
+    auto_record_events: Option<Arc<dyn RecorderEvents>>,
+    auto_record_settings: Option<RecordingSettings>,
+    manual_recording: Option<ManualRecording>,
+    /// File of the manual take being recorded and the metadata embedded in
+    /// it once it is finished.
+    manual_take: Option<(PathBuf, AudioMetadata)>,
+    sleep_inhibitor: Option<SleepInhibitor>,
+    noise_reduction: Option<NoiseReductionSettings>,
+    /// Gain set while recording, kept over the settings of later sessions.
+    input_gain_settings: Option<InputGainSettings>,
+    /// Gain applied by the running session or monitor.
+    input_gain: Arc<InputGain>,
+    monitor: Option<MonitorSession>,
+    /// Speech level measured by the last monitor session, the baseline of
+    /// later sessions unless the settings give one.
+    soundcheck_level: Option<f32>,
+    /// WAV file played into recordings instead of the input device.
+    input_file: Option<PathBuf>,
 }
 
 impl Recorder {
     pub fn new() -> Self {
         Self {
             auto_record_state: None,
-            writer: None,
+            auto_record_thread: None,
+            auto_record_events: None,
+            auto_record_settings: None,
+            manual_recording: None,
+            manual_take: None,
+            sleep_inhibitor: None,
+            noise_reduction: None,
+            input_gain_settings: None,
+            input_gain: Arc::new(InputGain::default()),
+            monitor: None,
+            soundcheck_level: None,
+            input_file: None,
         }
     }
 
+    /// Plays the given WAV file into recordings instead of the input
+    /// device, for scripted runs without a microphone.
+    pub fn set_input_file(&mut self, path: Option<PathBuf>) {
+        self.input_file = path;
+    }
+
     /// Starts a standard recording and writes to a WAV file.
     ///
     /// This function sets up an audio input stream, configures a WAV file
@@ -37,194 +98,689 @@ impl Recorder {
     /// progress, it returns an error. The filename is also validated to
     /// prevent directory traversal attacks.
     ///
+    /// With a `segment_length`, the recording is instead written as a series
+    /// of segment files overlapping by `segment_overlap`, which can be joined
+    /// with `stitch_segments`. Otherwise the take of `sentence`, if given,
+    /// gets its metadata embedded once the recording is stopped.
+    ///
     /// # Arguments
     /// * `filename` - The name of the WAV file to create.
+    /// * `segment_length` - Length of each segment, if segmenting.
+    /// * `segment_overlap` - Audio each segment shares with the next.
+    /// * `min_free_space_mb` - Free space the volume must have, in MB.
+    /// * `sentence` - Sentence the take is recorded for, if any.
+    /// * `project_directory` - Directory of the sentence's project.
     ///
     /// # Returns
     /// * `Ok(String)` - A success message indicating the recording has started.
-    /// * `Err(String)` - An error message if the recording could not be
-    ///   started.
-    pub fn start_recording(&mut self, filename: String) -> Result<String, String> {
+    /// * `Err(RecordrError)` - The reason the recording could not be started.
+    pub fn start_recording(
+        &mut self,
+        filename: String,
+        segment_length: Option<Duration>,
+        segment_overlap: Duration,
+        min_free_space_mb: u64,
+        sentence: Option<Sentence>,
+        project_directory: Option<String>,
+    ) -> Result<String, RecordrError> {
         // Prevent starting a new recording if one is already in progress.
-        if self.writer.is_some() {
-            return Err("Recording is already in progress".into());
+        if self.manual_recording.is_some() {
+            return Err(RecordrError::invalid_state(
+                "Recording is already in progress",
+            ));
         }
 
         // Validate the filename to prevent directory traversal attacks.
         if filename.contains("..") {
-            return Err("Invalid filename".into());
+            return Err(RecordrError::invalid_input("Invalid filename").with_context(filename));
+        }
+        // Manual takes go in the project's recordings folder, which may not
+        // exist until the first take.
+        if let Some(parent) = Path::new(&filename).parent() {
+            std::fs::create_dir_all(long_path(parent))
+                .map_err(|e| RecordrError::from(e).with_context(filename.clone()))?;
         }
+        check_free_space(Path::new(&filename), min_free_space_mb)?;
 
         debug!("Setting up audio inputs and writer...");
-        // Get default audio input device and configuration.
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or("No input device available")?;
-        let config = device.default_input_config().map_err(|e| e.to_string())?;
+        let source: Box<dyn AudioSource> = match &self.input_file {
+            Some(path) => Box::new(BufferSource::from_wav(path)?),
+            None => Box::new(default_input_config()?),
+        };
 
         // Configure WAV file writer with the sample rate and channels from the audio
         // device.
-        let channels = config.channels();
-        let sample_rate = config.sample_rate().0;
         let spec = WavSpec {
-            channels: channels as u16,
-            sample_rate: sample_rate as u32,
+            channels: source.channels(),
+            sample_rate: source.sample_rate() as u32,
             bits_per_sample: 16,
             sample_format: HoundSampleFormat::Int,
         };
 
-        let writer = WavWriter::create(&filename, spec).map_err(|e| e.to_string())?;
-        let writer = Arc::new(Mutex::new(writer));
-
-        // Clone the writer to use within the audio stream callback.
-        let writer_clone = Arc::clone(&writer);
-
-        // Error handling for the audio stream.
-        let err_fn = move |err| {
-            error!("An error occurred on stream: {}", err);
+        let writer: Arc<Mutex<dyn SampleSink>> = match segment_length {
+            Some(segment_length) => Arc::new(Mutex::new(
+                SegmentedWriter::create(
+                    Path::new(&filename),
+                    spec,
+                    segment_length,
+                    segment_overlap,
+                )
+                .map_err(|e| RecordrError::from(e).with_context(filename.clone()))?,
+            )),
+            None => Arc::new(Mutex::new(
+                create_wav(Path::new(&filename), spec)
+                    .map_err(|e| RecordrError::from(e).with_context(filename.clone()))?,
+            )),
         };
 
-        debug!("Building audio stream...");
-        let stream = match config.sample_format() {
-            SampleFormat::F32 => device.build_input_stream(
-                &config.config(),
-                move |data: &[f32], _| {
-                    let mut writer = writer_clone.lock().unwrap();
-                    write_input_data(data, &mut *writer);
-                },
-                err_fn,
-            ),
-            SampleFormat::I16 => device.build_input_stream(
-                &config.config(),
-                move |data: &[i16], _| {
-                    let mut writer = writer_clone.lock().unwrap();
-                    write_input_data(data, &mut *writer);
-                },
-                err_fn,
-            ),
-            SampleFormat::U16 => device.build_input_stream(
-                &config.config(),
-                move |data: &[u16], _| {
-                    let mut writer = writer_clone.lock().unwrap();
-                    write_input_data(data, &mut *writer);
-                },
-                err_fn,
-            ),
-            _ => return Err("Unsupported sample format".into()),
-        }
-        .map_err(|e| e.to_string())?;
-
-        // Play the stream
-        stream.play().map_err(|e| e.to_string())?;
-
-        // Save the writer in the recorder state
-        self.writer = Some(writer);
-
-        // Save the stream in thread-local storage
-        RECORDING_STREAM.with(|s| {
-            *s.borrow_mut() = Some(stream);
-        });
+        let device_name = source.name();
+        self.manual_recording = Some(start_manual_recording(source, writer)?);
+        self.manual_take = sentence
+            .filter(|_| segment_length.is_none())
+            .map(|sentence| {
+                let mut metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+                    .device(device_name);
+                if let Some(directory) = &project_directory {
+                    metadata = metadata.project_directory(directory);
+                }
+                (PathBuf::from(&filename), metadata)
+            });
+        self.sleep_inhibitor = Some(SleepInhibitor::acquire("Recording audio"));
 
         Ok("Recording started".into())
     }
 
-    /// Stops the current recording and finalizes the WAV file.
-    pub fn stop_recording(&mut self) -> Result<String, String> {
-        if self.writer.is_some() {
-            self.writer = None; // Dropping the writer finalizes the WAV file.
+    /// Stops the current recording, finalizes the WAV file and embeds the
+    /// take's metadata, if it is for a sentence.
+    pub fn stop_recording(&mut self) -> Result<String, RecordrError> {
+        if let Some(recording) = self.manual_recording.take() {
+            self.sleep_inhibitor = None;
+            let manual_take = self.manual_take.take();
+            recording.stop()?;
 
-            // Stop the stream
-            RECORDING_STREAM.with(|s| {
-                if let Some(stream) = s.borrow_mut().take() {
-                    drop(stream); // Stops the audio stream
+            if let Some((path, metadata)) = manual_take {
+                if let Err(e) = embed_metadata(&path, &metadata) {
+                    error!("Failed to embed metadata in {}: {}", path.display(), e);
                 }
-            });
+            }
 
             Ok("Recording stopped".into())
         } else {
-            Err("No recording in progress".into())
+            Err(RecordrError::invalid_state("No recording in progress"))
         }
     }
 
     /// Starts the auto-recording process with sentence detection and silence
-    /// handling.
+    /// handling, from the sentence at `start_index` if given. The session's
+    /// events carry `session_id`, or a new ID if none is given, so a caller
+    /// can match events sent before this returns. Returns the session ID.
+    #[allow(clippy::too_many_arguments)]
     pub fn start_auto_record(
         &mut self,
         sentences: Vec<Sentence>,
+        start_index: Option<usize>,
+        session_id: Option<String>,
         project_directory: String,
-        silence_threshold: f32,
-        silence_duration_ms: u64,
-        silence_padding_ms: u64,
-        window: tauri::Window,
-    ) -> Result<(), String> {
-        debug!("Starting auto-recording...");
-        let audio_config = self.create_audio_config()?;
+        layout: ProjectLayout,
+        settings: RecordingSettings,
+        events: Arc<dyn RecorderEvents>,
+    ) -> Result<String, RecordrError> {
+        let audio_source = self.create_audio_source(&settings)?;
+        self.start_auto_record_with_source(
+            sentences,
+            start_index,
+            session_id,
+            project_directory,
+            layout,
+            settings,
+            audio_source,
+            events,
+        )
+    }
 
-        trace!("Audio config created");
+    /// Starts auto-recording from the given audio source instead of the
+    /// input device selected in the settings.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_auto_record_with_source(
+        &mut self,
+        sentences: Vec<Sentence>,
+        start_index: Option<usize>,
+        session_id: Option<String>,
+        project_directory: String,
+        layout: ProjectLayout,
+        settings: RecordingSettings,
+        audio_source: Box<dyn AudioSource>,
+        events: Arc<dyn RecorderEvents>,
+    ) -> Result<String, RecordrError> {
+        debug!("Starting auto-recording...");
+        check_silence_threshold(settings.silence_threshold)?;
+        check_voice_probability(settings.voice_probability)?;
+        // An encrypted project's files are only written once complete, and a
+        // session recording would be held in memory until the session ends.
+        if settings.continuous_session && storage::has_key(Path::new(&project_directory)) {
+            return Err(RecordrError::invalid_input(
+                "Continuous sessions can't be recorded in encrypted projects",
+            ));
+        }
+        check_free_space(Path::new(&project_directory), settings.min_free_space_mb)?;
+        // The rehearsal is over once recording starts for real.
+        self.stop_monitor_mode();
+        self.auto_record_settings = Some(settings.clone());
+        let raw_tap = settings.keep_raw_takes.then(Arc::<RawTap>::default);
+        let audio_source = self.with_input_gain(audio_source, &settings, raw_tap.clone());
 
-        let auto_record_state = AutoRecordStateBuilder::new()
+        let mut auto_record_state = AutoRecordStateBuilder::new()
             .sentences(sentences)
+            .start_index(start_index)
+            .session_id(session_id)
             .project_directory(project_directory)
-            .silence_threshold(silence_threshold)
-            .silence_duration(silence_duration_ms)
-            .silence_padding(silence_padding_ms)
-            .audio_config(audio_config)
-            .build()?;
+            .layout(layout)
+            .voice_probability(settings.voice_probability)
+            .silence_threshold(settings.silence_threshold)
+            .silence_duration(settings.silence_duration_ms)
+            .silence_padding(settings.silence_padding_ms)
+            .silence_confirmation(settings.silence_confirmation_ms)
+            .pre_sentence_delay(settings.pre_sentence_delay_ms)
+            .output_format(settings.output_format)
+            .channel_mode(settings.channel_mode)
+            .emit_prompt_text(settings.emit_prompt_text)
+            .vad_preprocessing(settings.vad_preprocessing)
+            .filters(settings.filters)
+            .transients(settings.transients)
+            .duplicate_speech(settings.duplicate_speech)
+            .write_sidecar(settings.write_sidecar)
+            .raw_tap(raw_tap)
+            .auto_advance(settings.auto_advance)
+            .requeue_skipped(settings.requeue_skipped)
+            .recording_order(settings.recording_order)
+            .overwrite_recorded(settings.overwrite_recorded)
+            .takes_per_sentence(settings.takes_per_sentence)
+            .min_take_duration(settings.min_take_duration_ms)
+            .min_voiced_duration(settings.min_voiced_ms)
+            .max_take_duration(settings.max_take_duration_ms)
+            .take_timeout_action(settings.take_timeout_action)
+            .pause_policy(settings.pause_policy)
+            .sprint_length(settings.sprint_minutes)
+            .rest_length(settings.rest_minutes)
+            .max_session_length(settings.max_session_minutes)
+            .max_session_sentences(settings.max_session_sentences)
+            .level_baseline(settings.level_baseline_dbfs.or(self.soundcheck_level))
+            .level_drift_threshold(settings.level_drift_threshold_db)
+            .cues(settings.cues)
+            .noise_reduction(self.noise_reduction.unwrap_or(settings.noise_reduction))
+            .audio_source(audio_source)
+            .build()
+            .map_err(RecordrError::invalid_input)?;
 
         trace!("Auto-recording state created");
 
+        let events = if settings.write_session_log {
+            open_session_log(&mut auto_record_state, &settings, events)
+        } else {
+            events
+        };
+        if settings.board_tape.enabled {
+            auto_record_state.board_tape = self.start_board_tape(&auto_record_state, &settings);
+        }
+        let session_id = auto_record_state.session_id.clone();
         let state_arc = Arc::new(Mutex::new(auto_record_state));
 
         self.auto_record_state = Some(Arc::clone(&state_arc));
 
         {
             let mut state = state_arc.lock().unwrap();
-            state.start_recording().map_err(|e| e.to_string())?;
+            state
+                .start_recording()
+                .map_err(RecordrError::invalid_state)?;
         }
 
-        self.run_auto_record(state_arc, window)
+        self.auto_record_events = Some(Arc::clone(&events));
+        watch_disk_space(
+            Arc::clone(&state_arc),
+            Arc::clone(&events),
+            settings.min_free_space_mb,
+        );
+        if settings.continuous_session {
+            self.run_continuous_record(state_arc, events);
+        } else {
+            index_recorded_takes(Arc::clone(&state_arc));
+            self.run_auto_record(state_arc, events)?;
+        }
+        Ok(session_id)
     }
 
-    pub fn stop_auto_record(&mut self) -> Result<(), String> {
+    /// Starts monitor mode, which listens like an auto-record session and
+    /// reports voice, silence and levels without recording anything.
+    /// Returns the ID carried by its events.
+    pub fn start_monitor_mode(
+        &mut self,
+        settings: RecordingSettings,
+        events: Arc<dyn RecorderEvents>,
+    ) -> Result<String, RecordrError> {
+        if self.is_auto_recording() {
+            return Err(RecordrError::invalid_state(
+                "Cannot monitor while auto-recording",
+            ));
+        }
+        self.stop_monitor_mode();
+
+        debug!("Starting monitor mode...");
+        let audio_source = self.create_audio_source(&settings)?;
+        let audio_source = self.with_input_gain(audio_source, &settings, None);
+        let session = start_monitor(audio_source, &settings, Uuid::new_v4().to_string(), events)?;
+        let session_id = session.session_id.clone();
+        self.monitor = Some(session);
+        Ok(session_id)
+    }
+
+    /// Stops monitor mode. Returns `false` if it wasn't running. The speech
+    /// level it measured becomes the level baseline of later sessions.
+    pub fn stop_monitor_mode(&mut self) -> bool {
+        match self.monitor.take() {
+            Some(session) => {
+                debug!("Stopping monitor mode...");
+                if let Some(level) = session.stop() {
+                    debug!("Soundcheck level: {:.1} dBFS", level);
+                    self.soundcheck_level = Some(level);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether an auto-record session is running, paused or not.
+    pub fn is_auto_recording(&self) -> bool {
+        self.auto_record_state
+            .as_ref()
+            .is_some_and(|state| state.lock().unwrap().state != RecordingState::Idle)
+    }
+
+    /// Blocks until the running auto-record session has finished.
+    pub fn wait_auto_record(&mut self) {
+        if let Some(handle) = self.auto_record_thread.take() {
+            if handle.join().is_err() {
+                error!("Auto-record thread panicked");
+            }
+        }
+    }
+
+    pub fn stop_auto_record(&mut self) -> Result<(), RecordrError> {
         debug!("Stopping auto-recording...");
         if let Some(state_arc) = self.auto_record_state.take() {
             let mut state = state_arc.lock().unwrap();
-            state.stop_recording().map_err(|e| e.to_string())?;
+            state
+                .stop_recording()
+                .map_err(RecordrError::invalid_state)?;
             Ok(())
         } else {
-            Err("No auto-recording in progress".into())
+            Err(no_auto_recording())
         }
     }
 
-    pub fn pause_auto_record(&mut self) -> Result<(), String> {
+    pub fn pause_auto_record(&mut self) -> Result<(), RecordrError> {
         debug!("Pausing auto-recording...");
         if let Some(state_arc) = &self.auto_record_state {
             let mut state = state_arc.lock().unwrap();
-            state.pause_recording().map_err(|e| e.to_string())
+            state
+                .pause_recording()
+                .map_err(RecordrError::invalid_state)?;
+            self.emit_event(AutoRecordEvent::new(
+                AutoRecordEventKind::Paused,
+                &state.session_id,
+            ));
+            Ok(())
         } else {
-            Err("No auto-recording in progress".into())
+            Err(no_auto_recording())
         }
     }
 
-    pub fn resume_auto_record(&mut self) -> Result<(), String> {
+    pub fn resume_auto_record(&mut self) -> Result<(), RecordrError> {
         debug!("Resuming auto-recording...");
         if let Some(state_arc) = &self.auto_record_state {
             let mut state = state_arc.lock().unwrap();
-            state.resume_recording().map_err(|e| e.to_string())
+            state
+                .resume_recording()
+                .map_err(RecordrError::invalid_state)?;
+            self.emit_event(AutoRecordEvent::new(
+                AutoRecordEventKind::Resumed,
+                &state.session_id,
+            ));
+            Ok(())
         } else {
-            Err("No auto-recording in progress".into())
+            Err(no_auto_recording())
+        }
+    }
+
+    /// Abandons the take in progress to skip its sentence or record it
+    /// again. A take waiting for confirmation is kept when skipping and
+    /// discarded when retrying.
+    pub fn interrupt_take(&mut self, interrupt: TakeInterrupt) -> Result<(), RecordrError> {
+        debug!("Interrupting take: {:?}", interrupt);
+        let state_arc = self
+            .auto_record_state
+            .as_ref()
+            .ok_or_else(no_auto_recording)?;
+        let mut state = state_arc.lock().unwrap();
+        let was_recording = state.state == RecordingState::Recording;
+        let skipped_id = state
+            .interrupt_take(interrupt)
+            .map_err(RecordrError::invalid_state)?;
+        // Interrupting a take pauses the session until the recording
+        // thread has applied it, which then resumes it.
+        if was_recording && state.state == RecordingState::Paused {
+            self.emit_event(AutoRecordEvent::new(
+                AutoRecordEventKind::Paused,
+                &state.session_id,
+            ));
+        }
+        if let Some(skipped_id) = skipped_id {
+            self.emit_event(
+                AutoRecordEvent::new(AutoRecordEventKind::SentenceSkipped, &state.session_id)
+                    .sentence(skipped_id),
+            );
+        }
+        Ok(())
+    }
+
+    fn emit_event(&self, event: AutoRecordEvent) {
+        if let Some(events) = &self.auto_record_events {
+            event.emit(events.as_ref());
+        }
+    }
+
+    /// Jumps a paused auto-record session to the sentence at `index`, or to
+    /// the sentence with `sentence_id`. Recording continues from there once
+    /// the session is resumed. Sentences passed over are reported as
+    /// skipped.
+    pub fn set_current_sentence(
+        &mut self,
+        index: Option<usize>,
+        sentence_id: Option<usize>,
+    ) -> Result<(), RecordrError> {
+        debug!("Setting current sentence...");
+        let state_arc = self
+            .auto_record_state
+            .as_ref()
+            .ok_or_else(no_auto_recording)?;
+        let mut state = state_arc.lock().unwrap();
+        let index = match (index, sentence_id) {
+            (Some(index), _) => index,
+            (None, Some(id)) => state
+                .sentences
+                .iter()
+                .position(|s| s.id == id)
+                .ok_or_else(|| RecordrError::not_found(format!("Sentence not found: {}", id)))?,
+            (None, None) => {
+                return Err(RecordrError::invalid_input(
+                    "Either an index or a sentence ID is required",
+                ))
+            }
+        };
+        let skipped_ids = state
+            .set_current_sentence(index)
+            .map_err(RecordrError::invalid_state)?;
+
+        if let Some(events) = &self.auto_record_events {
+            for sentence_id in skipped_ids {
+                AutoRecordEvent::new(AutoRecordEventKind::SentenceSkipped, &state.session_id)
+                    .sentence(sentence_id)
+                    .emit(events.as_ref());
+            }
         }
+        Ok(())
+    }
+
+    /// Rebuilds the audio input of a session paused because its device was
+    /// lost, optionally switching to another device, and resumes recording
+    /// the current sentence.
+    pub fn reconnect_audio_device(
+        &mut self,
+        device_name: Option<String>,
+    ) -> Result<(), RecordrError> {
+        debug!("Reconnecting audio device...");
+        let state_arc = self
+            .auto_record_state
+            .clone()
+            .ok_or_else(no_auto_recording)?;
+        let mut settings = self
+            .auto_record_settings
+            .clone()
+            .ok_or_else(no_auto_recording)?;
+        if device_name.is_some() {
+            settings.device_name = device_name;
+        }
+
+        let mut state = state_arc.lock().unwrap();
+        if state.state != RecordingState::Paused {
+            return Err(RecordrError::invalid_state(
+                "Can only reconnect a paused auto-recording",
+            ));
+        }
+        let audio_source = self.create_audio_source(&settings)?;
+        let audio_source: Box<dyn AudioSource> = Box::new(
+            GainSource::new(audio_source, Arc::clone(&self.input_gain))
+                .raw_tap(state.raw_tap.clone()),
+        );
+        state.audio_source = audio_source;
+        state
+            .resume_recording()
+            .map_err(RecordrError::invalid_state)
+    }
+
+    /// Accepts or rejects the take waiting for confirmation when
+    /// auto-advance is off. A rejected take is recorded again.
+    pub fn decide_take(&mut self, decision: TakeDecision) -> Result<(), RecordrError> {
+        let state_arc = self
+            .auto_record_state
+            .as_ref()
+            .ok_or_else(no_auto_recording)?;
+        let mut state = state_arc.lock().unwrap();
+        state
+            .decide_take(decision)
+            .map_err(RecordrError::invalid_state)
+    }
+
+    /// Enables or disables noise reduction for the active session and any
+    /// session started afterwards, overriding the project setting.
+    pub fn set_noise_reduction(
+        &mut self,
+        enabled: bool,
+        strength: f32,
+    ) -> Result<(), RecordrError> {
+        if !(0.0..=1.0).contains(&strength) {
+            return Err(RecordrError::invalid_input(
+                "Noise reduction strength must be between 0.0 and 1.0",
+            ));
+        }
+        let noise_reduction = NoiseReductionSettings { enabled, strength };
+        self.noise_reduction = Some(noise_reduction);
+        if let Some(state_arc) = &self.auto_record_state {
+            state_arc.lock().unwrap().noise_reduction = noise_reduction;
+        }
+        Ok(())
+    }
+
+    /**
+     * Changes the VAD voice probability, the silence threshold, in dBFS,
+     * and the silence duration and padding, in ms, of the running session
+     * or monitor without stopping it. The probability, threshold and
+     * duration apply from the next chunk of audio, the padding from the
+     * next take written. Sessions started afterwards use their own
+     * settings.
+     */
+    pub fn update_auto_record_settings(
+        &mut self,
+        voice_probability: Option<f32>,
+        silence_threshold: Option<f32>,
+        silence_duration: Option<u64>,
+        silence_padding: Option<u64>,
+    ) -> Result<(), RecordrError> {
+        if let Some(probability) = voice_probability {
+            check_voice_probability(probability)?;
+        }
+        if let Some(threshold) = silence_threshold {
+            check_silence_threshold(threshold)?;
+        }
+        if self.auto_record_state.is_none() && self.monitor.is_none() {
+            return Err(no_auto_recording());
+        }
+        let duration = silence_duration.map(Duration::from_millis);
+        let padding = silence_padding.map(Duration::from_millis);
+        if let Some(state_arc) = &self.auto_record_state {
+            state_arc.lock().unwrap().set_silence(
+                voice_probability,
+                silence_threshold,
+                duration,
+                padding,
+            );
+        }
+        // A reconnected device carries on with the new settings.
+        if let Some(settings) = &mut self.auto_record_settings {
+            settings.voice_probability = voice_probability.unwrap_or(settings.voice_probability);
+            settings.silence_threshold = silence_threshold.unwrap_or(settings.silence_threshold);
+            settings.silence_duration_ms = silence_duration.unwrap_or(settings.silence_duration_ms);
+            settings.silence_padding_ms = silence_padding.unwrap_or(settings.silence_padding_ms);
+        }
+        if let Some(monitor) = &self.monitor {
+            monitor.set_silence(voice_probability, silence_threshold, duration);
+        }
+        debug!(
+            "Updated silence settings: voice probability {:?}, threshold {:?} dBFS, \
+             duration {:?} ms, padding {:?} ms",
+            voice_probability, silence_threshold, silence_duration, silence_padding
+        );
+        Ok(())
+    }
+
+    /// Sets the order sentences are recorded in for the active session,
+    /// from the sentence after the current one. Later sessions follow the
+    /// project setting again.
+    pub fn set_recording_order(&mut self, strategy: RecordingOrder) -> Result<(), RecordrError> {
+        let state_arc = self
+            .auto_record_state
+            .as_ref()
+            .ok_or_else(no_auto_recording)?;
+        state_arc.lock().unwrap().set_recording_order(strategy);
+        Ok(())
+    }
+
+    /// Sets the software input gain, and optionally turns the AGC on or
+    /// off, taking effect at once if a session or monitor is running.
+    pub fn set_input_gain(&mut self, gain_db: f32, agc: Option<bool>) -> Result<(), RecordrError> {
+        if !(-MAX_INPUT_GAIN_DB..=MAX_INPUT_GAIN_DB).contains(&gain_db) {
+            return Err(RecordrError::invalid_input(format!(
+                "Input gain must be between -{0} and {0} dB",
+                MAX_INPUT_GAIN_DB
+            )));
+        }
+        let settings = InputGainSettings {
+            gain_db,
+            agc: agc.unwrap_or(self.input_gain.get().agc),
+        };
+        self.input_gain_settings = Some(settings);
+        self.input_gain.set(settings);
+        Ok(())
     }
 
-    fn create_audio_config(&self) -> Result<AudioConfig, String> {
+    /// Applies the input gain to `audio_source`, starting from the gain set
+    /// while recording, if any, or else the settings'. The audio before the
+    /// gain goes to `raw_tap`, if given.
+    fn with_input_gain(
+        &self,
+        audio_source: Box<dyn AudioSource>,
+        settings: &RecordingSettings,
+        raw_tap: Option<Arc<RawTap>>,
+    ) -> Box<dyn AudioSource> {
+        self.input_gain
+            .set(self.input_gain_settings.unwrap_or(settings.input_gain));
+        Box::new(GainSource::new(audio_source, Arc::clone(&self.input_gain)).raw_tap(raw_tap))
+    }
+
+    /// Input latency of the running auto-record session, if its source
+    /// knows it.
+    pub fn input_latency(&self) -> Result<Option<InputLatency>, RecordrError> {
+        let state_arc = self
+            .auto_record_state
+            .as_ref()
+            .ok_or_else(no_auto_recording)?;
+        let state = state_arc.lock().unwrap();
+        Ok(state.audio_source.latency())
+    }
+
+    fn create_audio_source(
+        &self,
+        settings: &RecordingSettings,
+    ) -> Result<Box<dyn AudioSource>, RecordrError> {
+        if let Some(path) = &self.input_file {
+            return Ok(Box::new(BufferSource::from_wav(path)?));
+        }
+        Ok(match &settings.input_source {
+            InputSource::Device => {
+                let audio_config = self.create_audio_config(settings)?;
+                trace!("Audio config created");
+                Box::new(audio_config)
+            }
+            InputSource::Rtp {
+                address,
+                channels,
+                sample_rate,
+            } => Box::new(RtpSource::new(address, *channels, *sample_rate)?),
+        })
+    }
+
+    /// Starts taping the session from a stream of its own on the recording
+    /// device. A tape that can't be started doesn't stop the session.
+    fn start_board_tape(
+        &self,
+        state: &AutoRecordState,
+        settings: &RecordingSettings,
+    ) -> Option<BoardTape> {
+        if !matches!(settings.input_source, InputSource::Device) {
+            debug!("Only sessions recorded from a device get a board tape");
+            return None;
+        }
+        let result = self
+            .create_audio_source(settings)
+            .map_err(|e| e.to_string())
+            .and_then(|source| {
+                start_board_tape(
+                    source,
+                    Path::new(&state.project_directory),
+                    &state.session_id,
+                    settings.board_tape,
+                )
+                .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(tape) => Some(tape),
+            Err(e) => {
+                warn!("Failed to start board tape: {}", e);
+                None
+            }
+        }
+    }
+
+    fn create_audio_config(
+        &self,
+        settings: &RecordingSettings,
+    ) -> Result<AudioConfig, RecordrError> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or("No input device available")?;
+        let device = match &settings.device_name {
+            Some(name) => host
+                .input_devices()
+                .map_err(|e| RecordrError::new(ErrorKind::DeviceError, e.to_string()))?
+                .find(|device| device.name().map(|n| &n == name).unwrap_or(false))
+                .ok_or_else(|| no_input_device().with_context(name.clone()))?,
+            None => host.default_input_device().ok_or_else(no_input_device)?,
+        };
 
-        let config =
-            find_supported_config(&device).ok_or("No supported audio configuration found")?;
+        let config = find_supported_config(&device, settings.sample_rate).ok_or_else(|| {
+            RecordrError::new(
+                ErrorKind::UnsupportedFormat,
+                "No supported audio configuration found",
+            )
+        })?;
 
         trace!("Selected audio configuration:");
         trace!("Sample format: {:?}", config.sample_format());
@@ -232,23 +788,66 @@ impl Recorder {
         trace!("Channels: {}", config.channels());
         trace!("Buffer size: {:?}", config.buffer_size());
 
+        let low_latency = if settings.low_latency {
+            let low_latency = low_latency_config(&device, &config);
+            match &low_latency {
+                Some(config) => debug!("Using low-latency input: {:?}", config.buffer_size),
+                None => warn!("Low-latency input unavailable; using the default buffer"),
+            }
+            low_latency
+        } else {
+            None
+        };
+
         Ok(AudioConfig {
             device: DeviceWrapper(device),
             supported_config: config.clone(),
-            config: config.config().clone(),
+            low_latency: low_latency.is_some(),
+            config: low_latency.unwrap_or_else(|| config.config()),
             sample_rate: config.sample_rate().0 as usize,
+            capture_latency_us: Arc::default(),
         })
     }
 
+    fn run_continuous_record(
+        &mut self,
+        state_arc: Arc<Mutex<AutoRecordState>>,
+        events: Arc<dyn RecorderEvents>,
+    ) {
+        debug!("Moving continuous session recording to thread");
+        let (cancel_token, session_id) = {
+            let state = state_arc.lock().unwrap();
+            (state.cancel_token.clone(), state.session_id.clone())
+        };
+        let handle = std::thread::spawn(move || {
+            let _span = info_span!("session", id = %session_id, continuous = true).entered();
+            let _sleep_inhibitor = SleepInhibitor::acquire("Recording session");
+            if let Err(e) = record_continuous(&state_arc, events.as_ref(), &cancel_token) {
+                error!("Error recording session: {}", e);
+                log_error(&state_arc, None, &e);
+            }
+            finalize_recording(&state_arc, events.as_ref());
+        });
+        self.auto_record_thread = Some(handle);
+    }
+
     fn run_auto_record(
         &mut self,
         state_arc: Arc<Mutex<AutoRecordState>>,
-        window: tauri::Window,
-    ) -> Result<(), String> {
+        events: Arc<dyn RecorderEvents>,
+    ) -> Result<(), RecordrError> {
         debug!("Moving auto-record to thread");
         let thread_state_arc = Arc::clone(&state_arc);
 
-        std::thread::spawn(move || {
+        let (cancel_token, session_id) = {
+            let state = state_arc.lock().unwrap();
+            (state.cancel_token.clone(), state.session_id.clone())
+        };
+
+        let handle = std::thread::spawn(move || {
+            let _span = info_span!("session", id = %session_id).entered();
+            let _sleep_inhibitor = SleepInhibitor::acquire("Auto-recording sentences");
+
             loop {
                 let should_continue = {
                     let state = thread_state_arc.lock().unwrap();
@@ -260,30 +859,189 @@ impl Recorder {
                     break;
                 }
 
-                let sentence_option = {
-                    let state = thread_state_arc.lock().unwrap();
-                    if state.current_sentence_index >= state.sentences.len() {
+                let (sentence_option, emit_prompt_text, take_number) = {
+                    let mut state = thread_state_arc.lock().unwrap();
+                    state.sentence_started.get_or_insert_with(Instant::now);
+                    let sentence = if state.current_sentence_index >= state.sentences.len() {
                         None
                     } else {
                         Some(state.sentences[state.current_sentence_index].clone())
-                    }
+                    };
+                    (sentence, state.emit_prompt_text, state.take_number())
                 };
 
                 if let Some(sentence) = sentence_option {
+                    let _span =
+                        info_span!("sentence", id = sentence.id, take = ?take_number).entered();
                     // Let the UI know that we're starting a new sentence
-                    window
-                        .emit("auto-record-start-sentence", sentence.id)
-                        .unwrap_or_else(|e| eprintln!("Failed to emit event: {}", e));
-
-                    match record_sentence(&thread_state_arc) {
-                        Ok(()) => handle_successful_recording(&thread_state_arc, &window),
-                        Err(RecorderError::RecordingPaused) => {
-                            if !handle_paused_recording(&thread_state_arc) {
+                    let mut event =
+                        AutoRecordEvent::new(AutoRecordEventKind::SentenceStarted, &session_id)
+                            .sentence(sentence.id)
+                            .take_number(take_number);
+                    if emit_prompt_text {
+                        event = event.text(sentence.text.clone());
+                    }
+                    event.emit(events.as_ref());
+
+                    // Give the narrator time to read the prompt before listening
+                    let result =
+                        wait_pre_sentence_delay(&thread_state_arc, &cancel_token, |remaining| {
+                            AutoRecordEvent::new(AutoRecordEventKind::CountdownTick, &session_id)
+                                .sentence(sentence.id)
+                                .remaining(remaining)
+                                .emit(events.as_ref());
+                        })
+                        .and_then(|()| {
+                            give_cue(&thread_state_arc, events.as_ref(), Cue::Armed, sentence.id);
+                            record_sentence(&thread_state_arc, &cancel_token)
+                        });
+
+                    // A timed-out take that was kept carries on like any
+                    // finished take. One rejected as empty or too short
+                    // comes back as that error instead, and is recorded
+                    // again.
+                    let result = match result {
+                        Err(RecorderError::TakeTimedOut) => {
+                            AutoRecordEvent::new(AutoRecordEventKind::TakeTimeout, &session_id)
+                                .sentence(sentence.id)
+                                .take_number(take_number)
+                                .emit(events.as_ref());
+                            let action = thread_state_arc.lock().unwrap().take_timeout_action;
+                            match action {
+                                TakeTimeoutAction::Keep => Ok(()),
+                                TakeTimeoutAction::Discard => Err(RecorderError::TakeTimedOut),
+                            }
+                        }
+                        result => result,
+                    };
+                    log_take(&thread_state_arc, sentence.id, take_number, &result);
+
+                    match result {
+                        Ok(()) => {
+                            let index = thread_state_arc.lock().unwrap().current_sentence_index;
+                            check_duplicate_speech(&thread_state_arc, events.as_ref(), sentence.id);
+                            give_cue(
+                                &thread_state_arc,
+                                events.as_ref(),
+                                Cue::Captured,
+                                sentence.id,
+                            );
+                            if !advance_after_take(
+                                &thread_state_arc,
+                                events.as_ref(),
+                                &cancel_token,
+                            ) {
+                                break;
+                            }
+                            report_progress(&thread_state_arc, events.as_ref(), index);
+                            if session_quota_reached(&thread_state_arc, events.as_ref(), index) {
+                                break;
+                            }
+                            if !rest_after_sprint(&thread_state_arc, events.as_ref()) {
                                 break;
                             }
                         }
+                        Err(
+                            e @ (RecorderError::RecordingPaused
+                            | RecorderError::CpalBuildStreamError(_)
+                            | RecorderError::StreamPlayError(_)),
+                        ) => {
+                            if !matches!(e, RecorderError::RecordingPaused) {
+                                // The device could not be opened for this
+                                // sentence; wait for it like a lost device.
+                                error!("Error recording sentence: {}", e);
+                                log_error(&thread_state_arc, Some(sentence.id), &e);
+                                mark_device_lost(&thread_state_arc);
+                            }
+                            let (suspended, device_lost, skipped_id, interrupted) = {
+                                let mut state = thread_state_arc.lock().unwrap();
+                                let interrupt = state.take_interrupt.take();
+                                // A pause before any speech, such as while
+                                // the sentence is armed, leaves it current.
+                                let speaking = std::mem::take(&mut state.paused_speaking);
+                                let discard = interrupt == Some(TakeInterrupt::Skip)
+                                    || (interrupt.is_none()
+                                        && speaking
+                                        && state.pause_policy == PausePolicy::Discard
+                                        && !state.suspended
+                                        && !state.device_lost
+                                        && !state.low_disk_space);
+                                let skipped_id = if discard {
+                                    state.sentence_started = None;
+                                    state.skip_current()
+                                } else {
+                                    None
+                                };
+                                // An interrupted take carries on without
+                                // waiting for the session to be resumed.
+                                let interrupted =
+                                    interrupt.is_some() && state.resume_recording().is_ok();
+                                (state.suspended, state.device_lost, skipped_id, interrupted)
+                            };
+                            if let Some(skipped_id) = skipped_id {
+                                AutoRecordEvent::new(
+                                    AutoRecordEventKind::SentenceSkipped,
+                                    &session_id,
+                                )
+                                .sentence(skipped_id)
+                                .emit(events.as_ref());
+                            }
+                            if interrupted {
+                                AutoRecordEvent::new(AutoRecordEventKind::Resumed, &session_id)
+                                    .emit(events.as_ref());
+                            }
+                            if suspended {
+                                AutoRecordEvent::new(AutoRecordEventKind::Suspended, &session_id)
+                                    .sentence(sentence.id)
+                                    .emit(events.as_ref());
+                            }
+                            if device_lost {
+                                AutoRecordEvent::new(AutoRecordEventKind::DeviceLost, &session_id)
+                                    .sentence(sentence.id)
+                                    .emit(events.as_ref());
+                            }
+                            if !interrupted && !handle_paused_recording(&thread_state_arc) {
+                                break;
+                            }
+                        }
+                        Err(RecorderError::TakeTooShort(speech_ms)) => {
+                            // Record the same sentence again.
+                            debug!("Discarded take with {} ms of speech", speech_ms);
+                            AutoRecordEvent::new(AutoRecordEventKind::TakeRejected, &session_id)
+                                .sentence(sentence.id)
+                                .take_number(take_number)
+                                .emit(events.as_ref());
+                        }
+                        Err(RecorderError::EmptyTake(voiced_ms)) => {
+                            // Arm the same sentence again.
+                            debug!("Discarded take with {} ms of voice", voiced_ms);
+                            AutoRecordEvent::new(AutoRecordEventKind::EmptyTake, &session_id)
+                                .sentence(sentence.id)
+                                .take_number(take_number)
+                                .emit(events.as_ref());
+                        }
+                        Err(RecorderError::TakeTimedOut) => {
+                            let skipped_id = {
+                                let mut state = thread_state_arc.lock().unwrap();
+                                state.sentence_started = None;
+                                state.skip_current()
+                            };
+                            if let Some(skipped_id) = skipped_id {
+                                AutoRecordEvent::new(
+                                    AutoRecordEventKind::SentenceSkipped,
+                                    &session_id,
+                                )
+                                .sentence(skipped_id)
+                                .emit(events.as_ref());
+                            }
+                        }
+                        Err(RecorderError::Cancelled) => {
+                            debug!("Auto-record cancelled");
+                            break;
+                        }
                         Err(e) => {
                             error!("Error recording sentence: {}", e);
+                            log_error(&thread_state_arc, Some(sentence.id), &e);
                             break;
                         }
                     }
@@ -292,19 +1050,39 @@ impl Recorder {
                 }
             }
 
-            finalize_recording(&thread_state_arc, &window);
+            finalize_recording(&thread_state_arc, events.as_ref());
         });
+        self.auto_record_thread = Some(handle);
 
         Ok(())
     }
 }
 
-fn handle_successful_recording(state_arc: &Arc<Mutex<AutoRecordState>>, window: &tauri::Window) {
-    let mut state = state_arc.lock().unwrap();
+fn no_input_device() -> RecordrError {
+    RecordrError::new(ErrorKind::NoInputDevice, "No input device available")
+}
+
+fn no_auto_recording() -> RecordrError {
+    RecordrError::invalid_state("No auto-recording in progress")
+}
+
+/// Marks the sentence recorded with the take just confirmed and lets the UI
+/// know it is finished.
+fn handle_successful_recording(state: &mut AutoRecordState, events: &dyn RecorderEvents) {
     let current_index = state.current_sentence_index;
     let total_sentences = state.sentences.len();
-    let sentence = &state.sentences[current_index];
-    let sentence_id = sentence.id;
+    let last_take = state.last_take.take();
+    if let Some(stats) = &last_take {
+        state
+            .take_durations
+            .push(Duration::from_millis(stats.duration_ms));
+    }
+    let sentence = &mut state.sentences[current_index];
+    sentence.stats = last_take.clone();
+    sentence.skipped = false;
+    sentence.rerecord = None;
+    sentence.status = SentenceStatus::Recorded;
+    let sentence = sentence.clone();
     let audio_file_path = sentence.audio_file_path.clone().unwrap_or_default();
 
     debug!(
@@ -314,27 +1092,248 @@ fn handle_successful_recording(state_arc: &Arc<Mutex<AutoRecordState>>, window:
     );
 
     // Let the UI know that we've finished processing the sentence
-    window
-        .emit(
-            "auto-record-finish-sentence",
-            json!({
-                "id": sentence_id,
-                "audioFilePath": audio_file_path
-            }),
-        )
-        .unwrap_or_else(|e| eprintln!("Failed to emit event: {}", e));
+    let mut event = AutoRecordEvent::new(AutoRecordEventKind::SentenceFinished, &state.session_id)
+        .sentence(sentence.id)
+        .take_number(state.take_number())
+        .audio_file_path(audio_file_path)
+        .review(&sentence);
+    if let Some(stats) = sentence.stats.clone() {
+        event = event.stats(stats);
+    }
+    event.emit(events);
 
-    state.current_sentence_index += 1;
+    let drift = last_take.and_then(|stats| state.level_tracker.add_take(stats.rms_dbfs));
+    if let Some(drift) = drift {
+        debug!("Speech level drifted {:.1} dB from the baseline", drift);
+        AutoRecordEvent::new(AutoRecordEventKind::LevelDrift, &state.session_id)
+            .sentence(sentence.id)
+            .level_drift(drift)
+            .emit(events);
+    }
 }
 
-fn handle_paused_recording(state_arc: &Arc<Mutex<AutoRecordState>>) -> bool {
-    println!(
-        "Recording paused during sentence {}. Waiting to resume...",
+/// Moves past a finished take, first waiting for it to be confirmed or
+/// retried when auto-advance is off or the take sounds like a duplicate.
+/// The sentence is only marked recorded once the take is confirmed; a
+/// retried take leaves it as it was. Returns `false` if the session ended
+/// while waiting.
+fn advance_after_take(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    cancel: &CancellationToken,
+) -> bool {
+    {
+        let mut state = state_arc.lock().unwrap();
+        if state.auto_advance && !std::mem::take(&mut state.confirm_take) {
+            handle_successful_recording(&mut state, events);
+            state.next_take();
+            return true;
+        }
+        state.awaiting_confirmation = true;
+        state.take_decision = None;
+        let sentence_id = state.sentences[state.current_sentence_index].id;
+        AutoRecordEvent::new(AutoRecordEventKind::AwaitingConfirmation, &state.session_id)
+            .sentence(sentence_id)
+            .take_number(state.take_number())
+            .emit(events);
+    }
+
+    debug!("Waiting for the take to be confirmed or retried");
+    loop {
+        if cancel.is_cancelled() {
+            return false;
+        }
         {
-            let state = state_arc.lock().unwrap();
-            state.current_sentence_index + 1
+            let mut state = state_arc.lock().unwrap();
+            if state.state == RecordingState::Idle {
+                return false;
+            }
+            if let Some(decision) = state.take_decision.take() {
+                state.awaiting_confirmation = false;
+                if decision == TakeDecision::Confirm {
+                    handle_successful_recording(&mut state, events);
+                    state.next_take();
+                } else {
+                    state.last_take = None;
+                }
+                return true;
+            }
         }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Emits the session's progress once it has moved past the sentence at
+/// `index`. Retakes and further takes of the same sentence are not reported.
+fn report_progress(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    index: usize,
+) {
+    let mut state = state_arc.lock().unwrap();
+    if state.current_sentence_index == index {
+        return;
+    }
+    let progress = state.finish_sentence_timing();
+    AutoRecordEvent::new(AutoRecordEventKind::SessionProgress, &state.session_id)
+        .progress(progress)
+        .emit(events);
+}
+
+/// Ends the session once it has moved past the sentence at `index` with its
+/// quota used up, emitting `session_quota_reached` with the session's
+/// progress so the narrator can pick up from there next time.
+fn session_quota_reached(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    index: usize,
+) -> bool {
+    let mut state = state_arc.lock().unwrap();
+    if state.current_sentence_index == index
+        || state.remaining_sentences() == 0
+        || !state.quota_reached()
+    {
+        return false;
+    }
+    debug!(
+        "Session quota reached after {} sentences",
+        state.sentence_times.len()
     );
+    let progress = state.finish_sentence_timing();
+    AutoRecordEvent::new(AutoRecordEventKind::SessionQuotaReached, &state.session_id)
+        .progress(progress)
+        .emit(events);
+    true
+}
+
+/// Cues the narrator, if cues are enabled, with an event and a beep. A beep
+/// that can't be played is logged rather than failing the take.
+fn give_cue(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    events: &dyn RecorderEvents,
+    cue: Cue,
+    sentence_id: usize,
+) {
+    let cues = {
+        let state = state_arc.lock().unwrap();
+        if !state.cues.enabled {
+            return;
+        }
+        AutoRecordEvent::new(AutoRecordEventKind::Cue, &state.session_id)
+            .sentence(sentence_id)
+            .cue(cue)
+            .emit(events);
+        if let Some(tape) = &state.board_tape {
+            tape.cue(cue, state.cues.volume);
+        }
+        state.cues
+    };
+    if cues.volume > 0.0 {
+        // Played on a thread of its own, so the session carries on. Only
+        // listening waits for the armed beep to die away, keeping it out
+        // of the take.
+        let player = std::thread::spawn(move || {
+            if let Err(e) = play_cue(cue, cues.volume) {
+                warn!("Failed to play {:?} cue: {}", cue, e);
+            }
+        });
+        if cue == Cue::Armed && player.join().is_err() {
+            error!("Cue playback thread panicked");
+        }
+    }
+}
+
+/// Pauses the session for a rest once its sprint is over, then waits for it
+/// to be resumed. Returns `false` if the session ended while resting.
+fn rest_after_sprint(state_arc: &Arc<Mutex<AutoRecordState>>, events: &dyn RecorderEvents) -> bool {
+    {
+        let mut state = state_arc.lock().unwrap();
+        if !state.sprint_over()
+            || state.remaining_sentences() == 0
+            || state.pause_recording().is_err()
+        {
+            return true;
+        }
+        debug!("Sprint over, pausing for a rest");
+        AutoRecordEvent::new(AutoRecordEventKind::RestBreak, &state.session_id)
+            .remaining(state.rest_length)
+            .emit(events);
+    }
+    handle_paused_recording(state_arc)
+}
+
+/**
+ * Starts the session's log, recording the device and settings it runs
+ * with, and returns `events` wrapped to log every event. A log that can't
+ * be created is only warned about, as it must not stop the recording.
+ */
+fn open_session_log(
+    state: &mut AutoRecordState,
+    settings: &RecordingSettings,
+    events: Arc<dyn RecorderEvents>,
+) -> Arc<dyn RecorderEvents> {
+    match SessionLog::create(Path::new(&state.project_directory), &state.session_id) {
+        Ok(Some(log)) => {
+            log.session_started(state.audio_source.as_ref(), settings);
+            state.session_log = Some(Arc::clone(&log));
+            Arc::new(LoggedEvents { events, log })
+        }
+        Ok(None) => events,
+        Err(e) => {
+            warn!("Failed to create session log: {}", e);
+            events
+        }
+    }
+}
+
+/// Adds how a take ended, and what the VAD made of it, to the session log.
+fn log_take(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    sentence_id: usize,
+    take_number: Option<u32>,
+    result: &Result<(), RecorderError>,
+) {
+    let state = state_arc.lock().unwrap();
+    let Some(log) = &state.session_log else {
+        return;
+    };
+    let outcome = match result {
+        Ok(()) => "recorded",
+        Err(RecorderError::TakeTooShort(_)) => "tooShort",
+        Err(RecorderError::EmptyTake(_)) => "empty",
+        Err(RecorderError::TakeTimedOut) => "timedOut",
+        Err(RecorderError::RecordingPaused) => "interrupted",
+        Err(RecorderError::Cancelled) => "cancelled",
+        Err(_) => "failed",
+    };
+    log.take(sentence_id, take_number, outcome, state.vad_summary);
+}
+
+fn log_error(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    sentence_id: Option<usize>,
+    error: &RecorderError,
+) {
+    if let Some(log) = &state_arc.lock().unwrap().session_log {
+        log.error(sentence_id, error);
+    }
+}
+
+fn handle_paused_recording(state_arc: &Arc<Mutex<AutoRecordState>>) -> bool {
+    {
+        let state = state_arc.lock().unwrap();
+        let sentence_id = state
+            .sentences
+            .get(state.current_sentence_index)
+            .map(|s| s.id);
+        debug!(
+            "Recording paused during sentence {}, waiting to resume",
+            state.current_sentence_index + 1
+        );
+        if let Some(log) = &state.session_log {
+            log.waiting(sentence_id, "resume");
+        }
+    }
 
     while {
         let state = state_arc.lock().unwrap();
@@ -350,16 +1349,285 @@ fn handle_paused_recording(state_arc: &Arc<Mutex<AutoRecordState>>) -> bool {
     false // This line is unreachable, but Rust requires it for completeness
 }
 
-fn finalize_recording(state_arc: &Arc<Mutex<AutoRecordState>>, window: &tauri::Window) {
+fn finalize_recording(state_arc: &Arc<Mutex<AutoRecordState>>, events: &dyn RecorderEvents) {
+    // Finishing the tape waits on its thread, so not under the lock.
+    let board_tape = state_arc.lock().unwrap().board_tape.take();
+    if let Some(tape) = board_tape {
+        tape.stop();
+    }
+    let mut state = state_arc.lock().unwrap();
+
     // Let the UI know that we've finished the auto-recording process
-    window
-        .emit("auto-record-complete", true)
-        .unwrap_or_else(|e| eprintln!("Failed to emit event: {}", e));
+    AutoRecordEvent::new(AutoRecordEventKind::Complete, &state.session_id).emit(events);
 
-    let mut state = state_arc.lock().unwrap();
     state.state = RecordingState::Idle;
 }
 
-thread_local! {
-  static RECORDING_STREAM: RefCell<Option<Stream>> = RefCell::new(None);
+/// A manual recording in progress. The input stream lives on a thread of
+/// its own, which keeps it until told to stop, so the recording can be
+/// stopped from whichever thread handles the command.
+struct ManualRecording {
+    writer: Arc<Mutex<dyn SampleSink>>,
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl ManualRecording {
+    /// Stops the stream, waits for it to be dropped and completes the file.
+    fn stop(self) -> Result<(), RecorderError> {
+        drop(self.stop);
+        if self.thread.join().is_err() {
+            error!("Recording thread panicked");
+        }
+        self.writer.lock().unwrap().finish()
+    }
+}
+
+fn start_manual_recording(
+    source: Box<dyn AudioSource>,
+    writer: Arc<Mutex<dyn SampleSink>>,
+) -> Result<ManualRecording, RecorderError> {
+    let (started_tx, started_rx) = bounded(1);
+    let (stop, stop_rx) = bounded::<()>(0);
+    let stream_writer = Arc::clone(&writer);
+    let thread = std::thread::spawn(move || {
+        // Streams can't move between threads, so the source is started on
+        // the thread that keeps it alive.
+        debug!("Building audio stream...");
+        let started = source.start(
+            Box::new(move |data: &[i16]| {
+                let mut writer = stream_writer.lock().unwrap();
+                write_input_data(data, &mut *writer);
+            }),
+            Box::new(|err: RecorderError| error!("An error occurred on stream: {}", err)),
+        );
+        let stream = match started {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = started_tx.send(Err(e));
+                return;
+            }
+        };
+        let _ = started_tx.send(Ok(()));
+        // Returns once the recording is stopped and the sender dropped.
+        let _ = stop_rx.recv();
+        drop(stream);
+        debug!("Recording stream stopped");
+    });
+
+    started_rx
+        .recv()
+        .unwrap_or_else(|_| Err(RecorderError::Other("Recording thread exited".to_string())))?;
+    Ok(ManualRecording {
+        writer,
+        stop,
+        thread,
+    })
+}
+
+/// The default input device in its default configuration, which manual
+/// recordings use.
+fn default_input_config() -> Result<AudioConfig, RecordrError> {
+    let host = cpal::default_host();
+    let device = host.default_input_device().ok_or_else(no_input_device)?;
+    let config = device.default_input_config().map_err(RecorderError::from)?;
+    Ok(AudioConfig {
+        device: DeviceWrapper(device),
+        supported_config: config.clone(),
+        low_latency: false,
+        config: config.config(),
+        sample_rate: config.sample_rate().0 as usize,
+        capture_latency_us: Arc::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::stream::get_chunk_size;
+    use crossbeam_channel::{unbounded, Receiver};
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: usize = 16000;
+    const EVENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+    /// Silence followed by a tone loud enough to pass the energy gate.
+    fn tone_after_silence(silence_ms: usize, tone_ms: usize) -> Vec<i16> {
+        let silence = vec![0; SAMPLE_RATE * silence_ms / 1000];
+        let tone = (0..SAMPLE_RATE * tone_ms / 1000).map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            ((2.0 * PI * 220.0 * t).sin() * 0.25 * i16::MAX as f32) as i16
+        });
+        silence.into_iter().chain(tone).collect()
+    }
+
+    fn test_settings() -> RecordingSettings {
+        RecordingSettings {
+            // Leave the decision to the energy gate, so the tone counts as
+            // voice whatever the VAD model makes of it.
+            voice_probability: 0.0,
+            silence_threshold: -50.0,
+            silence_duration_ms: 500,
+            silence_padding_ms: 200,
+            min_take_duration_ms: 0,
+            min_voiced_ms: 0,
+            write_session_log: false,
+            min_free_space_mb: 0,
+            ..RecordingSettings::default()
+        }
+    }
+
+    fn project_directory() -> String {
+        let directory = std::env::temp_dir().join(format!("recordr-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory.to_string_lossy().into_owned()
+    }
+
+    /// Starts a session recording `samples` with its events sent to the
+    /// returned channel.
+    fn start_session(
+        recorder: &mut Recorder,
+        sentences: Vec<Sentence>,
+        samples: Vec<i16>,
+        settings: RecordingSettings,
+    ) -> Receiver<AutoRecordEvent> {
+        let (sender, receiver) = unbounded();
+        recorder
+            .start_auto_record_with_source(
+                sentences,
+                None,
+                None,
+                project_directory(),
+                ProjectLayout::default(),
+                settings,
+                Box::new(BufferSource::new(samples, 1, SAMPLE_RATE)),
+                Arc::new(sender),
+            )
+            .unwrap();
+        receiver
+    }
+
+    /// Receives events until the session is complete.
+    fn events_until_complete(receiver: &Receiver<AutoRecordEvent>) -> Vec<AutoRecordEvent> {
+        let mut events = Vec::new();
+        loop {
+            let event = receiver
+                .recv_timeout(EVENT_TIMEOUT)
+                .expect("session did not complete");
+            let complete = matches!(event.kind, AutoRecordEventKind::Complete);
+            events.push(event);
+            if complete {
+                return events;
+            }
+        }
+    }
+
+    #[test]
+    fn sends_sentence_events_in_order() {
+        let mut recorder = Recorder::new();
+        let receiver = start_session(
+            &mut recorder,
+            vec![Sentence::new(1, "One".into())],
+            tone_after_silence(500, 1000),
+            test_settings(),
+        );
+        let events = events_until_complete(&receiver);
+        recorder.wait_auto_record();
+
+        let kinds: Vec<_> = events
+            .iter()
+            .filter(|event| !matches!(event.kind, AutoRecordEventKind::SessionProgress))
+            .map(|event| event.kind)
+            .collect();
+        assert!(
+            matches!(
+                kinds[..],
+                [
+                    AutoRecordEventKind::SentenceStarted,
+                    AutoRecordEventKind::SentenceFinished,
+                    AutoRecordEventKind::Complete,
+                ]
+            ),
+            "unexpected events: {:?}",
+            kinds
+        );
+        let session_id = &events[0].session_id;
+        assert!(events.iter().all(|event| &event.session_id == session_id));
+        let finished = events
+            .iter()
+            .find(|event| matches!(event.kind, AutoRecordEventKind::SentenceFinished))
+            .unwrap();
+        assert_eq!(finished.sentence_id, Some(1));
+        assert!(Path::new(finished.audio_file_path.as_deref().unwrap()).exists());
+    }
+
+    #[test]
+    fn trims_take_to_speech_and_padding() {
+        let settings = test_settings();
+        let mut recorder = Recorder::new();
+        let receiver = start_session(
+            &mut recorder,
+            vec![Sentence::new(1, "One".into())],
+            tone_after_silence(500, 1000),
+            settings.clone(),
+        );
+        let events = events_until_complete(&receiver);
+        recorder.wait_auto_record();
+
+        let finished = events
+            .iter()
+            .find(|event| matches!(event.kind, AutoRecordEventKind::SentenceFinished))
+            .expect("no take was finished");
+        let stats = finished.stats.as_ref().unwrap();
+
+        // Trimming keeps a chunk either side of the speech, then the padding
+        // in whole chunks, so lengths are only exact to a chunk.
+        let chunk_ms = 1000 * get_chunk_size(SAMPLE_RATE).unwrap() as u64 / SAMPLE_RATE as u64;
+        let padding_ms = settings.silence_padding_ms / chunk_ms * chunk_ms + chunk_ms;
+        let near = |actual: u64, expected: u64| actual.abs_diff(expected) <= chunk_ms;
+        assert!(near(stats.leading_silence_ms, padding_ms), "{:?}", stats);
+        assert!(near(stats.trailing_silence_ms, padding_ms), "{:?}", stats);
+        assert!(
+            near(stats.duration_ms, 1000 + 2 * padding_ms),
+            "{:?}",
+            stats
+        );
+
+        let reader = hound::WavReader::open(finished.audio_file_path.as_deref().unwrap()).unwrap();
+        let file_ms = 1000 * u64::from(reader.duration()) / u64::from(reader.spec().sample_rate);
+        assert!(
+            near(file_ms, stats.duration_ms),
+            "{} ms in the file",
+            file_ms
+        );
+    }
+
+    #[test]
+    fn pausing_then_stopping_ends_session_without_take() {
+        let mut recorder = Recorder::new();
+        // Nothing is said until long after the session has been stopped.
+        let receiver = start_session(
+            &mut recorder,
+            vec![Sentence::new(1, "One".into())],
+            tone_after_silence(60_000, 1000),
+            test_settings(),
+        );
+        let started = receiver.recv_timeout(EVENT_TIMEOUT).unwrap();
+        assert!(matches!(started.kind, AutoRecordEventKind::SentenceStarted));
+
+        recorder.pause_auto_record().unwrap();
+        recorder.stop_auto_record().unwrap();
+        let events = events_until_complete(&receiver);
+        recorder.wait_auto_record();
+
+        let kinds: Vec<_> = events.iter().map(|event| event.kind).collect();
+        assert!(
+            matches!(
+                kinds[..],
+                [AutoRecordEventKind::Paused, AutoRecordEventKind::Complete]
+            ),
+            "unexpected events: {:?}",
+            kinds
+        );
+    }
 }
@@ -1,19 +1,12 @@
-use super::auto_record::AutoRecordState;
-use super::config::RecordingState;
-use cpal::Stream;
-use hound::WavWriter;
-use log::{debug, error};
-use std::fs::File;
-use std::io::BufWriter;
+use super::source::SourceStream;
+use super::utils::WavFileWriter;
+use log::error;
 use std::ops::Drop;
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
 pub struct RecordingSession {
-    pub stream: Option<Stream>,
-    pub writer: Arc<Mutex<WavWriter<BufWriter<File>>>>,
-    pub path: PathBuf,
-    pub state_arc: Arc<Mutex<AutoRecordState>>,
+    pub stream: Option<SourceStream>,
+    pub writer: Arc<Mutex<WavFileWriter>>,
 }
 
 impl Drop for RecordingSession {
@@ -26,18 +19,5 @@ impl Drop for RecordingSession {
         if let Err(e) = writer.flush() {
             error!("Failed to flush writer: {}", e);
         }
-        drop(writer);
-
-        let is_stopped = {
-            let state = self.state_arc.lock().unwrap();
-            state.state == RecordingState::Idle
-        };
-
-        if is_stopped {
-            debug!("Auto-record stopped. Cleaning up WAV file.");
-            if let Err(e) = std::fs::remove_file(&self.path) {
-                error!("Failed to remove WAV file: {}", e);
-            }
-        }
     }
 }
@@ -0,0 +1,99 @@
+use super::editing::{ensure_backup, read_wav, write_wav, AudioBuffer};
+use super::errors::RecorderError;
+use super::stats::measure_take;
+use super::stream::{get_chunk_size, VAD_RESAMPLER, VAD_SAMPLE_RATE, VAD_VOICE_PROBABILITY};
+use super::vad_filter::VadFilter;
+use crate::models::{SentenceStats, VadPreprocessingSettings};
+use log::debug;
+use samplerate::Samplerate;
+use std::path::Path;
+use std::time::Duration;
+use voice_activity_detector::VoiceActivityDetector;
+
+/// Frames from the start of the first chunk in which speech is detected to
+/// the end of the last one, analysed as during recording. `None` if no
+/// speech is found.
+fn voiced_frames(
+    buffer: &AudioBuffer,
+    vad_preprocessing: VadPreprocessingSettings,
+) -> Result<Option<(usize, usize)>, RecorderError> {
+    let sample_rate = buffer.spec.sample_rate;
+    let channels = buffer.spec.channels.max(1) as usize;
+    let chunk_size = get_chunk_size(sample_rate as usize)?;
+    let mut vad = VoiceActivityDetector::builder()
+        .sample_rate(VAD_SAMPLE_RATE as i64)
+        .chunk_size(get_chunk_size(VAD_SAMPLE_RATE)?)
+        .build()
+        .map_err(|e| RecorderError::Other(format!("Failed to build VAD: {}", e)))?;
+    let mut vad_filter = VadFilter::new(vad_preprocessing, sample_rate);
+    let mut converter = Samplerate::new(VAD_RESAMPLER, sample_rate, VAD_SAMPLE_RATE as u32, 1)
+        .map_err(|e| RecorderError::Other(e.to_string()))?;
+
+    let mut voiced: Option<(usize, usize)> = None;
+    for (index, chunk) in buffer.samples.chunks(chunk_size * channels).enumerate() {
+        let mut mono: Vec<f32> = chunk
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        vad_filter.process(&mut mono);
+        let downsampled = converter
+            .process(&mono)
+            .map_err(|e| RecorderError::Other(e.to_string()))?;
+        if vad.predict(downsampled) >= VAD_VOICE_PROBABILITY {
+            let start = index * chunk_size;
+            let end = start + mono.len();
+            voiced = Some(voiced.map_or((start, end), |(first, _)| (first, end)));
+        }
+    }
+    Ok(voiced)
+}
+
+/**
+ * Trims the silence before and after the speech in a recorded take down to
+ * `padding`, finding the speech with the same voice detection used while
+ * recording. Silence already trimmed away can't be brought back, so a take
+ * with less than `padding` keeps what it has. The original is kept as a
+ * `.bak` file so the edit can be undone. Returns the take's new stats, or
+ * `None` if no speech was found and the take was left alone.
+ */
+pub(crate) fn retrim_take(
+    path: &Path,
+    padding: Duration,
+    vad_preprocessing: VadPreprocessingSettings,
+) -> Result<Option<SentenceStats>, RecorderError> {
+    let mut buffer = read_wav(path)?;
+    let Some((voice_start, voice_end)) = voiced_frames(&buffer, vad_preprocessing)? else {
+        debug!("No speech found in {:?}, leaving it untrimmed", path);
+        return Ok(None);
+    };
+
+    let frames = buffer.frame_count();
+    let padding_frames = (padding.as_secs_f64() * buffer.spec.sample_rate as f64) as usize;
+    let start = voice_start.saturating_sub(padding_frames);
+    let end = (voice_end + padding_frames).min(frames);
+    let channels = buffer.spec.channels.max(1) as usize;
+    if (start, end) != (0, frames) {
+        ensure_backup(path)?;
+        buffer.samples = buffer.samples[start * channels..end * channels].to_vec();
+        write_wav(path, &buffer)?;
+    }
+
+    let samples: Vec<i16> = buffer
+        .samples
+        .iter()
+        .map(|&s| {
+            (s * 32768.0)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+        })
+        .collect();
+    let to_duration =
+        |frames: usize| Duration::from_secs_f64(frames as f64 / buffer.spec.sample_rate as f64);
+    Ok(Some(measure_take(
+        &samples,
+        buffer.spec.channels,
+        buffer.spec.sample_rate,
+        to_duration(voice_start - start),
+        to_duration(end.saturating_sub(voice_end)),
+    )))
+}
@@ -0,0 +1,182 @@
+use super::convert::{remix, resample};
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use crate::storage;
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels, Encoder};
+use serde::Deserialize;
+use std::num::{NonZeroU32, NonZeroU8};
+use std::path::Path;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// Opus always runs at 48 kHz; other rates are resampled.
+const OPUS_SAMPLE_RATE: u32 = 48000;
+/// 20 ms frames, the usual choice for speech.
+const OPUS_FRAME_LEN: usize = 960;
+/// Largest Opus packet, as recommended by libopus.
+const OPUS_MAX_PACKET: usize = 4000;
+/// Ogg stream serial number. Each review file holds a single stream, so it
+/// only has to be consistent within the file.
+const OGG_SERIAL: u32 = 0x7265_6364;
+
+/// Compressed format of review copies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewFormat {
+    /// Opus in an Ogg container.
+    Opus,
+    /// Vorbis in an Ogg container.
+    Ogg,
+}
+
+impl ReviewFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReviewFormat::Opus => "opus",
+            ReviewFormat::Ogg => "ogg",
+        }
+    }
+}
+
+/// Encodes a lightweight copy of the WAV file at `source` to `output`,
+/// leaving the source untouched. Both codecs take at most two channels, so
+/// wider takes are reduced to stereo.
+pub fn encode_review_copy(
+    source: &Path,
+    output: &Path,
+    format: ReviewFormat,
+    bitrate_kbps: u32,
+) -> Result<(), RecorderError> {
+    let buffer = read_wav(source)?;
+    let channels = buffer.spec.channels.clamp(1, 2);
+    let bitrate = bitrate_kbps
+        .checked_mul(1000)
+        .filter(|&bitrate| bitrate > 0)
+        .ok_or_else(|| RecorderError::Other("Bitrate must be non-zero".into()))?;
+
+    let data = match format {
+        ReviewFormat::Opus => {
+            let samples = resample(&buffer, OPUS_SAMPLE_RATE)?;
+            let samples = remix(&samples, buffer.spec.channels, channels);
+            encode_opus(&samples, channels, buffer.spec.sample_rate, bitrate)?
+        }
+        ReviewFormat::Ogg => {
+            let samples = remix(&buffer.samples, buffer.spec.channels, channels);
+            encode_vorbis(&samples, channels, buffer.spec.sample_rate, bitrate)?
+        }
+    };
+    storage::write(output, &data).map_err(|e| RecorderError::Other(e.to_string()))
+}
+
+pub(super) fn codec_error(e: impl ToString) -> RecorderError {
+    RecorderError::Other(e.to_string())
+}
+
+/// Encodes 48 kHz interleaved samples as Ogg Opus (RFC 7845).
+fn encode_opus(
+    samples: &[f32],
+    channels: u16,
+    input_sample_rate: u32,
+    bitrate: u32,
+) -> Result<Vec<u8>, RecorderError> {
+    let mode = if channels == 1 {
+        Channels::Mono
+    } else {
+        Channels::Stereo
+    };
+    let mut encoder =
+        Encoder::new(OPUS_SAMPLE_RATE, mode, Application::Audio).map_err(codec_error)?;
+    encoder
+        .set_bitrate(Bitrate::Bits(bitrate as i32))
+        .map_err(codec_error)?;
+    let pre_skip = encoder.get_lookahead().map_err(codec_error)? as u16;
+
+    let mut data = Vec::new();
+    let mut writer = PacketWriter::new(&mut data);
+
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version
+    head.push(channels as u8);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0); // Mono or stereo channel mapping
+    writer
+        .write_packet(head, OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(codec_error)?;
+
+    let vendor = concat!("recordr ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes()); // No user comments
+    writer
+        .write_packet(tags, OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)
+        .map_err(codec_error)?;
+
+    // The last frame is padded with silence; its granule position marks
+    // where the audio really ends.
+    let frame_len = OPUS_FRAME_LEN * channels as usize;
+    let total_frames = (samples.len() / channels as usize) as u64;
+    let frame_count = samples.len().div_ceil(frame_len).max(1);
+    let mut frame = vec![0.0f32; frame_len];
+    for i in 0..frame_count {
+        let chunk = samples.get(i * frame_len..).unwrap_or_default();
+        let chunk = &chunk[..chunk.len().min(frame_len)];
+        frame[..chunk.len()].copy_from_slice(chunk);
+        frame[chunk.len()..].fill(0.0);
+
+        let packet = encoder
+            .encode_vec_float(&frame, OPUS_MAX_PACKET)
+            .map_err(codec_error)?;
+        let last = i + 1 == frame_count;
+        let encoded = ((i + 1) * OPUS_FRAME_LEN) as u64;
+        let granule = pre_skip as u64 + if last { total_frames } else { encoded };
+        let end_info = if last {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        writer
+            .write_packet(packet, OGG_SERIAL, end_info, granule)
+            .map_err(codec_error)?;
+    }
+    drop(writer);
+    Ok(data)
+}
+
+/// Encodes interleaved samples as Ogg Vorbis at the source's sample rate.
+fn encode_vorbis(
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    bitrate: u32,
+) -> Result<Vec<u8>, RecorderError> {
+    let invalid = || RecorderError::Other("Invalid audio for Vorbis encoding".into());
+    let mut data = Vec::new();
+    let mut builder = VorbisEncoderBuilder::new(
+        NonZeroU32::new(sample_rate).ok_or_else(invalid)?,
+        NonZeroU8::new(channels as u8).ok_or_else(invalid)?,
+        &mut data,
+    )
+    .map_err(codec_error)?;
+    builder.bitrate_management_strategy(VorbisBitrateManagementStrategy::Vbr {
+        target_bitrate: NonZeroU32::new(bitrate).ok_or_else(invalid)?,
+    });
+    let mut encoder = builder.build().map_err(codec_error)?;
+
+    // The encoder takes one buffer per channel.
+    let planar: Vec<Vec<f32>> = (0..channels as usize)
+        .map(|c| {
+            samples
+                .iter()
+                .skip(c)
+                .step_by(channels as usize)
+                .copied()
+                .collect()
+        })
+        .collect();
+    encoder.encode_audio_block(&planar).map_err(codec_error)?;
+    encoder.finish().map_err(codec_error)?;
+    Ok(data)
+}
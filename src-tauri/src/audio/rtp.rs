@@ -0,0 +1,148 @@
+use super::errors::RecorderError;
+use super::source::{AudioSource, DataCallback, ErrorCallback, SourceStream};
+use crate::errors::RecordrError;
+use log::{debug, error, trace};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Fixed part of the RTP header (RFC 3550).
+const RTP_HEADER_LEN: usize = 12;
+/// Largest possible UDP payload.
+const MAX_PACKET_LEN: usize = 65536;
+/// How often the receive thread checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Receives 16-bit linear PCM (`L16`, RFC 3551) over RTP on a UDP port, so
+/// a booth machine can stream its microphone to the recording machine.
+/// Packets are played in arrival order; lost packets leave a gap rather
+/// than being concealed.
+#[derive(Debug)]
+pub struct RtpSource {
+    address: SocketAddr,
+    channels: u16,
+    sample_rate: usize,
+}
+
+impl RtpSource {
+    pub fn new(address: &str, channels: u16, sample_rate: u32) -> Result<Self, RecordrError> {
+        let address = address.parse().map_err(|_| {
+            RecordrError::invalid_input("Invalid RTP listen address").with_context(address)
+        })?;
+        if channels == 0 || sample_rate == 0 {
+            return Err(RecordrError::invalid_input(
+                "RTP channels and sample rate must be non-zero",
+            ));
+        }
+        Ok(Self {
+            address,
+            channels,
+            sample_rate: sample_rate as usize,
+        })
+    }
+}
+
+impl AudioSource for RtpSource {
+    fn name(&self) -> Option<String> {
+        Some(format!("rtp://{}", self.address))
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn start(
+        &self,
+        mut on_data: DataCallback,
+        mut on_error: ErrorCallback,
+    ) -> Result<SourceStream, RecorderError> {
+        let socket = UdpSocket::bind(self.address)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        debug!("Listening for RTP audio on {}", self.address);
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            std::thread::spawn(move || {
+                let mut packet = vec![0u8; MAX_PACKET_LEN];
+                let mut samples = Vec::new();
+                while !stopped.load(Ordering::Relaxed) {
+                    let len = match socket.recv(&mut packet) {
+                        Ok(len) => len,
+                        Err(e)
+                            if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                        {
+                            continue
+                        }
+                        Err(e) => {
+                            error!("RTP receive failed: {}", e);
+                            on_error(e.into());
+                            break;
+                        }
+                    };
+                    match rtp_payload(&packet[..len]) {
+                        Some(payload) => {
+                            samples.clear();
+                            samples.extend(
+                                payload
+                                    .chunks_exact(2)
+                                    .map(|b| i16::from_be_bytes([b[0], b[1]])),
+                            );
+                            on_data(&samples);
+                        }
+                        None => trace!("Ignoring malformed RTP packet of {} bytes", len),
+                    }
+                }
+            })
+        };
+
+        Ok(Box::new(RtpReceiver {
+            stopped,
+            thread: Some(thread),
+        }))
+    }
+}
+
+/// Returns the payload of an RTP packet, skipping the CSRC list, header
+/// extension and padding.
+fn rtp_payload(packet: &[u8]) -> Option<&[u8]> {
+    if packet.len() < RTP_HEADER_LEN || packet[0] >> 6 != 2 {
+        return None;
+    }
+    let has_padding = packet[0] & 0x20 != 0;
+    let has_extension = packet[0] & 0x10 != 0;
+    let csrc_count = (packet[0] & 0x0f) as usize;
+
+    let mut start = RTP_HEADER_LEN + csrc_count * 4;
+    if has_extension {
+        let header = packet.get(start..start + 4)?;
+        let words = u16::from_be_bytes([header[2], header[3]]) as usize;
+        start += 4 + words * 4;
+    }
+    let mut end = packet.len();
+    if has_padding {
+        end = end.checked_sub(*packet.last()? as usize)?;
+    }
+    packet.get(start..end)
+}
+
+struct RtpReceiver {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for RtpReceiver {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
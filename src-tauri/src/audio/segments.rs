@@ -0,0 +1,212 @@
+use super::errors::RecorderError;
+use super::utils::{create_wav, SampleSink, WavFileWriter};
+use crate::storage;
+use hound::{WavReader, WavSpec};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+type SegmentWriter = WavFileWriter;
+
+/// Lists the finished segments of a long recording. Rewritten after every
+/// segment, so a crash leaves a manifest describing everything but the
+/// segment in progress.
+#[derive(Debug, Serialize, Deserialize)]
+struct SegmentManifest {
+    sample_rate: u32,
+    channels: u16,
+    /// Samples (across all channels) each segment contributes when stitched.
+    segment_samples: u64,
+    /// Samples each segment repeats from the start of the next one.
+    overlap_samples: u64,
+    segments: Vec<String>,
+}
+
+/// Writes a long recording as a series of fixed-length WAV segments, each
+/// extended by an overlap into the next, so a crash loses at most the
+/// segment being written.
+pub struct SegmentedWriter {
+    directory: PathBuf,
+    stem: String,
+    spec: WavSpec,
+    manifest: SegmentManifest,
+    index: usize,
+    current: Option<SegmentWriter>,
+    current_written: u64,
+    next: Option<SegmentWriter>,
+}
+
+impl SegmentedWriter {
+    /// Starts a segmented recording for `path`. Segments are written next to
+    /// it as `<stem>.partNNN.wav`, along with a `<stem>.segments.json`
+    /// manifest. The overlap must be shorter than a segment, or a segment
+    /// would have to start before the previous one has.
+    pub fn create(
+        path: &Path,
+        spec: WavSpec,
+        segment_length: Duration,
+        overlap: Duration,
+    ) -> Result<Self, RecorderError> {
+        let to_samples = |duration: Duration| {
+            let frames = (duration.as_secs_f64() * spec.sample_rate as f64) as u64;
+            frames * spec.channels as u64
+        };
+        if to_samples(segment_length) == 0 {
+            return Err(RecorderError::Other("Segment length is too short".into()));
+        }
+        if to_samples(overlap) >= to_samples(segment_length) {
+            return Err(RecorderError::Other(
+                "Segment overlap must be shorter than the segment length".into(),
+            ));
+        }
+
+        let mut writer = Self {
+            directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            stem: path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "recording".into()),
+            spec,
+            manifest: SegmentManifest {
+                sample_rate: spec.sample_rate,
+                channels: spec.channels,
+                segment_samples: to_samples(segment_length),
+                overlap_samples: to_samples(overlap),
+                segments: Vec::new(),
+            },
+            index: 0,
+            current: None,
+            current_written: 0,
+            next: None,
+        };
+        writer.current = Some(writer.create_segment(0)?);
+        writer.save_manifest()?;
+        Ok(writer)
+    }
+
+    fn segment_name(&self, index: usize) -> String {
+        format!("{}.part{:03}.wav", self.stem, index)
+    }
+
+    fn create_segment(&self, index: usize) -> Result<SegmentWriter, RecorderError> {
+        let path = self.directory.join(self.segment_name(index));
+        debug!("Starting recording segment {}", path.display());
+        create_wav(&path, self.spec)
+    }
+
+    fn save_manifest(&self) -> Result<(), RecorderError> {
+        let json = serde_json::to_string_pretty(&self.manifest)
+            .map_err(|e| RecorderError::Other(e.to_string()))?;
+        fs::write(
+            self.directory.join(format!("{}.segments.json", self.stem)),
+            json,
+        )?;
+        Ok(())
+    }
+
+    /// Finalizes the current segment and continues in the next one.
+    fn rotate(&mut self) -> Result<(), RecorderError> {
+        if let Some(current) = self.current.take() {
+            current.finalize()?;
+            self.manifest.segments.push(self.segment_name(self.index));
+            self.save_manifest()?;
+        }
+        self.index += 1;
+        self.current_written = self.current_written - self.manifest.segment_samples;
+        self.current = match self.next.take() {
+            Some(next) => Some(next),
+            None => Some(self.create_segment(self.index)?),
+        };
+        Ok(())
+    }
+
+    fn write(&mut self, sample: i16) -> Result<(), RecorderError> {
+        let segment_samples = self.manifest.segment_samples;
+        if self.current_written >= segment_samples + self.manifest.overlap_samples {
+            self.rotate()?;
+        }
+        if self.current_written >= segment_samples && self.next.is_none() {
+            self.next = Some(self.create_segment(self.index + 1)?);
+        }
+
+        if let Some(current) = self.current.as_mut() {
+            current.write_sample(sample)?;
+        }
+        if let Some(next) = self.next.as_mut() {
+            next.write_sample(sample)?;
+        }
+        self.current_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), RecorderError> {
+        if let Some(current) = self.current.take() {
+            current.finalize()?;
+            self.manifest.segments.push(self.segment_name(self.index));
+        }
+        if let Some(next) = self.next.take() {
+            next.finalize()?;
+            self.manifest
+                .segments
+                .push(self.segment_name(self.index + 1));
+        }
+        self.save_manifest()
+    }
+}
+
+impl SampleSink for SegmentedWriter {
+    fn write_sample(&mut self, sample: i16) -> Result<(), RecorderError> {
+        self.write(sample)
+    }
+
+    fn finish(&mut self) -> Result<(), RecorderError> {
+        SegmentedWriter::finish(self)
+    }
+}
+
+impl Drop for SegmentedWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to finalize segmented recording: {}", e);
+        }
+    }
+}
+
+/// Joins the segments listed in a manifest into a single WAV file, dropping
+/// the overlap each segment shares with the next. Segments of an encrypted
+/// project are decrypted to read them and the joined file is encrypted.
+pub fn stitch_segments(manifest_path: &Path, output_path: &Path) -> Result<(), RecorderError> {
+    let manifest: SegmentManifest = serde_json::from_str(&fs::read_to_string(manifest_path)?)
+        .map_err(|e| RecorderError::Other(e.to_string()))?;
+    let directory = manifest_path.parent().unwrap_or(Path::new(""));
+    let last = manifest
+        .segments
+        .len()
+        .checked_sub(1)
+        .ok_or_else(|| RecorderError::Other("No finished segments to stitch".into()))?;
+
+    let mut writer: Option<SegmentWriter> = None;
+    for (i, name) in manifest.segments.iter().enumerate() {
+        let segment = storage::open(&directory.join(name))
+            .map_err(|e| RecorderError::Other(e.to_string()))?;
+        let mut reader = WavReader::new(segment)?;
+        if writer.is_none() {
+            writer = Some(create_wav(output_path, reader.spec())?);
+        }
+        let output = writer.as_mut().unwrap();
+        let take = if i == last {
+            usize::MAX
+        } else {
+            manifest.segment_samples as usize
+        };
+        for sample in reader.samples::<i16>().take(take) {
+            output.write_sample(sample?)?;
+        }
+    }
+    if let Some(writer) = writer {
+        writer.finalize()?;
+    }
+    Ok(())
+}
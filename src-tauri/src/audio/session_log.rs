@@ -0,0 +1,212 @@
+use super::errors::RecorderError;
+use super::events::{AutoRecordEvent, AutoRecordEventKind, RecorderEvents};
+use super::source::AudioSource;
+use crate::models::RecordingSettings;
+use crate::paths::long_path;
+use crate::storage;
+use chrono::{DateTime, Local, Utc};
+use log::{debug, warn};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Folder in the project directory that session logs are written to.
+const LOG_DIRECTORY: &str = "logs";
+
+/// Speech probabilities the VAD gave the chunks of one take.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VadSummary {
+    pub chunks: usize,
+    /// Chunks at or above the voice probability threshold.
+    pub voiced_chunks: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl VadSummary {
+    pub fn add(&mut self, probability: f32, is_voice: bool) {
+        if self.chunks == 0 {
+            (self.min, self.max) = (probability, probability);
+        } else {
+            self.min = self.min.min(probability);
+            self.max = self.max.max(probability);
+        }
+        self.chunks += 1;
+        self.voiced_chunks += is_voice as usize;
+        self.mean += (probability - self.mean) / self.chunks as f32;
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Entry<'a> {
+    #[serde(rename_all = "camelCase")]
+    SessionStarted {
+        device: Option<String>,
+        sample_rate: usize,
+        channels: u16,
+        app_version: &'static str,
+        settings: &'a RecordingSettings,
+    },
+    Event {
+        event: &'a AutoRecordEvent,
+    },
+    #[serde(rename_all = "camelCase")]
+    Take {
+        sentence_id: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        take_number: Option<u32>,
+        outcome: &'a str,
+        vad: VadSummary,
+    },
+    #[serde(rename_all = "camelCase")]
+    Waiting {
+        sentence_id: Option<usize>,
+        reason: &'a str,
+    },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        sentence_id: Option<usize>,
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Line<'a> {
+    time: DateTime<Utc>,
+    session_id: &'a str,
+    #[serde(flatten)]
+    entry: Entry<'a>,
+}
+
+/**
+ * Audit trail of one recording session, written as JSON lines to the
+ * project's `logs` folder: the device and settings used, every event, a
+ * summary of the VAD's decisions on each take and any errors. Each line is
+ * flushed as it is written, so the log survives a crash. A log that can no
+ * longer be written to is given up on rather than failing the session.
+ */
+#[derive(Debug)]
+pub struct SessionLog {
+    session_id: String,
+    path: PathBuf,
+    file: Mutex<Option<BufWriter<File>>>,
+}
+
+impl SessionLog {
+    /// Creates the log of session `session_id` in `project_directory`.
+    /// Encrypted projects get no log, as it would be stored in the clear.
+    pub fn create(
+        project_directory: &Path,
+        session_id: &str,
+    ) -> Result<Option<Arc<Self>>, RecorderError> {
+        if storage::has_key(project_directory) {
+            debug!("Not writing a session log for an encrypted project");
+            return Ok(None);
+        }
+        let directory = project_directory.join(LOG_DIRECTORY);
+        fs::create_dir_all(long_path(&directory))?;
+        let path = directory.join(format!(
+            "session_{}_{}.jsonl",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            session_id
+        ));
+        let file = File::create(long_path(&path))?;
+        debug!("Writing session log to {:?}", path);
+        Ok(Some(Arc::new(Self {
+            session_id: session_id.to_string(),
+            path,
+            file: Mutex::new(Some(BufWriter::new(file))),
+        })))
+    }
+
+    fn write(&self, entry: Entry) {
+        let mut file = self.file.lock().unwrap();
+        let Some(writer) = file.as_mut() else {
+            return;
+        };
+        let line = Line {
+            time: Utc::now(),
+            session_id: &self.session_id,
+            entry,
+        };
+        let result = serde_json::to_writer(&mut *writer, &line)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(writer))
+            .and_then(|()| writer.flush());
+        if let Err(e) = result {
+            warn!("Failed to write session log {:?}: {}", self.path, e);
+            *file = None;
+        }
+    }
+
+    /// Records the device the session records from and its settings.
+    pub fn session_started(&self, source: &dyn AudioSource, settings: &RecordingSettings) {
+        self.write(Entry::SessionStarted {
+            device: source.name(),
+            sample_rate: source.sample_rate(),
+            channels: source.channels(),
+            app_version: env!("CARGO_PKG_VERSION"),
+            settings,
+        });
+    }
+
+    pub fn event(&self, event: &AutoRecordEvent) {
+        self.write(Entry::Event { event });
+    }
+
+    /// Records how a take ended and what the VAD made of it.
+    pub fn take(
+        &self,
+        sentence_id: usize,
+        take_number: Option<u32>,
+        outcome: &str,
+        vad: VadSummary,
+    ) {
+        self.write(Entry::Take {
+            sentence_id,
+            take_number,
+            outcome,
+            vad,
+        });
+    }
+
+    /// Records that the session is waiting, e.g. to be resumed.
+    pub fn waiting(&self, sentence_id: Option<usize>, reason: &str) {
+        self.write(Entry::Waiting {
+            sentence_id,
+            reason,
+        });
+    }
+
+    pub fn error(&self, sentence_id: Option<usize>, error: &impl std::fmt::Display) {
+        self.write(Entry::Error {
+            sentence_id,
+            message: error.to_string(),
+        });
+    }
+}
+
+/// Passes a session's events on to `events`, logging each one. Countdown
+/// ticks and monitor levels are too frequent to be worth keeping.
+pub struct LoggedEvents {
+    pub events: Arc<dyn RecorderEvents>,
+    pub log: Arc<SessionLog>,
+}
+
+impl RecorderEvents for LoggedEvents {
+    fn send(&self, event: AutoRecordEvent) {
+        if !matches!(
+            event.kind,
+            AutoRecordEventKind::CountdownTick | AutoRecordEventKind::Level
+        ) {
+            self.log.event(&event);
+        }
+        self.events.send(event);
+    }
+}
@@ -0,0 +1,85 @@
+use super::auto_record::AutoRecordState;
+use super::config::InputLatency;
+use super::errors::RecorderError;
+use crate::models::{
+    FilterSettings, NoiseReductionSettings, OutputFormat, SentenceStats, VadPreprocessingSettings,
+};
+use crate::storage;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Provenance of a single take, written next to its WAV file so the file is
+/// self-describing once copied out of the project.
+#[derive(Serialize)]
+struct Sidecar {
+    sentence_id: usize,
+    text: String,
+    take: u32,
+    recorded_at: DateTime<Utc>,
+    device: Option<String>,
+    sample_rate: usize,
+    channels: u16,
+    input_latency: Option<InputLatency>,
+    output_format: OutputFormat,
+    voice_probability: f32,
+    silence_threshold: f32,
+    silence_duration_ms: u64,
+    silence_padding_ms: u64,
+    silence_confirmation_ms: u64,
+    noise_reduction: NoiseReductionSettings,
+    filters: FilterSettings,
+    vad_preprocessing: VadPreprocessingSettings,
+    /// Span of detected speech within the file.
+    speech_start_ms: Option<u64>,
+    speech_end_ms: Option<u64>,
+    stats: Option<SentenceStats>,
+    app_version: &'static str,
+}
+
+/// Path of the sidecar of the take at `wav_path`: the take's whole file name
+/// with `.json` appended. Swapping the extension instead could land on the
+/// project file, or on the sidecar of a take with the same stem.
+pub fn sidecar_path(wav_path: &Path) -> PathBuf {
+    let mut path = wav_path.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Writes the sidecar for the take of the current sentence that was just
+/// recorded to `wav_path`.
+pub fn write_sidecar(state: &AutoRecordState, wav_path: &Path) -> Result<(), RecorderError> {
+    let sentence = &state.sentences[state.current_sentence_index];
+    let stats = state.last_take.clone();
+    let sidecar = Sidecar {
+        sentence_id: sentence.id,
+        text: sentence.text.clone(),
+        take: state.take_counts.get(&sentence.id).copied().unwrap_or(1),
+        recorded_at: Utc::now(),
+        device: state.audio_source.name(),
+        sample_rate: state.audio_source.sample_rate(),
+        channels: state.output_channels(),
+        input_latency: state.audio_source.latency(),
+        output_format: state.output_format,
+        voice_probability: state.voice_probability,
+        silence_threshold: state.silence_threshold,
+        silence_duration_ms: state.silence_duration.as_millis() as u64,
+        silence_padding_ms: state.silence_padding.as_millis() as u64,
+        silence_confirmation_ms: state.silence_confirmation.as_millis() as u64,
+        noise_reduction: state.noise_reduction,
+        filters: state.filters,
+        vad_preprocessing: state.vad_preprocessing,
+        speech_start_ms: stats.as_ref().map(|s| s.leading_silence_ms),
+        speech_end_ms: stats
+            .as_ref()
+            .map(|s| s.duration_ms.saturating_sub(s.trailing_silence_ms)),
+        stats,
+        app_version: env!("CARGO_PKG_VERSION"),
+    };
+
+    let json =
+        serde_json::to_string_pretty(&sidecar).map_err(|e| RecorderError::Other(e.to_string()))?;
+    storage::write(&sidecar_path(wav_path), json.as_bytes())
+        .map_err(|e| RecorderError::Other(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use super::stats::to_dbfs;
+use crate::errors::RecordrError;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Lowest silence threshold, in dBFS: the noise floor of 16-bit audio.
+pub const MIN_SILENCE_THRESHOLD_DBFS: f32 = -96.0;
+
+/// Most a chunk's peak may stand above its RMS level, in dB, for it to
+/// count as voice. Speech stays well below this, while a click packs its
+/// energy into a few samples.
+const MAX_VOICE_CREST_DB: f32 = 20.0;
+
+/// Refuses silence thresholds outside the range of 16-bit audio.
+pub fn check_silence_threshold(threshold_dbfs: f32) -> Result<(), RecordrError> {
+    if !(MIN_SILENCE_THRESHOLD_DBFS..=0.0).contains(&threshold_dbfs) {
+        return Err(RecordrError::invalid_input(format!(
+            "Silence threshold must be between {} and 0 dBFS",
+            MIN_SILENCE_THRESHOLD_DBFS
+        )));
+    }
+    Ok(())
+}
+
+/// Refuses VAD speech probabilities outside 0 to 1.
+pub fn check_voice_probability(voice_probability: f32) -> Result<(), RecordrError> {
+    if !(0.0..=1.0).contains(&voice_probability) {
+        return Err(RecordrError::invalid_input(
+            "Voice probability must be between 0.0 and 1.0",
+        ));
+    }
+    Ok(())
+}
+
+/// Peak level of a block of samples, in dBFS.
+fn peak_dbfs(samples: &[i16]) -> f32 {
+    let peak = samples.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+    to_dbfs(f32::from(peak) / 32768.0)
+}
+
+/// Level of a block of samples, in dBFS, from its RMS.
+pub fn rms_dbfs(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return MIN_SILENCE_THRESHOLD_DBFS;
+    }
+    let sum_squares: f64 = samples.iter().map(|&s| f64::from(s).powi(2)).sum();
+    to_dbfs((sum_squares / samples.len() as f64).sqrt() as f32 / 32768.0)
+}
+
+/// Voice and silence detection settings shared with the audio callback of a
+/// session or monitor, so a change made while recording takes effect on the
+/// next chunk.
+#[derive(Debug, Default)]
+pub struct SilenceGate {
+    /// Bits of the `f32` VAD speech probability that counts as voice.
+    voice_probability: AtomicU32,
+    /// Bits of the `f32` threshold in dBFS.
+    threshold_dbfs: AtomicU32,
+    /// Silence that ends a take, including the confirmation window, in ms.
+    end_of_take_ms: AtomicU64,
+}
+
+impl SilenceGate {
+    pub fn new(voice_probability: f32, threshold_dbfs: f32, end_of_take_silence: Duration) -> Self {
+        let gate = Self::default();
+        gate.set_voice_probability(voice_probability);
+        gate.set_threshold(threshold_dbfs);
+        gate.set_end_of_take_silence(end_of_take_silence);
+        gate
+    }
+
+    pub fn set_voice_probability(&self, voice_probability: f32) {
+        self.voice_probability
+            .store(voice_probability.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_threshold(&self, threshold_dbfs: f32) {
+        self.threshold_dbfs
+            .store(threshold_dbfs.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn set_end_of_take_silence(&self, silence: Duration) {
+        self.end_of_take_ms
+            .store(silence.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn end_of_take_silence(&self) -> Duration {
+        Duration::from_millis(self.end_of_take_ms.load(Ordering::Relaxed))
+    }
+
+    /**
+     * Whether a chunk is voice: the VAD gave it at least the voice
+     * probability, it is loud enough and it is not a click. Chunks below
+     * the threshold count as silence whatever the VAD makes of them, so
+     * breaths and room noise don't start or prolong a take on their own.
+     * A loud chunk whose peak stands far above its RMS level is taken for
+     * a click, such as a lip smack or a key being hit, since RMS alone
+     * can't tell one from speech. Thresholds above 0 dBFS leave the energy
+     * gate open, though clicks are still left out.
+     */
+    pub fn is_voice(&self, probability: f32, chunk: &[i16]) -> bool {
+        let voice_probability = f32::from_bits(self.voice_probability.load(Ordering::Relaxed));
+        if probability < voice_probability {
+            return false;
+        }
+        let threshold = f32::from_bits(self.threshold_dbfs.load(Ordering::Relaxed));
+        let level = rms_dbfs(chunk);
+        if peak_dbfs(chunk) - level > MAX_VOICE_CREST_DB {
+            return false;
+        }
+        threshold > 0.0 || level >= threshold
+    }
+}
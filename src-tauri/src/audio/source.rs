@@ -0,0 +1,239 @@
+use super::config::{AudioConfig, InputLatency};
+use super::errors::RecorderError;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{BufferSize, InputCallbackInfo, Sample, SampleFormat};
+use hound::{SampleFormat as HoundSampleFormat, WavReader};
+use log::{error, trace};
+use std::any::Any;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Keeps a started source delivering audio until it is dropped.
+pub type SourceStream = Box<dyn Any>;
+
+/// Callback receiving interleaved 16-bit samples from a source.
+pub type DataCallback = Box<dyn FnMut(&[i16]) + Send>;
+
+/// Callback told when a started source fails, e.g. because the device was
+/// unplugged.
+pub type ErrorCallback = Box<dyn FnMut(RecorderError) + Send>;
+
+/// Input audio for auto-recording. The input device is one source; others
+/// can feed prerecorded or generated audio through the same VAD, trimming
+/// and writing pipeline.
+pub trait AudioSource: fmt::Debug + Send {
+    /// Name recorded in take provenance, if the source has one.
+    fn name(&self) -> Option<String>;
+    fn channels(&self) -> u16;
+    fn sample_rate(&self) -> usize;
+    /// Starts delivering audio to `on_data`. Failures after the source has
+    /// started are reported to `on_error`.
+    fn start(
+        &self,
+        on_data: DataCallback,
+        on_error: ErrorCallback,
+    ) -> Result<SourceStream, RecorderError>;
+    /// Input latency, for sources where it is known.
+    fn latency(&self) -> Option<InputLatency> {
+        None
+    }
+}
+
+/// Records the delay between capture and the data callback reported by the
+/// host, if it reports one.
+fn record_capture_latency(info: &InputCallbackInfo, latency_us: &AtomicU32) {
+    let timestamp = info.timestamp();
+    if let Some(latency) = timestamp.callback.duration_since(&timestamp.capture) {
+        latency_us.store(latency.as_micros() as u32, Ordering::Relaxed);
+    }
+}
+
+impl AudioSource for AudioConfig {
+    fn name(&self) -> Option<String> {
+        self.device.0.name().ok()
+    }
+
+    fn channels(&self) -> u16 {
+        self.supported_config.channels()
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn start(
+        &self,
+        mut on_data: DataCallback,
+        mut on_error: ErrorCallback,
+    ) -> Result<SourceStream, RecorderError> {
+        let err_fn = move |err| on_error(RecorderError::CpalStreamError(err));
+        let latency_us = Arc::clone(&self.capture_latency_us);
+        trace!("Building input stream with config: {:?}", self.config);
+
+        let stream = match self.supported_config.sample_format() {
+            SampleFormat::I16 => self.device.0.build_input_stream(
+                &self.config,
+                move |data: &[i16], info: &InputCallbackInfo| {
+                    record_capture_latency(info, &latency_us);
+                    on_data(data)
+                },
+                err_fn,
+            ),
+            SampleFormat::F32 => self.device.0.build_input_stream(
+                &self.config,
+                move |data: &[f32], info: &InputCallbackInfo| {
+                    record_capture_latency(info, &latency_us);
+                    // Convert f32 samples to i16
+                    let data_i16: Vec<i16> = data
+                        .iter()
+                        .map(|&sample| (sample * i16::MAX as f32) as i16)
+                        .collect();
+                    on_data(&data_i16)
+                },
+                err_fn,
+            ),
+            SampleFormat::U16 => self.device.0.build_input_stream(
+                &self.config,
+                move |data: &[u16], info: &InputCallbackInfo| {
+                    record_capture_latency(info, &latency_us);
+                    let data_i16: Vec<i16> = data.iter().map(|sample| sample.to_i16()).collect();
+                    on_data(&data_i16)
+                },
+                err_fn,
+            ),
+        }
+        .map_err(RecorderError::CpalBuildStreamError)?;
+
+        if let Err(e) = stream.play() {
+            error!("Failed to start stream. Error: {}", e);
+            return Err(RecorderError::StreamPlayError(e.to_string()));
+        }
+        Ok(Box::new(stream))
+    }
+
+    fn latency(&self) -> Option<InputLatency> {
+        let buffer_frames = match self.config.buffer_size {
+            BufferSize::Fixed(frames) => Some(frames),
+            BufferSize::Default => None,
+        };
+        let capture_latency_us = self.capture_latency_us.load(Ordering::Relaxed);
+        Some(InputLatency {
+            low_latency: self.low_latency,
+            buffer_frames,
+            buffer_latency_ms: buffer_frames
+                .map(|frames| frames as f64 * 1000.0 / self.sample_rate as f64),
+            capture_latency_ms: (capture_latency_us > 0)
+                .then(|| capture_latency_us as f64 / 1000.0),
+        })
+    }
+}
+
+/// Length of audio handed to the callback at a time by `BufferSource`.
+const BUFFER_BLOCK: Duration = Duration::from_millis(10);
+
+/// Plays a fixed buffer of samples in real time, followed by silence until
+/// stopped, so silence detection ends the take the same way it does with a
+/// microphone.
+#[derive(Debug)]
+pub struct BufferSource {
+    samples: Arc<Vec<i16>>,
+    channels: u16,
+    sample_rate: usize,
+}
+
+impl BufferSource {
+    pub fn new(samples: Vec<i16>, channels: u16, sample_rate: usize) -> Self {
+        Self {
+            samples: Arc::new(samples),
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// Loads a WAV file, converting its samples to 16-bit.
+    pub fn from_wav(path: &Path) -> Result<Self, RecorderError> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            HoundSampleFormat::Float => reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| (s * i16::MAX as f32) as i16))
+                .collect::<Result<Vec<_>, _>>()?,
+            HoundSampleFormat::Int => {
+                let shift = spec.bits_per_sample.saturating_sub(16);
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| (s >> shift) as i16))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        Ok(Self::new(samples, spec.channels, spec.sample_rate as usize))
+    }
+}
+
+impl AudioSource for BufferSource {
+    fn name(&self) -> Option<String> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn start(
+        &self,
+        mut on_data: DataCallback,
+        _on_error: ErrorCallback,
+    ) -> Result<SourceStream, RecorderError> {
+        let block_len = (self.sample_rate as f64 * BUFFER_BLOCK.as_secs_f64()) as usize
+            * self.channels as usize;
+        let samples = Arc::clone(&self.samples);
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            std::thread::spawn(move || {
+                let silence = vec![0i16; block_len];
+                let start = Instant::now();
+                let mut blocks = samples.chunks(block_len);
+                let mut delivered = 0u32;
+                while !stopped.load(Ordering::Relaxed) {
+                    on_data(blocks.next().unwrap_or(&silence));
+                    delivered += 1;
+                    // Pace delivery against the clock rather than sleeping a
+                    // fixed amount, so timing does not drift.
+                    if let Some(wait) = (BUFFER_BLOCK * delivered).checked_sub(start.elapsed()) {
+                        std::thread::sleep(wait);
+                    }
+                }
+            })
+        };
+
+        Ok(Box::new(BufferPlayback {
+            stopped,
+            thread: Some(thread),
+        }))
+    }
+}
+
+struct BufferPlayback {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for BufferPlayback {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
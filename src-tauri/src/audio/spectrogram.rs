@@ -0,0 +1,72 @@
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f32::consts::PI;
+use std::path::Path;
+
+/// Samples analysed for each column of a spectrogram.
+const FFT_SIZE: usize = 512;
+/// Levels this far below full scale and quieter are drawn white.
+const DYNAMIC_RANGE_DB: f32 = 90.0;
+
+fn png_error(e: png::EncodingError) -> RecorderError {
+    RecorderError::Other(e.to_string())
+}
+
+/// Renders the spectrogram of a WAV file as a `width` by `height` grayscale
+/// PNG, with low frequencies at the bottom and louder energy darker. The
+/// columns are spread evenly over the take, so long takes are sampled
+/// rather than analysed in full.
+pub fn spectrogram_png(path: &Path, width: usize, height: usize) -> Result<Vec<u8>, RecorderError> {
+    let buffer = read_wav(path)?;
+    let channels = buffer.spec.channels.max(1) as usize;
+    let mono: Vec<f32> = buffer
+        .samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let window: Vec<f32> = (0..FFT_SIZE)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+        .collect();
+    // A full-scale sine through the Hann window peaks at a quarter of the
+    // FFT size.
+    let full_scale = (FFT_SIZE as f32 / 4.0).powi(2);
+    let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+    let bins = FFT_SIZE / 2;
+    let mut frame = vec![Complex::default(); FFT_SIZE];
+    let mut pixels = vec![u8::MAX; width * height];
+
+    for x in 0..width {
+        let start = mono.len().saturating_sub(FFT_SIZE) * x / width.saturating_sub(1).max(1);
+        for (i, value) in frame.iter_mut().enumerate() {
+            let sample = mono.get(start + i).copied().unwrap_or(0.0);
+            *value = Complex::new(sample * window[i], 0.0);
+        }
+        fft.process(&mut frame);
+
+        for y in 0..height {
+            let low = bins * y / height;
+            let high = (bins * (y + 1) / height).max(low + 1);
+            let power = frame[low..high]
+                .iter()
+                .map(|bin| bin.norm_sqr())
+                .fold(0.0, f32::max);
+            let db = 10.0 * (power / full_scale).max(1e-12).log10();
+            let level = ((db + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+            pixels[(height - 1 - y) * width + x] = (f32::from(u8::MAX) * (1.0 - level)) as u8;
+        }
+    }
+
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(&pixels).map_err(png_error)?;
+        writer.finish().map_err(png_error)?;
+    }
+    Ok(png)
+}
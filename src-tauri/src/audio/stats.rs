@@ -0,0 +1,80 @@
+use super::transients::detect_transients;
+use super::utils::downmix_to_mono;
+use crate::models::{SentenceStats, Transient};
+use std::time::Duration;
+
+/// Level reported for digital silence, the noise floor of 16-bit audio.
+const MIN_DBFS: f32 = -96.0;
+
+pub(super) fn to_dbfs(level: f32) -> f32 {
+    if level > 0.0 {
+        (20.0 * level.log10()).max(MIN_DBFS)
+    } else {
+        MIN_DBFS
+    }
+}
+
+/// Measures a finished take and looks for breaths, clicks and pops in it.
+/// `leading_silence` and `trailing_silence` are the spans before the first
+/// and after the last chunk in which speech was detected.
+pub fn measure_take(
+    samples: &[i16],
+    channels: u16,
+    sample_rate: u32,
+    leading_silence: Duration,
+    trailing_silence: Duration,
+) -> SentenceStats {
+    let transients = detect_transients(&downmix_to_mono(samples, channels), sample_rate);
+    measure_levels(
+        samples,
+        channels,
+        sample_rate,
+        leading_silence,
+        trailing_silence,
+        transients,
+    )
+}
+
+/// Measures a finished take like `measure_take`, with the transients
+/// already found in it.
+pub fn measure_levels(
+    samples: &[i16],
+    channels: u16,
+    sample_rate: u32,
+    leading_silence: Duration,
+    trailing_silence: Duration,
+    transients: Vec<Transient>,
+) -> SentenceStats {
+    let frames = samples.len() / channels.max(1) as usize;
+    let peak = samples
+        .iter()
+        .map(|s| (*s as i32).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    let sum_of_squares: f64 = samples
+        .iter()
+        .map(|&s| {
+            let s = s as f64 / 32768.0;
+            s * s
+        })
+        .sum();
+    let rms = if samples.is_empty() {
+        0.0
+    } else {
+        (sum_of_squares / samples.len() as f64).sqrt()
+    };
+    let clipped_samples = samples
+        .iter()
+        .filter(|&&s| s == i16::MAX || s == i16::MIN)
+        .count();
+
+    SentenceStats {
+        duration_ms: (frames as u64 * 1000) / sample_rate.max(1) as u64,
+        peak_dbfs: to_dbfs(peak as f32 / 32768.0),
+        rms_dbfs: to_dbfs(rms as f32),
+        clipped_samples,
+        leading_silence_ms: leading_silence.as_millis() as u64,
+        trailing_silence_ms: trailing_silence.as_millis() as u64,
+        transients,
+    }
+}
@@ -1,22 +1,117 @@
-use super::auto_record::AutoRecordState;
+use super::auto_record::{AutoRecordState, PartialTake};
 use super::config::{AudioChunkWithVAD, AudioEvent, RecordingState};
+use super::denoise::reduce_noise;
 use super::errors::RecorderError;
+use super::filters::FilterChain;
+use super::fingerprint::SpeechFingerprint;
+use super::metadata::{embed_metadata, AudioMetadata};
+use super::power::SuspendDetector;
+use super::raw_take::{write_raw_take, RawTap};
 use super::recording_session::RecordingSession;
-use crate::models::Sentence;
-use cpal::traits::{DeviceTrait, StreamTrait};
-use cpal::{SampleFormat, Stream};
-use crossbeam_channel::{bounded, Receiver, Sender};
-use hound::{SampleFormat as HoundSampleFormat, WavSpec, WavWriter};
+use super::session_log::VadSummary;
+use super::sidecar::write_sidecar;
+use super::silence::SilenceGate;
+use super::source::SourceStream;
+use super::stats::measure_levels;
+use super::transients::{attenuate_transients, detect_transients};
+use super::utils::{
+    create_wav, downmix_to_mono, remix_channels, wav_spec, write_pcm_sample, WavFileWriter,
+};
+use super::vad_filter::VadFilter;
+use crate::jobs::CancellationToken;
+use crate::models::{PausePolicy, Sentence, TakeTimeoutAction, Transient};
+use crate::paths::long_path;
+use crate::temp_files::TempFile;
+use chrono::Local;
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError, Sender};
 use log::{debug, error, trace};
 use samplerate::{ConverterType, Samplerate};
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use voice_activity_detector::VoiceActivityDetector;
 
-type AudioStream = Result<Stream, RecorderError>;
+/// Sample rate the VAD model analyses audio at.
+pub(super) const VAD_SAMPLE_RATE: usize = 16000;
+/// VAD speech probability at or above which a chunk counts as voice, unless
+/// a session sets its own. Retrimming always uses it.
+pub(super) const VAD_VOICE_PROBABILITY: f32 = 0.5;
+/// Resampler used to bring the input down to `VAD_SAMPLE_RATE`.
+pub(super) const VAD_RESAMPLER: ConverterType = ConverterType::SincBestQuality;
+/// Silence before voice for it to count as the start of speech.
+const SPEECH_ONSET_GAP: Duration = Duration::from_millis(200);
+/// How often the recording thread checks on the session while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/**
+ * Take being captured, shared between the audio callback and the thread
+ * recording the sentence. The callback takes only this lock, never the
+ * session's, and the recording thread never holds both at once, so
+ * neither can end up waiting on the other. Decisions the recording thread
+ * acts on are sent to it as `AudioEvent`s rather than read from here.
+ */
+struct TakeCapture {
+    chunks: Vec<AudioChunkWithVAD>,
+    speaking: bool,
+    /// When voice was last detected in the take.
+    last_active: Option<Instant>,
+    vad_summary: VadSummary,
+    /// Set once silence has ended the take; later chunks are dropped.
+    ended: bool,
+}
+
+impl TakeCapture {
+    fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            speaking: false,
+            last_active: None,
+            vad_summary: VadSummary::default(),
+            ended: false,
+        }
+    }
+
+    /// Adds a chunk with the VAD's speech probability for it, returning the
+    /// event it brings about, if any. Chunks below the gate's threshold
+    /// count as silence.
+    fn add(&mut self, chunk: &[i16], probability: f32, gate: &SilenceGate) -> Option<AudioEvent> {
+        if self.ended {
+            return None;
+        }
+        let is_voice = gate.is_voice(probability, chunk);
+        let end_of_take_silence = gate.end_of_take_silence();
+        self.vad_summary.add(probability, is_voice);
+        self.chunks.push(AudioChunkWithVAD {
+            chunk: chunk.to_vec(),
+            is_voice,
+        });
+        let elapsed = self
+            .last_active
+            .map_or(Duration::MAX, |time| time.elapsed());
+        trace!(
+            "Processing audio chunk: voice_probability: {}, is_voice: {}, speaking: {}, elapsed: {}",
+            probability,
+            is_voice,
+            self.speaking,
+            elapsed.as_millis()
+        );
+
+        if is_voice {
+            self.last_active = Some(Instant::now());
+            if elapsed >= SPEECH_ONSET_GAP {
+                self.speaking = true;
+                return Some(AudioEvent::Voice);
+            }
+        } else if self.speaking && elapsed >= end_of_take_silence {
+            // Until then, speech resuming within the silence confirmation
+            // window continues the same take.
+            debug!("Silence duration reached, ending the take");
+            self.speaking = false;
+            self.ended = true;
+            return Some(AudioEvent::Silence);
+        }
+        None
+    }
+}
 
 /**
  * Record a sentence. This function initializes the recording buffers,
@@ -24,65 +119,242 @@ type AudioStream = Result<Stream, RecorderError>;
  * voice, to signify that the recording has begun, and detection of silence,
  * to determine when to end the sentence recording.
  */
-pub fn record_sentence(state_arc: &Arc<Mutex<AutoRecordState>>) -> Result<(), RecorderError> {
+pub fn record_sentence(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    cancel: &CancellationToken,
+) -> Result<(), RecorderError> {
     debug!("record_sentence: Starting to record sentence");
-    let (sentence, writer, path) = prepare_recording(state_arc)?;
-    let (audio_chunks, voice_tx, voice_rx) = initialize_recording_buffers();
+    // The take is deleted when this returns unless it is kept.
+    let (sentence, take, writer) = prepare_recording(state_arc)?;
+    let raw_tap = state_arc.lock().unwrap().raw_tap.clone();
+    let capture = Arc::new(Mutex::new(TakeCapture::new()));
+    let (event_tx, event_rx) = unbounded();
+    let resumed = resume_partial_take(state_arc, &capture, raw_tap.as_deref());
 
     debug!(
         "record_sentence: Recording sentence: {} ({})",
         sentence.id, sentence.text
     );
 
-    let stream = build_audio_stream(state_arc, writer.clone(), audio_chunks.clone(), voice_tx)?;
+    let stream = build_audio_stream(state_arc, Arc::clone(&capture), event_tx)?;
 
     let session = RecordingSession {
         stream: Some(stream),
         writer: writer.clone(),
-        path: path.clone(),
-        state_arc: state_arc.clone(),
     };
 
-    trace!("record_sentence: Recording session initialized, starting stream");
-    if let Err(e) = session.stream.as_ref().unwrap().play() {
-        error!("record_sentence: Failed to start stream. Error: {}", e);
-        return Err(RecorderError::StreamPlayError(e.to_string()));
-    }
+    trace!("record_sentence: Recording session initialized");
 
     let result = (|| {
-        wait_for_audio_event(state_arc, AudioEvent::Voice, &voice_rx)?;
-        wait_for_audio_event(state_arc, AudioEvent::Silence, &voice_rx)?;
+        // A continued take already has its speech.
+        if !resumed {
+            wait_for_audio_event(state_arc, AudioEvent::Voice, &event_rx, None, cancel)?;
+        }
+        let max_take_duration = state_arc.lock().unwrap().max_take_duration;
+        let deadline = (!max_take_duration.is_zero()).then(|| Instant::now() + max_take_duration);
+        wait_for_audio_event(state_arc, AudioEvent::Silence, &event_rx, deadline, cancel)?;
         Ok(())
     })();
 
+    // Capturing stops here, before the take is written from the thread
+    // recording it.
+    drop(session);
+    let raw = raw_tap.map(|raw_tap| raw_tap.finish());
+    let TakeCapture {
+        chunks,
+        vad_summary,
+        ..
+    } = std::mem::replace(&mut *capture.lock().unwrap(), TakeCapture::new());
+    let timeout_action = {
+        let mut state = state_arc.lock().unwrap();
+        state.vad_summary = vad_summary;
+        state.take_timeout_action
+    };
     if let Err(e) = &result {
         error!("record_sentence: Error during recording: {:?}", e);
+        match e {
+            RecorderError::TakeTimedOut if timeout_action == TakeTimeoutAction::Keep => {
+                // Write what the take has so far. It is checked like a
+                // finished take, so a timeout can't keep one too short.
+                write_trimmed_audio(state_arc, &chunks, &writer);
+                writer.lock().unwrap().finish()?;
+                drop(writer);
+                reject_empty_take(state_arc)?;
+                reject_short_take(state_arc)?;
+                finish_take(state_arc, &sentence, take, raw.as_deref())?;
+            }
+            RecorderError::RecordingPaused => {
+                state_arc.lock().unwrap().paused_speaking =
+                    chunks.iter().any(|chunk| chunk.is_voice);
+                keep_partial_take(state_arc, &sentence, chunks, raw.unwrap_or_default());
+            }
+            _ => {}
+        }
     } else {
         debug!("record_sentence: Successfully recorded sentence");
+        write_trimmed_audio(state_arc, &chunks, &writer);
+        // Finishing the WAV file writes it out, encrypted if the project is,
+        // before it is checked and kept or discarded.
+        writer.lock().unwrap().finish()?;
+        drop(writer);
+        reject_empty_take(state_arc)?;
+        reject_short_take(state_arc)?;
+        finish_take(state_arc, &sentence, take, raw.as_deref())?;
     }
 
     result
 }
 
+/**
+ * Rejects a take without voice, or with less voice in total than the
+ * minimum voiced duration, returning `EmptyTake` so it is discarded and the
+ * sentence armed again. This catches a cough or blip followed by noise,
+ * whose speech span can still pass the minimum take duration.
+ */
+fn reject_empty_take(state_arc: &Arc<Mutex<AutoRecordState>>) -> Result<(), RecorderError> {
+    let mut state = state_arc.lock().unwrap();
+    let voiced = state.last_voiced;
+    if !voiced.is_zero() && voiced >= state.min_voiced_duration {
+        return Ok(());
+    }
+    state.last_take = None;
+    Err(RecorderError::EmptyTake(voiced.as_millis() as u64))
+}
+
+/// Short takes of a sentence discarded in a row before the next one is
+/// kept, so a narrator whose line really is that short isn't stuck on it.
+const MAX_SHORT_TAKE_RETRIES: u32 = 3;
+
+/**
+ * Rejects the take if it has less speech than the minimum take duration,
+ * returning `TakeTooShort` so it is discarded and recorded again. After
+ * `MAX_SHORT_TAKE_RETRIES` such takes in a row the next is kept anyway.
+ */
+fn reject_short_take(state_arc: &Arc<Mutex<AutoRecordState>>) -> Result<(), RecorderError> {
+    let mut state = state_arc.lock().unwrap();
+    let speech_ms = state
+        .last_take
+        .as_ref()
+        .map_or(0, |stats| stats.speech_ms());
+    if speech_ms >= state.min_take_duration.as_millis() as u64 {
+        state.short_takes = 0;
+        return Ok(());
+    }
+    if state.short_takes >= MAX_SHORT_TAKE_RETRIES {
+        debug!(
+            "Keeping take with {} ms of speech after {} short takes",
+            speech_ms, state.short_takes
+        );
+        state.short_takes = 0;
+        return Ok(());
+    }
+    state.short_takes += 1;
+    state.last_take = None;
+    Err(RecorderError::TakeTooShort(speech_ms))
+}
+
+/**
+ * Waits out the pre-sentence delay before a sentence is recorded, calling
+ * `on_tick` with the remaining time once per second. Ends early if the
+ * session is paused, stopped or cancelled.
+ */
+pub fn wait_pre_sentence_delay(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    cancel: &CancellationToken,
+    mut on_tick: impl FnMut(Duration),
+) -> Result<(), RecorderError> {
+    let delay = state_arc.lock().unwrap().pre_sentence_delay;
+    if delay.is_zero() {
+        return Ok(());
+    }
+
+    debug!("Waiting {} ms before recording", delay.as_millis());
+    let start = Instant::now();
+    let mut next_tick = Duration::ZERO;
+    loop {
+        if cancel.is_cancelled() {
+            return Err(RecorderError::Cancelled);
+        }
+        check_recording_state(state_arc)?;
+
+        let elapsed = start.elapsed();
+        if elapsed >= delay {
+            return Ok(());
+        }
+        if elapsed >= next_tick {
+            on_tick(delay - elapsed);
+            next_tick += Duration::from_secs(1);
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/**
+ * Moves the finished take into place, counts it, points the sentence at
+ * its file and, if enabled, writes its sidecar. Once the session's lock is
+ * released it embeds the take's metadata, rewriting the file, and writes
+ * its raw take from `raw`, if given. Failed metadata, sidecars or raw
+ * takes are logged rather than failing the take.
+ */
+fn finish_take(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    sentence: &Sentence,
+    take: TempFile,
+    raw: Option<&[i16]>,
+) -> Result<(), RecorderError> {
+    let path = &take
+        .keep()
+        .map_err(|e| RecorderError::Other(e.to_string()))?;
+    let (metadata, project_directory, channels, sample_rate) = {
+        let mut state = state_arc.lock().unwrap();
+        *state.take_counts.entry(sentence.id).or_insert(0) += 1;
+        let index = state.current_sentence_index;
+        state.sentences[index].audio_file_path = Some(path.to_string_lossy().to_string());
+        if state.write_sidecar {
+            if let Err(e) = write_sidecar(&state, path) {
+                error!("Failed to write sidecar for {}: {}", path.display(), e);
+            }
+        }
+        let metadata = AudioMetadata::new(sentence.id, &sentence.text, Local::now())
+            .project_directory(&state.project_directory)
+            .device(state.audio_source.name());
+        (
+            metadata,
+            state.project_directory.clone(),
+            state.audio_source.channels(),
+            state.audio_source.sample_rate() as u32,
+        )
+    };
+    if let Err(e) = embed_metadata(path, &metadata) {
+        error!("Failed to embed metadata in {}: {}", path.display(), e);
+    }
+    if let Some(raw) = raw {
+        if let Err(e) = write_raw_take(&project_directory, channels, sample_rate, path, raw) {
+            error!("Failed to write raw take of {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
 fn prepare_recording(
     state_arc: &Arc<Mutex<AutoRecordState>>,
-) -> Result<(Sentence, Arc<Mutex<WavWriter<BufWriter<File>>>>, PathBuf), RecorderError> {
+) -> Result<(Sentence, TempFile, Arc<Mutex<WavFileWriter>>), RecorderError> {
     let state = state_arc.lock().unwrap();
     let sentence = state.sentences[state.current_sentence_index].clone();
-    let project_dir = get_or_create_project_directory(&state.project_directory)?;
+    let recordings_dir = create_recordings_directory(&state)?;
 
     debug!("Initializing writer for sentence: {}", sentence.id);
 
     // Create WAV file path
-    let path = project_dir.join(format!("{}.wav", sentence.text.trim().replace(" ", "_")));
+    let path = recordings_dir.join(state.take_file_name(&sentence));
 
     // Create WAV writer
-    let spec = WavSpec {
-        channels: state.audio_config.supported_config.channels() as u16,
-        sample_rate: state.audio_config.sample_rate as u32,
-        bits_per_sample: 16,
-        sample_format: HoundSampleFormat::Int,
-    };
+    let spec = wav_spec(
+        state.output_channels(),
+        state.audio_source.sample_rate() as u32,
+        state.output_format,
+    );
 
     // Output a debug log of the audio configuration
     debug!("Audio configuration:");
@@ -90,53 +362,136 @@ fn prepare_recording(
     debug!("  Sample rate: {} Hz", spec.sample_rate);
     debug!("  Bits per sample: {}", spec.bits_per_sample);
     debug!("  Sample format: {:?}", spec.sample_format);
-    debug!("  Device: {:?}", state.audio_config.device.0.name());
+    debug!("  Source: {:?}", state.audio_source);
+
+    let take = TempFile::new(path);
+    let writer = Arc::new(Mutex::new(create_wav(take.path(), spec)?));
 
-    let writer = Arc::new(Mutex::new(WavWriter::create(&path, spec)?));
+    Ok((sentence, take, writer))
+}
 
-    Ok((sentence, writer, path))
+/**
+ * Keeps the audio of a take interrupted by a pause if the pause policy asks
+ * for it. After a suspend or a lost device the audio is unreliable and is
+ * always dropped, as is a take abandoned with a skip or retry or one whose
+ * sentence is no longer current.
+ */
+fn keep_partial_take(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    sentence: &Sentence,
+    chunks: Vec<AudioChunkWithVAD>,
+    raw: Vec<i16>,
+) {
+    let mut state = state_arc.lock().unwrap();
+    let current_id = state
+        .sentences
+        .get(state.current_sentence_index)
+        .map(|s| s.id);
+    if state.pause_policy != PausePolicy::KeepPartial
+        || state.suspended
+        || state.device_lost
+        || state.take_interrupt.is_some()
+        || current_id != Some(sentence.id)
+    {
+        return;
+    }
+    debug!("Keeping {} chunks of the interrupted take", chunks.len());
+    state.partial_take = Some(PartialTake {
+        sentence_index: state.current_sentence_index,
+        chunks,
+        raw,
+    });
 }
 
-fn initialize_recording_buffers() -> (Arc<Mutex<Vec<AudioChunkWithVAD>>>, Sender<()>, Receiver<()>)
-{
-    let audio_chunks: Arc<Mutex<Vec<AudioChunkWithVAD>>> = Arc::new(Mutex::new(Vec::new()));
-    let (voice_tx, voice_rx) = bounded(1);
-    (audio_chunks, voice_tx, voice_rx)
+/**
+ * Seeds the buffers, and the raw tap if raw takes are kept, with the
+ * partial take of the current sentence, if one was kept. Returns `true` if
+ * it contains speech, so the take continues where it left off; otherwise
+ * the take starts afresh.
+ */
+fn resume_partial_take(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    capture: &Arc<Mutex<TakeCapture>>,
+    raw_tap: Option<&RawTap>,
+) -> bool {
+    let (partial, vad_summary) = {
+        let mut state = state_arc.lock().unwrap();
+        let partial = state
+            .partial_take
+            .take()
+            .filter(|partial| partial.sentence_index == state.current_sentence_index);
+        (partial, state.vad_summary)
+    };
+    let has_speech = partial
+        .as_ref()
+        .is_some_and(|partial| partial.chunks.iter().any(|chunk| chunk.is_voice));
+    let mut capture = capture.lock().unwrap();
+    let mut raw = Vec::new();
+    if let Some(partial) = partial {
+        debug!("Continuing take with {} kept chunks", partial.chunks.len());
+        capture.chunks = partial.chunks;
+        raw = partial.raw;
+    }
+    if let Some(raw_tap) = raw_tap {
+        raw_tap.start(raw);
+    }
+    if has_speech {
+        // Silence is measured from now, not from before the pause.
+        capture.speaking = true;
+        capture.last_active = Some(Instant::now());
+        capture.vad_summary = vad_summary;
+    }
+    has_speech
 }
 
 /**
  * This is the main loop that waits for audio events. When an event is
- * received, a break allows the record_sentence function to continue.
+ * received, a break allows the record_sentence function to continue. Waiting
+ * past `deadline` fails with `TakeTimedOut`.
  */
 fn wait_for_audio_event(
     state_arc: &Arc<Mutex<AutoRecordState>>,
     event: AudioEvent,
-    voice_rx: &Receiver<()>,
+    events: &Receiver<AudioEvent>,
+    deadline: Option<Instant>,
+    cancel: &CancellationToken,
 ) -> Result<(), RecorderError> {
     debug!("Waiting for audio event: {:?}", event);
+    let mut suspend_detector = SuspendDetector::new();
     loop {
-        check_recording_state(state_arc)?;
+        if cancel.is_cancelled() {
+            return Err(RecorderError::Cancelled);
+        }
 
-        match event {
-            AudioEvent::Voice => {
-                if voice_rx.try_recv().is_ok() {
-                    trace!("Voice detected");
-                    break;
-                }
-            }
-            AudioEvent::Silence => {
-                let state = state_arc.lock().unwrap();
-                let last_active = *state.last_active_time.lock().unwrap();
-                let elapsed = last_active.elapsed();
-
-                if elapsed >= state.silence_duration {
-                    trace!("Silence detected");
-                    break;
-                }
+        if suspend_detector.tick() {
+            // The in-flight take is unreliable after a suspend, so pause and
+            // let the sentence be recorded again on resume.
+            let mut state = state_arc.lock().unwrap();
+            if state.pause_recording().is_ok() {
+                debug!("System suspend detected, pausing auto-record");
+                state.suspended = true;
             }
         }
 
-        std::thread::sleep(Duration::from_millis(100));
+        check_recording_state(state_arc)?;
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            debug!(
+                "Take reached the maximum duration while waiting for {:?}",
+                event
+            );
+            return Err(RecorderError::TakeTimedOut);
+        }
+
+        match events.recv_timeout(POLL_INTERVAL) {
+            Ok(received) if received == event => {
+                trace!("{:?} detected", event);
+                break;
+            }
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            // The stream has gone, so nothing more will be heard.
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(POLL_INTERVAL),
+        }
     }
     debug!("Finished waiting for audio event: {:?}", event);
     Ok(())
@@ -152,162 +507,110 @@ fn check_recording_state(state_arc: &Arc<Mutex<AutoRecordState>>) -> Result<(),
 }
 
 /**
- * Builds an audio input stream and configured the VAD that is used to
+ * Starts the session's audio source and configures the VAD that is used to
  * detect speech.
  */
 fn build_audio_stream(
     state_arc: &Arc<Mutex<AutoRecordState>>,
-    writer: Arc<Mutex<WavWriter<BufWriter<File>>>>,
-    audio_chunks: Arc<Mutex<Vec<AudioChunkWithVAD>>>,
-    voice_tx: Sender<()>,
-) -> AudioStream {
+    capture: Arc<Mutex<TakeCapture>>,
+    events: Sender<AudioEvent>,
+) -> Result<SourceStream, RecorderError> {
     debug!("Building audio stream");
-    let (sample_format, original_sample_rate) = {
+    // The callback works from a copy of the settings, so it never needs the
+    // session's lock.
+    let (original_sample_rate, channels, vad_preprocessing, gate) = {
         let state = state_arc.lock().unwrap();
         (
-            state.audio_config.supported_config.sample_format(),
-            state.audio_config.sample_rate,
+            state.audio_source.sample_rate(),
+            state.audio_source.channels(),
+            state.vad_preprocessing,
+            Arc::clone(&state.silence_gate),
         )
     };
 
-    trace!("Audio stream sample format: {:?}", sample_format);
-
-    let err_fn = |err| eprintln!("Stream error: {}", err);
-
+    // Chunks hold whole frames, so channels never get out of step.
     let chunk_size = get_chunk_size(original_sample_rate)?;
-    let downsampled_chunk_size = get_chunk_size(16000)?;
+    let chunk_len = chunk_size * channels.max(1) as usize;
+    let downsampled_chunk_size = get_chunk_size(VAD_SAMPLE_RATE)?;
     trace!("Using chunk size of {} for original audio and chunk size of {} for downsampled audio (VAD)", chunk_size, downsampled_chunk_size);
     let mut vad = VoiceActivityDetector::builder()
-        .sample_rate(16000)
+        .sample_rate(VAD_SAMPLE_RATE as i64)
         .chunk_size(downsampled_chunk_size)
         .build()
         .expect("Failed to build VAD");
+    let mut vad_filter = VadFilter::new(vad_preprocessing, original_sample_rate as u32);
 
     // Create a shared buffer for accumulating data
     let data_buffer = Arc::new(Mutex::new(Vec::new()));
 
-    match sample_format {
-        SampleFormat::I16 => {
-            let input_data_fn = {
-                let state_arc = Arc::clone(state_arc);
-                let writer = Arc::clone(&writer);
-                let audio_chunks = Arc::clone(&audio_chunks);
-                let voice_tx = voice_tx.clone();
-                let data_buffer = Arc::clone(&data_buffer);
-
-                move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                    trace!("Input callback data length: {}", data.len());
-                    let mut converter = Samplerate::new(
-                        ConverterType::SincBestQuality,
-                        original_sample_rate as u32,
-                        16000,
-                        1,
-                    )
-                    .expect("Failed to create Samplerate converter");
-
-                    let chunk_size = get_chunk_size(original_sample_rate).unwrap();
-                    let mut buffer = data_buffer.lock().unwrap();
-
-                    // Accumulate the incoming data
-                    buffer.extend_from_slice(data);
-
-                    while buffer.len() >= chunk_size {
-                        // Split off a chunk of the required size
-                        let chunk: Vec<i16> = buffer.drain(..chunk_size).collect();
-
-                        process_audio_chunk(
-                            &chunk,
-                            &mut vad,
-                            &mut converter,
-                            &state_arc,
-                            &audio_chunks,
-                            &writer,
-                            &voice_tx,
-                            chunk_size,
-                        );
-                    }
-                }
-            };
-
-            let state = state_arc.lock().unwrap();
-            trace!(
-                "Building input stream with config: {:?}",
-                state.audio_config.config
-            );
-            state
-                .audio_config
-                .device
-                .0
-                .build_input_stream(&state.audio_config.config, input_data_fn, err_fn)
-                .map_err(RecorderError::CpalBuildStreamError)
+    let input_data_fn = {
+        let data_buffer = Arc::clone(&data_buffer);
+
+        move |data: &[i16]| {
+            trace!("Input callback data length: {}", data.len());
+            let mut converter = Samplerate::new(
+                VAD_RESAMPLER,
+                original_sample_rate as u32,
+                VAD_SAMPLE_RATE as u32,
+                1,
+            )
+            .expect("Failed to create Samplerate converter");
+
+            let mut buffer = data_buffer.lock().unwrap();
+
+            // Accumulate the incoming data
+            buffer.extend_from_slice(data);
+
+            // Process chunks while we have enough data
+            while buffer.len() >= chunk_len {
+                // Split off a chunk of the required size
+                let chunk: Vec<i16> = buffer.drain(..chunk_len).collect();
+
+                process_audio_chunk(
+                    &chunk,
+                    &mut vad,
+                    &mut vad_filter,
+                    &mut converter,
+                    &capture,
+                    &events,
+                    &gate,
+                    chunk_size,
+                    channels,
+                );
+            }
         }
-        SampleFormat::F32 => {
-            let input_data_fn = {
-                let state_arc = Arc::clone(state_arc);
-                let writer = Arc::clone(&writer);
-                let audio_chunks = Arc::clone(&audio_chunks);
-                let voice_tx = voice_tx.clone();
-                let data_buffer = Arc::clone(&data_buffer);
-
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    trace!("Input callback data length: {}", data.len());
-
-                    let mut converter = Samplerate::new(
-                        ConverterType::SincBestQuality,
-                        original_sample_rate as u32,
-                        16000,
-                        1,
-                    )
-                    .expect("Failed to create Samplerate converter");
-
-                    // Convert f32 samples to i16
-                    let data_i16: Vec<i16> = data
-                        .iter()
-                        .map(|&sample| (sample * i16::MAX as f32) as i16)
-                        .collect();
-
-                    let chunk_size = get_chunk_size(original_sample_rate).unwrap();
-                    let mut buffer = data_buffer.lock().unwrap();
-
-                    // Accumulate the incoming data
-                    buffer.extend_from_slice(&data_i16);
-
-                    // Process chunks while we have enough data
-                    while buffer.len() >= chunk_size {
-                        // Split off a chunk of the required size
-                        let chunk: Vec<i16> = buffer.drain(..chunk_size).collect();
-
-                        process_audio_chunk(
-                            &chunk,
-                            &mut vad,
-                            &mut converter,
-                            &state_arc,
-                            &audio_chunks,
-                            &writer,
-                            &voice_tx,
-                            chunk_size,
-                        );
-                    }
-                }
-            };
-
-            let state = state_arc.lock().unwrap();
-            trace!(
-                "Building input stream with config: {:?}",
-                state.audio_config.config
-            );
-            state
-                .audio_config
-                .device
-                .0
-                .build_input_stream(&state.audio_config.config, input_data_fn, err_fn)
-                .map_err(RecorderError::CpalBuildStreamError)
+    };
+
+    // A failing device pauses the session instead of leaving it waiting
+    // for audio that will never come. This takes the session's lock, so the
+    // stream is never dropped while it is held.
+    let on_error = {
+        let state_arc = Arc::clone(state_arc);
+        move |e: RecorderError| {
+            error!("Audio input failed: {}", e);
+            mark_device_lost(&state_arc);
         }
-        _ => Err(RecorderError::Other("Unsupported sample format".into())),
+    };
+
+    let state = state_arc.lock().unwrap();
+    state
+        .audio_source
+        .start(Box::new(input_data_fn), Box::new(on_error))
+}
+
+/**
+ * Pauses the session after its audio input failed, so it can continue once
+ * the device is reconnected.
+ */
+pub fn mark_device_lost(state_arc: &Arc<Mutex<AutoRecordState>>) {
+    let mut state = state_arc.lock().unwrap();
+    if state.pause_recording().is_ok() {
+        debug!("Audio device lost, pausing auto-record");
+        state.device_lost = true;
     }
 }
 
-fn get_chunk_size(sample_rate: usize) -> Result<usize, RecorderError> {
+pub(super) fn get_chunk_size(sample_rate: usize) -> Result<usize, RecorderError> {
     let chunk_size = (sample_rate as f32 / 31.25).round() as usize;
     // Ensure chunk_size is a multiple of 256 for compatibility
     let chunk_size = ((chunk_size + 255) / 256) * 256;
@@ -315,19 +618,21 @@ fn get_chunk_size(sample_rate: usize) -> Result<usize, RecorderError> {
 }
 
 /**
- * Processes an audio chunk using VAD, calculating the probability of
- * speech as well as keeping track of the elapsed time since silence was
- * detected.
+ * Processes an audio chunk using VAD, adding it to the take with the
+ * probability of speech and sending the recording thread any event it
+ * brings about.
  */
+#[allow(clippy::too_many_arguments)]
 fn process_audio_chunk(
     data: &[i16],
     vad: &mut VoiceActivityDetector,
+    vad_filter: &mut VadFilter,
     converter: &mut Samplerate,
-    state_arc: &Arc<Mutex<AutoRecordState>>,
-    audio_chunks: &Arc<Mutex<Vec<AudioChunkWithVAD>>>,
-    writer: &Arc<Mutex<WavWriter<BufWriter<File>>>>,
-    voice_tx: &Sender<()>,
+    capture: &Mutex<TakeCapture>,
+    events: &Sender<AudioEvent>,
+    gate: &SilenceGate,
     chunk_size: usize,
+    channels: u16,
 ) {
     let ratio = converter.ratio();
     let adjusted_chunk_size = (chunk_size as f64 / ratio).ceil() as usize;
@@ -338,16 +643,11 @@ fn process_audio_chunk(
         ratio
     );
 
-    let mut remaining_data = data;
-
-    while !remaining_data.is_empty() {
-        let (chunk, rest) = if remaining_data.len() >= adjusted_chunk_size {
-            remaining_data.split_at(adjusted_chunk_size)
-        } else {
-            (remaining_data, &[][..])
-        };
-
-        let chunk_f32: Vec<f32> = chunk.iter().map(|&s| s as f32 / 32768.0).collect();
+    for chunk in data.chunks(adjusted_chunk_size * channels.max(1) as usize) {
+        // The VAD analyses a mono downmix. Only its copy is filtered; `chunk`
+        // is what gets written.
+        let mut chunk_f32 = downmix_to_mono(chunk, channels);
+        vad_filter.process(&mut chunk_f32);
         let downsampled_chunk = { converter.process(&chunk_f32).expect("Failed to downsample") };
         trace!(
             "Original chunk length: {}, Downsampled chunk length: {}",
@@ -355,103 +655,38 @@ fn process_audio_chunk(
             downsampled_chunk.len()
         );
 
-        let probability = vad.predict(downsampled_chunk.clone());
-        let is_voice = probability >= 0.5;
-
-        {
-            let mut chunks = audio_chunks.lock().unwrap();
-            chunks.push(AudioChunkWithVAD {
-                chunk: chunk.to_vec(),
-                is_voice,
-            });
-        }
-
-        let elapsed = {
-            let state = state_arc.lock().unwrap();
-            let last_active = state.last_active_time.lock().unwrap();
-            last_active.elapsed()
-        };
-
-        let speaking = {
-            let state = state_arc.lock().unwrap();
-            let speaking = state.is_speaking.lock().unwrap();
-            *speaking
-        };
-
-        trace!("Processing audio chunk: voice_probability: {}, is_voice: {}, speaking: {}, elapsed: {}", probability, is_voice, speaking, elapsed.as_millis());
-
-        if is_voice {
-            handle_voice_detected(state_arc, elapsed, voice_tx);
-        } else {
-            handle_silence_detected(state_arc, audio_chunks, writer, elapsed);
-        }
-
-        remaining_data = rest;
-    }
-}
-
-fn handle_voice_detected(
-    state_arc: &Arc<Mutex<AutoRecordState>>,
-    elapsed: Duration,
-    voice_tx: &Sender<()>,
-) {
-    {
-        let state = state_arc.lock().unwrap();
-        *state.last_active_time.lock().unwrap() = Instant::now();
-    }
-
-    if elapsed >= Duration::from_millis(200) {
-        trace!("Voice detected, notifying voice_tx");
-        {
-            let state = state_arc.lock().unwrap();
-            *state.is_speaking.lock().unwrap() = true;
-        }
-        let _ = voice_tx.try_send(());
-    }
-}
-
-fn handle_silence_detected(
-    state_arc: &Arc<Mutex<AutoRecordState>>,
-    audio_chunks: &Arc<Mutex<Vec<AudioChunkWithVAD>>>,
-    writer: &Arc<Mutex<WavWriter<BufWriter<File>>>>,
-    elapsed: Duration,
-) {
-    let silence_duration = {
-        let state = state_arc.lock().unwrap();
-        state.silence_duration
-    };
-
-    trace!(
-        "Silence detected, elapsed: {}, silence_duration: {}",
-        elapsed.as_millis(),
-        silence_duration.as_millis()
-    );
-
-    if elapsed >= silence_duration {
-        let state = state_arc.lock().unwrap();
-        if *state.is_speaking.lock().unwrap() {
-            debug!("Silence duration reached, stopping speaking and writing trimmed audio");
-            *state.is_speaking.lock().unwrap() = false;
-            drop(state);
-            write_trimmed_audio(state_arc, audio_chunks, writer);
-            debug!("Finished writing trimmed audio");
+        let probability = vad.predict(downsampled_chunk);
+        let event = capture.lock().unwrap().add(chunk, probability, gate);
+        if let Some(event) = event {
+            let _ = events.send(event);
         }
     }
 }
 
 fn write_trimmed_audio(
     state_arc: &Arc<Mutex<AutoRecordState>>,
-    audio_chunks: &Arc<Mutex<Vec<AudioChunkWithVAD>>>,
-    writer: &Arc<Mutex<WavWriter<BufWriter<File>>>>,
+    chunks: &[AudioChunkWithVAD],
+    writer: &Arc<Mutex<WavFileWriter>>,
 ) {
     let (silence_padding, sample_rate) = {
         let state = state_arc.lock().unwrap();
-        (state.silence_padding, state.audio_config.sample_rate)
+        (state.silence_padding, state.audio_source.sample_rate())
     };
 
     let padding_samples = (silence_padding.as_secs_f32() * sample_rate as f32) as usize;
     let chunk_size = get_chunk_size(sample_rate).unwrap();
-    let chunks = audio_chunks.lock().unwrap();
+
+    {
+        let mut state = state_arc.lock().unwrap();
+        let voiced_samples: usize = chunks
+            .iter()
+            .filter(|chunk| chunk.is_voice)
+            .map(|chunk| chunk.chunk.len())
+            .sum();
+        let channels = state.audio_source.channels().max(1) as f64;
+        state.last_voiced =
+            Duration::from_secs_f64(voiced_samples as f64 / channels / sample_rate as f64);
+    }
 
     /*
      * Finds the start and end indices of the speech portion within the
@@ -484,57 +719,133 @@ fn write_trimmed_audio(
         return; // or handle this error case as appropriate
     }
 
-    let mut writer = writer.lock().unwrap();
-
-    let mut write_samples = |chunks: &[AudioChunkWithVAD]| {
-        for chunk in chunks {
-            for &sample in &chunk.chunk {
-                if let Err(e) = writer.write_sample(sample) {
-                    error!("Failed to write sample: {}", e);
-                    return Err(e);
-                }
-            }
-        }
-        Ok(())
-    };
+    let padding_start = start_index.saturating_sub(padding_samples / chunk_size);
+    let padding_end = (end_index + 1 + padding_samples / chunk_size).min(chunks.len());
+    let samples: Vec<i16> = chunks[padding_start..padding_end]
+        .iter()
+        .flat_map(|chunk| chunk.chunk.iter().copied())
+        .collect();
 
-    // Write padding before speech
-    if let Err(e) = write_samples(
-        &chunks[start_index.saturating_sub(padding_samples / chunk_size)..start_index],
-    ) {
-        error!("Error writing padding before speech: {}", e);
-        return;
-    }
+    // Silence kept around the speech, measured from the VAD decisions
+    let first_voice = chunks
+        .iter()
+        .position(|chunk| chunk.is_voice)
+        .unwrap_or(padding_start)
+        .max(padding_start);
+    let last_voice = chunks
+        .iter()
+        .rposition(|chunk| chunk.is_voice)
+        .map_or(padding_end, |i| i + 1)
+        .min(padding_end);
+    let leading_samples: usize = chunks[padding_start..first_voice]
+        .iter()
+        .map(|chunk| chunk.chunk.len())
+        .sum();
+    let trailing_samples: usize = chunks[last_voice..padding_end]
+        .iter()
+        .map(|chunk| chunk.chunk.len())
+        .sum();
 
-    // Write speech
-    if let Err(e) = write_samples(&chunks[start_index..=end_index]) {
-        error!("Error writing speech: {}", e);
-        return;
+    let (input_channels, output_channels) = {
+        let state = state_arc.lock().unwrap();
+        (state.audio_source.channels(), state.output_channels())
+    };
+    let samples = remix_channels(&samples, input_channels, output_channels);
+    let (samples, transients) = post_process_samples(state_arc, samples);
+    // Fingerprinted before the lock is taken again, so the FFT doesn't hold
+    // up the audio callback.
+    let fingerprint_enabled = state_arc.lock().unwrap().duplicate_speech.enabled;
+    let fingerprint = fingerprint_enabled
+        .then(|| {
+            SpeechFingerprint::new(
+                &downmix_to_mono(&samples, output_channels),
+                sample_rate as u32,
+            )
+        })
+        .flatten();
+
+    // Leading and trailing counts are of the captured, not remixed, audio.
+    let samples_to_duration = |count: usize| {
+        Duration::from_secs_f64(count as f64 / input_channels as f64 / sample_rate as f64)
+    };
+    let stats = measure_levels(
+        &samples,
+        output_channels,
+        sample_rate as u32,
+        samples_to_duration(leading_samples),
+        samples_to_duration(trailing_samples),
+        transients,
+    );
+    {
+        let mut state = state_arc.lock().unwrap();
+        state.last_fingerprint = fingerprint;
+        state.last_take = Some(stats);
     }
 
-    // Write padding after speech
-    let padding_end = (end_index + 1 + padding_samples / chunk_size).min(chunks.len());
-    if let Err(e) = write_samples(&chunks[end_index + 1..padding_end]) {
-        error!("Error writing padding after speech: {}", e);
-        return;
+    let mut writer = writer.lock().unwrap();
+    for sample in samples {
+        if let Err(e) = write_pcm_sample(&mut writer, sample) {
+            error!("Error writing trimmed audio: {}", e);
+            return;
+        }
     }
 }
 
 /**
- * Creates or gets the project directory based on the provided path.
+ * Applies the optional post-processing stages to a finished take before it
+ * is written. Returns the processed samples with the breaths, clicks and
+ * pops found in them, looked for once before any are turned down.
  */
-fn get_or_create_project_directory(
-    project_directory: &str,
-) -> Result<std::path::PathBuf, RecorderError> {
-    debug!(
-        "Getting or creating project directory: {}",
-        project_directory
-    );
-    let project_dir = tauri::api::path::home_dir()
-        .map(|home| home.join(project_directory))
-        .unwrap_or_else(|| std::path::PathBuf::from(project_directory));
+fn post_process_samples(
+    state_arc: &Arc<Mutex<AutoRecordState>>,
+    mut samples: Vec<i16>,
+) -> (Vec<i16>, Vec<Transient>) {
+    let (filters, noise_reduction, transients, channels, sample_rate) = {
+        let state = state_arc.lock().unwrap();
+        (
+            state.filters,
+            state.noise_reduction,
+            state.transients,
+            state.output_channels(),
+            state.audio_source.sample_rate() as u32,
+        )
+    };
+
+    FilterChain::new(filters, channels, sample_rate).process(&mut samples);
+
+    if noise_reduction.enabled {
+        debug!(
+            "Applying noise reduction (strength {})",
+            noise_reduction.strength
+        );
+        samples = reduce_noise(&samples, channels, sample_rate, noise_reduction.strength);
+    }
 
-    std::fs::create_dir_all(&project_dir)?;
+    let found = detect_transients(&downmix_to_mono(&samples, channels), sample_rate);
+    if transients.attenuate {
+        debug!(
+            "Attenuating {} breaths, clicks and pops by {} dB",
+            found.len(),
+            transients.attenuation_db
+        );
+        attenuate_transients(
+            &mut samples,
+            channels,
+            sample_rate,
+            &found,
+            transients.attenuation_db,
+        );
+    }
+    (samples, found)
+}
 
-    Ok(project_dir)
+/// Creates the folder the session records into, laid out as the project
+/// says within its directory.
+pub(super) fn create_recordings_directory(
+    state: &AutoRecordState,
+) -> Result<std::path::PathBuf, RecorderError> {
+    let directory = state.recordings_directory();
+    debug!("Recording into {}", directory.display());
+    std::fs::create_dir_all(long_path(&directory))?;
+    Ok(directory)
 }
@@ -0,0 +1,242 @@
+use super::auto_record::{split_take_number, take_file_stem, take_wav_name};
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use super::sidecar::sidecar_path;
+use super::stats::measure_take;
+use crate::models::Sentence;
+use crate::qc::normalize_text;
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Measurements of one take of a sentence, for choosing the best take.
+#[derive(Debug, Serialize)]
+pub struct TakeMetrics {
+    pub audio_file_path: String,
+    /// Number of the take, for numbered takes.
+    pub take: Option<u32>,
+    /// Whether this is the take the sentence currently uses.
+    pub current: bool,
+    pub duration_ms: u64,
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    pub clipped_samples: usize,
+    /// Word error rate of the take's transcript against the prompt, when a
+    /// transcript was given.
+    pub word_error_rate: Option<f32>,
+}
+
+/// Take number encoded in `file_name` if it is a take of `stem`: `None`
+/// for the unnumbered take, `Some(n)` for a numbered one.
+fn take_number(file_name: &str, stem: &str) -> Option<Option<u32>> {
+    let name = file_name.strip_suffix(".wav")?;
+    if name == stem {
+        return Some(None);
+    }
+    match split_take_number(name) {
+        Some((take_stem, take)) if take_stem == stem => Some(Some(take)),
+        _ => None,
+    }
+}
+
+/// Finds every take of `sentence` in `directory`, ordered by take number.
+fn find_takes(
+    sentence: &Sentence,
+    directory: &Path,
+) -> Result<Vec<(Option<u32>, PathBuf)>, RecorderError> {
+    let stem = take_file_stem(sentence);
+    let mut takes: Vec<(Option<u32>, PathBuf)> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let take = take_number(name.to_str()?, &stem)?;
+            Some((take, entry.path()))
+        })
+        .collect();
+    takes.sort();
+    Ok(takes)
+}
+
+/// Path for a new take of `sentence` in `directory`, numbered after its
+/// last take.
+pub fn next_take_path(sentence: &Sentence, directory: &Path) -> Result<PathBuf, RecorderError> {
+    let last = find_takes(sentence, directory)?
+        .into_iter()
+        .map(|(take, _)| take.unwrap_or(1))
+        .max()
+        .unwrap_or(0);
+    let take = (last > 0).then_some(last + 1);
+    Ok(directory.join(take_wav_name(&take_file_stem(sentence), take)))
+}
+
+/// Renames every take of `sentence` in `directory` after `renamed`, along
+/// with each take's sidecar and edit backup. Nothing is renamed if any new
+/// name is already taken, and the files renamed so far are put back if a
+/// rename fails. Returns the old and new path of each take.
+pub fn rename_takes(
+    sentence: &Sentence,
+    renamed: &Sentence,
+    directory: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, RecorderError> {
+    relocate_takes(sentence, renamed, directory, directory)
+}
+
+/// Moves every take of `sentence` from `directory` to `target_directory`,
+/// like `rename_takes`.
+pub fn move_takes(
+    sentence: &Sentence,
+    directory: &Path,
+    target_directory: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, RecorderError> {
+    relocate_takes(sentence, sentence, directory, target_directory)
+}
+
+fn relocate_takes(
+    sentence: &Sentence,
+    renamed: &Sentence,
+    directory: &Path,
+    target_directory: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>, RecorderError> {
+    let stem = take_file_stem(renamed);
+    let moves: Vec<(PathBuf, PathBuf)> = find_takes(sentence, directory)?
+        .into_iter()
+        .flat_map(|(take, path)| {
+            let target = target_directory.join(take_wav_name(&stem, take));
+            let mut backup = path.clone().into_os_string();
+            backup.push(".bak");
+            let mut target_backup = target.clone().into_os_string();
+            target_backup.push(".bak");
+            [
+                (sidecar_path(&path), sidecar_path(&target)),
+                (PathBuf::from(backup), PathBuf::from(target_backup)),
+                (path, target),
+            ]
+        })
+        .filter(|(from, _)| from.exists())
+        .collect();
+
+    // A target may be taken by a file that is itself moved away.
+    if let Some((_, taken)) = moves
+        .iter()
+        .find(|(_, to)| to.exists() && !moves.iter().any(|(from, _)| from == to))
+    {
+        return Err(RecorderError::Other(format!(
+            "{} already exists",
+            taken.display()
+        )));
+    }
+    rename_all(&moves)?;
+    Ok(moves
+        .into_iter()
+        .filter(|(from, _)| from.extension() == Some(OsStr::new("wav")))
+        .collect())
+}
+
+/// Path a file is moved to on its way to `target`.
+fn staging_path(target: &Path) -> PathBuf {
+    let mut path = target.as_os_str().to_owned();
+    path.push(".renaming");
+    PathBuf::from(path)
+}
+
+/// Renames each file in two steps, first out of the way and then into
+/// place, so files can take each other's names. If a rename fails, the
+/// files renamed so far are moved back.
+fn rename_all(moves: &[(PathBuf, PathBuf)]) -> Result<(), RecorderError> {
+    let moves: Vec<_> = moves.iter().filter(|(from, to)| from != to).collect();
+    let mut staged = 0;
+    let mut placed = 0;
+    let result = (|| {
+        for (from, to) in &moves {
+            debug!("Renaming {:?} to {:?}", from, to);
+            fs::rename(from, staging_path(to))?;
+            staged += 1;
+        }
+        for (_, to) in &moves {
+            fs::rename(staging_path(to), to)?;
+            placed += 1;
+        }
+        Ok::<_, RecorderError>(())
+    })();
+    if result.is_err() {
+        for (_, to) in moves[..placed].iter().rev() {
+            if let Err(e) = fs::rename(to, staging_path(to)) {
+                warn!("Failed to move {:?} back: {}", to, e);
+            }
+        }
+        for (from, to) in moves[..staged].iter().rev() {
+            if let Err(e) = fs::rename(staging_path(to), from) {
+                warn!("Failed to move {:?} back: {}", from, e);
+            }
+        }
+    }
+    result
+}
+
+/// Word-level edit distance between the transcript and the prompt, divided
+/// by the number of words in the prompt. Punctuation and case are ignored.
+pub fn word_error_rate(reference: &str, transcript: &str) -> f32 {
+    let reference = normalize_text(reference);
+    let transcript = normalize_text(transcript);
+    let reference: Vec<&str> = reference.split_whitespace().collect();
+    let transcript: Vec<&str> = transcript.split_whitespace().collect();
+    if reference.is_empty() {
+        return if transcript.is_empty() { 0.0 } else { 1.0 };
+    }
+
+    let mut previous: Vec<usize> = (0..=transcript.len()).collect();
+    for (i, reference_word) in reference.iter().enumerate() {
+        let mut current = vec![i + 1; transcript.len() + 1];
+        for (j, transcript_word) in transcript.iter().enumerate() {
+            let substitution = previous[j] + usize::from(reference_word != transcript_word);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[transcript.len()] as f32 / reference.len() as f32
+}
+
+/// Measures every take of `sentence` found in `directory`. `transcripts`
+/// maps take file paths to what a speech recognizer heard in them.
+pub fn compare_takes(
+    sentence: &Sentence,
+    directory: &Path,
+    transcripts: &HashMap<String, String>,
+) -> Result<Vec<TakeMetrics>, RecorderError> {
+    let current = sentence.audio_file_path.as_deref().map(Path::new);
+    find_takes(sentence, directory)?
+        .into_iter()
+        .map(|(take, path)| {
+            let buffer = read_wav(&path)?;
+            let samples: Vec<i16> = buffer
+                .samples
+                .iter()
+                .map(|&s| (s * 32768.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+                .collect();
+            let stats = measure_take(
+                &samples,
+                buffer.spec.channels,
+                buffer.spec.sample_rate,
+                Duration::ZERO,
+                Duration::ZERO,
+            );
+            let audio_file_path = path.to_string_lossy().to_string();
+            Ok(TakeMetrics {
+                take,
+                current: current.is_some_and(|current| current == path),
+                duration_ms: stats.duration_ms,
+                peak_dbfs: stats.peak_dbfs,
+                rms_dbfs: stats.rms_dbfs,
+                clipped_samples: stats.clipped_samples,
+                word_error_rate: transcripts
+                    .get(&audio_file_path)
+                    .map(|transcript| word_error_rate(&sentence.text, transcript)),
+                audio_file_path,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,369 @@
+use super::editing::read_wav;
+use super::errors::RecorderError;
+use super::stats::to_dbfs;
+use super::vad_filter::HighPass;
+use crate::models::{Transient, TransientKind};
+use std::path::Path;
+
+/// Length of the frames a take is analysed in, short enough to tell a click
+/// from the speech around it.
+const FRAME_MS: usize = 5;
+
+/// Plosive pops thump below the low band's cutoff and clicks crackle above
+/// the high band's, while most speech energy lies between the two.
+const LOW_BAND_HZ: f32 = 200.0;
+const HIGH_BAND_HZ: f32 = 5000.0;
+
+/// Frames either side of a frame its high band is compared with to find
+/// clicks, and how far above their median a click rises, in dB.
+const CLICK_CONTEXT_FRAMES: usize = 10;
+const CLICK_RISE_DB: f32 = 15.0;
+
+/// Levels of pops and breaths relative to the take's speech level, in dB.
+const POP_LEVEL_DB: f32 = -10.0;
+/// A pop either starts abruptly, this much louder than the frames before
+/// it, or has the speech band around it this far below its low band, in
+/// dB. Low-pitched vowels carry their low band too, but build up and have
+/// formants.
+const POP_ONSET_DB: f32 = 12.0;
+const POP_SPEECH_MARGIN_DB: f32 = 15.0;
+/// Frames either side of a frame looked at for a pop's onset and speech
+/// band, spanning a pitch period of even low voices.
+const POP_CONTEXT_FRAMES: usize = 4;
+const MIN_BREATH_DB: f32 = -45.0;
+const MAX_BREATH_DB: f32 = -15.0;
+
+/// Lengths outside which a span is unlikely to be what it sounds like.
+const MAX_CLICK_MS: u64 = 15;
+const MAX_POP_MS: u64 = 150;
+const MIN_BREATH_MS: u64 = 150;
+const MAX_BREATH_MS: u64 = 1000;
+
+/// Takes whose speech is quieter than this are too quiet to analyse.
+const MIN_SPEECH_DBFS: f32 = -60.0;
+
+/// Most a transient may be turned down, in dB.
+const MAX_ATTENUATION_DB: f32 = 40.0;
+
+/// Ramp into and out of an attenuated span, so the change in gain does not
+/// click itself.
+const FADE_MS: u64 = 5;
+
+/// Levels of one frame, in dBFS.
+struct Frame {
+    level: f32,
+    low: f32,
+    /// Band between the low and high bands, where speech lies.
+    mid: f32,
+    high: f32,
+    peak: f32,
+}
+
+/// Splits mono samples in the -1.0..1.0 range into frames and measures
+/// each one's level overall and in the low, speech and high bands.
+fn measure_frames(mono: &[f32], frame_len: usize, sample_rate: u32) -> Vec<Frame> {
+    let mut low_cut = HighPass::new(LOW_BAND_HZ, sample_rate);
+    let mut high_pass = HighPass::new(HIGH_BAND_HZ, sample_rate);
+    mono.chunks(frame_len)
+        .map(|frame| {
+            let (mut total, mut low, mut mid, mut high) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+            let mut peak = 0.0f32;
+            for &x in frame {
+                // The low band is what a high-pass at its cutoff takes out,
+                // and the speech band what is left of the rest without the
+                // high band.
+                let above_low = low_cut.process(x);
+                let l = x - above_low;
+                let h = high_pass.process(x);
+                let m = above_low - h;
+                total += f64::from(x * x);
+                low += f64::from(l * l);
+                mid += f64::from(m * m);
+                high += f64::from(h * h);
+                peak = peak.max(x.abs());
+            }
+            let rms = |sum: f64| to_dbfs((sum / frame.len() as f64).sqrt() as f32);
+            Frame {
+                level: rms(total),
+                low: rms(low),
+                mid: rms(mid),
+                high: rms(high),
+                peak: to_dbfs(peak),
+            }
+        })
+        .collect()
+}
+
+/// Level of the loudest tenth of the frames, taken as the level of the
+/// speech.
+fn speech_level(frames: &[Frame]) -> f32 {
+    let mut levels: Vec<f32> = frames.iter().map(|frame| frame.level).collect();
+    levels.sort_by(f32::total_cmp);
+    levels[levels.len() * 9 / 10]
+}
+
+fn classify(frames: &[Frame], index: usize, speech_db: f32) -> Option<TransientKind> {
+    let frame = &frames[index];
+    let context = &frames[index.saturating_sub(CLICK_CONTEXT_FRAMES)
+        ..frames.len().min(index + CLICK_CONTEXT_FRAMES + 1)];
+    let mut highs: Vec<f32> = context.iter().map(|frame| frame.high).collect();
+    highs.sort_by(f32::total_cmp);
+    let median_high = highs[highs.len() / 2];
+
+    let relative = frame.level - speech_db;
+    let breath_level = (MIN_BREATH_DB..=MAX_BREATH_DB).contains(&relative);
+    let before = &frames[index.saturating_sub(POP_CONTEXT_FRAMES)..index];
+    let sudden = !before.is_empty()
+        && before
+            .iter()
+            .all(|previous| frame.level >= previous.level + POP_ONSET_DB);
+    let around = &frames[index.saturating_sub(POP_CONTEXT_FRAMES)
+        ..frames.len().min(index + POP_CONTEXT_FRAMES + 1)];
+    let no_speech = around
+        .iter()
+        .all(|other| other.mid <= frame.low - POP_SPEECH_MARGIN_DB);
+    // A quarter or more of the frame's energy is in the high band, far more
+    // than just before and after, and the frame is loud enough to be heard.
+    if frame.high >= median_high + CLICK_RISE_DB
+        && frame.high >= frame.level - 6.0
+        && relative >= MIN_BREATH_DB
+    {
+        Some(TransientKind::Click)
+    } else if frame.low >= speech_db + POP_LEVEL_DB
+        && frame.low >= frame.level - 3.0
+        && (sudden || no_speech)
+    {
+        Some(TransientKind::Pop)
+    } else if breath_level && frame.low < frame.level - 10.0 {
+        Some(TransientKind::Breath)
+    } else {
+        None
+    }
+}
+
+fn plausible_length(kind: TransientKind, length_ms: u64) -> bool {
+    match kind {
+        TransientKind::Click => length_ms <= MAX_CLICK_MS,
+        TransientKind::Pop => length_ms <= MAX_POP_MS,
+        TransientKind::Breath => (MIN_BREATH_MS..=MAX_BREATH_MS).contains(&length_ms),
+    }
+}
+
+/**
+ * Finds likely breaths, mouth clicks and plosive pops in a take, given as
+ * mono samples in the -1.0..1.0 range: short bursts whose energy lies
+ * outside the bands most speech occupies, judged against the level of the
+ * take's own speech. Clicks are spikes in the high band, pops are sudden
+ * bursts carried by the low band and breaths are quiet spans with little
+ * low-band energy. These are heuristics, meant to point an editor at what to check.
+ */
+pub fn detect_transients(mono: &[f32], sample_rate: u32) -> Vec<Transient> {
+    let frame_len = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let frames = measure_frames(mono, frame_len, sample_rate);
+    if frames.is_empty() {
+        return Vec::new();
+    }
+    let speech_db = speech_level(&frames);
+    if speech_db < MIN_SPEECH_DBFS {
+        return Vec::new();
+    }
+
+    let kinds: Vec<Option<TransientKind>> = (0..frames.len())
+        .map(|index| classify(&frames, index, speech_db))
+        .collect();
+    let to_ms = |frame: usize| (frame * frame_len) as u64 * 1000 / u64::from(sample_rate.max(1));
+    let mut transients = Vec::new();
+    let mut start = 0;
+    while start < kinds.len() {
+        let kind = kinds[start];
+        let end = start + kinds[start..].iter().take_while(|&&k| k == kind).count();
+        let (start_ms, end_ms) = (to_ms(start), to_ms(end));
+        if let Some(kind) = kind.filter(|&kind| plausible_length(kind, end_ms - start_ms)) {
+            transients.push(Transient {
+                kind,
+                start_ms,
+                end_ms,
+                peak_dbfs: frames[start..end]
+                    .iter()
+                    .map(|frame| frame.peak)
+                    .fold(f32::MIN, f32::max),
+            });
+        }
+        start = end;
+    }
+    transients
+}
+
+/// Finds the breaths, clicks and pops in the take at `path`.
+pub fn find_transients(path: &Path) -> Result<Vec<Transient>, RecorderError> {
+    let buffer = read_wav(path)?;
+    let channels = buffer.spec.channels.max(1) as usize;
+    let mono: Vec<f32> = buffer
+        .samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok(detect_transients(&mono, buffer.spec.sample_rate))
+}
+
+/// Turns the transients in interleaved 16-bit audio down by
+/// `attenuation_db`, fading into and out of each one.
+pub fn attenuate_transients(
+    samples: &mut [i16],
+    channels: u16,
+    sample_rate: u32,
+    transients: &[Transient],
+    attenuation_db: f32,
+) {
+    let channels = channels.max(1) as usize;
+    let frames = samples.len() / channels;
+    let floor = 10f32.powf(-attenuation_db.clamp(0.0, MAX_ATTENUATION_DB) / 20.0);
+    let to_frame = |ms: u64| ((ms * u64::from(sample_rate) / 1000) as usize).min(frames);
+    let fade = to_frame(FADE_MS).max(1);
+
+    for transient in transients {
+        let start = to_frame(transient.start_ms);
+        let end = to_frame(transient.end_ms);
+        let from = start.saturating_sub(fade);
+        let to = (end + fade).min(frames);
+        for frame in from..to {
+            // How far the gain has moved from 1.0 towards the floor
+            let depth = if frame < start {
+                (frame - from + 1) as f32 / (start - from + 1) as f32
+            } else if frame >= end {
+                (to - frame) as f32 / (to - end + 1) as f32
+            } else {
+                1.0
+            };
+            let gain = 1.0 - (1.0 - floor) * depth;
+            for sample in &mut samples[frame * channels..(frame + 1) * channels] {
+                *sample = (f32::from(*sample) * gain).round() as i16;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn samples(ms: usize) -> usize {
+        SAMPLE_RATE as usize * ms / 1000
+    }
+
+    fn silence(ms: usize) -> Vec<f32> {
+        vec![0.0; samples(ms)]
+    }
+
+    /// Vowel-like tone: a low fundamental with harmonics up through the
+    /// speech band, faded in and out.
+    fn vowel(ms: usize, f0: f32) -> Vec<f32> {
+        let len = samples(ms);
+        let fade = samples(30);
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let tone: f32 = (1..=30)
+                    .map(|k| (2.0 * PI * f0 * k as f32 * t).sin() / k as f32)
+                    .sum();
+                let envelope = (i.min(len - i) as f32 / fade as f32).min(1.0);
+                0.2 * tone * envelope
+            })
+            .collect()
+    }
+
+    /// Low thump that starts at once and dies away, like a plosive hitting
+    /// the mic.
+    fn pop(ms: usize) -> Vec<f32> {
+        (0..samples(ms))
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (2.0 * PI * 60.0 * t).sin() * (-t / 0.015).exp()
+            })
+            .collect()
+    }
+
+    /// Quiet noise with little energy in the low band, like breath.
+    fn breath(ms: usize) -> Vec<f32> {
+        let mut seed = 0x1234_5678u32;
+        let mut previous = 0.0;
+        (0..samples(ms))
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                // Differencing tilts the noise towards high frequencies.
+                let x = noise - previous;
+                previous = noise;
+                0.01 * x
+            })
+            .collect()
+    }
+
+    fn kinds_at(transients: &[Transient], from_ms: u64, to_ms: u64) -> Vec<TransientKind> {
+        transients
+            .iter()
+            .filter(|t| t.start_ms < to_ms && t.end_ms > from_ms)
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn finds_click_between_words() {
+        let mut audio = [vowel(500, 150.0), silence(200), vowel(500, 150.0)].concat();
+        audio[samples(600)] = 0.9;
+
+        let transients = detect_transients(&audio, SAMPLE_RATE);
+        assert_eq!(
+            kinds_at(&transients, 590, 610),
+            [TransientKind::Click],
+            "{:?}",
+            transients
+        );
+    }
+
+    #[test]
+    fn finds_pop_between_words() {
+        let audio = [
+            vowel(500, 150.0),
+            silence(200),
+            pop(80),
+            silence(200),
+            vowel(500, 150.0),
+        ]
+        .concat();
+
+        let transients = detect_transients(&audio, SAMPLE_RATE);
+        assert!(
+            kinds_at(&transients, 700, 780).contains(&TransientKind::Pop),
+            "{:?}",
+            transients
+        );
+    }
+
+    #[test]
+    fn low_pitched_vowel_is_not_a_pop() {
+        let audio = [silence(200), vowel(800, 90.0), silence(200)].concat();
+
+        let transients = detect_transients(&audio, SAMPLE_RATE);
+        assert!(
+            transients.iter().all(|t| t.kind != TransientKind::Pop),
+            "{:?}",
+            transients
+        );
+    }
+
+    #[test]
+    fn finds_breath_between_words() {
+        let audio = [vowel(500, 150.0), breath(300), vowel(500, 150.0)].concat();
+
+        let transients = detect_transients(&audio, SAMPLE_RATE);
+        assert!(
+            kinds_at(&transients, 550, 750).contains(&TransientKind::Breath),
+            "{:?}",
+            transients
+        );
+    }
+}
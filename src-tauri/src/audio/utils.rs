@@ -1,25 +1,133 @@
+use super::errors::RecorderError;
+use crate::models::OutputFormat;
+use crate::storage::{self, FileSink};
 use cpal::traits::DeviceTrait;
-use cpal::{SampleRate, SupportedStreamConfig};
-use hound::WavWriter;
-use log::trace;
-use std::fs::File;
-use std::io::BufWriter;
-
-/// Writes the input audio data to the WAV file, converting it to i16 format.
-pub fn write_input_data<T>(input: &[T], writer: &mut WavWriter<BufWriter<File>>)
+use cpal::{
+    BufferSize, InputCallbackInfo, SampleFormat, SampleRate, StreamConfig, SupportedBufferSize,
+    SupportedStreamConfig,
+};
+use hound::{SampleFormat as HoundSampleFormat, WavSpec, WavWriter};
+use log::{debug, trace, warn};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// WAV file written as audio arrives.
+pub type WavFileWriter = WavWriter<FileSink>;
+
+/// Creates a WAV file to stream audio to. Files of an encrypted project are
+/// only written, encrypted, once the writer is finalized or flushed.
+pub fn create_wav(path: &Path, spec: WavSpec) -> Result<WavFileWriter, RecorderError> {
+    let sink = storage::create(path).map_err(|e| RecorderError::Other(e.to_string()))?;
+    Ok(WavWriter::new(sink, spec)?)
+}
+
+/// Destination for the samples of a standard recording.
+pub trait SampleSink: Send {
+    fn write_sample(&mut self, sample: i16) -> Result<(), RecorderError>;
+    /// Completes the files written so far.
+    fn finish(&mut self) -> Result<(), RecorderError>;
+}
+
+impl SampleSink for WavFileWriter {
+    fn write_sample(&mut self, sample: i16) -> Result<(), RecorderError> {
+        Ok(WavWriter::write_sample(self, sample)?)
+    }
+
+    fn finish(&mut self) -> Result<(), RecorderError> {
+        // Flushing brings the header up to date, as finalizing would.
+        Ok(self.flush()?)
+    }
+}
+
+/// Set while writing input data is failing, so the failure is logged once
+/// rather than for every sample.
+static WRITE_FAILING: AtomicBool = AtomicBool::new(false);
+
+/// Writes the input audio data to the sink, converting it to i16 format.
+/// The rest of a block is dropped once a sample fails to write.
+pub fn write_input_data<T, S>(input: &[T], writer: &mut S)
 where
     T: cpal::Sample,
+    S: SampleSink + ?Sized,
 {
     for &sample in input.iter() {
-        let sample_i16 = sample.to_i16();
-        writer.write_sample(sample_i16).unwrap_or_else(|e| {
-            eprintln!("Failed to write sample: {}", e);
-        });
+        if let Err(e) = writer.write_sample(sample.to_i16()) {
+            if !WRITE_FAILING.swap(true, Ordering::Relaxed) {
+                warn!("Failed to write input audio: {}", e);
+            }
+            return;
+        }
+    }
+    if WRITE_FAILING.swap(false, Ordering::Relaxed) {
+        debug!("Writing input audio again");
+    }
+}
+
+/// Averages interleaved 16-bit frames to mono samples in the -1.0..1.0
+/// range.
+pub fn downmix_to_mono(samples: &[i16], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().map(|&s| s as f32).sum::<f32>() / frame.len() as f32 / 32768.0)
+        .collect()
+}
+
+/// Converts interleaved audio between channel counts. Mono output averages
+/// the input channels, mono input is copied to every output channel, and
+/// otherwise the first channels are kept, padding with silence.
+pub fn remix_channels(samples: &[i16], from: u16, to: u16) -> Vec<i16> {
+    let (from, to) = (from.max(1) as usize, to.max(1) as usize);
+    if from == to {
+        return samples.to_vec();
+    }
+    let mut output = Vec::with_capacity(samples.len() / from * to);
+    for frame in samples.chunks_exact(from) {
+        if to == 1 {
+            let sum: i32 = frame.iter().map(|&s| s as i32).sum();
+            output.push((sum / from as i32) as i16);
+        } else if from == 1 {
+            output.extend(std::iter::repeat(frame[0]).take(to));
+        } else {
+            output.extend((0..to).map(|c| frame.get(c).copied().unwrap_or(0)));
+        }
     }
+    output
 }
 
-/// Helper function to find a supported audio configuration.
-pub fn find_supported_config(device: &cpal::Device) -> Option<SupportedStreamConfig> {
+/// Builds the WAV spec for the given channel count, sample rate and output
+/// format.
+pub fn wav_spec(channels: u16, sample_rate: u32, format: OutputFormat) -> WavSpec {
+    let (bits_per_sample, sample_format) = match format {
+        OutputFormat::Wav16 => (16, HoundSampleFormat::Int),
+        OutputFormat::Wav24 => (24, HoundSampleFormat::Int),
+        OutputFormat::Wav32Float => (32, HoundSampleFormat::Float),
+    };
+    WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    }
+}
+
+/// Writes a 16-bit sample to the writer, scaling it to the writer's bit depth.
+pub fn write_pcm_sample(writer: &mut WavFileWriter, sample: i16) -> Result<(), hound::Error> {
+    let spec = writer.spec();
+    match (spec.sample_format, spec.bits_per_sample) {
+        (HoundSampleFormat::Float, _) => writer.write_sample(sample as f32 / 32768.0),
+        (HoundSampleFormat::Int, 24) => writer.write_sample((sample as i32) << 8),
+        (HoundSampleFormat::Int, 32) => writer.write_sample((sample as i32) << 16),
+        _ => writer.write_sample(sample),
+    }
+}
+
+/// Helper function to find a supported audio configuration, preferring the
+/// given sample rate when the device supports it.
+pub fn find_supported_config(
+    device: &cpal::Device,
+    preferred_rate: Option<u32>,
+) -> Option<SupportedStreamConfig> {
     device
         .supported_input_configs()
         .ok()?
@@ -36,9 +144,54 @@ pub fn find_supported_config(device: &cpal::Device) -> Option<SupportedStreamCon
             let max_rate = config_range.max_sample_rate().0;
 
             // Include common sample rates for speech
-            [48000, 44100, 32000, 16000, 8000]
-                .iter()
-                .find(|&&rate| rate >= min_rate && rate <= max_rate)
-                .map(|&rate| config_range.with_sample_rate(SampleRate(rate)))
+            preferred_rate
+                .into_iter()
+                .chain([48000, 44100, 32000, 16000, 8000])
+                .find(|&rate| rate >= min_rate && rate <= max_rate)
+                .map(|rate| config_range.with_sample_rate(SampleRate(rate)))
         })
 }
+
+/// Buffer size requested in low-latency mode, about 2.7 ms at 48 kHz.
+/// Smaller buffers tend to drop out on busy machines for no audible gain.
+const LOW_LATENCY_FRAMES: u32 = 128;
+
+/// Returns `config` with a small fixed buffer if the device accepts one.
+/// The stream is briefly opened to check, as many drivers only refuse a
+/// buffer size when the stream is built.
+pub fn low_latency_config(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+) -> Option<StreamConfig> {
+    let frames = match *config.buffer_size() {
+        SupportedBufferSize::Range { min, max } => LOW_LATENCY_FRAMES.clamp(min, max),
+        SupportedBufferSize::Unknown => {
+            debug!("Device does not report buffer sizes; using its default buffer");
+            return None;
+        }
+    };
+    let mut stream_config = config.config();
+    stream_config.buffer_size = BufferSize::Fixed(frames);
+
+    let err_fn = |e| trace!("Low-latency probe stream error: {}", e);
+    let probe = match config.sample_format() {
+        SampleFormat::I16 => device.build_input_stream(
+            &stream_config,
+            |_: &[i16], _: &InputCallbackInfo| {},
+            err_fn,
+        ),
+        SampleFormat::F32 => device.build_input_stream(
+            &stream_config,
+            |_: &[f32], _: &InputCallbackInfo| {},
+            err_fn,
+        ),
+        _ => return None,
+    };
+    match probe {
+        Ok(_) => Some(stream_config),
+        Err(e) => {
+            debug!("Device refused a {}-frame buffer: {}", frames, e);
+            None
+        }
+    }
+}
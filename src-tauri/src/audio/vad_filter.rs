@@ -0,0 +1,84 @@
+use crate::models::VadPreprocessingSettings;
+use std::f32::consts::PI;
+
+/// Light filtering applied to the audio the VAD analyses, never to the audio
+/// that is written. Filter state carries across chunks, so one filter is
+/// used per stream.
+pub struct VadFilter {
+    high_pass: Option<HighPass>,
+    gain: f32,
+}
+
+impl VadFilter {
+    pub fn new(settings: VadPreprocessingSettings, sample_rate: u32) -> Self {
+        Self {
+            high_pass: settings
+                .high_pass_hz
+                .filter(|&cutoff| cutoff > 0.0)
+                .map(|cutoff| HighPass::new(cutoff, sample_rate)),
+            gain: 10f32.powf(settings.gain_db / 20.0),
+        }
+    }
+
+    /// Filters samples in the -1.0..1.0 range in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if let Some(high_pass) = &mut self.high_pass {
+            for sample in samples.iter_mut() {
+                *sample = high_pass.process(*sample);
+            }
+        }
+        if self.gain != 1.0 {
+            for sample in samples.iter_mut() {
+                *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+            }
+        }
+    }
+}
+
+/// Second-order Butterworth high-pass filter, to strip rumble below the
+/// voice range.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct HighPass {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl HighPass {
+    pub(super) fn new(cutoff_hz: f32, sample_rate: u32) -> Self {
+        // Keep the cutoff below Nyquist so the coefficients stay stable.
+        let cutoff = cutoff_hz.min(sample_rate as f32 * 0.45);
+        let omega = 2.0 * PI * cutoff / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 + cos) / 2.0 / a0,
+            b1: -(1.0 + cos) / a0,
+            b2: (1.0 + cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    pub(super) fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
@@ -0,0 +1,202 @@
+use crate::errors::RecordrError;
+use crate::paths::long_path;
+use crate::storage::{self, ReadSeek};
+use log::{debug, warn};
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Runtime, UriSchemeContext, UriSchemeResponder};
+use tauri_plugin_fs::FsExt;
+
+/// URI scheme that streams audio files to the webview. The frontend builds
+/// the URL with `convertFileSrc(path, 'audio')`, which percent-encodes the
+/// path and picks the form the platform's webview accepts.
+pub const AUDIO_SCHEME: &str = "audio";
+
+/// Most bytes sent in one response. Media elements request the rest of a
+/// file range by range as they play or seek.
+const MAX_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Encrypted files kept decrypted for the ranges that follow.
+const DECRYPTED_CACHE_SIZE: usize = 4;
+
+/// An encrypted file decrypted for the scheme, with the modification time
+/// and size it had on disk, so a file changed since is decrypted again.
+struct DecryptedFile {
+    path: PathBuf,
+    stamp: (Option<SystemTime>, u64),
+    data: Arc<[u8]>,
+}
+
+/// Files decrypted lately, the most recently used last. Encrypted files can
+/// only be decrypted whole, and a media element requests a take range by
+/// range.
+static DECRYPTED: Mutex<Vec<DecryptedFile>> = Mutex::new(Vec::new());
+
+/// Audio files the scheme serves, with their MIME types.
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("wav", "audio/wav"),
+    ("flac", "audio/flac"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+];
+
+fn content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+        .map(|&(_, content_type)| content_type)
+}
+
+/// Bytes `start..end` asked for by a `Range` header of the form
+/// `bytes=start-end`, `bytes=start-` or `bytes=-suffix`, limited to the
+/// file. Only the first of several ranges is served.
+fn parse_range(value: &str, size: u64) -> Option<(u64, u64)> {
+    let range = value.strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => (size.saturating_sub(suffix.parse().ok()?), size),
+        (start, "") => (start.parse().ok()?, size),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.saturating_add(1),
+        ),
+    };
+    (start < size && start < end).then_some((start, end.min(size)))
+}
+
+fn empty_response(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+/// Opens the file at `path`, decrypting an encrypted file only if it is not
+/// in the cache.
+fn open(path: &Path) -> Result<Box<dyn ReadSeek>, RecordrError> {
+    if !storage::is_encrypted(path)? {
+        return storage::open(path);
+    }
+    let metadata = fs::metadata(long_path(path))
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    let stamp = (metadata.modified().ok(), metadata.len());
+    // Decrypting under the lock keeps the ranges requested at once from
+    // each decrypting the file.
+    let mut cache = DECRYPTED.lock().unwrap();
+    let cached = cache
+        .iter()
+        .position(|file| file.path == path && file.stamp == stamp);
+    let file = match cached {
+        Some(index) => cache.remove(index),
+        None => {
+            cache.retain(|file| file.path != path);
+            if cache.len() >= DECRYPTED_CACHE_SIZE {
+                cache.remove(0);
+            }
+            DecryptedFile {
+                path: path.to_path_buf(),
+                stamp,
+                data: storage::read(path)?.into(),
+            }
+        }
+    };
+    let data = Arc::clone(&file.data);
+    cache.push(file);
+    Ok(Box::new(Cursor::new(data)))
+}
+
+/// Reads the requested range of the file at `path`, at most
+/// `MAX_CHUNK_SIZE` bytes of it.
+fn read_chunk(path: &Path, range: Option<&str>) -> Result<Response<Vec<u8>>, RecordrError> {
+    let mut file = open(path)?;
+    let size = file.seek(SeekFrom::End(0))?;
+    let (start, end) = match range {
+        Some(range) => match parse_range(range, size) {
+            Some(range) => range,
+            None => {
+                return Ok(Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                    .body(Vec::new())
+                    .unwrap_or_default());
+            }
+        },
+        None => (0, size),
+    };
+    let end = end.min(start + MAX_CHUNK_SIZE);
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut body = Vec::with_capacity((end - start) as usize);
+    file.take(end - start).read_to_end(&mut body)?;
+
+    // A file too large for one response is sent as its first range even
+    // when none was asked for; media elements carry on from there.
+    let partial = range.is_some() || end < size;
+    let mut response = Response::builder()
+        .status(if partial {
+            StatusCode::PARTIAL_CONTENT
+        } else {
+            StatusCode::OK
+        })
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, body.len())
+        .header(
+            header::CONTENT_TYPE,
+            content_type(path).unwrap_or("application/octet-stream"),
+        );
+    if partial {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end.saturating_sub(1), size),
+        );
+    }
+    response
+        .body(body)
+        .map_err(|e| RecordrError::invalid_state(e.to_string()))
+}
+
+/**
+ * Handles a request on the `audio` scheme: serves the audio file named by
+ * the URL's path in ranges, so multi-minute takes play and seek without
+ * being copied across the IPC boundary whole. Only audio files inside the
+ * file system scope, i.e. the directories of the projects opened, are
+ * served.
+ */
+pub fn handle_audio_request<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let path = percent_decode_str(request.uri().path().trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .to_string();
+    let path = PathBuf::from(path);
+    if content_type(&path).is_none() || !ctx.app_handle().fs_scope().is_allowed(&path) {
+        debug!("Refusing to serve {:?}", path);
+        responder.respond(empty_response(StatusCode::FORBIDDEN));
+        return;
+    }
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = match read_chunk(&path, range.as_deref()) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to serve {:?}: {}", path, e);
+                empty_response(StatusCode::NOT_FOUND)
+            }
+        };
+        responder.respond(response);
+    });
+}
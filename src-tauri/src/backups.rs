@@ -0,0 +1,148 @@
+use crate::errors::RecordrError;
+use crate::file_utils::{allow_project_directory, unlock_and_read_project};
+use crate::jobs::run_blocking;
+use crate::models::Project;
+use crate::temp_files::TempFile;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use log::{debug, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Subfolder of a project directory that holds its backups.
+const BACKUPS_DIR: &str = ".backups";
+/// Timestamp in backup file names, which sorts in creation order.
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// Copy of a project file taken before it was overwritten.
+#[derive(Debug, Serialize)]
+pub struct ProjectBackup {
+    pub file_name: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+}
+
+fn backups_dir(project_file: &Path) -> PathBuf {
+    project_file
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(BACKUPS_DIR)
+}
+
+/// Time a backup of `project_file` was taken, if `file_name` is one.
+fn backup_time(project_file: &Path, file_name: &str) -> Option<DateTime<Utc>> {
+    let stem = project_file.file_stem()?.to_str()?;
+    let timestamp = file_name
+        .strip_prefix(stem)?
+        .strip_prefix('.')?
+        .strip_suffix(".json")?;
+    NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT)
+        .ok()
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+/// Backups of `project_file`, newest first.
+fn find_backups(project_file: &Path) -> Result<Vec<ProjectBackup>, RecordrError> {
+    let directory = backups_dir(project_file);
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups: Vec<ProjectBackup> = fs::read_dir(&directory)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let created_at = backup_time(project_file, &file_name)?;
+            Some(ProjectBackup {
+                size: entry.metadata().ok()?.len(),
+                file_name,
+                created_at,
+            })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(backups)
+}
+
+/// Copies the project file into the backups folder before it is replaced,
+/// then deletes all but the `keep` newest backups. The file is copied as
+/// is, so backups of encrypted projects stay encrypted. Nothing is kept
+/// when `keep` is 0.
+pub fn back_up_project_file(project_file: &Path, keep: usize) -> Result<(), RecordrError> {
+    if keep == 0 || !project_file.exists() {
+        return Ok(());
+    }
+    let directory = backups_dir(project_file);
+    fs::create_dir_all(&directory)?;
+    let stem = project_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("project");
+    let backup = directory.join(format!(
+        "{}.{}.json",
+        stem,
+        Utc::now().format(BACKUP_TIMESTAMP_FORMAT)
+    ));
+    debug!("Backing up {:?} to {:?}", project_file, backup);
+    fs::copy(project_file, &backup)
+        .map_err(|e| RecordrError::from(e).with_context(backup.display().to_string()))?;
+
+    for old in find_backups(project_file)?.iter().skip(keep) {
+        let path = directory.join(&old.file_name);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to delete old backup {}: {}", path.display(), e);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the backups of the project file at `file_path`, newest first.
+/// Takes the path rather than the project so the backups of a project that
+/// no longer opens can be listed.
+#[tauri::command]
+pub async fn list_project_backups(file_path: String) -> Result<Vec<ProjectBackup>, RecordrError> {
+    run_blocking(move || find_backups(Path::new(&file_path))).await
+}
+
+/// Replaces the project file at `file_path` with one of its backups and
+/// opens it. The file being replaced is backed up first, so a restore can
+/// itself be undone.
+#[tauri::command]
+pub async fn restore_project_backup(
+    app: AppHandle,
+    file_path: String,
+    backup: String,
+    passphrase: Option<String>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let project = restore_backup(&file_path, &backup, passphrase.as_deref())?;
+        allow_project_directory(&app, &project);
+        Ok(project)
+    })
+    .await
+}
+
+/// Restores `backup` over the project file at `file_path` and reads it.
+pub fn restore_backup(
+    file_path: &str,
+    backup: &str,
+    passphrase: Option<&str>,
+) -> Result<Project, RecordrError> {
+    let project_file = Path::new(file_path);
+    if backup_time(project_file, backup).is_none() {
+        return Err(RecordrError::not_found("Not a backup of this project").with_context(backup));
+    }
+    let backup_path = backups_dir(project_file).join(backup);
+    if !backup_path.exists() {
+        return Err(RecordrError::not_found("Backup not found").with_context(backup));
+    }
+    // The current file is kept however many backups are allowed.
+    back_up_project_file(project_file, usize::MAX)?;
+    // Copied next to the project file and renamed over it, so a failed
+    // copy never leaves a partly written project.
+    let restored = TempFile::new(project_file);
+    fs::copy(&backup_path, restored.path())
+        .map_err(|e| RecordrError::from(e).with_context(file_path))?;
+    restored.keep()?;
+    unlock_and_read_project(file_path, passphrase)
+}
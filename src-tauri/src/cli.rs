@@ -0,0 +1,190 @@
+use crate::audio::{AutoRecordEvent, AutoRecordEventKind, BufferSource, Recorder, RecorderEvents};
+use crate::errors::RecordrError;
+use crate::file_utils::{unlock_and_read_project, write_project};
+use crate::models::SentenceStatus;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use log::info;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Command line interface. Without a subcommand the app window is opened.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /**
+     * Parses the command line, or returns no command if the arguments are
+     * not for the command line interface, such as the `-psn_*` argument
+     * macOS passes to apps opened from the Finder, so the app window
+     * opens. Help and version requests, and errors in the arguments of a
+     * subcommand, are printed and exit as usual.
+     */
+    pub fn parse_or_gui() -> Self {
+        match Self::try_parse() {
+            Ok(cli) => cli,
+            Err(e) if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) => {
+                e.exit()
+            }
+            Err(e) => {
+                let first = std::env::args().nth(1).unwrap_or_default();
+                let is_subcommand = Self::command()
+                    .get_subcommands()
+                    .any(|command| command.get_name() == first);
+                if is_subcommand {
+                    e.exit();
+                }
+                info!("Ignoring command line arguments: {}", e.kind());
+                Self { command: None }
+            }
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run an auto-record session without the app window, printing events
+    /// to stdout as JSON lines.
+    Record(RecordArgs),
+    /// Serve the app's commands as JSON-RPC 2.0 over stdin and stdout, one
+    /// message per line, or over WebSocket, for other frontends and test
+    /// drivers.
+    Rpc(RpcArgs),
+}
+
+#[derive(Args)]
+pub struct RpcArgs {
+    /// Address to accept WebSocket connections on, such as
+    /// `127.0.0.1:9001`, instead of using stdin and stdout.
+    #[arg(long)]
+    pub listen: Option<String>,
+    /// Accept connections on an address other than loopback. Anyone who
+    /// can reach it and learns the token can operate the recorder.
+    #[arg(long, requires = "listen")]
+    pub allow_remote: bool,
+    /// WAV file to play into recordings instead of the input device, which
+    /// makes runs reproducible.
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RecordArgs {
+    /// Path of the project JSON.
+    #[arg(long)]
+    project: String,
+    /// ID of the first sentence to record.
+    #[arg(long)]
+    from: Option<usize>,
+    /// ID of the last sentence to record.
+    #[arg(long)]
+    to: Option<usize>,
+    /// WAV file to play into the session instead of the input device. Each
+    /// sentence hears the file followed by silence, which makes runs
+    /// reproducible.
+    #[arg(long)]
+    input: Option<PathBuf>,
+    /// Record sentences that already have a take again instead of passing
+    /// over them.
+    #[arg(long)]
+    overwrite: bool,
+    /// Passphrase of an encrypted project. Read from the environment so it
+    /// does not show up in the process list.
+    #[arg(long, env = "RECORDR_PASSPHRASE", hide_env_values = true)]
+    passphrase: Option<String>,
+}
+
+/// Prints each event as a JSON line and keeps finished takes so the project
+/// can be updated once the session ends.
+#[derive(Default)]
+struct JsonLinesEvents {
+    finished: Mutex<Vec<AutoRecordEvent>>,
+}
+
+impl RecorderEvents for JsonLinesEvents {
+    fn send(&self, event: AutoRecordEvent) {
+        match serde_json::to_string(&event) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("Failed to serialize event: {}", e),
+        }
+        if matches!(event.kind, AutoRecordEventKind::SentenceFinished) {
+            self.finished.lock().unwrap().push(event);
+        }
+    }
+}
+
+/// Records the sentences of a project between `--from` and `--to`, then
+/// saves the project with the new takes.
+pub fn record(args: RecordArgs) -> Result<(), RecordrError> {
+    let mut project = unlock_and_read_project(&args.project, args.passphrase.as_deref())?;
+    let sentences = project
+        .sentences
+        .iter()
+        .filter(|s| args.from.is_none_or(|from| s.id >= from))
+        .filter(|s| args.to.is_none_or(|to| s.id <= to))
+        .cloned()
+        .collect::<Vec<_>>();
+    if sentences.is_empty() {
+        return Err(RecordrError::invalid_input(
+            "No sentences in the given range",
+        ));
+    }
+
+    // Nobody is around to confirm takes or end a rest in headless mode.
+    let mut settings = project.metadata.recording_settings.clone();
+    settings.auto_advance = true;
+    settings.sprint_minutes = 0;
+    if args.overwrite {
+        settings.overwrite_recorded = true;
+    }
+    // A WAV input can't be listened to twice.
+    if args.input.is_some() {
+        settings.board_tape.enabled = false;
+    }
+
+    let events = Arc::new(JsonLinesEvents::default());
+    let mut recorder = Recorder::new();
+    let project_directory = project.metadata.directory.clone();
+    let layout = project.metadata.layout.clone();
+    match &args.input {
+        Some(input) => recorder.start_auto_record_with_source(
+            sentences,
+            None,
+            None,
+            project_directory,
+            layout,
+            settings,
+            Box::new(BufferSource::from_wav(input)?),
+            events.clone(),
+        )?,
+        None => recorder.start_auto_record(
+            sentences,
+            None,
+            None,
+            project_directory,
+            layout,
+            settings,
+            events.clone(),
+        )?,
+    };
+    recorder.wait_auto_record();
+
+    for event in events.finished.lock().unwrap().iter() {
+        if let Some(sentence) = project
+            .sentences
+            .iter_mut()
+            .find(|s| Some(s.id) == event.sentence_id)
+        {
+            sentence.recorded = true;
+            sentence.audio_file_path = event.audio_file_path.clone();
+            sentence.stats = event.stats.clone();
+            sentence.skipped = false;
+            sentence.status = SentenceStatus::Recorded;
+        }
+    }
+    write_project(&project)
+}
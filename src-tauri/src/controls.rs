@@ -0,0 +1,516 @@
+use crate::audio::{Recorder, TakeInterrupt};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::app_data_file_path;
+use hidapi::HidApi;
+use log::{debug, info, warn};
+use midir::{MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::State;
+
+/// Name this app registers with the MIDI system.
+const MIDI_CLIENT_NAME: &str = "recordr";
+/// How long a footswitch read blocks before checking whether to stop.
+const HID_POLL_MS: i32 = 100;
+/// Largest HID input report read from a footswitch.
+const HID_REPORT_LEN: usize = 64;
+/// Name of the media session on D-Bus, for MPRIS, followed by the process
+/// ID so the app and the RPC server can both register one.
+const MEDIA_DBUS_NAME: &str = "recordr.instance";
+/// How often the media key listener checks whether a session is running.
+const MEDIA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Session command triggered by a pedal or MIDI controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlAction {
+    Pause,
+    Resume,
+    /// Pauses a recording session and resumes a paused one, for single
+    /// pedals.
+    TogglePause,
+    Skip,
+    Retry,
+}
+
+/// MIDI message that triggers a binding. Controllers fire at values of 64
+/// and above, the usual "pedal down" threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MidiTrigger {
+    Note { number: u8 },
+    ControlChange { number: u8 },
+    ProgramChange { number: u8 },
+}
+
+impl MidiTrigger {
+    fn matches(&self, message: &[u8]) -> bool {
+        match (message, self) {
+            ([status, note, velocity, ..], MidiTrigger::Note { number }) => {
+                status & 0xf0 == 0x90 && note == number && *velocity > 0
+            }
+            ([status, controller, value, ..], MidiTrigger::ControlChange { number }) => {
+                status & 0xf0 == 0xb0 && controller == number && *value >= 64
+            }
+            ([status, program, ..], MidiTrigger::ProgramChange { number }) => {
+                status & 0xf0 == 0xc0 && program == number
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiBinding {
+    #[serde(flatten)]
+    pub trigger: MidiTrigger,
+    /// MIDI channel from 0 to 15; `None` matches any channel.
+    #[serde(default)]
+    pub channel: Option<u8>,
+    pub action: ControlAction,
+}
+
+/// USB footswitch, identified by its vendor and product IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HidDeviceId {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+/// Fires when byte `byte` of the footswitch's input report changes to
+/// `value`. Pedals report a different value per pedal while held down.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HidBinding {
+    pub byte: usize,
+    pub value: u8,
+    pub action: ControlAction,
+}
+
+/// Hands-free control of auto-record sessions, so narrators can pause,
+/// skip and retry while holding a script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    /// MIDI input port to listen on; `None` disables MIDI control.
+    pub midi_port: Option<String>,
+    pub midi_bindings: Vec<MidiBinding>,
+    /// Footswitch to listen on; `None` disables footswitch control.
+    pub hid_device: Option<HidDeviceId>,
+    pub hid_bindings: Vec<HidBinding>,
+    /// Whether the keyboard's play/pause media key pauses and resumes the
+    /// session, through the system's media session. The media session is
+    /// only registered while an auto-record session runs, so other players
+    /// keep the keys the rest of the time.
+    pub media_keys: bool,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self {
+            midi_port: None,
+            midi_bindings: Vec::new(),
+            hid_device: None,
+            hid_bindings: Vec::new(),
+            media_keys: false,
+        }
+    }
+}
+
+/// MIDI input port or HID device that can be bound to controls.
+#[derive(Debug, Serialize)]
+pub struct ControlDevice {
+    pub name: String,
+    /// Set for HID devices.
+    pub id: Option<HidDeviceId>,
+}
+
+/// Configured controls and the listeners applying them.
+pub struct InputControls {
+    settings: ControlSettings,
+    midi: Option<MidiInputConnection<()>>,
+    hid: Option<HidListener>,
+    media: Option<MediaKeyListener>,
+    /// Window the Windows media session is attached to, once there is one.
+    window_handle: Option<usize>,
+}
+
+pub type ControlsState = Mutex<InputControls>;
+
+impl InputControls {
+    /// Starts listening with `settings`. A device that cannot be opened is
+    /// logged and left out rather than failing startup.
+    pub fn start(settings: ControlSettings, recorder: &Arc<Mutex<Recorder>>) -> Self {
+        let mut controls = Self {
+            settings: ControlSettings::default(),
+            midi: None,
+            hid: None,
+            media: None,
+            window_handle: None,
+        };
+        if let Err(e) = controls.apply(settings, recorder) {
+            warn!("Failed to start input controls: {}", e);
+        }
+        controls
+    }
+
+    pub fn settings(&self) -> &ControlSettings {
+        &self.settings
+    }
+
+    /// Attaches the media session to the app window. Windows only delivers
+    /// media keys to a session with a window, so they start working there
+    /// once this is called.
+    pub fn attach_window(&mut self, window_handle: usize, recorder: &Arc<Mutex<Recorder>>) {
+        self.window_handle = Some(window_handle);
+        if let Err(e) = self.apply(self.settings.clone(), recorder) {
+            warn!("Failed to restart input controls: {}", e);
+        }
+    }
+
+    /// Replaces the listeners with ones for `settings`.
+    fn apply(
+        &mut self,
+        settings: ControlSettings,
+        recorder: &Arc<Mutex<Recorder>>,
+    ) -> Result<(), RecordrError> {
+        // Devices are released before they are opened again.
+        self.midi = None;
+        self.hid = None;
+        self.media = None;
+        self.settings = settings;
+        if let Some(port) = &self.settings.midi_port {
+            self.midi = Some(connect_midi(port, &self.settings.midi_bindings, recorder)?);
+            info!("Listening for MIDI controls on {}", port);
+        }
+        if let Some(device) = self.settings.hid_device {
+            self.hid = Some(HidListener::start(
+                device,
+                self.settings.hid_bindings.clone(),
+                recorder,
+            )?);
+            info!(
+                "Listening for footswitch controls on {:04x}:{:04x}",
+                device.vendor_id, device.product_id
+            );
+        }
+        if self.settings.media_keys
+            && (cfg!(not(target_os = "windows")) || self.window_handle.is_some())
+        {
+            self.media = Some(MediaKeyListener::start(self.window_handle, recorder));
+        }
+        Ok(())
+    }
+}
+
+/// Runs a control action against the current session. Actions that do not
+/// apply, such as resuming a session that is not paused, are ignored.
+fn dispatch(recorder: &Mutex<Recorder>, action: ControlAction) {
+    debug!("Input control: {:?}", action);
+    let mut recorder = recorder.lock().unwrap();
+    let result = match action {
+        ControlAction::Pause => recorder.pause_auto_record(),
+        ControlAction::Resume => recorder.resume_auto_record(),
+        ControlAction::TogglePause => recorder
+            .pause_auto_record()
+            .or_else(|_| recorder.resume_auto_record()),
+        ControlAction::Skip => recorder.interrupt_take(TakeInterrupt::Skip),
+        ControlAction::Retry => recorder.interrupt_take(TakeInterrupt::Retry),
+    };
+    if let Err(e) = result {
+        debug!("Ignoring {:?} control: {}", action, e);
+    }
+}
+
+fn control_error(e: impl ToString) -> RecordrError {
+    RecordrError::new(ErrorKind::DeviceError, e.to_string())
+}
+
+fn connect_midi(
+    port_name: &str,
+    bindings: &[MidiBinding],
+    recorder: &Arc<Mutex<Recorder>>,
+) -> Result<MidiInputConnection<()>, RecordrError> {
+    let input = MidiInput::new(MIDI_CLIENT_NAME).map_err(control_error)?;
+    let port = input
+        .ports()
+        .into_iter()
+        .find(|port| input.port_name(port).is_ok_and(|name| name == port_name))
+        .ok_or_else(|| RecordrError::not_found("MIDI input not found").with_context(port_name))?;
+
+    let bindings = bindings.to_vec();
+    let recorder = Arc::clone(recorder);
+    input
+        .connect(
+            &port,
+            "recordr-controls",
+            move |_, message, _| {
+                let channel = message.first().map(|status| status & 0x0f);
+                let binding = bindings.iter().find(|binding| {
+                    binding.trigger.matches(message)
+                        && binding.channel.is_none_or(|c| Some(c) == channel)
+                });
+                if let Some(binding) = binding {
+                    dispatch(&recorder, binding.action);
+                }
+            },
+            (),
+        )
+        .map_err(control_error)
+}
+
+fn media_error(e: souvlaki::Error) -> RecordrError {
+    RecordrError::new(ErrorKind::DeviceError, format!("{:?}", e))
+}
+
+/// Registers a media session with the system so its play/pause media keys
+/// pause and resume the session: MPRIS on Linux, the Now Playing center on
+/// macOS and the system media transport controls on Windows.
+fn connect_media_keys(
+    window_handle: Option<usize>,
+    recorder: &Arc<Mutex<Recorder>>,
+) -> Result<MediaControls, RecordrError> {
+    let dbus_name = format!("{}{}", MEDIA_DBUS_NAME, std::process::id());
+    let config = PlatformConfig {
+        dbus_name: &dbus_name,
+        display_name: "Recordr",
+        hwnd: window_handle.map(|handle| handle as *mut std::ffi::c_void),
+    };
+    let mut controls = MediaControls::new(config).map_err(media_error)?;
+    let recorder = Arc::clone(recorder);
+    controls
+        .attach(move |event| {
+            let action = match event {
+                MediaControlEvent::Toggle => ControlAction::TogglePause,
+                MediaControlEvent::Play => ControlAction::Resume,
+                MediaControlEvent::Pause => ControlAction::Pause,
+                _ => return,
+            };
+            dispatch(&recorder, action);
+        })
+        .map_err(media_error)?;
+    // Systems route the media keys to a session that is playing or paused.
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Recording session"),
+            ..Default::default()
+        })
+        .map_err(media_error)?;
+    controls
+        .set_playback(MediaPlayback::Paused { progress: None })
+        .map_err(media_error)?;
+    Ok(controls)
+}
+
+/// Registers the media session while an auto-record session is running and
+/// removes it once the session ends, on a background thread. Media keys are
+/// a convenience, so a system without a media session is logged once per
+/// session and doesn't stop the other controls from being applied.
+struct MediaKeyListener {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MediaKeyListener {
+    fn start(window_handle: Option<usize>, recorder: &Arc<Mutex<Recorder>>) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            let recorder = Arc::clone(recorder);
+            std::thread::spawn(move || {
+                let mut media: Option<MediaControls> = None;
+                let mut failed = false;
+                while !stopped.load(Ordering::Relaxed) {
+                    let active = recorder.lock().unwrap().is_auto_recording();
+                    if active && media.is_none() && !failed {
+                        match connect_media_keys(window_handle, &recorder) {
+                            Ok(controls) => {
+                                media = Some(controls);
+                                info!("Listening for media keys");
+                            }
+                            Err(e) => {
+                                warn!("Failed to listen for media keys: {}", e);
+                                failed = true;
+                            }
+                        }
+                    } else if !active {
+                        failed = false;
+                        if let Some(mut controls) = media.take() {
+                            let _ = controls.detach();
+                            debug!("Stopped listening for media keys");
+                        }
+                    }
+                    std::thread::sleep(MEDIA_POLL_INTERVAL);
+                }
+                if let Some(mut controls) = media {
+                    let _ = controls.detach();
+                }
+            })
+        };
+        Self {
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MediaKeyListener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads input reports from a footswitch on a background thread.
+struct HidListener {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HidListener {
+    fn start(
+        id: HidDeviceId,
+        bindings: Vec<HidBinding>,
+        recorder: &Arc<Mutex<Recorder>>,
+    ) -> Result<Self, RecordrError> {
+        let api = HidApi::new().map_err(control_error)?;
+        let device = api.open(id.vendor_id, id.product_id).map_err(|e| {
+            control_error(e).with_context(format!("{:04x}:{:04x}", id.vendor_id, id.product_id))
+        })?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = Arc::clone(&stopped);
+            let recorder = Arc::clone(recorder);
+            std::thread::spawn(move || {
+                let mut report = [0u8; HID_REPORT_LEN];
+                let mut previous = [0u8; HID_REPORT_LEN];
+                while !stopped.load(Ordering::Relaxed) {
+                    let len = match device.read_timeout(&mut report, HID_POLL_MS) {
+                        Ok(0) => continue,
+                        Ok(len) => len,
+                        Err(e) => {
+                            warn!("Footswitch read failed: {}", e);
+                            break;
+                        }
+                    };
+                    // Only a change to the bound value fires, so holding a
+                    // pedal down does not repeat the action.
+                    for binding in &bindings {
+                        if binding.byte < len
+                            && report[binding.byte] == binding.value
+                            && previous[binding.byte] != binding.value
+                        {
+                            dispatch(&recorder, binding.action);
+                        }
+                    }
+                    previous[..len].copy_from_slice(&report[..len]);
+                }
+            })
+        };
+
+        Ok(Self {
+            stopped,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for HidListener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn get_controls_path() -> PathBuf {
+    app_data_file_path("controls.json")
+}
+
+/// Loads the control settings, falling back to no controls.
+pub fn load_control_settings() -> ControlSettings {
+    fs::read_to_string(get_controls_path())
+        .ok()
+        .and_then(|contents| {
+            serde_json::from_str(&contents)
+                .map_err(|e| warn!("Invalid control settings: {}", e))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn save_control_settings(settings: &ControlSettings) -> Result<(), RecordrError> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(get_controls_path(), json)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_input_controls(controls: State<ControlsState>) -> ControlSettings {
+    controls.lock().unwrap().settings().clone()
+}
+
+/// Replaces the control bindings, reopening the devices they listen on,
+/// and saves them.
+#[tauri::command]
+pub fn set_input_controls(
+    settings: ControlSettings,
+    controls: State<ControlsState>,
+    recorder: State<Arc<Mutex<Recorder>>>,
+) -> Result<ControlSettings, RecordrError> {
+    replace_input_controls(settings, &controls, recorder.inner())
+}
+
+/// Validates `settings`, starts listening with them and saves them.
+pub fn replace_input_controls(
+    settings: ControlSettings,
+    controls: &ControlsState,
+    recorder: &Arc<Mutex<Recorder>>,
+) -> Result<ControlSettings, RecordrError> {
+    let mut channels = settings.midi_bindings.iter().filter_map(|b| b.channel);
+    if let Some(channel) = channels.find(|&c| c > 15) {
+        return Err(RecordrError::invalid_input(format!(
+            "MIDI channel must be between 0 and 15, not {}",
+            channel
+        )));
+    }
+    controls.lock().unwrap().apply(settings.clone(), recorder)?;
+    save_control_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Lists the MIDI inputs and HID devices that controls can be bound to.
+#[tauri::command]
+pub fn list_control_devices() -> Result<Vec<ControlDevice>, RecordrError> {
+    let input = MidiInput::new(MIDI_CLIENT_NAME).map_err(control_error)?;
+    let mut devices: Vec<ControlDevice> = input
+        .ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .map(|name| ControlDevice { name, id: None })
+        .collect();
+
+    let api = HidApi::new().map_err(control_error)?;
+    devices.extend(api.device_list().map(|info| {
+        ControlDevice {
+            name: info
+                .product_string()
+                .unwrap_or("Unknown HID device")
+                .to_string(),
+            id: Some(HidDeviceId {
+                vendor_id: info.vendor_id(),
+                product_id: info.product_id(),
+            }),
+        }
+    }));
+    Ok(devices)
+}
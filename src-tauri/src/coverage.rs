@@ -0,0 +1,268 @@
+use crate::models::Sentence;
+use crate::qc::{normalize_text, primary_language};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Grapheme-to-phoneme rules for a language: each grapheme maps to
+/// space-separated phonemes, or to nothing when silent. The longest grapheme
+/// matching at a position wins, which is how context such as `c` before `e`
+/// is expressed. The rules are approximate and meant for coverage
+/// estimates, not pronunciation.
+type G2pTable = &'static [(&'static str, &'static str)];
+
+/// Castilian Spanish.
+const SPANISH: G2pTable = &[
+    ("a", "a"),
+    ("á", "a"),
+    ("b", "b"),
+    ("c", "k"),
+    ("ce", "θ e"),
+    ("ci", "θ i"),
+    ("cé", "θ e"),
+    ("cí", "θ i"),
+    ("ch", "tʃ"),
+    ("d", "d"),
+    ("e", "e"),
+    ("é", "e"),
+    ("f", "f"),
+    ("g", "g"),
+    ("ge", "x e"),
+    ("gi", "x i"),
+    ("gue", "g e"),
+    ("gui", "g i"),
+    ("güe", "g w e"),
+    ("güi", "g w i"),
+    ("h", ""),
+    ("i", "i"),
+    ("í", "i"),
+    ("j", "x"),
+    ("k", "k"),
+    ("l", "l"),
+    ("ll", "ʝ"),
+    ("m", "m"),
+    ("n", "n"),
+    ("ñ", "ɲ"),
+    ("o", "o"),
+    ("ó", "o"),
+    ("p", "p"),
+    ("qu", "k"),
+    ("r", "ɾ"),
+    ("rr", "r"),
+    ("s", "s"),
+    ("t", "t"),
+    ("u", "u"),
+    ("ú", "u"),
+    ("ü", "w"),
+    ("v", "b"),
+    ("w", "w"),
+    ("x", "k s"),
+    ("y", "ʝ"),
+    ("z", "θ"),
+];
+
+/// Standard Italian.
+const ITALIAN: G2pTable = &[
+    ("a", "a"),
+    ("à", "a"),
+    ("b", "b"),
+    ("c", "k"),
+    ("ce", "tʃ e"),
+    ("ci", "tʃ i"),
+    ("cia", "tʃ a"),
+    ("cio", "tʃ o"),
+    ("ciu", "tʃ u"),
+    ("ch", "k"),
+    ("d", "d"),
+    ("e", "e"),
+    ("è", "ɛ"),
+    ("é", "e"),
+    ("f", "f"),
+    ("g", "g"),
+    ("ge", "dʒ e"),
+    ("gi", "dʒ i"),
+    ("gia", "dʒ a"),
+    ("gio", "dʒ o"),
+    ("giu", "dʒ u"),
+    ("gh", "g"),
+    ("gli", "ʎ i"),
+    ("glia", "ʎ a"),
+    ("glie", "ʎ e"),
+    ("glio", "ʎ o"),
+    ("gn", "ɲ"),
+    ("h", ""),
+    ("i", "i"),
+    ("ì", "i"),
+    ("l", "l"),
+    ("m", "m"),
+    ("n", "n"),
+    ("o", "o"),
+    ("ò", "ɔ"),
+    ("ó", "o"),
+    ("p", "p"),
+    ("qu", "k w"),
+    ("r", "r"),
+    ("s", "s"),
+    ("sc", "s k"),
+    ("sce", "ʃ e"),
+    ("sci", "ʃ i"),
+    ("scia", "ʃ a"),
+    ("scio", "ʃ o"),
+    ("sciu", "ʃ u"),
+    ("t", "t"),
+    ("u", "u"),
+    ("ù", "u"),
+    ("v", "v"),
+    ("z", "ts"),
+];
+
+fn g2p_table(language: &str) -> Option<G2pTable> {
+    match primary_language(language).as_str() {
+        "es" => Some(SPANISH),
+        "it" => Some(ITALIAN),
+        _ => None,
+    }
+}
+
+/// Occurrences of each unit of one kind (characters, phonemes or diphones)
+/// across the script.
+#[derive(Debug, Serialize)]
+pub struct CoverageStats {
+    /// Total occurrences of all units.
+    pub total: usize,
+    pub counts: BTreeMap<String, usize>,
+    /// Units of the language's inventory that never occur. Empty when the
+    /// inventory is unknown.
+    pub missing: Vec<String>,
+    /// Share of the inventory that occurs at least once.
+    pub coverage_percent: Option<f32>,
+}
+
+impl CoverageStats {
+    fn new(counts: BTreeMap<String, usize>, inventory: Option<&BTreeSet<String>>) -> Self {
+        let (missing, coverage_percent) = match inventory {
+            Some(inventory) if !inventory.is_empty() => {
+                let missing: Vec<String> = inventory
+                    .iter()
+                    .filter(|unit| !counts.contains_key(*unit))
+                    .cloned()
+                    .collect();
+                let covered = inventory.len() - missing.len();
+                let percent = covered as f32 * 100.0 / inventory.len() as f32;
+                (missing, Some(percent))
+            }
+            _ => (Vec::new(), None),
+        };
+        Self {
+            total: counts.values().sum(),
+            counts,
+            missing,
+            coverage_percent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverageReport {
+    pub language: String,
+    pub characters: CoverageStats,
+    /// `None` when there is no grapheme-to-phoneme table for the language.
+    pub phonemes: Option<CoverageStats>,
+    /// Pairs of adjacent phonemes within a word, written `a-b`.
+    pub diphones: Option<CoverageStats>,
+    /// Characters the language's table has no rule for, and so were left
+    /// out of the phoneme counts.
+    pub unmapped_characters: BTreeMap<String, usize>,
+}
+
+/// Converts a lowercase word to phonemes with a longest-match scan,
+/// reporting characters without a rule to `unmapped`.
+fn word_phonemes(
+    word: &str,
+    table: G2pTable,
+    unmapped: &mut BTreeMap<String, usize>,
+) -> Vec<&'static str> {
+    let mut phonemes = Vec::new();
+    let mut rest = word;
+    while let Some(c) = rest.chars().next() {
+        let rule = table
+            .iter()
+            .filter(|(grapheme, _)| rest.starts_with(grapheme))
+            .max_by_key(|(grapheme, _)| grapheme.len());
+        match rule {
+            Some((grapheme, output)) => {
+                phonemes.extend(output.split_whitespace());
+                rest = &rest[grapheme.len()..];
+            }
+            None => {
+                *unmapped.entry(c.to_string()).or_insert(0) += 1;
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    phonemes
+}
+
+fn analyze(sentences: &[Sentence], language: &str) -> CoverageReport {
+    let table = g2p_table(language);
+    let mut characters: BTreeMap<String, usize> = BTreeMap::new();
+    let mut phonemes: BTreeMap<String, usize> = BTreeMap::new();
+    let mut diphones: BTreeMap<String, usize> = BTreeMap::new();
+    let mut unmapped: BTreeMap<String, usize> = BTreeMap::new();
+
+    for sentence in sentences {
+        let text = normalize_text(&sentence.text);
+        for word in text.split_whitespace() {
+            for c in word.chars() {
+                *characters.entry(c.to_string()).or_insert(0) += 1;
+            }
+            let Some(table) = table else {
+                continue;
+            };
+            let word_phonemes = word_phonemes(word, table, &mut unmapped);
+            for phoneme in &word_phonemes {
+                *phonemes.entry(phoneme.to_string()).or_insert(0) += 1;
+            }
+            for pair in word_phonemes.windows(2) {
+                *diphones
+                    .entry(format!("{}-{}", pair[0], pair[1]))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    let (phoneme_stats, diphone_stats) = match table {
+        Some(table) => {
+            let inventory: BTreeSet<String> = table
+                .iter()
+                .flat_map(|(_, output)| output.split_whitespace())
+                .map(str::to_string)
+                .collect();
+            let diphone_inventory: BTreeSet<String> = inventory
+                .iter()
+                .flat_map(|a| inventory.iter().map(move |b| format!("{}-{}", a, b)))
+                .collect();
+            (
+                Some(CoverageStats::new(phonemes, Some(&inventory))),
+                Some(CoverageStats::new(diphones, Some(&diphone_inventory))),
+            )
+        }
+        None => (None, None),
+    };
+
+    CoverageReport {
+        language: language.to_string(),
+        characters: CoverageStats::new(characters, None),
+        phonemes: phoneme_stats,
+        diphones: diphone_stats,
+        unmapped_characters: unmapped,
+    }
+}
+
+/// Reports how well the sentences cover the characters, phonemes and
+/// diphones of `language`, so a script can be balanced before recording.
+/// Phoneme statistics are only available for languages with a
+/// grapheme-to-phoneme table.
+#[tauri::command]
+pub fn analyze_script_coverage(sentences: Vec<Sentence>, language: String) -> CoverageReport {
+    analyze(&sentences, &language)
+}
@@ -0,0 +1,147 @@
+use crate::audio::sidecar_path;
+use crate::errors::RecordrError;
+use crate::jobs::run_blocking;
+use crate::models::{Project, Sentence};
+use crate::storage;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Width of each bucket of the take duration histogram.
+const HISTOGRAM_BUCKET_MS: u64 = 1000;
+/// Number of takes listed as recent activity.
+const RECENT_ACTIVITY_LIMIT: usize = 10;
+
+#[derive(Serialize)]
+pub struct HistogramBucket {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub count: usize,
+}
+
+#[derive(Serialize)]
+pub struct RecentTake {
+    pub sentence_id: usize,
+    pub text: String,
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Everything the project statistics screen shows, gathered in one call.
+#[derive(Serialize)]
+pub struct DashboardData {
+    pub total_sentences: usize,
+    pub recorded_sentences: usize,
+    pub completion_percent: f32,
+    pub total_duration_ms: u64,
+    /// Takes recorded per local calendar day.
+    pub recorded_per_day: BTreeMap<NaiveDate, usize>,
+    pub duration_histogram: Vec<HistogramBucket>,
+    /// Number of sentences carrying each QC flag, plus clipped takes and
+    /// takes with breaths, clicks or pops, by kind.
+    pub flag_counts: BTreeMap<String, usize>,
+    pub recent_activity: Vec<RecentTake>,
+}
+
+#[derive(Deserialize)]
+struct SidecarTimestamp {
+    recorded_at: DateTime<Utc>,
+}
+
+/// When the sentence's take was recorded, taken from its sidecar if one was
+/// written and from the WAV file's modification time otherwise.
+fn recorded_at(sentence: &Sentence) -> Option<DateTime<Utc>> {
+    let path = Path::new(sentence.audio_file_path.as_ref()?);
+    let from_sidecar = storage::read(&sidecar_path(path))
+        .ok()
+        .and_then(|json| serde_json::from_slice::<SidecarTimestamp>(&json).ok())
+        .map(|sidecar| sidecar.recorded_at);
+    from_sidecar.or_else(|| {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        Some(DateTime::<Utc>::from(modified))
+    })
+}
+
+fn duration_histogram(durations: &[u64]) -> Vec<HistogramBucket> {
+    let Some(&longest) = durations.iter().max() else {
+        return Vec::new();
+    };
+    let mut buckets: Vec<HistogramBucket> = (0..=longest / HISTOGRAM_BUCKET_MS)
+        .map(|i| HistogramBucket {
+            start_ms: i * HISTOGRAM_BUCKET_MS,
+            end_ms: (i + 1) * HISTOGRAM_BUCKET_MS,
+            count: 0,
+        })
+        .collect();
+    for duration in durations {
+        buckets[(duration / HISTOGRAM_BUCKET_MS) as usize].count += 1;
+    }
+    buckets
+}
+
+pub(crate) fn dashboard_data(project: &Project) -> DashboardData {
+    let recorded: Vec<&Sentence> = project.sentences.iter().filter(|s| s.recorded).collect();
+    let durations: Vec<u64> = recorded
+        .iter()
+        .filter_map(|s| s.stats.as_ref().map(|stats| stats.duration_ms))
+        .collect();
+
+    let mut flag_counts: BTreeMap<String, usize> = BTreeMap::new();
+    for sentence in &recorded {
+        for flag in &sentence.qc_flags {
+            *flag_counts.entry(flag.kind().to_string()).or_insert(0) += 1;
+        }
+        let Some(stats) = &sentence.stats else {
+            continue;
+        };
+        if stats.clipped_samples > 0 {
+            *flag_counts.entry("clipping".to_string()).or_insert(0) += 1;
+        }
+        // Takes are counted once for each kind of transient found in them.
+        let kinds: BTreeSet<&str> = stats.transients.iter().map(|t| t.kind.name()).collect();
+        for kind in kinds {
+            *flag_counts.entry(kind.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    let mut takes: Vec<RecentTake> = recorded
+        .iter()
+        .filter_map(|s| {
+            Some(RecentTake {
+                sentence_id: s.id,
+                text: s.text.clone(),
+                recorded_at: recorded_at(s)?,
+            })
+        })
+        .collect();
+    let mut recorded_per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for take in &takes {
+        let day = take.recorded_at.with_timezone(&Local).date_naive();
+        *recorded_per_day.entry(day).or_insert(0) += 1;
+    }
+    takes.sort_by(|a, b| b.recorded_at.cmp(&a.recorded_at));
+    takes.truncate(RECENT_ACTIVITY_LIMIT);
+
+    let total_sentences = project.sentences.len();
+    DashboardData {
+        total_sentences,
+        recorded_sentences: recorded.len(),
+        completion_percent: if total_sentences == 0 {
+            0.0
+        } else {
+            recorded.len() as f32 * 100.0 / total_sentences as f32
+        },
+        total_duration_ms: durations.iter().sum(),
+        recorded_per_day,
+        duration_histogram: duration_histogram(&durations),
+        flag_counts,
+        recent_activity: takes,
+    }
+}
+
+/// Aggregates the project statistics shown on the dashboard.
+#[tauri::command]
+pub async fn get_dashboard_data(project: Project) -> Result<DashboardData, RecordrError> {
+    run_blocking(move || Ok(dashboard_data(&project))).await
+}
@@ -0,0 +1,29 @@
+use super::{write_file, DatasetEntry, DatasetExporter};
+use crate::errors::RecordrError;
+use std::path::{Path, PathBuf};
+
+/// Coqui TTS's `coqui` formatter: a `metadata.csv` with an
+/// `audio_file|text|speaker_name` header, which also trains multi-speaker
+/// models.
+pub struct CoquiExporter;
+
+impl DatasetExporter for CoquiExporter {
+    fn name(&self) -> &'static str {
+        "coqui"
+    }
+
+    fn write_manifest(
+        &self,
+        entries: &[DatasetEntry],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>, RecordrError> {
+        let mut contents = String::from("audio_file|text|speaker_name\n");
+        for entry in entries {
+            contents.push_str(&format!(
+                "{}|{}|{}\n",
+                entry.audio_path, entry.text, entry.speaker
+            ));
+        }
+        Ok(vec![write_file(output_dir, "metadata.csv", contents)?])
+    }
+}
@@ -0,0 +1,49 @@
+use super::{write_file, DatasetEntry, DatasetExporter};
+use crate::errors::RecordrError;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// ESPnet's Kaldi-style data directory: `wav.scp`, `text`, `utt2spk` and
+/// `spk2utt`, each sorted by utterance ID as Kaldi's tools require. The
+/// IDs start with the speaker, so sorting by either agrees.
+pub struct EspnetExporter;
+
+impl DatasetExporter for EspnetExporter {
+    fn name(&self) -> &'static str {
+        "espnet"
+    }
+
+    fn write_manifest(
+        &self,
+        entries: &[DatasetEntry],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>, RecordrError> {
+        let mut entries: Vec<_> = entries.iter().collect();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut wav_scp = String::new();
+        let mut text = String::new();
+        let mut utt2spk = String::new();
+        let mut speakers: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for entry in &entries {
+            // Paths in `wav.scp` are read relative to wherever the recipe
+            // runs, so they are written out in full.
+            let audio_path = output_dir.join(&entry.audio_path);
+            wav_scp.push_str(&format!("{} {}\n", entry.id, audio_path.display()));
+            text.push_str(&format!("{} {}\n", entry.id, entry.text));
+            utt2spk.push_str(&format!("{} {}\n", entry.id, entry.speaker));
+            speakers.entry(&entry.speaker).or_default().push(&entry.id);
+        }
+        let spk2utt: String = speakers
+            .iter()
+            .map(|(speaker, ids)| format!("{} {}\n", speaker, ids.join(" ")))
+            .collect();
+
+        Ok(vec![
+            write_file(output_dir, "wav.scp", wav_scp)?,
+            write_file(output_dir, "text", text)?,
+            write_file(output_dir, "utt2spk", utt2spk)?,
+            write_file(output_dir, "spk2utt", spk2utt)?,
+        ])
+    }
+}
@@ -0,0 +1,25 @@
+use super::{write_file, DatasetEntry, DatasetExporter};
+use crate::errors::RecordrError;
+use std::path::{Path, PathBuf};
+
+/// LJSpeech's `metadata.csv`: `id|text|normalized text` without a header,
+/// the files being `wavs/<id>.wav`. The text is given as normalized too.
+pub struct LjSpeechExporter;
+
+impl DatasetExporter for LjSpeechExporter {
+    fn name(&self) -> &'static str {
+        "ljspeech"
+    }
+
+    fn write_manifest(
+        &self,
+        entries: &[DatasetEntry],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>, RecordrError> {
+        let contents: String = entries
+            .iter()
+            .map(|entry| format!("{}|{}|{}\n", entry.id, entry.text, entry.text))
+            .collect();
+        Ok(vec![write_file(output_dir, "metadata.csv", contents)?])
+    }
+}
@@ -0,0 +1,228 @@
+mod coqui;
+mod espnet;
+mod ljspeech;
+mod piper;
+
+use crate::audio::{ProgressEvents, WindowEvents};
+use crate::errors::RecordrError;
+use crate::jobs::{run_batch, run_job, CancellationToken, JobRegistry};
+use crate::models::{Project, Sentence};
+use crate::storage;
+use coqui::CoquiExporter;
+use espnet::EspnetExporter;
+use hound::WavReader;
+use ljspeech::LjSpeechExporter;
+use log::error;
+use piper::PiperExporter;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+/// Folder of the dataset the audio is copied into.
+const AUDIO_DIRECTORY: &str = "wavs";
+
+/// One recorded take as it appears in a dataset.
+pub struct DatasetEntry {
+    /// Utterance ID, also the stem of the exported file: the speaker
+    /// followed by the sentence ID, which stays the same when the script
+    /// is edited.
+    pub id: String,
+    /// Exported file, relative to the dataset directory with `/`
+    /// separators.
+    pub audio_path: String,
+    /// Prompt on a single line, without the characters the manifests use as
+    /// separators.
+    pub text: String,
+    pub speaker: String,
+    pub duration_ms: Option<u64>,
+}
+
+/// Writes the manifest one training toolchain expects for the exported
+/// takes, which are already in the dataset directory.
+pub trait DatasetExporter: Sync {
+    /// Name that selects the exporter, e.g. `ljspeech`.
+    fn name(&self) -> &'static str;
+    /// Writes the manifest files into `output_dir`, returning their paths.
+    fn write_manifest(
+        &self,
+        entries: &[DatasetEntry],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>, RecordrError>;
+}
+
+static EXPORTERS: [&dyn DatasetExporter; 4] = [
+    &LjSpeechExporter,
+    &CoquiExporter,
+    &PiperExporter,
+    &EspnetExporter,
+];
+
+fn find_exporter(format: &str) -> Result<&'static dyn DatasetExporter, RecordrError> {
+    EXPORTERS
+        .iter()
+        .find(|exporter| exporter.name().eq_ignore_ascii_case(format))
+        .copied()
+        .ok_or_else(|| RecordrError::invalid_input("Unknown dataset format").with_context(format))
+}
+
+/// Writes one manifest file into `output_dir`.
+fn write_file(output_dir: &Path, name: &str, contents: String) -> Result<PathBuf, RecordrError> {
+    let path = output_dir.join(name);
+    fs::write(&path, contents)
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    Ok(path)
+}
+
+/// Keeps letters, digits, `-` and `_`, which every toolchain accepts in
+/// speaker and utterance IDs.
+fn identifier(name: &str) -> String {
+    let id: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.is_empty() {
+        "speaker".to_string()
+    } else {
+        id
+    }
+}
+
+/// Puts a prompt on one line and drops the `|` that delimits the CSV
+/// manifests.
+fn manifest_text(text: &str) -> String {
+    text.replace('|', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Length of a take, from its measurements or else from its WAV header.
+fn duration_ms(sentence: &Sentence, data: &[u8]) -> Option<u64> {
+    if let Some(stats) = &sentence.stats {
+        return Some(stats.duration_ms);
+    }
+    let reader = WavReader::new(Cursor::new(data)).ok()?;
+    let spec = reader.spec();
+    Some(u64::from(reader.duration()) * 1000 / u64::from(spec.sample_rate.max(1)))
+}
+
+/**
+ * Copies every recorded take into the `wavs` folder of `output_dir`, named
+ * by utterance ID, and writes the manifest of `format` beside it. The
+ * speaker defaults to the project name. Takes of encrypted projects are
+ * exported decrypted. Takes are copied several at once, and a
+ * `dataset-export-progress` event is emitted as each is done; failed takes
+ * are reported and left out of the manifest but do not abort the export.
+ * The manifest lists the takes in script order. Returns the paths of the
+ * manifest files.
+ */
+pub(crate) fn write_dataset(
+    project: &Project,
+    output_dir: &Path,
+    format: &str,
+    speaker: Option<&str>,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<Vec<PathBuf>, RecordrError> {
+    let exporter = find_exporter(format)?;
+    let speaker = identifier(speaker.unwrap_or(&project.metadata.name));
+    let audio_dir = output_dir.join(AUDIO_DIRECTORY);
+    fs::create_dir_all(&audio_dir)
+        .map_err(|e| RecordrError::from(e).with_context(audio_dir.display().to_string()))?;
+
+    let takes: Vec<_> = project
+        .sentences
+        .iter()
+        .filter(|s| s.recorded)
+        .filter_map(|s| Some((s, s.audio_file_path.as_ref()?)))
+        .map(|(sentence, path)| (sentence, path, format!("{}_{:05}", speaker, sentence.id)))
+        .collect();
+    let mut entries: Vec<Option<DatasetEntry>> = takes.iter().map(|_| None).collect();
+
+    run_batch(
+        &takes,
+        cancel,
+        |(sentence, path, id)| {
+            let data = storage::read(Path::new(path))?;
+            let output = audio_dir.join(format!("{}.wav", id));
+            fs::write(&output, &data)
+                .map_err(|e| RecordrError::from(e).with_context(output.display().to_string()))?;
+            Ok(duration_ms(sentence, &data))
+        },
+        |index, result, progress| {
+            let (sentence, path, id) = &takes[index];
+            let error = match result {
+                Ok(duration_ms) => {
+                    entries[index] = Some(DatasetEntry {
+                        id: id.clone(),
+                        audio_path: format!("{}/{}.wav", AUDIO_DIRECTORY, id),
+                        text: manifest_text(&sentence.text),
+                        speaker: speaker.clone(),
+                        duration_ms,
+                    });
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to export {}: {}", path, e);
+                    Some(e.to_string())
+                }
+            };
+
+            events.notify(
+                "dataset-export-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": sentence.id,
+                    "audioFilePath": path,
+                    "error": error,
+                }),
+            );
+        },
+    );
+    cancel.check()?;
+
+    // The manifest follows the script, whatever order the takes were
+    // copied in.
+    let entries: Vec<_> = entries.into_iter().flatten().collect();
+    exporter.write_manifest(&entries, output_dir)
+}
+
+/// Exports the recorded takes as a training dataset in `format`:
+/// `ljspeech`, `coqui`, `piper` or `espnet`. Passing a `job_id` allows the
+/// export to be cancelled with `cancel_job`.
+#[tauri::command]
+pub async fn export_dataset(
+    project: Project,
+    output_dir: String,
+    format: String,
+    speaker: Option<String>,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<Vec<String>, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        let manifests = write_dataset(
+            &project,
+            Path::new(&output_dir),
+            &format,
+            speaker.as_deref(),
+            &WindowEvents::new(window),
+            &cancel,
+        )?;
+        Ok(manifests
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect())
+    })
+    .await
+}
@@ -0,0 +1,35 @@
+use super::{write_file, DatasetEntry, DatasetExporter};
+use crate::errors::RecordrError;
+use std::path::{Path, PathBuf};
+
+/// Piper's LJSpeech-style `metadata.csv` without a header, as read by its
+/// preprocessing step: `id|text`, or `id|speaker|text` when the entries
+/// come from more than one speaker. The files are `wavs/<id>.wav`.
+pub struct PiperExporter;
+
+impl DatasetExporter for PiperExporter {
+    fn name(&self) -> &'static str {
+        "piper"
+    }
+
+    fn write_manifest(
+        &self,
+        entries: &[DatasetEntry],
+        output_dir: &Path,
+    ) -> Result<Vec<PathBuf>, RecordrError> {
+        let multi_speaker = entries
+            .iter()
+            .any(|entry| entry.speaker != entries[0].speaker);
+        let contents: String = entries
+            .iter()
+            .map(|entry| {
+                if multi_speaker {
+                    format!("{}|{}|{}\n", entry.id, entry.speaker, entry.text)
+                } else {
+                    format!("{}|{}\n", entry.id, entry.text)
+                }
+            })
+            .collect();
+        Ok(vec![write_file(output_dir, "metadata.csv", contents)?])
+    }
+}
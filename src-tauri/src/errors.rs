@@ -0,0 +1,184 @@
+use crate::audio::RecorderError;
+use serde::Serialize;
+use std::fmt;
+
+/// Broad category of an error, used by the frontend to choose how to present
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NoInputDevice,
+    DeviceError,
+    DiskFull,
+    NotFound,
+    PermissionDenied,
+    InvalidState,
+    InvalidInput,
+    UnsupportedFormat,
+    FileTooLarge,
+    Io,
+    Parse,
+    Audio,
+    Cancelled,
+    /// The file belongs to an encrypted project that has not been unlocked.
+    KeyRequired,
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether the user can fix the problem and retry without restarting.
+    fn is_recoverable(self) -> bool {
+        !matches!(self, ErrorKind::Other)
+    }
+}
+
+/// Error returned by every command, serialized to the frontend as
+/// `{ kind, message, context, recoverable }`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordrError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Additional detail, such as the file or sentence involved.
+    pub context: Option<String>,
+    pub recoverable: bool,
+}
+
+impl RecordrError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            context: None,
+            recoverable: kind.is_recoverable(),
+        }
+    }
+
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn invalid_state(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidState, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidInput, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+}
+
+impl fmt::Display for RecordrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.context {
+            Some(context) => write!(f, "{} ({})", self.message, context),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for RecordrError {}
+
+/// Raw OS error codes for a full disk or an exceeded quota (`ENOSPC`,
+/// `EDQUOT`).
+#[cfg(unix)]
+const DISK_FULL_ERRORS: &[i32] = &[28, if cfg!(target_os = "linux") { 122 } else { 69 }];
+
+/// Raw OS error codes for a full disk (`ERROR_HANDLE_DISK_FULL`,
+/// `ERROR_DISK_FULL`).
+#[cfg(windows)]
+const DISK_FULL_ERRORS: &[i32] = &[39, 112];
+
+#[cfg(not(any(unix, windows)))]
+const DISK_FULL_ERRORS: &[i32] = &[];
+
+fn is_disk_full(error: &std::io::Error) -> bool {
+    error
+        .raw_os_error()
+        .is_some_and(|code| DISK_FULL_ERRORS.contains(&code))
+}
+
+impl From<std::io::Error> for RecordrError {
+    fn from(error: std::io::Error) -> Self {
+        let kind = if is_disk_full(&error) {
+            ErrorKind::DiskFull
+        } else {
+            match error.kind() {
+                std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+                std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+                _ => ErrorKind::Io,
+            }
+        };
+        RecordrError::new(kind, error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RecordrError {
+    fn from(error: serde_json::Error) -> Self {
+        RecordrError::new(ErrorKind::Parse, error.to_string())
+    }
+}
+
+impl From<csv::Error> for RecordrError {
+    fn from(error: csv::Error) -> Self {
+        RecordrError::new(ErrorKind::Parse, error.to_string())
+    }
+}
+
+impl From<hound::Error> for RecordrError {
+    fn from(error: hound::Error) -> Self {
+        match error {
+            hound::Error::IoError(e) => e.into(),
+            hound::Error::Unsupported => {
+                RecordrError::new(ErrorKind::UnsupportedFormat, "Unsupported audio format")
+            }
+            e => RecordrError::new(ErrorKind::Audio, e.to_string()),
+        }
+    }
+}
+
+impl From<RecorderError> for RecordrError {
+    fn from(error: RecorderError) -> Self {
+        let message = error.to_string();
+        match error {
+            RecorderError::RecordingPaused | RecorderError::RecordingStopped => {
+                RecordrError::new(ErrorKind::InvalidState, message)
+            }
+            RecorderError::Cancelled => RecordrError::new(ErrorKind::Cancelled, message),
+            RecorderError::IoError(e) => e.into(),
+            RecorderError::HoundError(e) => e.into(),
+            RecorderError::CpalStreamError(cpal::StreamError::DeviceNotAvailable)
+            | RecorderError::CpalBuildStreamError(cpal::BuildStreamError::DeviceNotAvailable)
+            | RecorderError::CpalPlayStreamError(cpal::PlayStreamError::DeviceNotAvailable)
+            | RecorderError::CpalDefaultStreamConfigError(
+                cpal::DefaultStreamConfigError::DeviceNotAvailable,
+            ) => RecordrError::new(ErrorKind::NoInputDevice, message),
+            RecorderError::CpalStreamError(_)
+            | RecorderError::CpalBuildStreamError(_)
+            | RecorderError::CpalPlayStreamError(_)
+            | RecorderError::CpalDefaultStreamConfigError(_)
+            | RecorderError::StreamPlayError(_) => {
+                RecordrError::new(ErrorKind::DeviceError, message)
+            }
+            RecorderError::TakeTooShort(_)
+            | RecorderError::EmptyTake(_)
+            | RecorderError::TakeTimedOut => RecordrError::new(ErrorKind::Audio, message),
+            RecorderError::Other(_) => RecordrError::new(ErrorKind::Other, message),
+        }
+    }
+}
+
+impl From<String> for RecordrError {
+    fn from(message: String) -> Self {
+        RecordrError::new(ErrorKind::Other, message)
+    }
+}
+
+impl From<&str> for RecordrError {
+    fn from(message: &str) -> Self {
+        RecordrError::new(ErrorKind::Other, message)
+    }
+}
@@ -1,16 +1,116 @@
-use crate::models::{Project, Sentence};
-use csv::ReaderBuilder;
-use serde::{Deserialize, Serialize};
+use crate::approved::refresh_approved_tree;
+use crate::audio::{move_takes, rename_takes, take_file_stem};
+use crate::backups::back_up_project_file;
+use crate::errors::{ErrorKind, RecordrError};
+use crate::importers::{import_file, ImportOptions};
+use crate::jobs::run_blocking;
+use crate::migrations::migrate_project;
+use crate::models::{Project, ProjectLayout, Sentence};
+use crate::paths::{normalize, relative_to, same_path};
+use crate::qc::{find_duplicates, DuplicateGroup};
+use crate::settings::{AppSettings, SettingsState};
+use crate::storage;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use csv::WriterBuilder;
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
-use tauri::api::path::app_local_data_dir;
+use tauri::{AppHandle, State};
+use tauri_plugin_fs::FsExt;
+
+/// File name of the project JSON for projects that are not stored under
+/// their name.
+pub const PROJECT_FILE_NAME: &str = "project.recordr.json";
 
 #[derive(Serialize, Deserialize)]
 pub struct RecentProject {
     pub path: String,
     pub name: String,
-    pub last_accessed: String, // ISO timestamp
+    #[serde(deserialize_with = "deserialize_timestamp")]
+    pub last_accessed: DateTime<Utc>,
+    /// Set when the project JSON no longer exists on disk.
+    #[serde(default)]
+    pub missing: bool,
+    /// Pinned projects are listed first and never pushed out of the list.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Parses timestamps written by older versions, which stored whatever string
+/// the frontend produced. Unparseable values sort as the oldest entries.
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    Ok(parse_timestamp(&value).unwrap_or(DateTime::<Utc>::UNIX_EPOCH))
+}
+
+pub(crate) fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(timestamp) = DateTime::parse_from_rfc2822(value) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+/// Formats a timestamp in the given UTC offset (in minutes), or in the
+/// system's local time zone when no offset is given. `format` uses chrono's
+/// strftime syntax and defaults to `%Y-%m-%d %H:%M`. A format chrono cannot
+/// parse is an `invalid_input` error rather than a panic.
+pub fn format_timestamp_in_zone(
+    timestamp: &DateTime<Utc>,
+    utc_offset_minutes: Option<i32>,
+    format: Option<&str>,
+) -> Result<String, RecordrError> {
+    let format = format.unwrap_or("%Y-%m-%d %H:%M");
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(RecordrError::invalid_input(format!(
+            "Invalid timestamp format: {}",
+            format
+        )));
+    }
+    match utc_offset_minutes {
+        Some(minutes) => {
+            let offset = minutes
+                .checked_mul(60)
+                .and_then(FixedOffset::east_opt)
+                .ok_or_else(|| {
+                    RecordrError::invalid_input(format!("Invalid UTC offset: {} minutes", minutes))
+                })?;
+            Ok(timestamp
+                .with_timezone(&offset)
+                .format_with_items(items.iter())
+                .to_string())
+        }
+        None => Ok(timestamp
+            .with_timezone(&Local)
+            .format_with_items(items.iter())
+            .to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn format_timestamp(
+    timestamp: DateTime<Utc>,
+    utc_offset_minutes: Option<i32>,
+    format: Option<String>,
+) -> Result<String, RecordrError> {
+    format_timestamp_in_zone(&timestamp, utc_offset_minutes, format.as_deref())
 }
 
 #[derive(Serialize, Deserialize)]
@@ -19,21 +119,40 @@ pub struct RecentProjectsData {
     pub recent_projects: Vec<RecentProject>,
 }
 
+/// Sentences read from an import file, along with any duplicated prompts in
+/// the file identified by row number.
+#[derive(Serialize)]
+pub struct ImportedSentences {
+    pub sentences: Vec<Sentence>,
+    pub duplicates: Vec<DuplicateGroup>,
+}
+
+/// Imports sentences from a prompt file. The format is the one named by
+/// `format`, else the one for the file's extension, else sniffed from its
+/// contents; `options` maps the file's columns or keys onto sentences.
 #[tauri::command]
-pub async fn import_sentences(file_path: &str, project_dir: &str) -> Result<Vec<Sentence>, String> {
+pub async fn import_sentences(
+    file_path: &str,
+    project_dir: &str,
+    format: Option<String>,
+    options: Option<ImportOptions>,
+) -> Result<ImportedSentences, RecordrError> {
     // 1. Read the file contents
-    let file_contents =
-        fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
-
-    // 2. Parse the sentences based on file extension
-    let sentences = match Path::new(file_path).extension().and_then(OsStr::to_str) {
-        Some("txt") => parse_txt(&file_contents),
-        Some("csv") => parse_delimited(&file_contents, b',')?,
-        Some("tsv") => parse_delimited(&file_contents, b'\t')?,
-        _ => return Err("Unsupported file format".into()),
-    };
+    let data = fs::read(file_path).map_err(|e| RecordrError::from(e).with_context(file_path))?;
+
+    // 2. Parse the sentences with the importer for the format. Their IDs are
+    // still the rows they came from.
+    let sentences = import_file(
+        Path::new(file_path),
+        &data,
+        format.as_deref(),
+        &options.unwrap_or_default(),
+    )?;
 
-    // 3. Construct the full audio file path for each sentence
+    // 3. Report prompts that would share an audio file
+    let duplicates = find_duplicates(sentences.iter().map(|s| (s.id, s.text.as_str())));
+
+    // 4. Construct the full audio file path for each sentence
     let sentences_with_paths: Vec<Sentence> = sentences
         .into_iter()
         .enumerate()
@@ -45,137 +164,728 @@ pub async fn import_sentences(file_path: &str, project_dir: &str) -> Result<Vec<
                 .to_string();
             Sentence {
                 id: (index + 1),
-                text: sentence.text,
-                recorded: false,
                 audio_file_path: Some(audio_file_path),
+                ..sentence
             }
         })
         .collect();
 
-    Ok(sentences_with_paths)
+    Ok(ImportedSentences {
+        sentences: sentences_with_paths,
+        duplicates,
+    })
 }
 
-fn parse_txt(file_contents: &str) -> Vec<Sentence> {
-    file_contents
-        .lines()
-        .enumerate()
-        .map(|(index, line)| Sentence {
-            id: (index + 1),
-            text: line.trim().to_string(),
-            recorded: false,
-            audio_file_path: None,
-        })
-        .collect()
+/// Writes the project's sentences to a CSV or TSV file, chosen by extension,
+/// with their review status, notes, tags and character. The file can be
+/// imported again, keeping the notes, tags and character.
+#[tauri::command]
+pub async fn export_sentences(project: Project, file_path: String) -> Result<(), RecordrError> {
+    run_blocking(move || {
+        let delimiter = match Path::new(&file_path).extension().and_then(OsStr::to_str) {
+            Some("csv") => b',',
+            Some("tsv") => b'\t',
+            _ => {
+                return Err(RecordrError::new(
+                    ErrorKind::UnsupportedFormat,
+                    "Unsupported file format",
+                )
+                .with_context(file_path))
+            }
+        };
+        let mut writer = WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(Vec::new());
+        writer.write_record([
+            "text",
+            "id",
+            "status",
+            "notes",
+            "tags",
+            "character",
+            "audio_file_path",
+        ])?;
+        for sentence in &project.sentences {
+            writer.write_record([
+                sentence.text.as_str(),
+                &sentence.id.to_string(),
+                &sentence.status.to_string(),
+                sentence.notes.as_deref().unwrap_or_default(),
+                &sentence.tags.join("; "),
+                sentence.character.as_deref().unwrap_or_default(),
+                sentence.audio_file_path.as_deref().unwrap_or_default(),
+            ])?;
+        }
+        let data = writer
+            .into_inner()
+            .map_err(|e| RecordrError::new(ErrorKind::Io, e.to_string()))?;
+        fs::write(&file_path, data).map_err(|e| RecordrError::from(e).with_context(file_path))
+    })
+    .await
 }
 
-// Function to parse both CSV and TSV with a configurable delimiter
-fn parse_delimited(file_contents: &str, delimiter: u8) -> Result<Vec<Sentence>, String> {
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(delimiter)
-        .from_reader(file_contents.as_bytes());
-    let mut sentences = Vec::new();
+#[tauri::command]
+pub async fn create_new_project(
+    app: AppHandle,
+    parent_dir: Option<String>,
+    project: Project,
+    settings: State<'_, SettingsState>,
+) -> Result<Project, RecordrError> {
+    let settings = settings.lock().unwrap().clone();
+    run_blocking(move || {
+        let project = create_project(parent_dir, project, &settings)?;
+        allow_project_directory(&app, &project);
+        Ok(project)
+    })
+    .await
+}
 
-    for (index, result) in rdr.records().enumerate() {
-        let record = result.map_err(|e| format!("Failed to parse: {}", e))?;
-        if let Some(text) = record.get(0) {
-            sentences.push(Sentence {
-                id: (index + 1),
-                text: text.to_string(),
-                recorded: false,
-                audio_file_path: None,
-            });
+/// Creates the project's directory in `parent_dir`, or in the configured
+/// recordings root, and writes the project into it with the default
+/// recording settings.
+pub fn create_project(
+    parent_dir: Option<String>,
+    mut project: Project,
+    settings: &AppSettings,
+) -> Result<Project, RecordrError> {
+    let parent_dir = parent_dir
+        .or_else(|| settings.recordings_root.clone())
+        .ok_or_else(|| RecordrError::invalid_input("No project location given or configured"))?;
+    project.metadata.recording_settings = settings.recording_defaults();
+    project.metadata.file_name = settings
+        .stable_project_file
+        .then(|| PROJECT_FILE_NAME.to_string());
+    if project.metadata.layout == ProjectLayout::default() {
+        project.metadata.layout.recordings = ProjectLayout::DEFAULT_RECORDINGS.to_string();
+    }
+
+    let project_path = normalize(&Path::new(&parent_dir).join(&project.metadata.name));
+    fs::create_dir_all(&project_path)?;
+    project.metadata.directory = project_path.to_string_lossy().to_string();
+    write_project(&project)?;
+    Ok(project)
+}
+
+/// Stores the takes inside the project directory by their path relative to
+/// it, so the project still finds them once its folder is moved or shared.
+/// Takes kept elsewhere stay absolute.
+fn relativize_audio_paths(project: &mut Project) {
+    let directory = PathBuf::from(&project.metadata.directory);
+    for sentence in project.sentences.iter_mut() {
+        let relative = sentence
+            .audio_file_path
+            .as_deref()
+            .and_then(|path| relative_to(Path::new(path), &directory));
+        if relative.is_some() {
+            sentence.audio_file_path = relative;
         }
     }
+}
 
-    Ok(sentences)
+/// Resolves take paths stored relative to the project directory.
+fn resolve_audio_paths(project: &mut Project) {
+    let directory = PathBuf::from(&project.metadata.directory);
+    for sentence in project.sentences.iter_mut() {
+        if let Some(path) = sentence.audio_file_path.as_mut() {
+            if !path.is_empty() && Path::new(path.as_str()).is_relative() {
+                *path = normalize(&directory.join(&*path))
+                    .to_string_lossy()
+                    .to_string();
+            }
+        }
+    }
+}
+
+/// Parses the project file at `file_path`, upgrading it from older schema
+/// versions. The project directory is taken to be wherever the file now
+/// is, not where it was last saved.
+fn parse_project(content: &[u8], file_path: &Path) -> Result<Project, RecordrError> {
+    let value = migrate_project(serde_json::from_slice(content)?)?;
+    let mut project: Project = serde_json::from_value(value)?;
+    if let Some(directory) = normalize(file_path).parent() {
+        project.metadata.directory = directory.to_string_lossy().to_string();
+    }
+    resolve_audio_paths(&mut project);
+    Ok(project)
+}
+
+/// Reads and parses a project file. A file that no longer parses, such as
+/// one cut short by a crash, falls back to the copy kept by the last save.
+pub fn read_project(file_path: &str) -> Result<Project, RecordrError> {
+    let content = storage::read(Path::new(file_path))?;
+    match parse_project(&content, Path::new(file_path)) {
+        Err(e) if e.kind == ErrorKind::Parse => {
+            let previous = storage::previous_path(Path::new(file_path));
+            let project = storage::read(&previous)
+                .ok()
+                .and_then(|content| parse_project(&content, Path::new(file_path)).ok())
+                .ok_or_else(|| e.with_context(file_path))?;
+            warn!(
+                "Project file {} is unreadable; opened {} instead",
+                file_path,
+                previous.display()
+            );
+            Ok(project)
+        }
+        result => result,
+    }
+}
+
+/// Unlocks the project at `file_path` with the passphrase, if one is given,
+/// and reads it.
+pub fn unlock_and_read_project(
+    file_path: &str,
+    passphrase: Option<&str>,
+) -> Result<Project, RecordrError> {
+    let file_path = normalize(Path::new(file_path));
+    if let Some(passphrase) = passphrase {
+        storage::unlock(&file_path, passphrase)?;
+    }
+    read_project(&file_path.to_string_lossy())
+}
+
+/// Path of the project file within the project directory.
+pub fn project_file_path(project: &Project) -> PathBuf {
+    let metadata = &project.metadata;
+    let file_name = metadata
+        .file_name
+        .clone()
+        .unwrap_or_else(|| format!("{}.json", metadata.name));
+    Path::new(&metadata.directory).join(file_name)
+}
+
+/// Writes the project file into the project directory. Encrypted projects
+/// are only written once unlocked, so they are never saved in plain form.
+pub fn write_project(project: &Project) -> Result<(), RecordrError> {
+    let directory = Path::new(&project.metadata.directory);
+    if project.metadata.encrypted && !storage::has_key(directory) {
+        return Err(RecordrError::new(
+            ErrorKind::KeyRequired,
+            "Project is encrypted and has not been unlocked",
+        )
+        .with_context(&project.metadata.directory));
+    }
+    let file_path = project_file_path(project);
+    let mut stored = project.clone();
+    relativize_audio_paths(&mut stored);
+    let project_data = serde_json::to_string_pretty(&stored)?;
+    storage::write_checked(&file_path, project_data.as_bytes(), |written| {
+        serde_json::from_slice::<Project>(written)?;
+        Ok(())
+    })?;
+    // The project is saved either way; a stale tree is fixed by the next save.
+    if let Err(e) = refresh_approved_tree(project) {
+        warn!("Failed to refresh the approved takes: {}", e);
+    }
+    Ok(())
 }
 
+/// Opens a project. Encrypted projects need their passphrase and otherwise
+/// fail with a `key_required` error.
 #[tauri::command]
-pub fn create_new_project(parent_dir: &str, mut project: Project) -> Result<Project, String> {
-    let project_path = Path::new(parent_dir).join(&project.metadata.name);
-    fs::create_dir_all(&project_path).map_err(|e| e.to_string())?;
+pub async fn open_project(
+    app: AppHandle,
+    file_path: String,
+    passphrase: Option<String>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let project = unlock_and_read_project(&file_path, passphrase.as_deref())?;
+        allow_project_directory(&app, &project);
+        Ok(project)
+    })
+    .await
+}
 
-    project.metadata.directory = project_path.to_string_lossy().to_string();
+/// Adds the project's directory to the file system scope. The capability
+/// only grants the app's data directory up front, so the webview can reach
+/// a project's files once that project has been opened.
+pub(crate) fn allow_project_directory(app: &AppHandle, project: &Project) {
+    let directory = &project.metadata.directory;
+    if let Err(e) = app.fs_scope().allow_directory(directory, true) {
+        warn!("Failed to allow project directory {}: {}", directory, e);
+    }
+}
 
-    let json_path = project_path.join(format!("{}.json", project.metadata.name));
-    let project_data = serde_json::to_string_pretty(&project).unwrap();
-    fs::write(json_path, project_data).map_err(|e| e.to_string())?;
+/// Saves the project, first backing up the file it replaces.
+#[tauri::command]
+pub async fn save_project(
+    project: Project,
+    settings: State<'_, SettingsState>,
+) -> Result<Project, RecordrError> {
+    let keep = settings.lock().unwrap().project_backups;
+    run_blocking(move || save_with_backup(project, keep)).await
+}
 
+/// Backs up the project file, keeping the `keep` newest backups, and writes
+/// the project over it.
+pub fn save_with_backup(project: Project, keep: usize) -> Result<Project, RecordrError> {
+    back_up_project_file(&project_file_path(&project), keep)?;
+    write_project(&project)?;
     Ok(project)
 }
 
+/// Index of the sentence with `sentence_id` in the project.
+fn sentence_index(project: &Project, sentence_id: usize) -> Result<usize, RecordrError> {
+    project
+        .sentences
+        .iter()
+        .position(|s| s.id == sentence_id)
+        .ok_or_else(|| RecordrError::not_found(format!("Sentence {} not found", sentence_id)))
+}
+
+fn sentence_text(text: &str) -> Result<String, RecordrError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(RecordrError::invalid_input(
+            "Sentence text must not be empty",
+        ));
+    }
+    Ok(text.to_string())
+}
+
+/// Gives `sentence` a file stem of its own, numbered after its text's, if
+/// its takes would otherwise be named like those of another of
+/// `sentences`.
+fn assign_file_stem(sentences: &[Sentence], sentence: &mut Sentence) {
+    sentence.file_stem = None;
+    let taken: HashSet<String> = sentences
+        .iter()
+        .filter(|s| s.id != sentence.id)
+        .map(take_file_stem)
+        .collect();
+    let stem = take_file_stem(sentence);
+    if taken.contains(&stem) {
+        sentence.file_stem = (2..)
+            .map(|n| format!("{}_{}", stem, n))
+            .find(|candidate| !taken.contains(candidate));
+    }
+}
+
+/// Inserts a new sentence at `position`, counted from 0, or at the end,
+/// and saves the project. Sentence IDs are never reused or reassigned, so
+/// the new sentence is numbered after the highest ID and references to
+/// other sentences stay valid; the list order is the display order.
 #[tauri::command]
-pub fn open_project(file_path: &str) -> Result<Project, String> {
-    let content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
-    let project: Project = serde_json::from_str(&content).map_err(|e| e.to_string())?;
-    Ok(project)
+pub async fn add_sentence(
+    mut project: Project,
+    text: String,
+    position: Option<usize>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let id = project.sentences.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        let mut sentence = Sentence::new(id, sentence_text(&text)?);
+        assign_file_stem(&project.sentences, &mut sentence);
+        let position = position
+            .unwrap_or(project.sentences.len())
+            .min(project.sentences.len());
+        project.sentences.insert(position, sentence);
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
 }
 
+/// Corrects a sentence's text and saves the project. Its takes are renamed
+/// after the new text, so recorded sentences keep their audio.
 #[tauri::command]
-pub fn save_project(project: Project) -> Result<Project, String> {
-    let file_path =
-        Path::new(&project.metadata.directory).join(format!("{}.json", project.metadata.name));
-    let project_data = serde_json::to_string_pretty(&project).unwrap();
-    fs::write(file_path, project_data).map_err(|e| e.to_string())?;
+pub async fn update_sentence_text(
+    mut project: Project,
+    sentence_id: usize,
+    text: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let index = sentence_index(&project, sentence_id)?;
+        let sentence = &project.sentences[index];
+        let mut renamed = Sentence {
+            text: sentence_text(&text)?,
+            ..sentence.clone()
+        };
+        assign_file_stem(&project.sentences, &mut renamed);
+        // Takes are written next to each other, so the current take shows
+        // where to look.
+        let recordings_dir = project
+            .metadata
+            .layout
+            .recordings_path(Path::new(&project.metadata.directory));
+        let directory = sentence
+            .audio_file_path
+            .as_deref()
+            .and_then(|path| Path::new(path).parent())
+            .unwrap_or(&recordings_dir);
+        let moves = rename_takes(sentence, &renamed, directory)
+            .map_err(|e| RecordrError::from(e).with_context(sentence.text.as_str()))?;
+
+        if let Some(current) = renamed.audio_file_path.as_deref().map(Path::new) {
+            if let Some((_, to)) = moves.iter().find(|(from, _)| from == current) {
+                renamed.audio_file_path = Some(to.to_string_lossy().to_string());
+            }
+        }
+        project.sentences[index] = renamed;
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/**
+ * Moves the project's takes, with their sidecars and edit backups, into
+ * the recordings folder of `layout` and saves the project with the new
+ * layout. Takes are found next to each sentence's current take, or in the
+ * old recordings folder for sentences without one. If a move fails, the
+ * takes moved so far are saved and the error returned; running the
+ * migration again moves the rest.
+ */
+#[tauri::command]
+pub async fn migrate_project_layout(
+    mut project: Project,
+    layout: ProjectLayout,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        layout.validate().map_err(RecordrError::invalid_input)?;
+        let project_dir = PathBuf::from(&project.metadata.directory);
+        let old_dir = project.metadata.layout.recordings_path(&project_dir);
+        let new_dir = layout.recordings_path(&project_dir);
+        fs::create_dir_all(&new_dir)?;
+
+        let mut result = Ok(());
+        for sentence in project.sentences.iter_mut() {
+            let directory = sentence
+                .audio_file_path
+                .as_deref()
+                .and_then(|path| Path::new(path).parent())
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| old_dir.clone());
+            if !directory.is_dir() || same_path(&directory, &new_dir) {
+                continue;
+            }
+            let moves = match move_takes(sentence, &directory, &new_dir) {
+                Ok(moves) => moves,
+                Err(e) => {
+                    result = Err(RecordrError::from(e).with_context(sentence.text.as_str()));
+                    break;
+                }
+            };
+            if let Some(current) = sentence.audio_file_path.as_deref().map(Path::new) {
+                if let Some((_, to)) = moves.iter().find(|(from, _)| from == current) {
+                    sentence.audio_file_path = Some(to.to_string_lossy().to_string());
+                }
+            }
+        }
+        if result.is_ok() {
+            project.metadata.layout = layout;
+        }
+        write_project(&project)?;
+        result.map(|()| project)
+    })
+    .await
+}
+
+/// Removes a sentence and saves the project. Its takes are left on disk.
+#[tauri::command]
+pub async fn delete_sentence(
+    mut project: Project,
+    sentence_id: usize,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let index = sentence_index(&project, sentence_id)?;
+        project.sentences.remove(index);
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/// Puts the sentences in the order of `sentence_ids`, which must list every
+/// sentence once, and saves the project. Their IDs stay the same.
+#[tauri::command]
+pub async fn reorder_sentences(
+    mut project: Project,
+    sentence_ids: Vec<usize>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        if sentence_ids.len() != project.sentences.len() {
+            return Err(RecordrError::invalid_input(
+                "Sentence order must list every sentence once",
+            ));
+        }
+        let mut remaining = std::mem::take(&mut project.sentences);
+        for id in sentence_ids {
+            let Some(index) = remaining.iter().position(|s| s.id == id) else {
+                return Err(RecordrError::invalid_input(
+                    "Sentence order must list every sentence once",
+                )
+                .with_context(id.to_string()));
+            };
+            project.sentences.push(remaining.swap_remove(index));
+        }
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/// Renames a project and saves it. Projects stored under their name move to
+/// the new name, or to the stable file name if that is configured, and the
+/// old file is removed. The project directory keeps its name, since takes
+/// are linked by path.
+#[tauri::command]
+pub async fn rename_project(
+    project: Project,
+    name: String,
+    settings: State<'_, SettingsState>,
+) -> Result<Project, RecordrError> {
+    let stable = settings.lock().unwrap().stable_project_file;
+    run_blocking(move || rename_project_file(project, &name, stable)).await
+}
+
+/// Renames a project, writing it under its new file name before the recent
+/// projects entry is pointed there and the old file is removed, so a failure
+/// never leaves the project without a file.
+pub fn rename_project_file(
+    mut project: Project,
+    name: &str,
+    stable: bool,
+) -> Result<Project, RecordrError> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(RecordrError::invalid_input("Invalid project name").with_context(name));
+    }
+    let old_path = project_file_path(&project);
+    project.metadata.name = name.to_string();
+    if stable && project.metadata.file_name.is_none() {
+        project.metadata.file_name = Some(PROJECT_FILE_NAME.to_string());
+    }
+    let new_path = project_file_path(&project);
+    if new_path != old_path && new_path.exists() {
+        return Err(
+            RecordrError::invalid_state("A project file with that name already exists")
+                .with_context(new_path.display().to_string()),
+        );
+    }
+
+    write_project(&project)?;
+    rename_recent_project(&old_path, &new_path, name);
+    if new_path != old_path {
+        if let Err(e) = fs::remove_file(&old_path) {
+            warn!(
+                "Failed to remove old project file {}: {}",
+                old_path.display(),
+                e
+            );
+        }
+    }
     Ok(project)
 }
 
-fn get_recent_projects_path() -> PathBuf {
-    let mut path =
-        app_local_data_dir(&tauri::Config::default()).expect("Failed to get app local data dir");
+/// Returns the path of a file stored in the app's local data directory,
+/// creating the directory if necessary. The directory is `recordr` in the
+/// system's local data directory, which the capabilities' `fs:scope` grants
+/// as `$LOCALDATA/recordr`, so the two must change together.
+pub(crate) fn app_data_file_path(file_name: &str) -> PathBuf {
+    let mut path = dirs::data_local_dir().expect("Failed to get app local data dir");
     path.push("recordr");
     fs::create_dir_all(&path).expect("Failed to create recordr directory");
-    path.push("recent_projects.json");
+    path.push(file_name);
     path
 }
 
+fn get_recent_projects_path() -> PathBuf {
+    app_data_file_path("recent_projects.json")
+}
+
+/// Saves the recent projects list. A failed save is logged rather than
+/// failing the command that changed the list.
 fn save_recent_projects_data(data: &RecentProjectsData) {
-    let json = serde_json::to_string(data).unwrap();
     let path = get_recent_projects_path();
-    // The directory has already been created in get_recent_projects_path.
-    fs::write(path, json).unwrap();
+    let result = serde_json::to_string(data)
+        .map_err(RecordrError::from)
+        .and_then(|json| {
+            storage::write_checked(&path, json.as_bytes(), |written| {
+                serde_json::from_slice::<RecentProjectsData>(written)?;
+                Ok(())
+            })
+        });
+    if let Err(e) = result {
+        warn!("Failed to save recent projects: {}", e);
+    }
 }
 
-#[tauri::command]
-pub fn get_recent_projects() -> RecentProjectsData {
+/// Entries the recent projects list is cut down to, unless more are pinned.
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Orders recent projects with pinned entries first, then by most recent
+/// access. Every pinned entry is kept, and the newest unpinned ones fill
+/// the list up to `MAX_RECENT_PROJECTS` entries.
+fn sort_recent_projects(recent_projects: &mut Vec<RecentProject>) {
+    recent_projects.sort_by(|a, b| {
+        b.pinned
+            .cmp(&a.pinned)
+            .then_with(|| b.last_accessed.cmp(&a.last_accessed))
+    });
+    let pinned = recent_projects
+        .iter()
+        .filter(|project| project.pinned)
+        .count();
+    recent_projects.truncate(pinned.max(MAX_RECENT_PROJECTS));
+}
+
+/// Loads the recent projects list, falling back to the copy kept by the
+/// last save and then to an empty list.
+fn load_recent_projects_data() -> RecentProjectsData {
     let path = get_recent_projects_path();
-    if path.exists() {
-        let contents = fs::read_to_string(&path).unwrap();
-        let mut data: RecentProjectsData =
-            serde_json::from_str(&contents).unwrap_or_else(|_| RecentProjectsData {
-                app_version: "unknown".into(),
-                recent_projects: vec![],
-            });
-        data.recent_projects
-            .sort_by(|a, b| b.last_accessed.cmp(&a.last_accessed));
-        data.recent_projects.truncate(10);
-        data
-    } else {
-        RecentProjectsData {
+    [path.clone(), storage::previous_path(&path)]
+        .iter()
+        .find_map(|path| {
+            let contents = fs::read(path).ok()?;
+            serde_json::from_slice(&contents).ok()
+        })
+        .unwrap_or_else(|| RecentProjectsData {
             app_version: "unknown".into(),
             recent_projects: vec![],
+        })
+}
+
+/// Returns the recent projects, flagging entries whose project JSON no longer
+/// exists. When `prune_missing` is set, those entries are removed instead.
+#[tauri::command]
+pub fn get_recent_projects(prune_missing: Option<bool>) -> RecentProjectsData {
+    let mut data = load_recent_projects_data();
+    for project in data.recent_projects.iter_mut() {
+        project.missing = !Path::new(&project.path).exists();
+    }
+    if prune_missing.unwrap_or(false) {
+        let before = data.recent_projects.len();
+        data.recent_projects.retain(|project| !project.missing);
+        if data.recent_projects.len() != before {
+            save_recent_projects_data(&data);
         }
     }
+    sort_recent_projects(&mut data.recent_projects);
+    data
+}
+
+/// Removes a project from the recent projects list.
+#[tauri::command]
+pub fn remove_recent_project(path: String) -> RecentProjectsData {
+    let mut data = get_recent_projects(None);
+    data.recent_projects
+        .retain(|project| !same_path(Path::new(&project.path), Path::new(&path)));
+    save_recent_projects_data(&data);
+    data
+}
+
+/// Pins (or unpins) a project so it stays at the top of the recent projects
+/// list.
+#[tauri::command]
+pub fn pin_recent_project(
+    path: String,
+    pinned: Option<bool>,
+) -> Result<RecentProjectsData, RecordrError> {
+    let mut data = get_recent_projects(None);
+    let project = data
+        .recent_projects
+        .iter_mut()
+        .find(|project| same_path(Path::new(&project.path), Path::new(&path)))
+        .ok_or_else(|| {
+            RecordrError::not_found("Project is not in the recent projects list").with_context(path)
+        })?;
+    project.pinned = pinned.unwrap_or(true);
+    sort_recent_projects(&mut data.recent_projects);
+    save_recent_projects_data(&data);
+    Ok(data)
+}
+
+/// Points the recent projects entry for `old_path` at the renamed project.
+fn rename_recent_project(old_path: &Path, new_path: &Path, name: &str) {
+    let mut data = load_recent_projects_data();
+    let Some(entry) = data
+        .recent_projects
+        .iter_mut()
+        .find(|project| same_path(Path::new(&project.path), old_path))
+    else {
+        return;
+    };
+    entry.path = new_path.to_string_lossy().to_string();
+    entry.name = name.to_string();
+    save_recent_projects_data(&data);
 }
 
 #[tauri::command]
-pub fn add_recent_project(new_project: RecentProject, app_version: String) -> RecentProjectsData {
-    let mut data = get_recent_projects();
+pub fn add_recent_project(
+    mut new_project: RecentProject,
+    app_version: String,
+) -> RecentProjectsData {
+    new_project.path = normalize(Path::new(&new_project.path))
+        .to_string_lossy()
+        .to_string();
+    let mut data = get_recent_projects(None);
     // Update the top-level app version.
     data.app_version = app_version;
     if let Some(existing) = data
         .recent_projects
         .iter_mut()
-        .find(|proj| proj.path == new_project.path)
+        .find(|proj| same_path(Path::new(&proj.path), Path::new(&new_project.path)))
     {
-        existing.last_accessed = new_project.last_accessed.clone();
+        existing.last_accessed = new_project.last_accessed;
         existing.name = new_project.name.clone();
+        existing.missing = false;
     } else {
         data.recent_projects.push(new_project);
     }
-    data.recent_projects
-        .sort_by(|a, b| b.last_accessed.cmp(&a.last_accessed));
-    data.recent_projects.truncate(10);
+    sort_recent_projects(&mut data.recent_projects);
     save_recent_projects_data(&data);
     data
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn recent_projects(pinned: usize, unpinned: usize) -> Vec<RecentProject> {
+        let now = Utc::now();
+        (0..pinned + unpinned)
+            .map(|i| RecentProject {
+                path: format!("/projects/{}/project.json", i),
+                name: format!("Project {}", i),
+                last_accessed: now - Duration::minutes(i as i64),
+                missing: false,
+                pinned: i < pinned,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_recent_projects_keeps_every_pinned_entry() {
+        let mut projects = recent_projects(11, 3);
+        sort_recent_projects(&mut projects);
+        assert_eq!(projects.len(), 11);
+        assert!(projects.iter().all(|project| project.pinned));
+    }
+
+    #[test]
+    fn test_sort_recent_projects_fills_up_with_newest_unpinned() {
+        let mut projects = recent_projects(3, 12);
+        projects.reverse();
+        sort_recent_projects(&mut projects);
+        let names: Vec<_> = projects
+            .iter()
+            .map(|project| project.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Project 0",
+                "Project 1",
+                "Project 2",
+                "Project 3",
+                "Project 4",
+                "Project 5",
+                "Project 6",
+                "Project 7",
+                "Project 8",
+                "Project 9",
+            ]
+        );
+    }
+}
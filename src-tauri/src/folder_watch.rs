@@ -0,0 +1,214 @@
+use crate::audio::{take_file_stem, ProgressEvents, WindowEvents};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::{project_file_path, read_project};
+use crate::integrity::take_stems;
+use crate::models::Project;
+use crate::paths::{long_path, normalize};
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use log::{debug, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// How often pending changes are checked for having settled.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a file must go unchanged before it is reported, so a file that
+/// is still being written is reported once.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// Watch of the open project's recordings folder.
+pub struct FolderWatch {
+    pub directory: PathBuf,
+    /// Stops the watch when dropped.
+    _watcher: RecommendedWatcher,
+}
+
+pub type FolderWatchState = Mutex<Option<FolderWatch>>;
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+}
+
+/// WAV files in `directory` when the watch starts.
+fn list_wavs(directory: &Path) -> Result<HashSet<PathBuf>, RecordrError> {
+    Ok(fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| normalize(&entry.path()))
+        .filter(|path| is_wav(path) && path.is_file())
+        .collect())
+}
+
+/// Sentence whose take `path` could be, by file name: the sentence it is
+/// named after, or whose earlier takes it is numbered after.
+fn match_sentence(project_file: &Path, path: &Path) -> Option<usize> {
+    let name = path.file_name()?.to_str()?;
+    // Read again each time, so sentences added since the watch started and
+    // takes linked in the meantime are known.
+    let project = match read_project(&project_file.to_string_lossy()) {
+        Ok(project) => project,
+        Err(e) => {
+            warn!(
+                "Failed to read {:?} to match {:?}: {}",
+                project_file, path, e
+            );
+            return None;
+        }
+    };
+    let stems: HashMap<String, usize> = project
+        .sentences
+        .iter()
+        .map(|s| (take_file_stem(s), s.id))
+        .collect();
+    take_stems(name)
+        .into_iter()
+        .find_map(|stem| stems.get(stem).copied())
+}
+
+/**
+ * Reports the WAV files in `directory` that were added, changed or removed
+ * until the watcher sending to `changes` is dropped. Whether a file was
+ * added or changed is judged against the files seen so far rather than
+ * the kind of the filesystem event, as editors often save through a temp
+ * file and a rename, which platforms report differently.
+ */
+fn report_changes(
+    directory: &Path,
+    project_file: &Path,
+    changes: Receiver<notify::Result<Event>>,
+    mut known: HashSet<PathBuf>,
+    events: &dyn ProgressEvents,
+) {
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match changes.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) if !matches!(event.kind, EventKind::Access(_)) => {
+                for path in event.paths.iter().filter(|path| is_wav(path)) {
+                    pending.insert(normalize(path), Instant::now());
+                }
+            }
+            Ok(Ok(_)) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => warn!("Error watching {:?}: {}", directory, e),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            let event = match (path.is_file(), known.contains(&path)) {
+                (true, false) => {
+                    known.insert(path.clone());
+                    "external-file-added"
+                }
+                (true, true) => "external-file-changed",
+                (false, true) => {
+                    known.remove(&path);
+                    "external-file-removed"
+                }
+                // Came and went before it settled, e.g. an editor's temp file
+                (false, false) => continue,
+            };
+            let sentence_id = match_sentence(project_file, &path);
+            debug!("{}: {:?}, sentence {:?}", event, path, sentence_id);
+            events.notify(
+                event,
+                serde_json::json!({
+                    "path": path.to_string_lossy(),
+                    "sentenceId": sentence_id,
+                }),
+            );
+        }
+    }
+    debug!("Stopped watching {:?}", directory);
+}
+
+/**
+ * Watches the recordings folder of `project` for WAV files added, changed
+ * or removed outside the app, e.g. by a DAW, reporting each with an
+ * `external-file-added`, `external-file-changed` or `external-file-removed`
+ * event, along with the sentence the file is named after, if any. Takes
+ * the app records itself are reported too. Any earlier watch is stopped
+ * first. Encrypted projects are refused, as files saved into the folder by
+ * other apps are in plain form.
+ */
+pub fn start_watch(
+    project: &Project,
+    events: Arc<dyn ProgressEvents>,
+    watch: &FolderWatchState,
+) -> Result<(), RecordrError> {
+    if project.metadata.encrypted {
+        return Err(RecordrError::invalid_state(
+            "Folders of encrypted projects can't be watched",
+        ));
+    }
+    let directory = project
+        .metadata
+        .layout
+        .recordings_path(Path::new(&project.metadata.directory));
+    fs::create_dir_all(long_path(&directory))?;
+    let known = list_wavs(&directory)?;
+
+    let (changes_tx, changes_rx) = unbounded();
+    let watch_error = |e: notify::Error| {
+        RecordrError::new(ErrorKind::Io, e.to_string()).with_context(directory.to_string_lossy())
+    };
+    let mut watcher = notify::recommended_watcher(move |result| {
+        let _ = changes_tx.send(result);
+    })
+    .map_err(watch_error)?;
+    watcher
+        .watch(&directory, RecursiveMode::NonRecursive)
+        .map_err(watch_error)?;
+
+    {
+        let directory = directory.clone();
+        let project_file = project_file_path(project);
+        std::thread::spawn(move || {
+            report_changes(
+                &directory,
+                &project_file,
+                changes_rx,
+                known,
+                events.as_ref(),
+            )
+        });
+    }
+    debug!("Watching {:?} for external changes", directory);
+    *watch.lock().unwrap() = Some(FolderWatch {
+        directory,
+        _watcher: watcher,
+    });
+    Ok(())
+}
+
+/// Stops watching the project folder, if it is watched.
+pub fn stop_watch(watch: &FolderWatchState) {
+    if let Some(stopped) = watch.lock().unwrap().take() {
+        debug!("Stopping the watch of {:?}", stopped.directory);
+    }
+}
+
+/// Watches the project's recordings folder for files added, changed or
+/// removed outside the app; see `start_watch`.
+#[tauri::command]
+pub fn watch_project_folder(
+    project: Project,
+    window: tauri::Window,
+    watch: State<FolderWatchState>,
+) -> Result<(), RecordrError> {
+    start_watch(&project, Arc::new(WindowEvents::new(window)), &watch)
+}
+
+#[tauri::command]
+pub fn unwatch_project_folder(watch: State<FolderWatchState>) {
+    stop_watch(&watch);
+}
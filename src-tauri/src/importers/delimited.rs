@@ -0,0 +1,56 @@
+use super::{decode, table_sentences, ImportOptions, Importer};
+use crate::errors::RecordrError;
+use crate::models::Sentence;
+use csv::ReaderBuilder;
+
+/// CSV or TSV with a header row.
+pub struct DelimitedImporter {
+    name: &'static str,
+    delimiter: u8,
+}
+
+impl DelimitedImporter {
+    pub const CSV: DelimitedImporter = DelimitedImporter {
+        name: "csv",
+        delimiter: b',',
+    };
+    pub const TSV: DelimitedImporter = DelimitedImporter {
+        name: "tsv",
+        delimiter: b'\t',
+    };
+}
+
+impl Importer for DelimitedImporter {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self.delimiter {
+            b'\t' => &["tsv"],
+            _ => &["csv"],
+        }
+    }
+
+    /// Looks for the delimiter in the header row.
+    fn sniff(&self, data: &[u8]) -> bool {
+        decode(data).is_ok_and(|text| {
+            text.lines()
+                .next()
+                .is_some_and(|header| header.contains(self.delimiter as char))
+        })
+    }
+
+    fn parse(&self, data: &[u8], options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_reader(decode(data)?.as_bytes());
+        let headers: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(str::to_string).collect()))
+            .collect::<Result<Vec<Vec<String>>, RecordrError>>()?;
+        table_sentences(&headers, rows, options)
+    }
+}
@@ -0,0 +1,127 @@
+use super::{decode, ImportOptions, Importer};
+use crate::errors::RecordrError;
+use crate::models::Sentence;
+
+/// A Fountain screenplay. Each block of dialogue becomes a prompt spoken by
+/// its character, with any parentheticals as notes; scene headings, action
+/// and the title page are left out. IDs are the character cues' line
+/// numbers.
+pub struct FountainImporter;
+
+/// Scene heading prefixes, which also mark a file as a screenplay.
+const SCENE_HEADINGS: [&str; 6] = ["INT.", "EXT.", "INT ", "EXT ", "INT/EXT", "I/E"];
+
+fn is_scene_heading(line: &str) -> bool {
+    let upper = line.to_uppercase();
+    line.starts_with('.') && !line.starts_with("..")
+        || SCENE_HEADINGS
+            .iter()
+            .any(|prefix| upper.starts_with(prefix))
+}
+
+/// Character named by a cue line, without extensions like `(V.O.)` or the
+/// dual dialogue caret, if the line is one: all upper case, or forced with
+/// `@`.
+fn character_cue(line: &str) -> Option<String> {
+    let (forced, name) = match line.strip_prefix('@') {
+        Some(name) => (true, name),
+        None => (false, line),
+    };
+    let name = name.trim_end_matches('^').trim();
+    let name = name.split('(').next().unwrap_or(name).trim();
+    let is_cue = forced
+        || (!is_scene_heading(line)
+            && !line.ends_with("TO:")
+            && name.chars().any(char::is_alphabetic)
+            && !name.chars().any(char::is_lowercase));
+    (is_cue && !name.is_empty()).then(|| name.to_string())
+}
+
+/// Removes boneyard (`/* */`) and note (`[[ ]]`) text, which isn't part of
+/// the script.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['/', '[']) {
+        let (open, close) = match &rest[start..] {
+            tail if tail.starts_with("/*") => ("/*", "*/"),
+            tail if tail.starts_with("[[") => ("[[", "]]"),
+            _ => {
+                stripped.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        stripped.push_str(&rest[..start]);
+        // Keep line breaks so line numbers stay right.
+        let after = &rest[start + open.len()..];
+        let end = after
+            .find(close)
+            .map_or(after.len(), |end| end + close.len());
+        stripped.extend(after[..end].chars().filter(|&c| c == '\n'));
+        rest = &after[end..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+impl Importer for FountainImporter {
+    fn name(&self) -> &'static str {
+        "fountain"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["fountain", "spmd"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        decode(data).is_ok_and(|text| {
+            text.lines()
+                .map(str::trim)
+                .any(|line| line.len() > 1 && is_scene_heading(line))
+        })
+    }
+
+    fn parse(&self, data: &[u8], _options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError> {
+        let text = strip_comments(decode(data)?);
+        let mut lines = text.lines().map(str::trim).enumerate().peekable();
+
+        // The title page is a run of `Key: value` lines at the very start.
+        if lines
+            .peek()
+            .is_some_and(|(_, line)| line.contains(':') && !is_scene_heading(line))
+        {
+            while lines.next_if(|(_, line)| !line.is_empty()).is_some() {}
+        }
+
+        let mut sentences = Vec::new();
+        let mut previous_blank = true;
+        while let Some((index, line)) = lines.next() {
+            let cue = previous_blank
+                .then(|| character_cue(line))
+                .flatten()
+                .filter(|_| lines.peek().is_some_and(|(_, next)| !next.is_empty()));
+            previous_blank = line.is_empty();
+            let Some(character) = cue else {
+                continue;
+            };
+
+            let mut dialogue = Vec::new();
+            let mut notes = Vec::new();
+            while let Some((_, line)) = lines.next_if(|(_, line)| !line.is_empty()) {
+                if line.starts_with('(') && line.ends_with(')') {
+                    notes.push(line.trim_matches(['(', ')']).to_string());
+                } else {
+                    dialogue.push(line);
+                }
+            }
+            if !dialogue.is_empty() {
+                let mut sentence = Sentence::new(index + 1, dialogue.join(" "));
+                sentence.character = Some(character);
+                sentence.notes = (!notes.is_empty()).then(|| notes.join("; "));
+                sentences.push(sentence);
+            }
+        }
+        Ok(sentences)
+    }
+}
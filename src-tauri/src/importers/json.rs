@@ -0,0 +1,74 @@
+use super::{decode, parse_tags, ImportOptions, Importer};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::models::Sentence;
+use serde_json::Value;
+
+/// An array of prompts, each either a string or an object with the text,
+/// notes, tags and character under the keys named by the options. Tags may
+/// be an array or a separated string. IDs are the entries' positions.
+pub struct JsonImporter;
+
+impl Importer for JsonImporter {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        decode(data).is_ok_and(|text| text.trim_start().starts_with('['))
+    }
+
+    fn parse(&self, data: &[u8], options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError> {
+        let entries: Vec<Value> =
+            serde_json::from_slice(data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data))?;
+        let text_key = options.text_column.as_deref().unwrap_or("text");
+
+        entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let id = index + 1;
+                let entry = match entry {
+                    Value::String(text) => return Ok(Sentence::new(id, text.clone())),
+                    Value::Object(entry) => entry,
+                    _ => {
+                        return Err(RecordrError::new(
+                            ErrorKind::Parse,
+                            format!("Entry {} is neither a string nor an object", id),
+                        ))
+                    }
+                };
+                let text = entry.get(text_key).and_then(Value::as_str).ok_or_else(|| {
+                    RecordrError::new(
+                        ErrorKind::Parse,
+                        format!("Entry {} has no \"{}\" text", id, text_key),
+                    )
+                })?;
+                let optional = |key: &str| {
+                    entry
+                        .get(key)
+                        .and_then(Value::as_str)
+                        .map(str::trim)
+                        .filter(|value| !value.is_empty())
+                        .map(str::to_string)
+                };
+                let mut sentence = Sentence::new(id, text.to_string());
+                sentence.notes = optional(options.notes_column());
+                sentence.character = optional(options.character_column());
+                sentence.tags = match entry.get(options.tags_column()) {
+                    Some(Value::Array(tags)) => tags
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .flat_map(parse_tags)
+                        .collect(),
+                    Some(Value::String(tags)) => parse_tags(tags),
+                    _ => Vec::new(),
+                };
+                Ok(sentence)
+            })
+            .collect()
+    }
+}
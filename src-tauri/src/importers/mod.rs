@@ -0,0 +1,192 @@
+mod delimited;
+mod fountain;
+mod json;
+mod text;
+mod xlsx;
+
+use crate::errors::{ErrorKind, RecordrError};
+use crate::models::Sentence;
+use delimited::DelimitedImporter;
+use fountain::FountainImporter;
+use json::JsonImporter;
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::path::Path;
+use text::TextImporter;
+use xlsx::XlsxImporter;
+
+/// How an import file's fields map onto sentences, shared by every format.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Column header, or JSON key, holding the prompt text. Tables default
+    /// to their first column and JSON to `text`.
+    pub text_column: Option<String>,
+    /// Column headers, or JSON keys, of the optional fields, defaulting to
+    /// `notes`, `tags` and `character`.
+    pub notes_column: Option<String>,
+    pub tags_column: Option<String>,
+    pub character_column: Option<String>,
+    /// Worksheet to read from a spreadsheet; the first one by default.
+    pub sheet: Option<String>,
+    /// Keep blank prompts instead of leaving them out.
+    pub keep_blank: bool,
+}
+
+impl ImportOptions {
+    fn notes_column(&self) -> &str {
+        self.notes_column.as_deref().unwrap_or("notes")
+    }
+
+    fn tags_column(&self) -> &str {
+        self.tags_column.as_deref().unwrap_or("tags")
+    }
+
+    fn character_column(&self) -> &str {
+        self.character_column.as_deref().unwrap_or("character")
+    }
+}
+
+/// Reads sentences from one file format. Each sentence's ID is its row or
+/// line number in the file, counting any header, so problems can be
+/// reported where they are; IDs are renumbered once the import is checked.
+pub trait Importer: Sync {
+    /// Name that selects the importer explicitly, e.g. `csv`.
+    fn name(&self) -> &'static str;
+    /// File extensions the importer is picked for.
+    fn extensions(&self) -> &'static [&'static str];
+    /// Whether the contents look like this format, for files whose
+    /// extension doesn't tell.
+    fn sniff(&self, data: &[u8]) -> bool;
+    fn parse(&self, data: &[u8], options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError>;
+}
+
+/// Every supported format, in the order they are sniffed: the most
+/// distinctive first and plain text, which accepts anything, last.
+static IMPORTERS: [&dyn Importer; 6] = [
+    &XlsxImporter,
+    &JsonImporter,
+    &FountainImporter,
+    &DelimitedImporter::TSV,
+    &DelimitedImporter::CSV,
+    &TextImporter,
+];
+
+/// Picks the importer for a file: the one named by `format`, else the one
+/// for its extension, else the first whose format the contents look like.
+fn find_importer(
+    format: Option<&str>,
+    path: &Path,
+    data: &[u8],
+) -> Result<&'static dyn Importer, RecordrError> {
+    if let Some(format) = format {
+        return IMPORTERS
+            .iter()
+            .find(|importer| importer.name().eq_ignore_ascii_case(format))
+            .copied()
+            .ok_or_else(|| {
+                RecordrError::invalid_input("Unknown import format").with_context(format)
+            });
+    }
+    let extension = path.extension().and_then(OsStr::to_str);
+    let by_extension = extension.and_then(|extension| {
+        IMPORTERS.iter().find(|importer| {
+            importer
+                .extensions()
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        })
+    });
+    by_extension
+        .or_else(|| IMPORTERS.iter().find(|importer| importer.sniff(data)))
+        .copied()
+        .ok_or_else(|| {
+            RecordrError::new(ErrorKind::UnsupportedFormat, "Unsupported file format")
+                .with_context(path.display().to_string())
+        })
+}
+
+/// Reads the sentences of an import file with the importer chosen by
+/// `find_importer`, trimmed and, unless kept, without blank prompts. IDs
+/// are left as row numbers.
+pub fn import_file(
+    path: &Path,
+    data: &[u8],
+    format: Option<&str>,
+    options: &ImportOptions,
+) -> Result<Vec<Sentence>, RecordrError> {
+    let importer = find_importer(format, path, data)?;
+    let sentences = importer
+        .parse(data, options)
+        .map_err(|e| e.with_context(path.display().to_string()))?;
+    Ok(sentences
+        .into_iter()
+        .map(|sentence| Sentence {
+            text: sentence.text.trim().to_string(),
+            ..sentence
+        })
+        .filter(|sentence| options.keep_blank || !sentence.text.is_empty())
+        .collect())
+}
+
+/// Text of a text-based import file, without any byte order mark.
+fn decode(data: &[u8]) -> Result<&str, RecordrError> {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    std::str::from_utf8(data)
+        .map_err(|e| RecordrError::new(ErrorKind::Parse, format!("File is not UTF-8 text: {}", e)))
+}
+
+fn parse_tags(tags: &str) -> Vec<String> {
+    tags.split([',', ';'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Maps the rows of a table with a header row to sentences, shared by the
+/// tabular formats. The text is in the `text_column` or else the first
+/// column; the notes, tags and character columns are optional, with tags
+/// separated by commas or semicolons. The header is row 1.
+fn table_sentences(
+    headers: &[String],
+    rows: impl IntoIterator<Item = Vec<String>>,
+    options: &ImportOptions,
+) -> Result<Vec<Sentence>, RecordrError> {
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    };
+    let text_column = match &options.text_column {
+        Some(name) => column(name).ok_or_else(|| {
+            RecordrError::invalid_input("Text column not found").with_context(name)
+        })?,
+        None => 0,
+    };
+    let notes_column = column(options.notes_column());
+    let tags_column = column(options.tags_column());
+    let character_column = column(options.character_column());
+
+    let mut sentences = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let Some(text) = row.get(text_column) else {
+            continue;
+        };
+        let optional = |column: Option<usize>| {
+            column
+                .and_then(|column| row.get(column))
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let mut sentence = Sentence::new(index + 2, text.to_string());
+        sentence.notes = optional(notes_column);
+        sentence.character = optional(character_column);
+        sentence.tags = optional(tags_column)
+            .map(|tags| parse_tags(&tags))
+            .unwrap_or_default();
+        sentences.push(sentence);
+    }
+    Ok(sentences)
+}
@@ -0,0 +1,28 @@
+use super::{decode, ImportOptions, Importer};
+use crate::errors::RecordrError;
+use crate::models::Sentence;
+
+/// One prompt per line.
+pub struct TextImporter;
+
+impl Importer for TextImporter {
+    fn name(&self) -> &'static str {
+        "txt"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    fn sniff(&self, data: &[u8]) -> bool {
+        decode(data).is_ok()
+    }
+
+    fn parse(&self, data: &[u8], _options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError> {
+        Ok(decode(data)?
+            .lines()
+            .enumerate()
+            .map(|(index, line)| Sentence::new(index + 1, line.to_string()))
+            .collect())
+    }
+}
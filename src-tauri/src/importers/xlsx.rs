@@ -0,0 +1,54 @@
+use super::{table_sentences, ImportOptions, Importer};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::models::Sentence;
+use calamine::{open_workbook_auto_from_rs, Reader};
+use std::io::Cursor;
+
+/// A spreadsheet worksheet with a header row, as in Excel or OpenDocument
+/// files.
+pub struct XlsxImporter;
+
+fn spreadsheet_error(error: impl std::fmt::Display) -> RecordrError {
+    RecordrError::new(
+        ErrorKind::Parse,
+        format!("Failed to read spreadsheet: {}", error),
+    )
+}
+
+impl Importer for XlsxImporter {
+    fn name(&self) -> &'static str {
+        "xlsx"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xlsx", "xlsm", "xls", "ods"]
+    }
+
+    /// Spreadsheets are zip archives, or OLE compound files for older
+    /// Excel versions.
+    fn sniff(&self, data: &[u8]) -> bool {
+        data.starts_with(b"PK\x03\x04") || data.starts_with(b"\xd0\xcf\x11\xe0")
+    }
+
+    fn parse(&self, data: &[u8], options: &ImportOptions) -> Result<Vec<Sentence>, RecordrError> {
+        let mut workbook =
+            open_workbook_auto_from_rs(Cursor::new(data.to_vec())).map_err(spreadsheet_error)?;
+        let sheet = match &options.sheet {
+            Some(sheet) => sheet.clone(),
+            None => workbook
+                .sheet_names()
+                .first()
+                .cloned()
+                .ok_or_else(|| RecordrError::invalid_input("Spreadsheet has no worksheets"))?,
+        };
+        let range = workbook
+            .worksheet_range(&sheet)
+            .map_err(|e| spreadsheet_error(e).with_context(&sheet))?;
+
+        let mut rows = range
+            .rows()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>());
+        let headers = rows.next().unwrap_or_default();
+        table_sentences(&headers, rows, options)
+    }
+}
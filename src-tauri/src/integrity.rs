@@ -0,0 +1,150 @@
+use crate::audio::{split_take_number, take_file_stem};
+use crate::errors::RecordrError;
+use crate::jobs::run_blocking;
+use crate::models::Project;
+use crate::paths::{long_path, normalize};
+use crate::storage;
+use hound::WavReader;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What is wrong with a sentence's take.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TakeIssue {
+    /// The sentence is marked recorded but points at no file.
+    NoAudioPath,
+    Missing,
+    /// The file is there but has nothing in it.
+    EmptyFile,
+    /// The file can't be read or isn't a valid WAV file.
+    Unreadable {
+        message: String,
+    },
+    /// The WAV file is valid but holds no audio.
+    NoAudio,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TakeProblem {
+    pub sentence_id: usize,
+    pub audio_file_path: Option<String>,
+    pub issue: TakeIssue,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IntegrityReport {
+    /// Number of sentences marked recorded, all of which were checked.
+    pub checked: usize,
+    pub problems: Vec<TakeProblem>,
+    /// WAV files in the recordings folder that are neither a sentence's
+    /// take, an earlier take of one nor a continuous session recording.
+    pub orphans: Vec<String>,
+}
+
+/// Checks that the file at `path` is a WAV file with some audio in it.
+fn check_take(path: &Path) -> Option<TakeIssue> {
+    let metadata = match fs::metadata(long_path(path)) {
+        Ok(metadata) => metadata,
+        Err(_) => return Some(TakeIssue::Missing),
+    };
+    if metadata.len() == 0 {
+        return Some(TakeIssue::EmptyFile);
+    }
+    // Only the header is parsed; encrypted takes are decrypted first.
+    let reader = storage::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| WavReader::new(file).map_err(|e| e.to_string()));
+    match reader {
+        Ok(reader) if reader.duration() == 0 => Some(TakeIssue::NoAudio),
+        Ok(_) => None,
+        Err(message) => Some(TakeIssue::Unreadable { message }),
+    }
+}
+
+/// Name shared by the takes `file_name` could be one of, with and without
+/// a trailing take number.
+pub(crate) fn take_stems(file_name: &str) -> Vec<&str> {
+    let Some(stem) = file_name.strip_suffix(".wav") else {
+        return Vec::new();
+    };
+    let mut stems = vec![stem];
+    if let Some((base, _)) = split_take_number(stem) {
+        stems.push(base);
+    }
+    stems
+}
+
+/// WAV files in `directory` that no sentence uses and that aren't earlier
+/// takes of any sentence or continuous session recordings.
+fn find_orphans(project: &Project, directory: &Path) -> Result<Vec<String>, RecordrError> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let used: HashSet<PathBuf> = project
+        .sentences
+        .iter()
+        .filter_map(|s| s.audio_file_path.as_deref())
+        .map(|path| normalize(Path::new(path)))
+        .collect();
+    let stems: HashSet<String> = project.sentences.iter().map(take_file_stem).collect();
+
+    let mut orphans: Vec<String> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let is_take = take_stems(&name)
+                .into_iter()
+                .any(|stem| stems.contains(stem));
+            if !name.ends_with(".wav") || is_take {
+                return None;
+            }
+            let path = normalize(&entry.path());
+            let is_session = path.with_extension("markers.json").exists();
+            (!is_session && !used.contains(&path)).then(|| path.to_string_lossy().to_string())
+        })
+        .collect();
+    orphans.sort();
+    Ok(orphans)
+}
+
+fn verify(project: &Project) -> Result<IntegrityReport, RecordrError> {
+    let mut checked = 0;
+    let mut problems = Vec::new();
+    for sentence in project.sentences.iter().filter(|s| s.recorded) {
+        checked += 1;
+        let issue = match sentence.audio_file_path.as_deref() {
+            Some(path) => check_take(Path::new(path)),
+            None => Some(TakeIssue::NoAudioPath),
+        };
+        if let Some(issue) = issue {
+            problems.push(TakeProblem {
+                sentence_id: sentence.id,
+                audio_file_path: sentence.audio_file_path.clone(),
+                issue,
+            });
+        }
+    }
+    let directory = project
+        .metadata
+        .layout
+        .recordings_path(Path::new(&project.metadata.directory));
+    Ok(IntegrityReport {
+        checked,
+        problems,
+        orphans: find_orphans(project, &directory)?,
+    })
+}
+
+/**
+ * Checks that every sentence marked recorded has a readable WAV take with
+ * some audio in it, and lists the WAV files in the recordings folder that
+ * belong to no sentence. Nothing is changed.
+ */
+#[tauri::command]
+pub async fn verify_project(project: Project) -> Result<IntegrityReport, RecordrError> {
+    run_blocking(move || verify(&project)).await
+}
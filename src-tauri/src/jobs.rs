@@ -0,0 +1,184 @@
+use crate::errors::{ErrorKind, RecordrError};
+use log::debug;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use tauri::State;
+
+/// Cooperative cancellation flag shared between a long-running operation and
+/// whoever may want to abort it. Operations poll `is_cancelled` between units
+/// of work and stop cleanly when it is set.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns a `Cancelled` error if the token has been cancelled.
+    pub fn check(&self) -> Result<(), RecordrError> {
+        if self.is_cancelled() {
+            Err(RecordrError::new(
+                ErrorKind::Cancelled,
+                "Operation cancelled",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Cancellation tokens of the jobs currently running, keyed by the job ID
+/// chosen by the frontend.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a job and returns its cancellation token. Jobs started
+    /// without an ID run to completion and cannot be cancelled. An ID
+    /// already in use is rejected, since the running job could no longer be
+    /// cancelled and would be unregistered when the new one finishes.
+    pub fn register(&self, job_id: Option<&str>) -> Result<CancellationToken, RecordrError> {
+        let token = CancellationToken::new();
+        if let Some(job_id) = job_id {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(job_id) {
+                return Err(RecordrError::invalid_input(format!(
+                    "Job {} is already running",
+                    job_id
+                )));
+            }
+            debug!("Registering job {}", job_id);
+            jobs.insert(job_id.to_string(), token.clone());
+        }
+        Ok(token)
+    }
+
+    pub fn finish(&self, job_id: Option<&str>) {
+        if let Some(job_id) = job_id {
+            debug!("Job {} finished", job_id);
+            self.jobs.lock().unwrap().remove(job_id);
+        }
+    }
+
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Runs blocking work on the async runtime's blocking pool so the invoke
+/// thread stays free.
+pub async fn run_blocking<T, F>(work: F) -> Result<T, RecordrError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, RecordrError> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| RecordrError::new(ErrorKind::Other, e.to_string()))?
+}
+
+/// Like `run_blocking`, but registers the work as a job that can be
+/// cancelled through `cancel_job`.
+pub async fn run_job<T, F>(
+    jobs: &JobRegistry,
+    job_id: Option<String>,
+    work: F,
+) -> Result<T, RecordrError>
+where
+    T: Send + 'static,
+    F: FnOnce(CancellationToken) -> Result<T, RecordrError> + Send + 'static,
+{
+    let token = jobs.register(job_id.as_deref())?;
+    let result = run_blocking(move || work(token)).await;
+    jobs.finish(job_id.as_deref());
+    result
+}
+
+/// How far a batch has got, passed along with the result of each item.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchProgress {
+    /// Items finished so far, failed ones included.
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+/**
+ * Runs `work` on each of `items` on a pool of worker threads, one per core,
+ * and hands each result to `done` on the calling thread as it comes in, so
+ * results arrive in the order they finish. A failed item doesn't stop the
+ * others. Once `cancel` is set no more items are started, while those
+ * already under way are finished and reported.
+ */
+pub fn run_batch<T, R, W, D>(items: &[T], cancel: &CancellationToken, work: W, mut done: D)
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T) -> Result<R, RecordrError> + Sync,
+    D: FnMut(usize, Result<R, RecordrError>, BatchProgress),
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(items.len());
+    debug!("Processing {} items on {} workers", items.len(), workers);
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let (tx, next, work) = (tx.clone(), &next, &work);
+            scope.spawn(move || {
+                while !cancel.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(item) = items.get(index) else {
+                        return;
+                    };
+                    if tx.send((index, work(item))).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut progress = BatchProgress {
+            completed: 0,
+            failed: 0,
+            total: items.len(),
+        };
+        for (index, result) in rx {
+            progress.completed += 1;
+            progress.failed += usize::from(result.is_err());
+            done(index, result, progress);
+        }
+    });
+}
+
+/// Requests cancellation of a running job. Returns `false` if no job with
+/// that ID is running.
+#[tauri::command]
+pub fn cancel_job(job_id: String, jobs: State<JobRegistry>) -> bool {
+    jobs.cancel(&job_id)
+}
@@ -0,0 +1,264 @@
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::{allow_project_directory, app_data_file_path, unlock_and_read_project};
+use crate::models::Project;
+use aes_gcm::aead::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, State, Wry};
+
+/// Commands available while kiosk mode is active. Everything else, including
+/// every destructive command, is rejected.
+const DEFAULT_ALLOWED_COMMANDS: &[&str] = &[
+    "start_auto_record",
+    "pause_auto_record",
+    "resume_auto_record",
+    "reconnect_audio_device",
+    "stop_auto_record",
+    "start_monitor_mode",
+    "stop_monitor_mode",
+    "confirm_sentence",
+    "retry_sentence",
+    "skip_sentence",
+    "save_project",
+    "load_audio_file",
+    "load_sentence_asset",
+    "play_sentence_asset",
+    "stop_sentence_asset",
+    "format_timestamp",
+];
+
+/// Commands that stay reachable in kiosk mode regardless of configuration,
+/// so a kiosk can always be inspected and unlocked.
+const KIOSK_COMMANDS: &[&str] = &["get_kiosk_status", "kiosk_open_project", "exit_kiosk_mode"];
+
+/// Restricted operation mode for unattended, participant-facing recording
+/// stations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KioskSettings {
+    pub enabled: bool,
+    /// Project JSON opened automatically while in kiosk mode.
+    pub project_path: Option<String>,
+    pub allowed_commands: Vec<String>,
+    /// Argon2 hash, in PHC string format, of the PIN required to leave
+    /// kiosk mode. Never sent to the frontend.
+    pub exit_pin_hash: Option<String>,
+    /// PIN saved in plain text by earlier versions, hashed on load.
+    #[serde(skip_serializing)]
+    exit_pin: Option<String>,
+}
+
+impl Default for KioskSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            project_path: None,
+            allowed_commands: DEFAULT_ALLOWED_COMMANDS
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+            exit_pin_hash: None,
+            exit_pin: None,
+        }
+    }
+}
+
+/// Kiosk status reported to the frontend.
+#[derive(Serialize)]
+pub struct KioskStatus {
+    pub enabled: bool,
+    pub project_path: Option<String>,
+    pub allowed_commands: Vec<String>,
+}
+
+pub type KioskState = Arc<Mutex<KioskSettings>>;
+
+fn get_kiosk_settings_path() -> PathBuf {
+    app_data_file_path("kiosk.json")
+}
+
+/// Loads the kiosk settings, falling back to kiosk mode being disabled. A
+/// PIN saved in plain text is replaced with its hash.
+pub fn load_kiosk_settings() -> KioskSettings {
+    let path = get_kiosk_settings_path();
+    let mut settings: KioskSettings = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| {
+            serde_json::from_str(&contents)
+                .map_err(|e| warn!("Invalid kiosk settings: {}", e))
+                .ok()
+        })
+        .unwrap_or_default();
+    if let Some(pin) = settings.exit_pin.take() {
+        let result = hash_pin(&pin).and_then(|hash| {
+            settings.exit_pin_hash = Some(hash);
+            save_kiosk_settings(&settings)
+        });
+        if let Err(e) = result {
+            warn!("Failed to hash kiosk exit PIN: {}", e);
+        }
+    }
+    settings
+}
+
+/// Hashes `pin` with a new random salt.
+fn hash_pin(pin: &str) -> Result<String, RecordrError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pin.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| RecordrError::new(ErrorKind::Other, e.to_string()))
+}
+
+/// Whether `pin` matches `hash`. The hashes are compared in constant time.
+fn verify_pin(hash: &str, pin: &str) -> bool {
+    PasswordHash::new(hash)
+        .map_err(|e| warn!("Invalid kiosk exit PIN hash: {}", e))
+        .is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(pin.as_bytes(), &hash)
+                .is_ok()
+        })
+}
+
+fn save_kiosk_settings(settings: &KioskSettings) -> Result<(), RecordrError> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(get_kiosk_settings_path(), json)?;
+    Ok(())
+}
+
+/// Decides whether a command may run. `payload` is the command's arguments,
+/// used to pin `open_project` to the kiosk project.
+pub fn check_command(
+    settings: &KioskSettings,
+    command: &str,
+    payload: &serde_json::Value,
+) -> Result<(), RecordrError> {
+    if !settings.enabled || KIOSK_COMMANDS.contains(&command) {
+        return Ok(());
+    }
+    if command == "open_project" {
+        let requested = payload.get("filePath").and_then(|p| p.as_str());
+        return match (&settings.project_path, requested) {
+            (Some(fixed), Some(requested)) if fixed == requested => Ok(()),
+            _ => Err(RecordrError::invalid_state(
+                "Only the kiosk project can be opened in kiosk mode",
+            )),
+        };
+    }
+    if settings.allowed_commands.iter().any(|c| c == command) {
+        Ok(())
+    } else {
+        Err(RecordrError::invalid_state("Not available in kiosk mode").with_context(command))
+    }
+}
+
+/// Wraps the command handler so commands that kiosk mode forbids are
+/// rejected before they are dispatched.
+pub fn guard_commands<F>(
+    kiosk: KioskState,
+    handler: F,
+) -> impl Fn(Invoke<Wry>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<Wry>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<Wry>| {
+        let check = {
+            let settings = kiosk.lock().unwrap();
+            let payload = match invoke.message.payload() {
+                InvokeBody::Json(payload) => payload,
+                InvokeBody::Raw(_) => &serde_json::Value::Null,
+            };
+            check_command(&settings, invoke.message.command(), payload)
+        };
+        match check {
+            Ok(()) => handler(invoke),
+            Err(e) => {
+                warn!("Rejected command in kiosk mode: {}", e);
+                invoke.resolver.reject(e);
+                true
+            }
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_kiosk_status(kiosk: State<KioskState>) -> KioskStatus {
+    let settings = kiosk.lock().unwrap();
+    KioskStatus {
+        enabled: settings.enabled,
+        project_path: settings.project_path.clone(),
+        allowed_commands: settings.allowed_commands.clone(),
+    }
+}
+
+/// Opens the fixed kiosk project.
+#[tauri::command]
+pub fn kiosk_open_project(
+    app: AppHandle,
+    passphrase: Option<String>,
+    kiosk: State<KioskState>,
+) -> Result<Project, RecordrError> {
+    let project_path = {
+        let settings = kiosk.lock().unwrap();
+        if !settings.enabled {
+            return Err(RecordrError::invalid_state("Kiosk mode is not enabled"));
+        }
+        settings
+            .project_path
+            .clone()
+            .ok_or_else(|| RecordrError::invalid_state("No kiosk project configured"))?
+    };
+    let project = unlock_and_read_project(&project_path, passphrase.as_deref())?;
+    allow_project_directory(&app, &project);
+    Ok(project)
+}
+
+/// Enables kiosk mode for the given project. The PIN is required to exit.
+#[tauri::command]
+pub fn enter_kiosk_mode(
+    project_path: String,
+    exit_pin: String,
+    allowed_commands: Option<Vec<String>>,
+    kiosk: State<KioskState>,
+) -> Result<(), RecordrError> {
+    if exit_pin.is_empty() {
+        return Err(RecordrError::invalid_input(
+            "An exit PIN is required for kiosk mode",
+        ));
+    }
+    let exit_pin_hash = hash_pin(&exit_pin)?;
+    let mut settings = kiosk.lock().unwrap();
+    settings.enabled = true;
+    settings.project_path = Some(project_path);
+    settings.exit_pin_hash = Some(exit_pin_hash);
+    if let Some(allowed_commands) = allowed_commands {
+        settings.allowed_commands = allowed_commands;
+    }
+    save_kiosk_settings(&settings)?;
+    info!("Kiosk mode enabled");
+    Ok(())
+}
+
+/// Leaves kiosk mode if the PIN matches.
+#[tauri::command]
+pub fn exit_kiosk_mode(pin: String, kiosk: State<KioskState>) -> Result<(), RecordrError> {
+    let mut settings = kiosk.lock().unwrap();
+    let matches = settings
+        .exit_pin_hash
+        .as_deref()
+        .is_some_and(|hash| verify_pin(hash, &pin));
+    if !matches {
+        return Err(RecordrError::invalid_input("Incorrect PIN"));
+    }
+    settings.enabled = false;
+    save_kiosk_settings(&settings)?;
+    info!("Kiosk mode disabled");
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use clap::Parser;
+use log::info;
+use std::sync::{Arc, Mutex};
+use tauri::{generate_context, generate_handler};
+
+mod analytics;
+mod approved;
+mod assets;
+mod audio;
+mod audio_protocol;
+mod backups;
+mod cli;
+mod controls;
+mod coverage;
+mod dashboard;
+mod datasets;
+mod errors;
+mod file_utils;
+mod folder_watch;
+mod importers;
+mod integrity;
+mod jobs;
+mod kiosk;
+mod localization;
+mod logging;
+mod migrations;
+mod models;
+mod paths;
+mod presets;
+mod qc;
+mod relink;
+mod report;
+mod rerecord;
+mod rpc;
+mod settings;
+mod skipped;
+mod storage;
+mod temp_files;
+
+use audio::{
+    apply_fade,
+    attach_external_audio,
+    compare_takes_metrics,
+    confirm_sentence,
+    convert_project_audio,
+    detect_project_transients,
+    export_review_audio,
+    get_engine_info,
+    get_input_latency,
+    import_sentence_audio,
+    load_audio_file,
+    pause_auto_record,
+    probe_audio,
+    read_audio_metadata,
+    reconnect_audio_device,
+    resume_auto_record,
+    retrim_project,
+    retrim_sentence,
+    retry_sentence,
+    send_to_external_editor,
+    set_current_sentence,
+    set_input_gain,
+    set_noise_reduction,
+    set_recording_order,
+    skip_sentence,
+    split_session_recording,
+    start_auto_record,
+    start_monitor_mode,
+    start_recording,
+    stitch_recording_segments,
+    stop_auto_record,
+    stop_monitor_mode,
+    stop_recording,
+    trim_audio,
+    undo_audio_edit,
+    update_auto_record_settings,
+    Recorder, // Import the Recorder struct
+};
+
+use cli::{Cli, Command};
+
+use controls::{
+    get_input_controls, list_control_devices, load_control_settings, set_input_controls,
+    InputControls,
+};
+
+use assets::{
+    attach_sentence_asset, load_sentence_asset, play_sentence_asset, remove_sentence_asset,
+    stop_sentence_asset,
+};
+
+use backups::{list_project_backups, restore_project_backup};
+
+use audio_protocol::{handle_audio_request, AUDIO_SCHEME};
+
+use coverage::analyze_script_coverage;
+
+use report::export_qc_report;
+
+use localization::export_localization;
+
+use datasets::export_dataset;
+
+use folder_watch::{unwatch_project_folder, watch_project_folder, FolderWatchState};
+
+use dashboard::get_dashboard_data;
+
+use analytics::{clear_session_analytics, get_session_analytics};
+
+use file_utils::{
+    add_recent_project, add_sentence, create_new_project, delete_sentence, export_sentences,
+    format_timestamp, get_recent_projects, import_sentences, migrate_project_layout, open_project,
+    pin_recent_project, remove_recent_project, rename_project, reorder_sentences, save_project,
+    update_sentence_text,
+};
+
+use jobs::{cancel_job, JobRegistry};
+
+use kiosk::{
+    enter_kiosk_mode, exit_kiosk_mode, get_kiosk_status, guard_commands, kiosk_open_project,
+    load_kiosk_settings,
+};
+
+use presets::{
+    delete_conversion_preset, export_conversion_presets, import_conversion_presets,
+    list_conversion_presets, save_conversion_preset,
+};
+
+use integrity::verify_project;
+use qc::{find_duplicate_sentences, report_detected_language};
+
+use logging::{set_log_forwarding, set_log_level};
+
+use settings::{get_settings, load_settings, update_settings};
+
+use relink::relink_missing_audio;
+use rerecord::{clear_rerecord_flags, flag_for_rerecord, list_flagged_for_rerecord};
+
+use skipped::{clear_skipped, list_skipped};
+
+use storage::encrypt_project;
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // Initialize the logger
+    logging::init();
+
+    // Partial files of a run that crashed are never completed
+    temp_files::clean_up_temp_files();
+
+    #[cfg(desktop)]
+    if let Some(command) = Cli::parse_or_gui().command {
+        let result = match command {
+            Command::Record(args) => cli::record(args),
+            Command::Rpc(args) => match args.listen {
+                Some(address) => rpc::serve_websocket(&address, args.allow_remote, args.input),
+                None => rpc::serve(args.input),
+            },
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    info!("Starting the application");
+
+    // Initialize the Recorder instance inside an Arc and Mutex for shared state
+    // management
+    let recorder = Arc::new(Mutex::new(Recorder::new()));
+
+    // Kiosk mode restricts which commands the frontend may invoke
+    let kiosk = Arc::new(Mutex::new(load_kiosk_settings()));
+
+    // Pedals and MIDI controllers drive the recorder directly
+    let controls = InputControls::start(load_control_settings(), &recorder);
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_fs::init())
+        .register_asynchronous_uri_scheme_protocol(AUDIO_SCHEME, handle_audio_request)
+        .manage(recorder) // Manage the Recorder instance
+        .manage(Arc::clone(&kiosk))
+        .manage(JobRegistry::new())
+        .manage(Mutex::new(load_settings()))
+        .manage(Mutex::new(controls))
+        .manage(FolderWatchState::default())
+        .invoke_handler(guard_commands(
+            kiosk,
+            generate_handler![
+                start_recording,
+                stop_recording,
+                stitch_recording_segments,
+                split_session_recording,
+                start_auto_record,
+                stop_auto_record,
+                start_monitor_mode,
+                stop_monitor_mode,
+                pause_auto_record,
+                resume_auto_record,
+                reconnect_audio_device,
+                set_current_sentence,
+                confirm_sentence,
+                retry_sentence,
+                skip_sentence,
+                set_noise_reduction,
+                set_recording_order,
+                update_auto_record_settings,
+                set_input_gain,
+                get_input_latency,
+                get_settings,
+                update_settings,
+                get_input_controls,
+                set_input_controls,
+                list_control_devices,
+                import_sentences,
+                export_sentences,
+                add_sentence,
+                update_sentence_text,
+                migrate_project_layout,
+                delete_sentence,
+                reorder_sentences,
+                attach_sentence_asset,
+                remove_sentence_asset,
+                load_sentence_asset,
+                play_sentence_asset,
+                stop_sentence_asset,
+                get_recent_projects,
+                add_recent_project,
+                remove_recent_project,
+                pin_recent_project,
+                format_timestamp,
+                create_new_project,
+                open_project,
+                save_project,
+                rename_project,
+                list_project_backups,
+                restore_project_backup,
+                encrypt_project,
+                load_audio_file,
+                read_audio_metadata,
+                probe_audio,
+                trim_audio,
+                apply_fade,
+                undo_audio_edit,
+                retrim_sentence,
+                retrim_project,
+                detect_project_transients,
+                convert_project_audio,
+                import_sentence_audio,
+                attach_external_audio,
+                send_to_external_editor,
+                watch_project_folder,
+                unwatch_project_folder,
+                export_review_audio,
+                list_conversion_presets,
+                save_conversion_preset,
+                delete_conversion_preset,
+                export_conversion_presets,
+                import_conversion_presets,
+                cancel_job,
+                report_detected_language,
+                find_duplicate_sentences,
+                verify_project,
+                get_dashboard_data,
+                get_session_analytics,
+                clear_session_analytics,
+                export_qc_report,
+                export_localization,
+                export_dataset,
+                analyze_script_coverage,
+                get_engine_info,
+                set_log_level,
+                set_log_forwarding,
+                compare_takes_metrics,
+                list_skipped,
+                clear_skipped,
+                flag_for_rerecord,
+                list_flagged_for_rerecord,
+                clear_rerecord_flags,
+                relink_missing_audio,
+                get_kiosk_status,
+                kiosk_open_project,
+                enter_kiosk_mode,
+                exit_kiosk_mode,
+            ]
+        ))
+        .setup(|app| {
+            logging::forward_to(app.handle().clone());
+            #[cfg(target_os = "windows")]
+            attach_media_keys(app);
+            Ok(())
+        })
+        .run(generate_context!())
+        .expect("error while running tauri application");
+}
+
+/// Attaches the media key controls to the main window, as the Windows media
+/// session only delivers keys to a window.
+#[cfg(target_os = "windows")]
+fn attach_media_keys(app: &tauri::App) {
+    use controls::ControlsState;
+    use tauri::Manager;
+
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    match window.hwnd() {
+        Ok(hwnd) => {
+            let recorder = app.state::<Arc<Mutex<Recorder>>>();
+            app.state::<ControlsState>()
+                .lock()
+                .unwrap()
+                .attach_window(hwnd.0 as usize, recorder.inner());
+        }
+        Err(e) => log::warn!("Failed to attach media keys to the window: {}", e),
+    }
+}
@@ -0,0 +1,237 @@
+use crate::audio::{ProgressEvents, WindowEvents};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::jobs::{run_batch, run_job, CancellationToken, JobRegistry};
+use crate::models::{Project, Sentence};
+use crate::storage;
+use csv::WriterBuilder;
+use hound::WavReader;
+use log::error;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+/// Name of the metadata file written next to the exported audio.
+const METADATA_FILE_NAME: &str = "metadata.csv";
+
+/// Column of the metadata CSV delivered to localization vendors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalizationColumn {
+    /// File name of the exported take.
+    Filename,
+    Text,
+    Character,
+    /// Language of the project, as a BCP 47 tag.
+    Language,
+    /// Length of the take in seconds.
+    Duration,
+}
+
+impl LocalizationColumn {
+    fn header(self) -> &'static str {
+        match self {
+            LocalizationColumn::Filename => "filename",
+            LocalizationColumn::Text => "text",
+            LocalizationColumn::Character => "character",
+            LocalizationColumn::Language => "language",
+            LocalizationColumn::Duration => "duration",
+        }
+    }
+}
+
+/// The layout most vendors ask for when none is given.
+const DEFAULT_COLUMNS: [LocalizationColumn; 5] = [
+    LocalizationColumn::Filename,
+    LocalizationColumn::Text,
+    LocalizationColumn::Character,
+    LocalizationColumn::Language,
+    LocalizationColumn::Duration,
+];
+
+/// Length of a take, from its measurements or else from its WAV header.
+fn take_duration_ms(sentence: &Sentence, data: &[u8]) -> Option<u64> {
+    if let Some(stats) = &sentence.stats {
+        return Some(stats.duration_ms);
+    }
+    let reader = WavReader::new(Cursor::new(data)).ok()?;
+    let spec = reader.spec();
+    Some(u64::from(reader.duration()) * 1000 / u64::from(spec.sample_rate.max(1)))
+}
+
+/// `file_name`, numbered `_2`, `_3`… if a take exported earlier already has
+/// that name, e.g. one from another recordings folder. Names are compared
+/// ignoring case, as they are on Windows and macOS.
+fn unique_file_name(used: &mut HashSet<String>, file_name: &str) -> String {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (file_name, String::new()),
+    };
+    let mut name = file_name.to_string();
+    let mut copy = 1;
+    while !used.insert(name.to_lowercase()) {
+        copy += 1;
+        name = format!("{}_{}{}", stem, copy, extension);
+    }
+    name
+}
+
+fn column_value(
+    column: LocalizationColumn,
+    project: &Project,
+    sentence: &Sentence,
+    file_name: &str,
+    duration_ms: Option<u64>,
+) -> String {
+    match column {
+        LocalizationColumn::Filename => file_name.to_string(),
+        LocalizationColumn::Text => sentence.text.clone(),
+        LocalizationColumn::Character => sentence.character.clone().unwrap_or_default(),
+        LocalizationColumn::Language => project.metadata.language.clone().unwrap_or_default(),
+        LocalizationColumn::Duration => duration_ms
+            .map(|ms| format!("{:.3}", ms as f64 / 1000.0))
+            .unwrap_or_default(),
+    }
+}
+
+/// Copies every recorded take into `output_dir` and writes `metadata.csv`
+/// beside them with the chosen columns, defaulting to
+/// `filename,text,character,language,duration`. Takes sharing a file name
+/// are numbered rather than overwriting each other. Takes of encrypted
+/// projects are exported decrypted. Takes are copied several at once, and a
+/// `localization-export-progress` event is emitted as each is done; failed
+/// files are reported and left out of the CSV but do not abort the batch.
+/// Returns the path of the CSV.
+pub(crate) fn write_localization_export(
+    project: &Project,
+    output_dir: &Path,
+    columns: &[LocalizationColumn],
+    delimiter: char,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<PathBuf, RecordrError> {
+    if !delimiter.is_ascii() || delimiter == '"' || delimiter == '\n' {
+        return Err(RecordrError::invalid_input("Invalid delimiter").with_context(delimiter));
+    }
+    let columns = if columns.is_empty() {
+        &DEFAULT_COLUMNS[..]
+    } else {
+        columns
+    };
+    fs::create_dir_all(output_dir)
+        .map_err(|e| RecordrError::from(e).with_context(output_dir.display().to_string()))?;
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter as u8)
+        .from_writer(Vec::new());
+    writer.write_record(columns.iter().map(|column| column.header()))?;
+
+    // Names are given in script order, before the takes are copied in
+    // whatever order they finish.
+    let mut used_names = HashSet::new();
+    let takes: Vec<(&Sentence, &String, String)> = project
+        .sentences
+        .iter()
+        .filter(|s| s.recorded)
+        .filter_map(|s| Some((s, s.audio_file_path.as_ref()?)))
+        .map(|(sentence, path)| {
+            let file_name = unique_file_name(
+                &mut used_names,
+                &Path::new(path)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+            );
+            (sentence, path, file_name)
+        })
+        .collect();
+    let mut durations = vec![None; takes.len()];
+
+    run_batch(
+        &takes,
+        cancel,
+        |(sentence, path, file_name)| {
+            let data = storage::read(Path::new(path))?;
+            let output = output_dir.join(file_name);
+            fs::write(&output, &data)
+                .map_err(|e| RecordrError::from(e).with_context(output.display().to_string()))?;
+            Ok(take_duration_ms(sentence, &data))
+        },
+        |index, result, progress| {
+            let (sentence, path, _) = &takes[index];
+            let error = match result {
+                Ok(duration_ms) => {
+                    durations[index] = Some(duration_ms);
+                    None
+                }
+                Err(e) => {
+                    error!("Failed to export {}: {}", path, e);
+                    Some(e.to_string())
+                }
+            };
+
+            events.notify(
+                "localization-export-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": sentence.id,
+                    "audioFilePath": path,
+                    "error": error,
+                }),
+            );
+        },
+    );
+    cancel.check()?;
+
+    // Rows follow the script, whatever order the takes were copied in.
+    for ((sentence, _, file_name), duration_ms) in takes.iter().zip(durations) {
+        let Some(duration_ms) = duration_ms else {
+            continue;
+        };
+        writer.write_record(
+            columns
+                .iter()
+                .map(|&column| column_value(column, project, sentence, file_name, duration_ms)),
+        )?;
+    }
+
+    let data = writer
+        .into_inner()
+        .map_err(|e| RecordrError::new(ErrorKind::Io, e.to_string()))?;
+    let metadata_path = output_dir.join(METADATA_FILE_NAME);
+    fs::write(&metadata_path, data)
+        .map_err(|e| RecordrError::from(e).with_context(metadata_path.display().to_string()))?;
+    Ok(metadata_path)
+}
+
+/// Exports the recorded takes with a per-file metadata CSV in the layout
+/// game localization vendors ask for. `columns` picks and orders the CSV
+/// columns and `delimiter` defaults to a comma. Passing a `job_id` allows
+/// the export to be cancelled with `cancel_job`.
+#[tauri::command]
+pub async fn export_localization(
+    project: Project,
+    output_dir: String,
+    columns: Option<Vec<LocalizationColumn>>,
+    delimiter: Option<char>,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<String, RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        write_localization_export(
+            &project,
+            Path::new(&output_dir),
+            &columns.unwrap_or_default(),
+            delimiter.unwrap_or(','),
+            &WindowEvents::new(window),
+            &cancel,
+        )
+        .map(|path| path.to_string_lossy().to_string())
+    })
+    .await
+}
@@ -0,0 +1,224 @@
+use crate::errors::{ErrorKind, RecordrError};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Serialize;
+use std::cell::Cell;
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::{Event, Level, Subscriber};
+use tracing_log::{AsLog, NormalizeEvent};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Layer, Registry};
+
+/// Event carrying warnings and errors to the frontend, for an in-app
+/// console.
+pub const LOG_EVENT: &str = "log-event";
+
+/// Filter used unless `RUST_LOG` sets one.
+const DEFAULT_FILTER: &str = "info";
+
+/// Most records forwarded to the frontend per `FORWARD_RATE_WINDOW`. The
+/// rest are dropped and counted, so a failing device logging on every
+/// callback can't flood the windows.
+const FORWARD_RATE_LIMIT: u32 = 20;
+const FORWARD_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Swaps the filter in place when the level is changed at runtime.
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+/// App whose windows receive `log-event`, once it is running.
+static APP: OnceLock<AppHandle> = OnceLock::new();
+/// Whether warnings and errors are forwarded to the frontend at all.
+static FORWARD_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Records forwarded in the current window and those dropped over the
+/// limit.
+struct RateLimit {
+    window_start: Option<Instant>,
+    sent: u32,
+    dropped: u32,
+}
+
+static RATE_LIMIT: Mutex<RateLimit> = Mutex::new(RateLimit {
+    window_start: None,
+    sent: 0,
+    dropped: 0,
+});
+
+impl RateLimit {
+    /// Counts a record, returning `None` if it is over the limit, or else
+    /// how many were dropped in the windows since the last one sent.
+    fn admit(&mut self) -> Option<u32> {
+        let now = Instant::now();
+        let mut dropped = 0;
+        if self
+            .window_start
+            .is_none_or(|start| now.duration_since(start) >= FORWARD_RATE_WINDOW)
+        {
+            self.window_start = Some(now);
+            self.sent = 0;
+            dropped = std::mem::take(&mut self.dropped);
+        }
+        if self.sent >= FORWARD_RATE_LIMIT {
+            self.dropped += 1;
+            return None;
+        }
+        self.sent += 1;
+        Some(dropped)
+    }
+}
+
+thread_local! {
+    /// Set while a record is being forwarded, so anything logged while
+    /// emitting it isn't forwarded in turn.
+    static FORWARDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Payload of `log-event`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEvent {
+    pub level: String,
+    /// Module the record came from, e.g. `recordr_lib::audio::stream`.
+    pub target: String,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Collects an event's message, followed by its other fields as
+/// `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            // Metadata of records bridged from the `log` crate.
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+}
+
+/// Sends warnings and errors to the frontend as `log-event`, unless turned
+/// off, at most `FORWARD_RATE_LIMIT` a second. Dropped records are counted
+/// in a warning sent with the next one forwarded.
+struct ForwardLayer;
+
+impl<S: Subscriber> Layer<S> for ForwardLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        if *metadata.level() > Level::WARN || !FORWARD_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(app) = APP.get() else {
+            return;
+        };
+        if FORWARDING.with(|forwarding| forwarding.get()) {
+            return;
+        }
+        let Some(dropped) = RATE_LIMIT.lock().unwrap().admit() else {
+            return;
+        };
+        FORWARDING.with(|forwarding| forwarding.set(true));
+        if dropped > 0 {
+            let _ = app.emit(
+                LOG_EVENT,
+                LogEvent {
+                    level: "warn".to_string(),
+                    target: module_path!().to_string(),
+                    message: format!("{} log records were not forwarded", dropped),
+                    timestamp: Utc::now(),
+                },
+            );
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let payload = LogEvent {
+            level: metadata.level().as_str().to_lowercase(),
+            target: metadata.target().to_string(),
+            message: visitor.message + &visitor.fields,
+            timestamp: Utc::now(),
+        };
+        let _ = app.emit(LOG_EVENT, payload);
+        FORWARDING.with(|forwarding| forwarding.set(false));
+    }
+}
+
+/**
+ * Sets up logging: records go to stderr, filtered by `RUST_LOG` or `info`
+ * by default, and warnings and errors also go to the frontend once
+ * `forward_to` has been called. Records from the `log` macros are bridged
+ * in, so they show within the session and sentence spans like the rest.
+ */
+pub fn init() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        // Stdout carries JSON-RPC messages in `rpc` mode.
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(ForwardLayer)
+        .try_init();
+    match result {
+        Ok(()) => {
+            let _ = FILTER.set(handle);
+        }
+        Err(e) => eprintln!("Failed to initialize logging: {}", e),
+    }
+}
+
+/// Starts forwarding warnings and errors to the app's windows.
+pub fn forward_to(app: AppHandle) {
+    let _ = APP.set(app);
+}
+
+/// Turns forwarding warnings and errors to the frontend as `log-event` on
+/// or off, until the app exits. They are still logged to stderr.
+#[tauri::command]
+pub fn set_log_forwarding(enabled: bool) {
+    FORWARD_ENABLED.store(enabled, Ordering::Relaxed);
+    info!(
+        "Log forwarding {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+}
+
+/// Changes which records are logged, from a level such as `debug` or
+/// directives like `warn,recordr_lib::audio=trace`, until the app exits.
+#[tauri::command]
+pub fn set_log_level(level: String) -> Result<(), RecordrError> {
+    let filter = EnvFilter::try_new(&level)
+        .map_err(|e| RecordrError::invalid_input(format!("Invalid log level {}: {}", level, e)))?;
+    let handle = FILTER
+        .get()
+        .ok_or_else(|| RecordrError::invalid_state("Logging is not initialized"))?;
+    handle
+        .reload(filter)
+        .map_err(|e| RecordrError::new(ErrorKind::Other, e.to_string()))?;
+    // The `log` macros check their own maximum before reaching the filter.
+    log::set_max_level(LevelFilter::current().as_log());
+    info!("Log level set to {}", level);
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::parse_timestamp;
+use crate::models::RecordingSettings;
+use crate::paths::relative_to;
+use log::info;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Schema version of the project files this build writes. Bump it with
+/// every change to the project model that older files need upgrading for,
+/// and add the upgrade to `MIGRATIONS`.
+pub const PROJECT_SCHEMA_VERSION: u32 = 3;
+
+/// Upgrades from each schema version to the next; entry `n` upgrades a
+/// version `n` project. Files written before versioning are version 0.
+const MIGRATIONS: [fn(&mut Map<String, Value>); PROJECT_SCHEMA_VERSION as usize] =
+    [migrate_v0, migrate_v1, migrate_v2];
+
+fn objects_mut<'a>(
+    object: &'a mut Map<String, Value>,
+    key: &str,
+) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    object
+        .get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Version 1 added review statuses and stored timestamps as RFC 3339.
+fn migrate_v0(project: &mut Map<String, Value>) {
+    if let Some(metadata) = project.get_mut("metadata").and_then(Value::as_object_mut) {
+        for key in ["created_at", "last_modified"] {
+            let Some(timestamp) = metadata.get(key).and_then(Value::as_str) else {
+                continue;
+            };
+            if let Some(parsed) = parse_timestamp(timestamp) {
+                metadata.insert(key.to_string(), Value::String(parsed.to_rfc3339()));
+            }
+        }
+    }
+    project
+        .entry("sentences")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    // Sentences recorded before statuses existed are only marked as
+    // recorded.
+    for sentence in objects_mut(project, "sentences") {
+        if !sentence.contains_key("status") {
+            let recorded = sentence.get("recorded").and_then(Value::as_bool) == Some(true);
+            let status = if recorded { "recorded" } else { "unrecorded" };
+            sentence.insert("status".to_string(), Value::String(status.to_string()));
+        }
+    }
+}
+
+/// Version 2 stored take paths relative to the project directory. Paths
+/// are made relative to the directory recorded in the file, so a project
+/// that has since been moved finds its takes again.
+fn migrate_v1(project: &mut Map<String, Value>) {
+    let Some(directory) = project
+        .get("metadata")
+        .and_then(|metadata| metadata.get("directory"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+    else {
+        return;
+    };
+    for sentence in objects_mut(project, "sentences") {
+        let Some(path) = sentence.get("audio_file_path").and_then(Value::as_str) else {
+            continue;
+        };
+        if let Some(relative) = relative_to(Path::new(path), Path::new(&directory)) {
+            sentence.insert("audio_file_path".to_string(), Value::String(relative));
+        }
+    }
+}
+
+/// Version 3 gave the silence threshold in dBFS. It was a fraction from 0
+/// to 1 before, which nothing used, so it is reset to the default.
+fn migrate_v2(project: &mut Map<String, Value>) {
+    let Some(settings) = project
+        .get_mut("metadata")
+        .and_then(|metadata| metadata.get_mut("recording_settings"))
+        .and_then(Value::as_object_mut)
+    else {
+        return;
+    };
+    if settings.contains_key("silence_threshold") {
+        settings.insert(
+            "silence_threshold".to_string(),
+            Value::from(RecordingSettings::default().silence_threshold),
+        );
+    }
+}
+
+/// Upgrades a parsed project file to the current schema version. Files from
+/// newer versions of the app are refused rather than read with their new
+/// fields dropped.
+pub fn migrate_project(mut project: Value) -> Result<Value, RecordrError> {
+    let object = project
+        .as_object_mut()
+        .ok_or_else(|| RecordrError::new(ErrorKind::Parse, "Project file is not a JSON object"))?;
+    let version = object
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+    if version > PROJECT_SCHEMA_VERSION {
+        return Err(RecordrError::new(
+            ErrorKind::UnsupportedFormat,
+            format!(
+                "Project was saved by a newer version of recordr (schema version {}, this \
+                 version reads up to {})",
+                version, PROJECT_SCHEMA_VERSION
+            ),
+        ));
+    }
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("Upgrading project from schema version {}", from);
+        migrate(object);
+    }
+    object.insert(
+        "schema_version".to_string(),
+        Value::from(PROJECT_SCHEMA_VERSION),
+    );
+    Ok(project)
+}
@@ -1,5 +1,9 @@
+use crate::migrations::PROJECT_SCHEMA_VERSION;
+use crate::paths::normalize;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sentence {
@@ -7,6 +11,172 @@ pub struct Sentence {
     pub text: String,
     pub recorded: bool,
     pub audio_file_path: Option<String>,
+    /// Quality-control issues found with the recorded take.
+    #[serde(default)]
+    pub qc_flags: Vec<QcFlag>,
+    /// Level and timing measurements of the recorded take.
+    #[serde(default)]
+    pub stats: Option<SentenceStats>,
+    /// Passed over during a session without a take.
+    #[serde(default)]
+    pub skipped: bool,
+    /// Free-form reviewer notes.
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub status: SentenceStatus,
+    /// File names of images or other context attached to the prompt, in the
+    /// project's assets directory.
+    #[serde(default)]
+    pub assets: Vec<String>,
+    /// Character speaking the line, for game and animation scripts.
+    #[serde(default)]
+    pub character: Option<String>,
+    /// Set by a reviewer who wants the sentence recorded again; cleared by
+    /// the next take.
+    #[serde(default)]
+    pub rerecord: Option<RerecordFlag>,
+    /// Name of the sentence's take files when its text would give them the
+    /// same name as another sentence's.
+    #[serde(default)]
+    pub file_stem: Option<String>,
+}
+
+/// A reviewer's request to record a sentence again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RerecordFlag {
+    pub reason: Option<String>,
+    pub flagged_at: DateTime<Utc>,
+    /// Revisited, like a skipped sentence, at the end of the next
+    /// auto-record session with `requeue_skipped` on.
+    pub queued: bool,
+}
+
+impl Sentence {
+    /// Sentence that has not been recorded yet.
+    pub fn new(id: usize, text: String) -> Self {
+        Self {
+            id,
+            text,
+            recorded: false,
+            audio_file_path: None,
+            qc_flags: Vec::new(),
+            stats: None,
+            skipped: false,
+            notes: None,
+            tags: Vec::new(),
+            status: SentenceStatus::Unrecorded,
+            assets: Vec::new(),
+            character: None,
+            rerecord: None,
+            file_stem: None,
+        }
+    }
+}
+
+/// Where a sentence stands in review. A new take resets an approved or
+/// rejected sentence to `Recorded`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SentenceStatus {
+    #[default]
+    Unrecorded,
+    Recorded,
+    Approved,
+    Rejected,
+}
+
+impl fmt::Display for SentenceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SentenceStatus::Unrecorded => "unrecorded",
+            SentenceStatus::Recorded => "recorded",
+            SentenceStatus::Approved => "approved",
+            SentenceStatus::Rejected => "rejected",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Measurements of a recorded take, taken when it is written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentenceStats {
+    pub duration_ms: u64,
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    /// Number of samples at full scale.
+    pub clipped_samples: usize,
+    /// Silence kept before the first and after the last detected speech.
+    pub leading_silence_ms: u64,
+    pub trailing_silence_ms: u64,
+    /// Likely breaths, mouth clicks and plosive pops, for editors to clean
+    /// up.
+    #[serde(default)]
+    pub transients: Vec<Transient>,
+}
+
+impl SentenceStats {
+    /// Length of the take between the first and last detected speech.
+    pub fn speech_ms(&self) -> u64 {
+        self.duration_ms
+            .saturating_sub(self.leading_silence_ms + self.trailing_silence_ms)
+    }
+}
+
+/// Kind of short noise found in a take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransientKind {
+    /// Quiet, noisy span between phrases.
+    Breath,
+    /// Very short spike of high-frequency energy, such as a lip smack.
+    Click,
+    /// Short burst of low-frequency energy from a plosive hitting the mic.
+    Pop,
+}
+
+impl TransientKind {
+    /// The serialized name of the kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransientKind::Breath => "breath",
+            TransientKind::Click => "click",
+            TransientKind::Pop => "pop",
+        }
+    }
+}
+
+/// A likely breath, click or pop in a take.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Transient {
+    pub kind: TransientKind,
+    /// Span of the noise from the start of the take.
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub peak_dbfs: f32,
+}
+
+/// A quality-control issue attached to a sentence's take.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QcFlag {
+    /// The take was spoken in a different language than the project's.
+    LanguageMismatch { expected: String, detected: String },
+}
+
+impl QcFlag {
+    /// The serialized `kind` tag of the flag.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QcFlag::LanguageMismatch { .. } => "language_mismatch",
+        }
+    }
+}
+
+fn current_schema_version() -> u32 {
+    PROJECT_SCHEMA_VERSION
 }
 
 impl fmt::Display for Sentence {
@@ -15,9 +185,392 @@ impl fmt::Display for Sentence {
     }
 }
 
-use chrono::{DateTime, Utc};
+/// Encoding used for the WAV files written by the recorder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Wav16,
+    Wav24,
+    Wav32Float,
+}
+
+/// Where auto-record takes its input audio from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputSource {
+    /// The input device named by `device_name`.
+    #[default]
+    Device,
+    /// 16-bit PCM streamed over RTP to a local UDP address, such as
+    /// `0.0.0.0:5004`. The stream format is not negotiated, so it has to be
+    /// given here.
+    Rtp {
+        address: String,
+        channels: u16,
+        sample_rate: u32,
+    },
+}
+
+/// Channel layout of the WAV files written by auto-record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelMode {
+    /// Keep every channel the input provides.
+    #[default]
+    Input,
+    Mono,
+    Stereo,
+}
+
+/// What happens to a take that reaches the maximum take duration without
+/// the narrator falling silent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TakeTimeoutAction {
+    /// Write the audio captured so far as the take and move on.
+    #[default]
+    Keep,
+    /// Delete the take and move on, marking the sentence skipped.
+    Discard,
+}
+
+/// What happens to a take that is interrupted by pausing the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PausePolicy {
+    /// Drop the partial take and move on, marking the sentence skipped.
+    /// Pausing before the narrator has started speaking skips nothing.
+    Discard,
+    /// Keep the audio captured so far and continue the same take on resume.
+    #[default]
+    KeepPartial,
+    /// Drop the partial take and record the sentence again on resume.
+    AutoRetry,
+}
+
+/// Order in which an auto-record session works through its sentences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum RecordingOrder {
+    /// Script order.
+    #[default]
+    Sequential,
+    /// A random order, the same every time for the same seed, so a long
+    /// session mixes short and long lines.
+    Shuffled { seed: u64 },
+    /// Shortest prompts first, in script order among prompts of the same
+    /// length.
+    ShortestFirst,
+    /// Sentences without a take first, then those re-recorded, each in
+    /// script order.
+    UnrecordedFirst,
+}
+
+/// Optional noise suppression applied to each finished take.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseReductionSettings {
+    pub enabled: bool,
+    /// Blend between the original (0.0) and fully denoised (1.0) audio.
+    pub strength: f32,
+}
 
-#[derive(Serialize, Deserialize)]
+impl Default for NoiseReductionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 1.0,
+        }
+    }
+}
+
+/// Filters applied to each take before it is written, to clean up
+/// low-frequency rumble and the DC offset of some interfaces.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    pub high_pass: bool,
+    /// Cutoff of the high-pass filter, from 80 to 120 Hz.
+    pub high_pass_hz: f32,
+    pub remove_dc_offset: bool,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            high_pass: false,
+            high_pass_hz: 100.0,
+            remove_dc_offset: false,
+        }
+    }
+}
+
+/// Turning down the breaths, clicks and pops found in each take before it
+/// is written.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransientSettings {
+    pub attenuate: bool,
+    /// How far they are turned down, from 0 to 40 dB.
+    pub attenuation_db: f32,
+}
+
+impl Default for TransientSettings {
+    fn default() -> Self {
+        Self {
+            attenuate: false,
+            attenuation_db: 12.0,
+        }
+    }
+}
+
+/// Warning when a take sounds like another sentence's take, as when the
+/// narrator reads the wrong line.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateSpeechSettings {
+    pub enabled: bool,
+    /// Similarity, from 0.0 to 1.0, above which two takes are taken to be
+    /// the same line.
+    pub threshold: f32,
+}
+
+impl Default for DuplicateSpeechSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.8,
+        }
+    }
+}
+
+/// Software gain applied to the input before voice detection and writing.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputGainSettings {
+    /// Fixed gain in dB, from -24 to 24.
+    pub gain_db: f32,
+    /// Slowly adjust the gain to keep speech at a steady level.
+    pub agc: bool,
+}
+
+/// Cues telling the narrator when to speak without watching the screen.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CueSettings {
+    /// Emit a `cue` event and play a beep when the recorder is armed for a
+    /// sentence and when its take has been captured.
+    pub enabled: bool,
+    /// Volume of the beep, from 0.0 to 1.0. Zero leaves only the event, for
+    /// a visual cue.
+    pub volume: f32,
+}
+
+impl Default for CueSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            volume: 0.3,
+        }
+    }
+}
+
+/// Continuous recording of a whole session alongside its takes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardTapeSettings {
+    /// Record everything the microphone picks up during the session, with
+    /// the cues mixed in, to an Opus file in the project's `board_tape`
+    /// folder. Only recording devices are taped.
+    pub enabled: bool,
+    pub bitrate_kbps: u32,
+}
+
+impl Default for BoardTapeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bitrate_kbps: 16,
+        }
+    }
+}
+
+/// Pre-processing applied only to the audio the VAD analyses, to make speech
+/// detection more reliable in noisy rooms. Recorded audio is not affected.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VadPreprocessingSettings {
+    /// Cutoff of a high-pass filter removing low-frequency rumble.
+    pub high_pass_hz: Option<f32>,
+    pub gain_db: f32,
+}
+
+/// Audio settings stored with a project so auto-record runs are reproducible
+/// without the frontend re-sending every parameter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingSettings {
+    pub input_source: InputSource,
+    /// Name of the input device; `None` uses the system default.
+    pub device_name: Option<String>,
+    /// Preferred capture sample rate; `None` picks the best supported rate.
+    pub sample_rate: Option<u32>,
+    /// Speech probability from the VAD at or above which audio counts as
+    /// voice, if it is also above the silence threshold. Higher values let
+    /// fewer clicks and breaths through but may miss quiet speech.
+    pub voice_probability: f32,
+    /// Level, in dBFS, below which audio counts as silence even where the
+    /// VAD hears voice, so breaths and room noise neither start nor prolong
+    /// a take.
+    pub silence_threshold: f32,
+    pub silence_duration_ms: u64,
+    pub silence_padding_ms: u64,
+    /// Extra silence required after `silence_duration_ms` before a take is
+    /// finalized. Speech resuming within it continues the same take.
+    pub silence_confirmation_ms: u64,
+    /// Time given to read the prompt before the recorder starts listening.
+    pub pre_sentence_delay_ms: u64,
+    pub output_format: OutputFormat,
+    pub channel_mode: ChannelMode,
+    /// Include the sentence text in `auto-record-start-sentence` events so
+    /// external displays can render prompts without the whole project.
+    pub emit_prompt_text: bool,
+    pub noise_reduction: NoiseReductionSettings,
+    pub filters: FilterSettings,
+    pub transients: TransientSettings,
+    /// Hold a take that sounds like another sentence's take for
+    /// confirmation, even with auto-advance.
+    pub duplicate_speech: DuplicateSpeechSettings,
+    pub input_gain: InputGainSettings,
+    pub vad_preprocessing: VadPreprocessingSettings,
+    /// Write a JSON file describing each take next to its WAV file.
+    pub write_sidecar: bool,
+    /// Also save each take as captured, untrimmed and unprocessed at the
+    /// input's sample rate, to the project's `raw` folder, so mistakes in
+    /// trimming, processing or conversion can be undone from it.
+    pub keep_raw_takes: bool,
+    /// Log the session's device, events, VAD decisions and errors to a
+    /// JSON-lines file in the project's `logs` folder.
+    pub write_session_log: bool,
+    /// Move on to the next sentence as soon as a take is written. When off,
+    /// each take waits for `confirm_sentence` or `retry_sentence`.
+    pub auto_advance: bool,
+    /// Once the last sentence is done, go back over the skipped sentences
+    /// before ending the session.
+    pub requeue_skipped: bool,
+    pub recording_order: RecordingOrder,
+    /// Record sentences that already have a take again. When off, they are
+    /// passed over unless queued to be recorded again or jumped to, so a
+    /// resumed run carries on where the last one stopped.
+    pub overwrite_recorded: bool,
+    /// Number of consecutive takes recorded of each sentence. With more than
+    /// one, take files are numbered, e.g. `Hello_world~take2.wav`.
+    pub takes_per_sentence: u32,
+    /// Takes with less speech than this are discarded and recorded again,
+    /// as they are almost always false triggers, up to a few times in a row
+    /// before a short take is kept. Zero, the default, keeps every take.
+    pub min_take_duration_ms: u64,
+    /// Takes in which voice was detected for less than this in total, such
+    /// as a cough or a noise blip, are deleted and the sentence is armed
+    /// again. Takes without any voice are always rejected.
+    pub min_voiced_ms: u64,
+    /// Handling of a take interrupted by a pause. Suspends and lost devices
+    /// always record the sentence again, as the partial take is unreliable.
+    pub pause_policy: PausePolicy,
+    /// Ask the input device for a small fixed buffer to cut capture latency
+    /// for narrators monitoring themselves. cpal offers no WASAPI exclusive
+    /// mode, so on Windows this is a small shared-mode buffer. Devices that
+    /// refuse it fall back to their default buffer.
+    pub low_latency: bool,
+    /// Longest a take may run once speech is detected, so a session does not
+    /// hang when silence is never reached, e.g. because of fan noise. Zero
+    /// disables the limit.
+    pub max_take_duration_ms: u64,
+    pub take_timeout_action: TakeTimeoutAction,
+    /// Length of a recording sprint in minutes. Once a sprint is over, the
+    /// session pauses after the take in progress and prompts for a rest;
+    /// resuming starts the next sprint. Zero disables sprints.
+    pub sprint_minutes: u32,
+    /// Rest suggested in the prompt between sprints, in minutes.
+    pub rest_minutes: u32,
+    /// Wall-clock length of a session in minutes, pauses included. Once it
+    /// is reached, the session ends after the sentence in progress. Zero
+    /// sets no limit.
+    pub max_session_minutes: u32,
+    /// Sentences to finish in a session before it ends. Zero sets no limit.
+    pub max_session_sentences: usize,
+    /// Speech level the session's takes are compared with, in dBFS. Without
+    /// one, the level measured by the last monitor session is used, and
+    /// without that the first takes of each session set the baseline.
+    pub level_baseline_dbfs: Option<f32>,
+    /// How far, in dB, the rolling speech level may move from the baseline
+    /// before the session warns that the narrator has drifted off-mic. Zero
+    /// disables the check.
+    pub level_drift_threshold_db: f32,
+    pub cues: CueSettings,
+    pub board_tape: BoardTapeSettings,
+    /// Record the session into one continuous WAV file with a marker file
+    /// of the takes, instead of a file per take. The takes are cut out
+    /// afterwards with `split_session_recording`. Not available in
+    /// encrypted projects.
+    pub continuous_session: bool,
+    /// Free space, in MB, the project volume must have to start a session.
+    /// A session pauses itself if the space drops below it. Zero disables
+    /// the checks.
+    pub min_free_space_mb: u64,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            input_source: InputSource::default(),
+            device_name: None,
+            sample_rate: None,
+            voice_probability: 0.5,
+            silence_threshold: -50.0,
+            silence_duration_ms: 2000,
+            silence_padding_ms: 300,
+            silence_confirmation_ms: 0,
+            pre_sentence_delay_ms: 0,
+            output_format: OutputFormat::default(),
+            channel_mode: ChannelMode::default(),
+            emit_prompt_text: false,
+            noise_reduction: NoiseReductionSettings::default(),
+            filters: FilterSettings::default(),
+            transients: TransientSettings::default(),
+            duplicate_speech: DuplicateSpeechSettings::default(),
+            input_gain: InputGainSettings::default(),
+            vad_preprocessing: VadPreprocessingSettings::default(),
+            write_sidecar: false,
+            keep_raw_takes: false,
+            write_session_log: true,
+            auto_advance: true,
+            requeue_skipped: false,
+            recording_order: RecordingOrder::default(),
+            overwrite_recorded: false,
+            takes_per_sentence: 1,
+            min_take_duration_ms: 0,
+            min_voiced_ms: 200,
+            pause_policy: PausePolicy::default(),
+            low_latency: false,
+            max_take_duration_ms: 120_000,
+            take_timeout_action: TakeTimeoutAction::default(),
+            sprint_minutes: 0,
+            rest_minutes: 5,
+            max_session_minutes: 0,
+            max_session_sentences: 0,
+            level_baseline_dbfs: None,
+            level_drift_threshold_db: 6.0,
+            cues: CueSettings::default(),
+            board_tape: BoardTapeSettings::default(),
+            continuous_session: false,
+            min_free_space_mb: 500,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ProjectMetadata {
     pub name: String,
     pub created_version: String,
@@ -25,10 +578,71 @@ pub struct ProjectMetadata {
     pub created_at: DateTime<Utc>,
     pub last_modified: DateTime<Utc>,
     pub directory: String,
+    #[serde(default)]
+    pub recording_settings: RecordingSettings,
+    /// Language the prompts are written in, as a BCP 47 tag such as `en-US`.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Whether the project's files are encrypted with a passphrase.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Name of the project JSON in the project directory. Older projects
+    /// have none and are stored as `<name>.json`.
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// Whether to keep an `approved/` directory of links to each sentence's
+    /// approved take, refreshed whenever the project is saved.
+    #[serde(default)]
+    pub approved_links: bool,
+    #[serde(default)]
+    pub layout: ProjectLayout,
+}
+
+/// Where a project keeps its audio, relative to the project directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectLayout {
+    /// Folder takes and session recordings are written to. Empty, as in
+    /// projects from before layouts, writes them next to the project file.
+    pub recordings: String,
+}
+
+impl ProjectLayout {
+    /// Folder new projects record into.
+    pub const DEFAULT_RECORDINGS: &'static str = "recordings";
+
+    /// Checks that the recordings folder lies within the project directory.
+    pub fn validate(&self) -> Result<(), String> {
+        let inside = Path::new(&self.recordings)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if inside {
+            Ok(())
+        } else {
+            Err(format!(
+                "Recordings folder {} is not inside the project directory",
+                self.recordings
+            ))
+        }
+    }
+
+    /// Directory the takes of the project in `project_directory` go in.
+    pub fn recordings_path(&self, project_directory: &Path) -> PathBuf {
+        let project_directory = normalize(project_directory);
+        if self.recordings.is_empty() {
+            project_directory
+        } else {
+            project_directory.join(&self.recordings)
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Project {
+    /// Version of the project file layout, for upgrading older files. New
+    /// projects sent by the frontend are at the current version.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub metadata: ProjectMetadata,
     pub sentences: Vec<Sentence>,
 }
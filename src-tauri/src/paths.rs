@@ -0,0 +1,129 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Longest path the Windows file APIs take without the `\\?\` prefix.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Whether paths differing only in case name the same file on the
+/// platform's usual file systems.
+const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Resolves `.` and `..` components without touching the file system, so
+/// it also works for paths that don't exist yet.
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !cleaned.pop() {
+                    cleaned.push(component);
+                }
+            }
+            other => cleaned.push(other),
+        }
+    }
+    cleaned
+}
+
+/// Drops the verbatim `\\?\` prefix, so paths are stored in the form users
+/// and other tools expect. `\\?\UNC\server\share` becomes
+/// `\\server\share`.
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(share) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", share))
+    } else if let Some(local) = text.strip_prefix(r"\\?\") {
+        PathBuf::from(local)
+    } else {
+        path
+    }
+}
+
+/**
+ * Normalizes a project or audio path for storing and comparing: relative
+ * paths are made absolute, `.` and `..` are resolved and any verbatim
+ * prefix is dropped. Symlinks and mapped network drives are kept as given,
+ * as resolving a mapped drive to its UNC share would change paths users
+ * recognise and have stored.
+ */
+pub fn normalize(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    strip_verbatim(clean(&absolute))
+}
+
+/// Normalized path as a string to compare, folded to lower case where the
+/// file system ignores case.
+fn comparison_key(path: &Path) -> String {
+    let key = normalize(path).to_string_lossy().into_owned();
+    if CASE_INSENSITIVE {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// Whether two paths name the same file, allowing for differences in case
+/// where the file system ignores it, `..` components and, for files that
+/// exist, symlinks and mapped drives.
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    comparison_key(a) == comparison_key(b)
+        || matches!(
+            (a.canonicalize(), b.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// Whether `path` is `directory` or inside it, compared like `same_path`
+/// but without touching the file system.
+pub fn is_within(path: &Path, directory: &Path) -> bool {
+    let (path, directory) = (comparison_key(path), comparison_key(directory));
+    Path::new(&path).starts_with(Path::new(&directory))
+}
+
+/// `path` relative to `directory`, if it lies within it, with `/`
+/// separators so it reads the same on every platform.
+pub fn relative_to(path: &Path, directory: &Path) -> Option<String> {
+    if !is_within(path, directory) {
+        return None;
+    }
+    let depth = normalize(directory).components().count();
+    let parts: Vec<_> = normalize(path)
+        .components()
+        .skip(depth)
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("/"))
+}
+
+/**
+ * Form of `path` to hand to the file system. On Windows, paths beyond
+ * `MAX_PATH`, common for deep project folders on network shares, get the
+ * verbatim prefix so they can be opened at all. Elsewhere paths are used
+ * as they are.
+ */
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if text.len() < MAX_PATH || text.starts_with(r"\\?\") || !path.is_absolute() {
+        return path.to_path_buf();
+    }
+    // Verbatim paths skip all parsing, so they must be clean and use
+    // backslashes only.
+    let cleaned = clean(path).to_string_lossy().replace('/', r"\");
+    match cleaned.strip_prefix(r"\\") {
+        Some(share) => PathBuf::from(format!(r"\\?\UNC\{}", share)),
+        None => PathBuf::from(format!(r"\\?\{}", cleaned)),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
@@ -0,0 +1,193 @@
+use crate::errors::RecordrError;
+use crate::file_utils::app_data_file_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// Container format produced by a conversion preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Wav,
+    Flac,
+    Opus,
+}
+
+/// A named set of output parameters shared by the batch exporter and the CLI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversionPreset {
+    pub name: String,
+    pub format: AudioFormat,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Bit depth for lossless formats.
+    pub bit_depth: Option<u16>,
+    /// Target bitrate for lossy formats, in kbps.
+    pub bitrate_kbps: Option<u32>,
+    /// Built-in presets cannot be modified or deleted.
+    #[serde(default)]
+    pub built_in: bool,
+}
+
+fn built_in_presets() -> Vec<ConversionPreset> {
+    vec![
+        ConversionPreset {
+            name: "asr-16k-mono-wav".into(),
+            format: AudioFormat::Wav,
+            sample_rate: 16000,
+            channels: 1,
+            bit_depth: Some(16),
+            bitrate_kbps: None,
+            built_in: true,
+        },
+        ConversionPreset {
+            name: "tts-22k-mono-wav".into(),
+            format: AudioFormat::Wav,
+            sample_rate: 22050,
+            channels: 1,
+            bit_depth: Some(16),
+            bitrate_kbps: None,
+            built_in: true,
+        },
+        ConversionPreset {
+            name: "podcast-48k-flac".into(),
+            format: AudioFormat::Flac,
+            sample_rate: 48000,
+            channels: 1,
+            bit_depth: Some(24),
+            bitrate_kbps: None,
+            built_in: true,
+        },
+        ConversionPreset {
+            name: "review-opus-64k".into(),
+            format: AudioFormat::Opus,
+            sample_rate: 48000,
+            channels: 1,
+            bit_depth: None,
+            bitrate_kbps: Some(64),
+            built_in: true,
+        },
+    ]
+}
+
+fn get_user_presets_path() -> PathBuf {
+    app_data_file_path("conversion_presets.json")
+}
+
+fn load_user_presets() -> Result<Vec<ConversionPreset>, RecordrError> {
+    let path = get_user_presets_path();
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let contents = fs::read_to_string(&path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn save_user_presets(presets: &[ConversionPreset]) -> Result<(), RecordrError> {
+    let json = serde_json::to_string_pretty(presets)?;
+    fs::write(get_user_presets_path(), json)?;
+    Ok(())
+}
+
+fn validate_preset(preset: &ConversionPreset) -> Result<(), RecordrError> {
+    if preset.name.trim().is_empty() {
+        return Err(RecordrError::invalid_input("Preset name cannot be empty"));
+    }
+    if preset.sample_rate == 0 || preset.channels == 0 {
+        return Err(RecordrError::invalid_input(
+            "Preset sample rate and channels must be non-zero",
+        ));
+    }
+    match preset.format {
+        AudioFormat::Wav | AudioFormat::Flac if preset.bit_depth.is_none() => Err(
+            RecordrError::invalid_input("Lossless presets require a bit depth"),
+        ),
+        AudioFormat::Opus if preset.bitrate_kbps.is_none() => Err(RecordrError::invalid_input(
+            "Lossy presets require a bitrate",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns every preset, built-in presets first.
+pub fn all_presets() -> Result<Vec<ConversionPreset>, RecordrError> {
+    let mut presets = built_in_presets();
+    presets.extend(load_user_presets()?);
+    Ok(presets)
+}
+
+/// Looks up a preset by name.
+pub fn find_preset(name: &str) -> Result<ConversionPreset, RecordrError> {
+    all_presets()?
+        .into_iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| RecordrError::not_found(format!("Unknown conversion preset: {}", name)))
+}
+
+#[tauri::command]
+pub fn list_conversion_presets() -> Result<Vec<ConversionPreset>, RecordrError> {
+    all_presets()
+}
+
+/// Adds a user preset, replacing any existing user preset of the same name.
+#[tauri::command]
+pub fn save_conversion_preset(
+    mut preset: ConversionPreset,
+) -> Result<Vec<ConversionPreset>, RecordrError> {
+    validate_preset(&preset)?;
+    if built_in_presets().iter().any(|p| p.name == preset.name) {
+        return Err(RecordrError::invalid_input(format!(
+            "Cannot overwrite built-in preset: {}",
+            preset.name
+        )));
+    }
+    preset.built_in = false;
+
+    let mut presets = load_user_presets()?;
+    presets.retain(|p| p.name != preset.name);
+    presets.push(preset);
+    save_user_presets(&presets)?;
+    all_presets()
+}
+
+#[tauri::command]
+pub fn delete_conversion_preset(name: String) -> Result<Vec<ConversionPreset>, RecordrError> {
+    let mut presets = load_user_presets()?;
+    presets.retain(|p| p.name != name);
+    save_user_presets(&presets)?;
+    all_presets()
+}
+
+/// Writes the user presets to a JSON file that can be shared with other
+/// machines.
+#[tauri::command]
+pub fn export_conversion_presets(file_path: String) -> Result<(), RecordrError> {
+    let json = serde_json::to_string_pretty(&load_user_presets()?)?;
+    fs::write(file_path, json)?;
+    Ok(())
+}
+
+/// Imports presets from a shared JSON file, replacing user presets with the
+/// same name. Entries that clash with built-in presets are rejected.
+#[tauri::command]
+pub fn import_conversion_presets(file_path: String) -> Result<Vec<ConversionPreset>, RecordrError> {
+    let contents = fs::read_to_string(file_path)?;
+    let imported: Vec<ConversionPreset> = serde_json::from_str(&contents)?;
+
+    let built_in = built_in_presets();
+    let mut presets = load_user_presets()?;
+    for mut preset in imported {
+        validate_preset(&preset)?;
+        if built_in.iter().any(|p| p.name == preset.name) {
+            return Err(RecordrError::invalid_input(format!(
+                "Cannot overwrite built-in preset: {}",
+                preset.name
+            )));
+        }
+        preset.built_in = false;
+        presets.retain(|p| p.name != preset.name);
+        presets.push(preset);
+    }
+    save_user_presets(&presets)?;
+    all_presets()
+}
@@ -0,0 +1,120 @@
+use crate::errors::RecordrError;
+use crate::file_utils::write_project;
+use crate::jobs::run_blocking;
+use crate::models::{Project, QcFlag};
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Sentences whose texts are identical once normalized. Duplicates share an
+/// audio filename, so recording one overwrites the other's take.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    /// Normalized text shared by the duplicates.
+    pub text: String,
+    /// Row numbers in the import file, or sentence IDs for a project audit.
+    pub ids: Vec<usize>,
+}
+
+/// Lowercases the text, drops punctuation and collapses whitespace, so
+/// trivially different prompts compare equal.
+pub fn normalize_text(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Groups `(id, text)` pairs by normalized text, returning only the groups
+/// with more than one entry, in order of first appearance.
+pub fn find_duplicates<'a>(
+    entries: impl IntoIterator<Item = (usize, &'a str)>,
+) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    let mut index_by_text: HashMap<String, usize> = HashMap::new();
+    for (id, text) in entries {
+        let text = normalize_text(text);
+        match index_by_text.get(&text) {
+            Some(&index) => groups[index].ids.push(id),
+            None => {
+                index_by_text.insert(text.clone(), groups.len());
+                groups.push(DuplicateGroup {
+                    text,
+                    ids: vec![id],
+                });
+            }
+        }
+    }
+    groups.retain(|group| group.ids.len() > 1);
+    groups
+}
+
+/// Lists the sentences of a project that duplicate each other.
+#[tauri::command]
+pub fn find_duplicate_sentences(project: Project) -> Vec<DuplicateGroup> {
+    find_duplicates(project.sentences.iter().map(|s| (s.id, s.text.as_str())))
+}
+
+/// Primary language subtag of a BCP 47 tag, so `en-US` and `en` compare
+/// equal.
+pub(crate) fn primary_language(tag: &str) -> String {
+    tag.split(['-', '_'])
+        .next()
+        .unwrap_or(tag)
+        .trim()
+        .to_lowercase()
+}
+
+/// Replaces any language mismatch flag on the sentence with one reflecting
+/// `detected`. Returns whether the take was flagged.
+fn update_language_flag(
+    project: &mut Project,
+    sentence_id: usize,
+    detected: &str,
+) -> Result<bool, RecordrError> {
+    let expected = project.metadata.language.clone().ok_or_else(|| {
+        RecordrError::invalid_state("Project language not set").with_context(&project.metadata.name)
+    })?;
+    let sentence = project
+        .sentences
+        .iter_mut()
+        .find(|s| s.id == sentence_id)
+        .ok_or_else(|| RecordrError::not_found(format!("Sentence {} not found", sentence_id)))?;
+
+    sentence
+        .qc_flags
+        .retain(|flag| !matches!(flag, QcFlag::LanguageMismatch { .. }));
+    if primary_language(&expected) == primary_language(detected) {
+        return Ok(false);
+    }
+
+    warn!(
+        "Sentence {} was spoken in {} but the project language is {}",
+        sentence_id, detected, expected
+    );
+    sentence.qc_flags.push(QcFlag::LanguageMismatch {
+        expected,
+        detected: detected.to_string(),
+    });
+    Ok(true)
+}
+
+/// Records the language detected in a sentence's take by a speech recognizer
+/// and flags the take when it differs from the project language. The project
+/// is saved and returned with the updated flags.
+#[tauri::command]
+pub async fn report_detected_language(
+    mut project: Project,
+    sentence_id: usize,
+    detected_language: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        update_language_flag(&mut project, sentence_id, &detected_language)?;
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
@@ -0,0 +1,141 @@
+use crate::errors::RecordrError;
+use crate::file_utils::write_project;
+use crate::jobs::run_blocking;
+use crate::models::Project;
+use log::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Adds the files under `directory` named in `wanted` to `found`, by name.
+/// Hidden folders, such as the project backups, and symlinked folders are
+/// skipped.
+fn find_files(
+    directory: &Path,
+    wanted: &HashSet<OsString>,
+    found: &mut HashMap<OsString, Vec<PathBuf>>,
+) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Skipping {:?}: {}", directory, e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name();
+        if file_type.is_dir() {
+            if !name.to_string_lossy().starts_with('.') {
+                find_files(&entry.path(), wanted, found);
+            }
+        } else if wanted.contains(&name) {
+            found.entry(name).or_default().push(entry.path());
+        }
+    }
+}
+
+/// Number of trailing components `a` and `b` have in common.
+fn shared_suffix(a: &Path, b: &Path) -> usize {
+    a.components()
+        .rev()
+        .zip(b.components().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// Candidate for the missing take at `path`: the only file with its name,
+/// or the one whose folders best match the take's old ones, e.g. the file
+/// in `recordings/` over a copy exported elsewhere. Ties are ambiguous.
+fn best_candidate<'a>(path: &Path, candidates: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let mut ranked: Vec<_> = candidates
+        .iter()
+        .map(|candidate| (shared_suffix(path, candidate), candidate))
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0));
+    match ranked.as_slice() {
+        [(_, only)] => Some(only),
+        [(best, candidate), (next, _), ..] if best > next => Some(candidate),
+        _ => None,
+    }
+}
+
+/**
+ * Finds the takes of sentences whose audio file no longer exists among the
+ * files under `search_dir`, by file name, and points the sentences at
+ * them. For projects whose takes were moved or that were shared without
+ * their folder layout. Takes that can't be found, or that more than one
+ * file could be, are left as they are. The project is saved and returned.
+ */
+#[tauri::command]
+pub async fn relink_missing_audio(
+    mut project: Project,
+    search_dir: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let search_dir = Path::new(&search_dir);
+        if !search_dir.is_dir() {
+            return Err(RecordrError::not_found("Folder not found")
+                .with_context(search_dir.to_string_lossy()));
+        }
+        let missing: Vec<usize> = project
+            .sentences
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                s.audio_file_path
+                    .as_deref()
+                    .is_some_and(|path| !Path::new(path).exists())
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if missing.is_empty() {
+            return Ok(project);
+        }
+
+        let wanted: HashSet<OsString> = missing
+            .iter()
+            .filter_map(|&index| project.sentences[index].audio_file_path.as_deref())
+            .filter_map(|path| Path::new(path).file_name())
+            .map(OsString::from)
+            .collect();
+        let mut found = HashMap::new();
+        find_files(search_dir, &wanted, &mut found);
+
+        let mut relinked = 0;
+        for index in missing {
+            let sentence = &mut project.sentences[index];
+            let Some(path) = sentence.audio_file_path.as_deref().map(PathBuf::from) else {
+                continue;
+            };
+            let candidates = path
+                .file_name()
+                .and_then(|name| found.get(name))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            match best_candidate(&path, candidates) {
+                Some(candidate) => {
+                    sentence.audio_file_path = Some(candidate.to_string_lossy().to_string());
+                    relinked += 1;
+                }
+                None if candidates.is_empty() => {
+                    warn!("No file found for the take of sentence {}", sentence.id)
+                }
+                None => warn!(
+                    "{} files could be the take of sentence {}; leaving it unlinked",
+                    candidates.len(),
+                    sentence.id
+                ),
+            }
+        }
+        debug!("Relinked {} takes", relinked);
+        if relinked > 0 {
+            write_project(&project)?;
+        }
+        Ok(project)
+    })
+    .await
+}
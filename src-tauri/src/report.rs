@@ -0,0 +1,235 @@
+use crate::audio::{spectrogram_png, ProgressEvents, WindowEvents};
+use crate::dashboard::dashboard_data;
+use crate::errors::RecordrError;
+use crate::jobs::{run_batch, run_job, CancellationToken, JobRegistry};
+use crate::models::{Project, QcFlag, Sentence, TransientKind};
+use crate::storage;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Local;
+use log::warn;
+use std::ffi::OsStr;
+use std::path::Path;
+use tauri::State;
+
+/// Size of the spectrogram thumbnails, in pixels.
+const THUMBNAIL_WIDTH: usize = 240;
+const THUMBNAIL_HEIGHT: usize = 64;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;width:100%}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}\
+th{background:#f3f3f3}td.num{text-align:right;white-space:nowrap}\
+tr.flagged td{background:#fff4e5}img{display:block}";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn flag_label(flag: &QcFlag) -> String {
+    match flag {
+        QcFlag::LanguageMismatch { expected, detected } => {
+            format!(
+                "Language mismatch: expected {}, heard {}",
+                expected, detected
+            )
+        }
+    }
+}
+
+/// QC issues of a sentence's take, including clipping and the breaths,
+/// clicks and pops found by its stats.
+fn issues(sentence: &Sentence) -> Vec<String> {
+    let mut issues: Vec<String> = sentence.qc_flags.iter().map(flag_label).collect();
+    let Some(stats) = &sentence.stats else {
+        return issues;
+    };
+    if stats.clipped_samples > 0 {
+        issues.push(format!("Clipping: {} samples", stats.clipped_samples));
+    }
+    for (kind, label) in [
+        (TransientKind::Breath, "Breaths"),
+        (TransientKind::Click, "Clicks"),
+        (TransientKind::Pop, "Pops"),
+    ] {
+        let count = stats.transients.iter().filter(|t| t.kind == kind).count();
+        if count > 0 {
+            issues.push(format!("{}: {}", label, count));
+        }
+    }
+    issues
+}
+
+/// Inline image of the take's spectrogram, or nothing if the take can't be
+/// read.
+fn spectrogram_cell(sentence: &Sentence) -> String {
+    let Some(path) = sentence
+        .audio_file_path
+        .as_deref()
+        .filter(|_| sentence.recorded)
+    else {
+        return String::new();
+    };
+    match spectrogram_png(Path::new(path), THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT) {
+        Ok(png) => format!(
+            "<img alt=\"Spectrogram\" width=\"{}\" height=\"{}\" src=\"data:image/png;base64,{}\">",
+            THUMBNAIL_WIDTH,
+            THUMBNAIL_HEIGHT,
+            BASE64.encode(png)
+        ),
+        Err(e) => {
+            warn!("Failed to render spectrogram of {}: {}", path, e);
+            String::new()
+        }
+    }
+}
+
+fn sentence_row(sentence: &Sentence) -> String {
+    let issues = issues(sentence);
+    let class = if issues.is_empty() {
+        ""
+    } else {
+        " class=\"flagged\""
+    };
+    let measurements = match &sentence.stats {
+        Some(stats) => format!(
+            "<td class=\"num\">{:.2} s</td><td class=\"num\">{:.1} dBFS</td>\
+             <td class=\"num\">{:.1} dBFS</td><td class=\"num\">{} / {} ms</td>",
+            stats.duration_ms as f64 / 1000.0,
+            stats.peak_dbfs,
+            stats.rms_dbfs,
+            stats.leading_silence_ms,
+            stats.trailing_silence_ms
+        ),
+        None => "<td></td><td></td><td></td><td></td>".to_string(),
+    };
+    format!(
+        "<tr{}><td class=\"num\">{}</td><td>{}</td><td>{}</td><td>{}</td>{}\
+         <td>{}</td><td>{}</td><td>{}</td></tr>\n",
+        class,
+        sentence.id,
+        escape(&sentence.text),
+        sentence.status,
+        issues
+            .iter()
+            .map(|issue| escape(issue))
+            .collect::<Vec<_>>()
+            .join("<br>"),
+        measurements,
+        escape(sentence.notes.as_deref().unwrap_or_default()),
+        escape(&sentence.tags.join(", ")),
+        spectrogram_cell(sentence)
+    )
+}
+
+/// Writes a self-contained HTML QC report of the project to `path`: a
+/// summary followed by each sentence's status, flags, measurements and a
+/// spectrogram thumbnail of its take. Sentences are rendered several at
+/// once, with a `qc-report-progress` event as each is done. Only HTML is
+/// written, so a path with any other extension is rejected rather than
+/// given HTML under the wrong name.
+pub(crate) fn write_qc_report(
+    project: &Project,
+    path: &Path,
+    events: &dyn ProgressEvents,
+    cancel: &CancellationToken,
+) -> Result<(), RecordrError> {
+    let is_html = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"));
+    if !is_html {
+        return Err(RecordrError::invalid_input(
+            "QC reports are exported as HTML only; print the report from a browser for a PDF",
+        )
+        .with_context(path.display().to_string()));
+    }
+    // A report written into an encrypted project would be sealed and
+    // unreadable in a browser.
+    if storage::has_key(path) {
+        return Err(RecordrError::invalid_state(
+            "QC reports can't be saved inside an encrypted project; choose a folder outside it",
+        ));
+    }
+    let summary = dashboard_data(project);
+    let name = escape(&project.metadata.name);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <title>QC report: {}</title><style>{}</style></head><body>\n\
+         <h1>QC report: {}</h1>\n<p>Generated {}</p>\n<ul>\
+         <li>Sentences recorded: {} of {} ({:.1}%)</li>\
+         <li>Total recorded duration: {:.1} min</li>",
+        name,
+        STYLE,
+        name,
+        Local::now().format("%Y-%m-%d %H:%M"),
+        summary.recorded_sentences,
+        summary.total_sentences,
+        summary.completion_percent,
+        summary.total_duration_ms as f64 / 60_000.0
+    );
+    for (flag, count) in &summary.flag_counts {
+        html.push_str(&format!("<li>Flagged {}: {}</li>", escape(flag), count));
+    }
+    html.push_str(
+        "</ul>\n<table><tr><th>ID</th><th>Text</th><th>Status</th><th>Flags</th>\
+         <th>Duration</th><th>Peak</th><th>RMS</th><th>Leading / trailing silence</th>\
+         <th>Notes</th><th>Tags</th><th>Spectrogram</th></tr>\n",
+    );
+
+    // Rows, with their spectrograms, are rendered several at once and put
+    // back in script order.
+    let mut rows = vec![String::new(); project.sentences.len()];
+    run_batch(
+        &project.sentences,
+        cancel,
+        |sentence| Ok(sentence_row(sentence)),
+        |index, result: Result<String, RecordrError>, progress| {
+            if let Ok(row) = result {
+                rows[index] = row;
+            }
+            events.notify(
+                "qc-report-progress",
+                serde_json::json!({
+                    "current": progress.completed,
+                    "failed": progress.failed,
+                    "total": progress.total,
+                    "id": project.sentences[index].id,
+                }),
+            );
+        },
+    );
+    cancel.check()?;
+
+    html.extend(rows);
+    html.push_str("</table>\n</body></html>\n");
+    storage::write(path, html.as_bytes())
+}
+
+/// Exports a QC report of the project as a single HTML file, for delivery
+/// alongside the dataset. There is no PDF output; as the spectrograms are
+/// embedded, the file can be shared on its own and printed to PDF from a
+/// browser. Passing a `job_id` allows the
+/// export to be cancelled with `cancel_job`. Reports can't be saved inside
+/// an encrypted project.
+#[tauri::command]
+pub async fn export_qc_report(
+    project: Project,
+    file_path: String,
+    job_id: Option<String>,
+    window: tauri::Window,
+    jobs: State<'_, JobRegistry>,
+) -> Result<(), RecordrError> {
+    run_job(&jobs, job_id, move |cancel| {
+        write_qc_report(
+            &project,
+            Path::new(&file_path),
+            &WindowEvents::new(window),
+            &cancel,
+        )
+    })
+    .await
+}
@@ -0,0 +1,70 @@
+use crate::errors::RecordrError;
+use crate::file_utils::write_project;
+use crate::jobs::run_blocking;
+use crate::models::{Project, RerecordFlag, Sentence};
+use chrono::Utc;
+
+/// Flags a sentence to be recorded again, keeping the reviewer's reason,
+/// so takes can be triaged while listening through them. With `enqueue`,
+/// the sentence is also revisited at the end of the next auto-record
+/// session, like a skipped one. The project is saved and returned.
+#[tauri::command]
+pub async fn flag_for_rerecord(
+    mut project: Project,
+    sentence_id: usize,
+    reason: Option<String>,
+    enqueue: Option<bool>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        let sentence = project
+            .sentences
+            .iter_mut()
+            .find(|s| s.id == sentence_id)
+            .ok_or_else(|| {
+                RecordrError::not_found(format!("Sentence {} not found", sentence_id))
+            })?;
+        sentence.rerecord = Some(RerecordFlag {
+            reason: reason
+                .map(|reason| reason.trim().to_string())
+                .filter(|reason| !reason.is_empty()),
+            flagged_at: Utc::now(),
+            queued: enqueue.unwrap_or(false),
+        });
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
+
+/// Lists the sentences flagged to be recorded again, for a session of
+/// their own.
+#[tauri::command]
+pub fn list_flagged_for_rerecord(project: Project) -> Vec<Sentence> {
+    project
+        .sentences
+        .into_iter()
+        .filter(|s| s.rerecord.is_some())
+        .collect()
+}
+
+/// Clears the re-record flag from the given sentences, or from every
+/// sentence when no IDs are given. The project is saved and returned.
+#[tauri::command]
+pub async fn clear_rerecord_flags(
+    mut project: Project,
+    sentence_ids: Option<Vec<usize>>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        for sentence in project.sentences.iter_mut() {
+            if sentence_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&sentence.id))
+            {
+                sentence.rerecord = None;
+            }
+        }
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
@@ -0,0 +1,1209 @@
+use crate::analytics::{clear_sessions, summarize_sessions, SessionAnalyticsRecorder};
+use crate::assets::{
+    attach_sentence_asset, load_sentence_asset, play_sentence_asset, remove_sentence_asset,
+    stop_sentence_asset,
+};
+use crate::audio::{
+    attach_audio, auto_record_settings, compare_takes_metrics, conversion_target, convert_project,
+    detect_transients_in_takes, editing, emit_file_updated, export_review_copies, get_engine_info,
+    import_project_audio, load_audio_file, probe_audio, read_audio_metadata,
+    retrim_project_sentence, retrim_project_takes, send_take_for_editing, split_session_recording,
+    start_sentence_index, stitch_recording_segments, AudioImport, AutoRecordEvent, ProgressEvents,
+    Recorder, RecorderEvents, ReviewFormat, TakeDecision, TakeInterrupt, AUTO_RECORD_EVENT,
+    DEFAULT_MIN_FREE_SPACE_MB, DEFAULT_SEGMENT_OVERLAP_MS,
+};
+use crate::backups::{list_project_backups, restore_backup};
+use crate::controls::{
+    list_control_devices, load_control_settings, replace_input_controls, ControlSettings,
+    ControlsState, InputControls,
+};
+use crate::coverage::analyze_script_coverage;
+use crate::dashboard::get_dashboard_data;
+use crate::datasets::write_dataset;
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::{
+    add_recent_project, add_sentence, create_project, delete_sentence, export_sentences,
+    format_timestamp, get_recent_projects, import_sentences, migrate_project_layout,
+    pin_recent_project, remove_recent_project, rename_project_file, reorder_sentences,
+    save_with_backup, unlock_and_read_project, update_sentence_text, RecentProject,
+};
+use crate::folder_watch::{start_watch, stop_watch, FolderWatchState};
+use crate::importers::ImportOptions;
+use crate::integrity::verify_project;
+use crate::jobs::{run_blocking, run_job, JobRegistry};
+use crate::localization::{write_localization_export, LocalizationColumn};
+use crate::logging::{set_log_forwarding, set_log_level};
+use crate::models::{Project, ProjectLayout, RecordingOrder, RecordingSettings, Sentence};
+use crate::presets::{
+    delete_conversion_preset, export_conversion_presets, import_conversion_presets,
+    list_conversion_presets, save_conversion_preset, AudioFormat, ConversionPreset,
+};
+use crate::qc::{find_duplicate_sentences, report_detected_language};
+use crate::relink::relink_missing_audio;
+use crate::report::write_qc_report;
+use crate::rerecord::{clear_rerecord_flags, flag_for_rerecord, list_flagged_for_rerecord};
+use crate::settings::{load_settings, replace_settings, AppSettings, SettingsState};
+use crate::skipped::{clear_skipped, list_skipped};
+use crate::storage::encrypt_project;
+use chrono::{DateTime, Utc};
+use crossbeam_channel::{unbounded, Sender};
+use log::{debug, error, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::header::{AUTHORIZATION, ORIGIN};
+use tungstenite::http::StatusCode;
+use tungstenite::Message;
+use uuid::Uuid;
+
+/// Standard JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// Code of errors returned by a command. The `RecordrError` is in `data`.
+const COMMAND_ERROR: i32 = -32000;
+
+/// Requests of one client run at once. Once there are this many, reading
+/// the next waits for the oldest to finish, so a client sending requests
+/// faster than they complete can't pile up tasks without limit.
+const MAX_PENDING_REQUESTS: usize = 64;
+/// How long a WebSocket connection waits for a message before sending
+/// the responses and notifications queued for it.
+const WEBSOCKET_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    /// Missing for notifications, which get no response.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<RecordrError> for RpcError {
+    fn from(error: RecordrError) -> Self {
+        Self {
+            code: COMMAND_ERROR,
+            message: error.message.clone(),
+            data: serde_json::to_value(error).ok(),
+        }
+    }
+}
+
+/// Deserializes a request's named params, which use the same camelCase names
+/// the frontend passes to `invoke`.
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// Binds the named params of a request to local variables.
+macro_rules! params {
+    ($params:expr => $($name:ident: $ty:ty),+ $(,)?) => {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            $($name: $ty),+
+        }
+        let Params { $($name),+ } = parse_params::<Params>($params)?;
+    };
+}
+
+fn reply<T: Serialize>(result: Result<T, RecordrError>) -> Result<Value, RpcError> {
+    let value = result?;
+    serde_json::to_value(value).map_err(|e| RecordrError::from(e).into())
+}
+
+/// Where responses and notifications are written. Events are sent to every
+/// client as notifications named like the window events the app receives,
+/// such as `auto-record-event` and `convert-progress`.
+enum Output {
+    /// Stdout, one JSON message per line.
+    Stdio(Mutex<io::Stdout>),
+    /// Messages queued for each open WebSocket connection.
+    WebSocket(Mutex<Vec<Sender<String>>>),
+}
+
+impl Output {
+    fn write(&self, message: &Value) {
+        match self {
+            Output::Stdio(stdout) => {
+                let mut stdout = stdout.lock().unwrap();
+                let result = writeln!(stdout, "{}", message).and_then(|_| stdout.flush());
+                if let Err(e) = result {
+                    error!("Failed to write JSON-RPC message: {}", e);
+                }
+            }
+            Output::WebSocket(clients) => {
+                let message = message.to_string();
+                // Connections that have closed are dropped.
+                clients
+                    .lock()
+                    .unwrap()
+                    .retain(|client| client.send(message.clone()).is_ok());
+            }
+        }
+    }
+
+    fn add_client(&self, client: Sender<String>) {
+        if let Output::WebSocket(clients) = self {
+            clients.lock().unwrap().push(client);
+        }
+    }
+}
+
+impl ProgressEvents for Output {
+    fn notify(&self, event: &str, payload: Value) {
+        self.write(&json!({ "jsonrpc": "2.0", "method": event, "params": payload }));
+    }
+}
+
+impl RecorderEvents for Output {
+    fn send(&self, event: AutoRecordEvent) {
+        match serde_json::to_value(&event) {
+            Ok(payload) => self.notify(AUTO_RECORD_EVENT, payload),
+            Err(e) => error!("Failed to serialize event: {}", e),
+        }
+    }
+}
+
+/// JSON-RPC 2.0 interface to the recorder, offering the app's commands
+/// under the same names and params, so other frontends and test drivers can
+/// operate it without the app window. The kiosk commands are left out, as
+/// they only restrict the window's own frontend.
+pub struct RpcServer {
+    recorder: Arc<Mutex<Recorder>>,
+    jobs: JobRegistry,
+    settings: SettingsState,
+    controls: ControlsState,
+    folder_watch: FolderWatchState,
+    output: Arc<Output>,
+}
+
+impl RpcServer {
+    /// Creates a server whose recordings hear `input`, if given, instead of
+    /// the input device.
+    fn new(output: Output, input: Option<PathBuf>) -> Self {
+        let mut recorder = Recorder::new();
+        recorder.set_input_file(input);
+        let recorder = Arc::new(Mutex::new(recorder));
+        let controls = InputControls::start(load_control_settings(), &recorder);
+        Self {
+            recorder,
+            jobs: JobRegistry::new(),
+            settings: Mutex::new(load_settings()),
+            controls: Mutex::new(controls),
+            folder_watch: FolderWatchState::default(),
+            output: Arc::new(output),
+        }
+    }
+
+    /// Handles one line of input, returning the response to send, if any.
+    async fn handle(self: Arc<Self>, line: String) -> Option<Value> {
+        let request = serde_json::from_str::<Value>(&line)
+            .map_err(|e| RpcError::new(PARSE_ERROR, e.to_string()))
+            .and_then(|value| {
+                serde_json::from_value::<Request>(value)
+                    .map_err(|e| RpcError::new(INVALID_REQUEST, e.to_string()))
+            });
+        let request = match request {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            Ok(_) => {
+                let error = RpcError::new(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported");
+                return Some(json!({ "jsonrpc": "2.0", "id": null, "error": error }));
+            }
+            Err(error) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": error })),
+        };
+
+        debug!("JSON-RPC call: {}", request.method);
+        let result = self.dispatch(&request.method, request.params).await;
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        })
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            // Recording
+            "start_recording" => {
+                params!(params =>
+                    filename: String,
+                    segment_length_secs: Option<u64>,
+                    segment_overlap_ms: Option<u64>,
+                    min_free_space_mb: Option<u64>,
+                    sentence: Option<Sentence>,
+                    project_directory: Option<String>,
+                );
+                reply(self.recorder.lock().unwrap().start_recording(
+                    filename,
+                    segment_length_secs.map(Duration::from_secs),
+                    Duration::from_millis(segment_overlap_ms.unwrap_or(DEFAULT_SEGMENT_OVERLAP_MS)),
+                    min_free_space_mb.unwrap_or(DEFAULT_MIN_FREE_SPACE_MB),
+                    sentence,
+                    project_directory,
+                ))
+            }
+            "stop_recording" => reply(self.recorder.lock().unwrap().stop_recording()),
+            "stitch_recording_segments" => {
+                params!(params => manifest_path: String, output_path: String);
+                reply(stitch_recording_segments(manifest_path, output_path).await)
+            }
+            "split_session_recording" => {
+                params!(params => project: Project, markers_path: String);
+                reply(split_session_recording(project, markers_path).await)
+            }
+            "start_auto_record" => {
+                params!(params =>
+                    sentences: Vec<Sentence>,
+                    start_index: Option<usize>,
+                    start_sentence_id: Option<usize>,
+                    session_id: Option<String>,
+                    project_directory: String,
+                    layout: Option<ProjectLayout>,
+                    recording_settings: Option<RecordingSettings>,
+                    silence_threshold: Option<f32>,
+                    silence_duration: Option<u64>,
+                    silence_padding: Option<u64>,
+                    overwrite: Option<bool>,
+                );
+                let app_settings = self.settings.lock().unwrap().clone();
+                let mut settings = auto_record_settings(
+                    recording_settings,
+                    silence_threshold,
+                    silence_duration,
+                    silence_padding,
+                    &app_settings,
+                );
+                if let Some(overwrite) = overwrite {
+                    settings.overwrite_recorded = overwrite;
+                }
+                let events = SessionAnalyticsRecorder::wrap(
+                    Arc::clone(&self.output) as Arc<dyn RecorderEvents>,
+                    &project_directory,
+                    app_settings.session_analytics,
+                );
+                let start_index = start_sentence_index(&sentences, start_index, start_sentence_id)?;
+                reply(self.recorder.lock().unwrap().start_auto_record(
+                    sentences,
+                    start_index,
+                    session_id,
+                    project_directory,
+                    layout.unwrap_or_default(),
+                    settings,
+                    events,
+                ))
+            }
+            "stop_auto_record" => reply(self.recorder.lock().unwrap().stop_auto_record()),
+            "start_monitor_mode" => {
+                params!(params =>
+                    recording_settings: Option<RecordingSettings>,
+                    silence_threshold: Option<f32>,
+                    silence_duration: Option<u64>,
+                    silence_padding: Option<u64>,
+                );
+                let settings = auto_record_settings(
+                    recording_settings,
+                    silence_threshold,
+                    silence_duration,
+                    silence_padding,
+                    &self.settings.lock().unwrap(),
+                );
+                let events = Arc::clone(&self.output);
+                reply(
+                    self.recorder
+                        .lock()
+                        .unwrap()
+                        .start_monitor_mode(settings, events),
+                )
+            }
+            "stop_monitor_mode" => reply(Ok(self.recorder.lock().unwrap().stop_monitor_mode())),
+            "pause_auto_record" => reply(self.recorder.lock().unwrap().pause_auto_record()),
+            "resume_auto_record" => reply(self.recorder.lock().unwrap().resume_auto_record()),
+            "reconnect_audio_device" => {
+                params!(params => device_name: Option<String>);
+                reply(
+                    self.recorder
+                        .lock()
+                        .unwrap()
+                        .reconnect_audio_device(device_name),
+                )
+            }
+            "set_current_sentence" => {
+                params!(params => index: Option<usize>, sentence_id: Option<usize>);
+                reply(
+                    self.recorder
+                        .lock()
+                        .unwrap()
+                        .set_current_sentence(index, sentence_id),
+                )
+            }
+            "confirm_sentence" => reply(
+                self.recorder
+                    .lock()
+                    .unwrap()
+                    .decide_take(TakeDecision::Confirm),
+            ),
+            "retry_sentence" => reply(
+                self.recorder
+                    .lock()
+                    .unwrap()
+                    .interrupt_take(TakeInterrupt::Retry),
+            ),
+            "skip_sentence" => reply(
+                self.recorder
+                    .lock()
+                    .unwrap()
+                    .interrupt_take(TakeInterrupt::Skip),
+            ),
+            "set_noise_reduction" => {
+                params!(params => enabled: bool, strength: f32);
+                reply(
+                    self.recorder
+                        .lock()
+                        .unwrap()
+                        .set_noise_reduction(enabled, strength),
+                )
+            }
+            "update_auto_record_settings" => {
+                params!(params =>
+                    voice_probability: Option<f32>,
+                    silence_threshold: Option<f32>,
+                    silence_duration: Option<u64>,
+                    silence_padding: Option<u64>,
+                );
+                reply(self.recorder.lock().unwrap().update_auto_record_settings(
+                    voice_probability,
+                    silence_threshold,
+                    silence_duration,
+                    silence_padding,
+                ))
+            }
+            "set_recording_order" => {
+                params!(params => strategy: RecordingOrder);
+                reply(self.recorder.lock().unwrap().set_recording_order(strategy))
+            }
+            "set_input_gain" => {
+                params!(params => gain_db: f32, agc: Option<bool>);
+                reply(self.recorder.lock().unwrap().set_input_gain(gain_db, agc))
+            }
+            "get_input_latency" => reply(self.recorder.lock().unwrap().input_latency()),
+
+            // Settings and controls
+            "get_settings" => reply(Ok(self.settings.lock().unwrap().clone())),
+            "update_settings" => {
+                params!(params => new_settings: AppSettings);
+                reply(replace_settings(new_settings, &self.settings))
+            }
+            "get_input_controls" => reply(Ok(self.controls.lock().unwrap().settings().clone())),
+            "set_input_controls" => {
+                params!(params => settings: ControlSettings);
+                reply(replace_input_controls(
+                    settings,
+                    &self.controls,
+                    &self.recorder,
+                ))
+            }
+            "list_control_devices" => reply(list_control_devices()),
+
+            // Projects
+            "import_sentences" => {
+                params!(params => file_path: String, project_dir: String, format: Option<String>, options: Option<ImportOptions>);
+                reply(import_sentences(&file_path, &project_dir, format, options).await)
+            }
+            "export_sentences" => {
+                params!(params => project: Project, file_path: String);
+                reply(export_sentences(project, file_path).await)
+            }
+            "add_sentence" => {
+                params!(params => project: Project, text: String, position: Option<usize>);
+                reply(add_sentence(project, text, position).await)
+            }
+            "update_sentence_text" => {
+                params!(params => project: Project, sentence_id: usize, text: String);
+                reply(update_sentence_text(project, sentence_id, text).await)
+            }
+            "migrate_project_layout" => {
+                params!(params => project: Project, layout: ProjectLayout);
+                reply(migrate_project_layout(project, layout).await)
+            }
+            "delete_sentence" => {
+                params!(params => project: Project, sentence_id: usize);
+                reply(delete_sentence(project, sentence_id).await)
+            }
+            "reorder_sentences" => {
+                params!(params => project: Project, sentence_ids: Vec<usize>);
+                reply(reorder_sentences(project, sentence_ids).await)
+            }
+            "attach_sentence_asset" => {
+                params!(params => project: Project, sentence_id: usize, file_path: String);
+                reply(attach_sentence_asset(project, sentence_id, file_path).await)
+            }
+            "remove_sentence_asset" => {
+                params!(params => project: Project, sentence_id: usize, asset: String);
+                reply(remove_sentence_asset(project, sentence_id, asset).await)
+            }
+            "load_sentence_asset" => {
+                params!(params => project: Project, asset: String);
+                reply(load_sentence_asset(project, asset).await)
+            }
+            "play_sentence_asset" => {
+                params!(params => project: Project, asset: String);
+                reply(play_sentence_asset(project, asset).await)
+            }
+            "stop_sentence_asset" => reply(Ok(stop_sentence_asset())),
+            "get_recent_projects" => {
+                params!(params => prune_missing: Option<bool>);
+                reply(Ok(get_recent_projects(prune_missing)))
+            }
+            "add_recent_project" => {
+                params!(params => new_project: RecentProject, app_version: String);
+                reply(Ok(add_recent_project(new_project, app_version)))
+            }
+            "remove_recent_project" => {
+                params!(params => path: String);
+                reply(Ok(remove_recent_project(path)))
+            }
+            "pin_recent_project" => {
+                params!(params => path: String, pinned: Option<bool>);
+                reply(pin_recent_project(path, pinned))
+            }
+            "format_timestamp" => {
+                params!(params =>
+                    timestamp: DateTime<Utc>,
+                    utc_offset_minutes: Option<i32>,
+                    format: Option<String>,
+                );
+                reply(format_timestamp(timestamp, utc_offset_minutes, format))
+            }
+            "create_new_project" => {
+                params!(params => parent_dir: Option<String>, project: Project);
+                let settings = self.settings.lock().unwrap().clone();
+                reply(run_blocking(move || create_project(parent_dir, project, &settings)).await)
+            }
+            "open_project" => {
+                params!(params => file_path: String, passphrase: Option<String>);
+                reply(
+                    run_blocking(move || {
+                        unlock_and_read_project(&file_path, passphrase.as_deref())
+                    })
+                    .await,
+                )
+            }
+            "save_project" => {
+                params!(params => project: Project);
+                let keep = self.settings.lock().unwrap().project_backups;
+                reply(run_blocking(move || save_with_backup(project, keep)).await)
+            }
+            "rename_project" => {
+                params!(params => project: Project, name: String);
+                let stable = self.settings.lock().unwrap().stable_project_file;
+                reply(run_blocking(move || rename_project_file(project, &name, stable)).await)
+            }
+            "list_project_backups" => {
+                params!(params => file_path: String);
+                reply(list_project_backups(file_path).await)
+            }
+            "restore_project_backup" => {
+                params!(params => file_path: String, backup: String, passphrase: Option<String>);
+                reply(
+                    run_blocking(move || {
+                        restore_backup(&file_path, &backup, passphrase.as_deref())
+                    })
+                    .await,
+                )
+            }
+            "encrypt_project" => {
+                params!(params => project: Project, passphrase: String);
+                reply(encrypt_project(project, passphrase).await)
+            }
+
+            // Audio files
+            "load_audio_file" => {
+                params!(params =>
+                    file_path: String,
+                    offset: Option<u64>,
+                    length: Option<u64>,
+                    max_size: Option<u64>,
+                );
+                reply(load_audio_file(file_path, offset, length, max_size).await)
+            }
+            "read_audio_metadata" => {
+                params!(params => path: String);
+                reply(read_audio_metadata(path).await)
+            }
+            "attach_external_audio" => {
+                params!(params =>
+                    project: Project,
+                    sentence_id: usize,
+                    path: String,
+                    copy: bool,
+                );
+                reply(
+                    run_blocking(move || {
+                        attach_audio(project, sentence_id, Path::new(&path), copy)
+                    })
+                    .await,
+                )
+            }
+            "probe_audio" => {
+                params!(params => path: String);
+                reply(probe_audio(path).await)
+            }
+            "trim_audio" => {
+                params!(params => file_path: String, start_ms: u64, end_ms: u64);
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_blocking(move || {
+                        editing::trim_audio(Path::new(&file_path), start_ms, end_ms)?;
+                        emit_file_updated(&*output, &file_path);
+                        Ok(())
+                    })
+                    .await,
+                )
+            }
+            "apply_fade" => {
+                params!(params => file_path: String, fade_in_ms: u64, fade_out_ms: u64);
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_blocking(move || {
+                        editing::apply_fade(Path::new(&file_path), fade_in_ms, fade_out_ms)?;
+                        emit_file_updated(&*output, &file_path);
+                        Ok(())
+                    })
+                    .await,
+                )
+            }
+            "undo_audio_edit" => {
+                params!(params => file_path: String);
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_blocking(move || {
+                        editing::undo_audio_edit(Path::new(&file_path))?;
+                        emit_file_updated(&*output, &file_path);
+                        Ok(())
+                    })
+                    .await,
+                )
+            }
+            "compare_takes_metrics" => {
+                params!(params =>
+                    project: Project,
+                    sentence_id: usize,
+                    transcripts: Option<HashMap<String, String>>,
+                );
+                reply(compare_takes_metrics(project, sentence_id, transcripts).await)
+            }
+            "get_engine_info" => reply(Ok(get_engine_info())),
+            "set_log_level" => {
+                params!(params => level: String);
+                reply(set_log_level(level))
+            }
+            "set_log_forwarding" => {
+                params!(params => enabled: bool);
+                set_log_forwarding(enabled);
+                reply(Ok(()))
+            }
+
+            // Batch jobs
+            "retrim_sentence" => {
+                params!(params =>
+                    project: Project,
+                    sentence_id: usize,
+                    silence_padding_ms: Option<u64>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_blocking(move || {
+                        retrim_project_sentence(project, sentence_id, silence_padding_ms, &*output)
+                    })
+                    .await,
+                )
+            }
+            "retrim_project" => {
+                params!(params =>
+                    project: Project,
+                    silence_padding_ms: Option<u64>,
+                    job_id: Option<String>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        retrim_project_takes(project, silence_padding_ms, &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "detect_project_transients" => {
+                params!(params => project: Project, job_id: Option<String>);
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        detect_transients_in_takes(project, &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "convert_project_audio" => {
+                params!(params =>
+                    project: Project,
+                    target_sample_rate: Option<u32>,
+                    target_format: Option<AudioFormat>,
+                    bit_depth: Option<u16>,
+                    preset: Option<String>,
+                    job_id: Option<String>,
+                );
+                let target = conversion_target(
+                    target_sample_rate,
+                    target_format,
+                    bit_depth,
+                    preset,
+                    &self.settings.lock().unwrap(),
+                )?;
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        convert_project(project, target, &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "import_sentence_audio" => {
+                params!(params =>
+                    project: Project,
+                    imports: Vec<AudioImport>,
+                    job_id: Option<String>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        import_project_audio(project, imports, &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "send_to_external_editor" => {
+                params!(params => project: Project, sentence_id: usize);
+                let events: Arc<dyn ProgressEvents> = Arc::clone(&self.output) as _;
+                reply(
+                    run_blocking(move || {
+                        send_take_for_editing(&project, sentence_id, events)
+                            .map(|path| path.to_string_lossy().to_string())
+                    })
+                    .await,
+                )
+            }
+            "watch_project_folder" => {
+                params!(params => project: Project);
+                let events: Arc<dyn ProgressEvents> = Arc::clone(&self.output) as _;
+                reply(start_watch(&project, events, &self.folder_watch))
+            }
+            "unwatch_project_folder" => reply(Ok(stop_watch(&self.folder_watch))),
+            "export_review_audio" => {
+                params!(params =>
+                    project: Project,
+                    format: ReviewFormat,
+                    bitrate_kbps: u32,
+                    job_id: Option<String>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        export_review_copies(&project, format, bitrate_kbps, &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "cancel_job" => {
+                params!(params => job_id: String);
+                reply(Ok(self.jobs.cancel(&job_id)))
+            }
+
+            // Conversion presets
+            "list_conversion_presets" => reply(list_conversion_presets()),
+            "save_conversion_preset" => {
+                params!(params => preset: ConversionPreset);
+                reply(save_conversion_preset(preset))
+            }
+            "delete_conversion_preset" => {
+                params!(params => name: String);
+                reply(delete_conversion_preset(name))
+            }
+            "export_conversion_presets" => {
+                params!(params => file_path: String);
+                reply(export_conversion_presets(file_path))
+            }
+            "import_conversion_presets" => {
+                params!(params => file_path: String);
+                reply(import_conversion_presets(file_path))
+            }
+
+            // Review and reports
+            "report_detected_language" => {
+                params!(params =>
+                    project: Project,
+                    sentence_id: usize,
+                    detected_language: String,
+                );
+                reply(report_detected_language(project, sentence_id, detected_language).await)
+            }
+            "find_duplicate_sentences" => {
+                params!(params => project: Project);
+                reply(Ok(find_duplicate_sentences(project)))
+            }
+            "verify_project" => {
+                params!(params => project: Project);
+                reply(verify_project(project).await)
+            }
+            "get_dashboard_data" => {
+                params!(params => project: Project);
+                reply(get_dashboard_data(project).await)
+            }
+            "get_session_analytics" => {
+                params!(params =>
+                    project_directory: Option<String>,
+                    since: Option<DateTime<Utc>>,
+                );
+                reply(
+                    run_blocking(move || {
+                        Ok(summarize_sessions(project_directory.as_deref(), since))
+                    })
+                    .await,
+                )
+            }
+            "clear_session_analytics" => reply(run_blocking(clear_sessions).await),
+            "export_qc_report" => {
+                params!(params => project: Project, file_path: String, job_id: Option<String>);
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        write_qc_report(&project, Path::new(&file_path), &*output, &cancel)
+                    })
+                    .await,
+                )
+            }
+            "export_localization" => {
+                params!(params =>
+                    project: Project,
+                    output_dir: String,
+                    columns: Option<Vec<LocalizationColumn>>,
+                    delimiter: Option<char>,
+                    job_id: Option<String>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        write_localization_export(
+                            &project,
+                            Path::new(&output_dir),
+                            &columns.unwrap_or_default(),
+                            delimiter.unwrap_or(','),
+                            &*output,
+                            &cancel,
+                        )
+                        .map(|path| path.to_string_lossy().to_string())
+                    })
+                    .await,
+                )
+            }
+            "export_dataset" => {
+                params!(params =>
+                    project: Project,
+                    output_dir: String,
+                    format: String,
+                    speaker: Option<String>,
+                    job_id: Option<String>,
+                );
+                let output = Arc::clone(&self.output);
+                reply(
+                    run_job(&self.jobs, job_id, move |cancel| {
+                        write_dataset(
+                            &project,
+                            Path::new(&output_dir),
+                            &format,
+                            speaker.as_deref(),
+                            &*output,
+                            &cancel,
+                        )
+                        .map(|paths| {
+                            paths
+                                .iter()
+                                .map(|path| path.to_string_lossy().to_string())
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .await,
+                )
+            }
+            "analyze_script_coverage" => {
+                params!(params => sentences: Vec<Sentence>, language: String);
+                reply(Ok(analyze_script_coverage(sentences, language)))
+            }
+            "list_skipped" => {
+                params!(params => project: Project);
+                reply(Ok(list_skipped(project)))
+            }
+            "clear_skipped" => {
+                params!(params => project: Project, sentence_ids: Option<Vec<usize>>);
+                reply(clear_skipped(project, sentence_ids).await)
+            }
+            "flag_for_rerecord" => {
+                params!(params => project: Project, sentence_id: usize, reason: Option<String>, enqueue: Option<bool>);
+                reply(flag_for_rerecord(project, sentence_id, reason, enqueue).await)
+            }
+            "list_flagged_for_rerecord" => {
+                params!(params => project: Project);
+                reply(Ok(list_flagged_for_rerecord(project)))
+            }
+            "clear_rerecord_flags" => {
+                params!(params => project: Project, sentence_ids: Option<Vec<usize>>);
+                reply(clear_rerecord_flags(project, sentence_ids).await)
+            }
+            "relink_missing_audio" => {
+                params!(params => project: Project, search_dir: String);
+                reply(relink_missing_audio(project, search_dir).await)
+            }
+
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", method),
+            )),
+        }
+    }
+}
+
+/// Requests of one client in progress, at most `MAX_PENDING_REQUESTS`.
+#[derive(Default)]
+struct PendingRequests(Vec<JoinHandle<()>>);
+
+impl PendingRequests {
+    /// Runs a request, handing its response to `respond`. Waits for the
+    /// oldest request first if the limit has been reached.
+    fn spawn(
+        &mut self,
+        server: &Arc<RpcServer>,
+        line: String,
+        respond: impl FnOnce(Value) + Send + 'static,
+    ) {
+        self.0.retain(|request| !request.inner().is_finished());
+        if self.0.len() >= MAX_PENDING_REQUESTS {
+            debug!("{} JSON-RPC requests in progress; waiting", self.0.len());
+            let oldest = self.0.remove(0);
+            let _ = tauri::async_runtime::block_on(oldest);
+        }
+        let server = Arc::clone(server);
+        self.0.push(tauri::async_runtime::spawn(async move {
+            if let Some(response) = server.handle(line).await {
+                respond(response);
+            }
+        }));
+    }
+
+    fn finish(self) {
+        for request in self.0 {
+            let _ = tauri::async_runtime::block_on(request);
+        }
+    }
+}
+
+/// Serves JSON-RPC requests read line by line from stdin until it is
+/// closed. Requests run concurrently, so a running job can be cancelled,
+/// and responses may arrive out of order. Once stdin is closed, requests in
+/// progress are finished and any auto-record session is stopped.
+pub fn serve(input: Option<PathBuf>) -> Result<(), RecordrError> {
+    info!("Serving JSON-RPC on stdin");
+    let server = Arc::new(RpcServer::new(
+        Output::Stdio(Mutex::new(io::stdout())),
+        input,
+    ));
+    let mut pending = PendingRequests::default();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let output = Arc::clone(&server.output);
+        pending.spawn(&server, line, move |response| output.write(&response));
+    }
+
+    pending.finish();
+    let mut recorder = server.recorder.lock().unwrap();
+    recorder.stop_monitor_mode();
+    if recorder.stop_auto_record().is_ok() {
+        recorder.wait_auto_record();
+    }
+    Ok(())
+}
+
+/// Serves JSON-RPC over WebSocket connections to `address` until the
+/// process is stopped, one request, response or notification per text
+/// message. Each connection gets the responses to its own requests, while
+/// events go to every connection. Sessions outlive the connection that
+/// started them, so a remote can reconnect to one.
+///
+/// Clients authenticate with a token printed at startup, sent as
+/// `Authorization: Bearer <token>`. Only loopback addresses are served
+/// unless `allow_remote` is set.
+pub fn serve_websocket(
+    address: &str,
+    allow_remote: bool,
+    input: Option<PathBuf>,
+) -> Result<(), RecordrError> {
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|e| RecordrError::from(e).with_context(address))?
+        .collect::<Vec<_>>();
+    if !allow_remote && !addresses.iter().all(|address| address.ip().is_loopback()) {
+        return Err(RecordrError::invalid_input(
+            "Only loopback addresses are served without --allow-remote",
+        )
+        .with_context(address));
+    }
+    let listener = TcpListener::bind(&addresses[..])
+        .map_err(|e| RecordrError::from(e).with_context(address))?;
+    let token: Arc<str> = Uuid::new_v4().simple().to_string().into();
+    info!("Serving JSON-RPC on ws://{}", listener.local_addr()?);
+    println!("JSON-RPC token: {}", token);
+    let server = Arc::new(RpcServer::new(
+        Output::WebSocket(Mutex::new(Vec::new())),
+        input,
+    ));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept JSON-RPC connection: {}", e);
+                continue;
+            }
+        };
+        let server = Arc::clone(&server);
+        let token = Arc::clone(&token);
+        thread::spawn(move || {
+            let peer = stream
+                .peer_addr()
+                .map_or_else(|_| "unknown".to_string(), |peer| peer.to_string());
+            debug!("JSON-RPC client {} connected", peer);
+            match serve_connection(&server, stream, &token) {
+                Ok(()) => debug!("JSON-RPC client {} disconnected", peer),
+                Err(e) => warn!("JSON-RPC connection to {} failed: {}", peer, e),
+            }
+        });
+    }
+    Ok(())
+}
+
+fn websocket_error(e: impl ToString) -> RecordrError {
+    RecordrError::new(ErrorKind::Other, e.to_string())
+}
+
+fn reject(status: StatusCode, message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Compares tokens in time independent of where they differ.
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Accepts a handshake that carries the token. Browsers send an `Origin`
+/// with every WebSocket handshake, and any page open in one could reach a
+/// loopback port, so those are refused outright.
+fn check_handshake(request: &Request, token: &str) -> Result<(), ErrorResponse> {
+    if request.headers().contains_key(ORIGIN) {
+        return Err(reject(
+            StatusCode::FORBIDDEN,
+            "Browser clients are not accepted",
+        ));
+    }
+    let presented = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| tokens_match(presented, token)) {
+        return Err(reject(StatusCode::UNAUTHORIZED, "Missing or wrong token"));
+    }
+    Ok(())
+}
+
+/// Handles the requests of one WebSocket client until it disconnects, then
+/// finishes those still in progress.
+fn serve_connection(
+    server: &Arc<RpcServer>,
+    stream: TcpStream,
+    token: &str,
+) -> Result<(), RecordrError> {
+    let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+        check_handshake(request, token)?;
+        Ok(response)
+    })
+    .map_err(websocket_error)?;
+    // Reads time out so queued messages are sent while the client is quiet.
+    socket
+        .get_ref()
+        .set_read_timeout(Some(WEBSOCKET_POLL_INTERVAL))?;
+    let (outgoing, queued) = unbounded();
+    server.output.add_client(outgoing.clone());
+    let mut pending = PendingRequests::default();
+    let result = loop {
+        match socket.read() {
+            Ok(Message::Text(line)) => {
+                let outgoing = outgoing.clone();
+                pending.spawn(server, line, move |response| {
+                    let _ = outgoing.send(response.to_string());
+                });
+            }
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => break Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => break Err(websocket_error(e)),
+        }
+        let sent = queued
+            .try_iter()
+            .try_for_each(|message| socket.send(Message::Text(message)));
+        if let Err(e) = sent {
+            break Err(websocket_error(e));
+        }
+    };
+    pending.finish();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn test_directory() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("recordr-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Handles a request on a thread of its own, as the runtime may, and
+    /// returns its result.
+    fn call(server: &Arc<RpcServer>, method: &str, params: Value) -> Value {
+        let line = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let server = Arc::clone(server);
+        let response =
+            thread::spawn(move || tauri::async_runtime::block_on(server.handle(line.to_string())))
+                .join()
+                .unwrap()
+                .unwrap();
+        assert!(response.get("error").is_none(), "{}", response);
+        response["result"].clone()
+    }
+
+    #[test]
+    fn test_manual_recording_stops_from_another_thread() {
+        let directory = test_directory();
+        let input = directory.join("input.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for i in 0..SAMPLE_RATE {
+            writer.write_sample((i % 100) as i16 * 100).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let server = Arc::new(RpcServer::new(
+            Output::Stdio(Mutex::new(io::stdout())),
+            Some(input),
+        ));
+        let take = directory.join("recordings").join("take.wav");
+        call(
+            &server,
+            "start_recording",
+            json!({ "filename": take.to_string_lossy() }),
+        );
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(
+            call(&server, "stop_recording", json!({})),
+            json!("Recording stopped")
+        );
+
+        // The stream was stopped and the file finalized, so its header
+        // gives the length of the audio written.
+        let reader = WavReader::open(&take).unwrap();
+        assert_eq!(reader.spec().sample_rate, SAMPLE_RATE);
+        assert!(reader.duration() > 0);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    fn handshake(headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::builder().uri("/");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_handshake_requires_token_and_no_origin() {
+        let status = |headers: &[(&str, &str)]| {
+            check_handshake(&handshake(headers), "secret").map_err(|response| response.status())
+        };
+        assert!(status(&[("Authorization", "Bearer secret")]).is_ok());
+        assert_eq!(status(&[]), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(
+            status(&[("Authorization", "Bearer secreT")]),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            status(&[
+                ("Authorization", "Bearer secret"),
+                ("Origin", "http://localhost")
+            ]),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::audio::check_silence_threshold;
+use crate::errors::RecordrError;
+use crate::file_utils::app_data_file_path;
+use crate::models::RecordingSettings;
+use crate::presets::find_preset;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+/// Backups kept of each project file unless configured otherwise.
+const DEFAULT_PROJECT_BACKUPS: usize = 20;
+
+/// Version of the settings file. Version 1 stores silence thresholds in
+/// dBFS; files without a version predate it.
+const SETTINGS_VERSION: u32 = 1;
+
+/// App-wide defaults, used whenever a command is not given a value and for
+/// the recording settings of new projects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Missing from files saved before versioning, which read as 0.
+    #[serde(default)]
+    pub settings_version: u32,
+    /// Input device used when a project does not name one; `None` uses the
+    /// system default.
+    pub default_device: Option<String>,
+    pub silence_threshold: f32,
+    pub silence_duration_ms: u64,
+    pub silence_padding_ms: u64,
+    /// Conversion preset used when an export names no target.
+    pub default_export_preset: Option<String>,
+    /// Directory new projects are created in when no other is given.
+    pub recordings_root: Option<String>,
+    /// Number of backups kept of each project file; 0 turns backups off.
+    pub project_backups: usize,
+    /// Store new projects as `project.recordr.json` rather than after their
+    /// name, so renaming them does not move the file.
+    pub stable_project_file: bool,
+    /// Keep counts and timings of each auto-record session in a local
+    /// store, for the session analytics. Off unless the user opts in.
+    pub session_analytics: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        let recording = RecordingSettings::default();
+        Self {
+            settings_version: SETTINGS_VERSION,
+            default_device: None,
+            silence_threshold: recording.silence_threshold,
+            silence_duration_ms: recording.silence_duration_ms,
+            silence_padding_ms: recording.silence_padding_ms,
+            default_export_preset: None,
+            recordings_root: None,
+            project_backups: DEFAULT_PROJECT_BACKUPS,
+            stable_project_file: true,
+            session_analytics: false,
+        }
+    }
+}
+
+impl AppSettings {
+    /// Recording settings for a new project.
+    pub fn recording_defaults(&self) -> RecordingSettings {
+        RecordingSettings {
+            device_name: self.default_device.clone(),
+            silence_threshold: self.silence_threshold,
+            silence_duration_ms: self.silence_duration_ms,
+            silence_padding_ms: self.silence_padding_ms,
+            ..RecordingSettings::default()
+        }
+    }
+}
+
+pub type SettingsState = Mutex<AppSettings>;
+
+fn get_settings_path() -> PathBuf {
+    app_data_file_path("settings.json")
+}
+
+/// Loads the app settings, falling back to the defaults.
+pub fn load_settings() -> AppSettings {
+    let path = get_settings_path();
+    let mut settings: AppSettings = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| {
+            serde_json::from_str(&contents)
+                .map_err(|e| warn!("Invalid app settings: {}", e))
+                .ok()
+        })
+        .unwrap_or_default();
+    // Thresholds were saved as fractions from 0 to 1 before they were in
+    // dBFS, and never used. Even a stored 0 would keep every take from
+    // counting as voice.
+    if settings.settings_version < 1 && (0.0..=1.0).contains(&settings.silence_threshold) {
+        settings.silence_threshold = AppSettings::default().silence_threshold;
+    }
+    settings.settings_version = SETTINGS_VERSION;
+    settings
+}
+
+fn save_settings(settings: &AppSettings) -> Result<(), RecordrError> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(get_settings_path(), json)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings(settings: State<SettingsState>) -> AppSettings {
+    settings.lock().unwrap().clone()
+}
+
+/// Replaces the app settings and saves them.
+#[tauri::command]
+pub fn update_settings(
+    new_settings: AppSettings,
+    settings: State<SettingsState>,
+) -> Result<AppSettings, RecordrError> {
+    replace_settings(new_settings, &settings)
+}
+
+/// Validates and saves `new_settings`, then makes them current.
+pub fn replace_settings(
+    mut new_settings: AppSettings,
+    settings: &SettingsState,
+) -> Result<AppSettings, RecordrError> {
+    check_silence_threshold(new_settings.silence_threshold)?;
+    new_settings.settings_version = SETTINGS_VERSION;
+    if let Some(preset) = &new_settings.default_export_preset {
+        find_preset(preset)?;
+    }
+    save_settings(&new_settings)?;
+    *settings.lock().unwrap() = new_settings.clone();
+    Ok(new_settings)
+}
@@ -0,0 +1,37 @@
+use crate::errors::RecordrError;
+use crate::file_utils::write_project;
+use crate::jobs::run_blocking;
+use crate::models::{Project, Sentence};
+
+/// Lists the sentences that were skipped in a session and still have no
+/// take.
+#[tauri::command]
+pub fn list_skipped(project: Project) -> Vec<Sentence> {
+    project
+        .sentences
+        .into_iter()
+        .filter(|s| s.skipped && !s.recorded)
+        .collect()
+}
+
+/// Clears the skipped mark from the given sentences, or from every sentence
+/// when no IDs are given. The project is saved and returned.
+#[tauri::command]
+pub async fn clear_skipped(
+    mut project: Project,
+    sentence_ids: Option<Vec<usize>>,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        for sentence in project.sentences.iter_mut() {
+            if sentence_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&sentence.id))
+            {
+                sentence.skipped = false;
+            }
+        }
+        write_project(&project)?;
+        Ok(project)
+    })
+    .await
+}
@@ -0,0 +1,386 @@
+use crate::approved::remove_approved_tree;
+use crate::audio::{sidecar_path, ORIGINALS_DIRECTORY, RAW_DIRECTORY, TAPE_DIRECTORY};
+use crate::errors::{ErrorKind, RecordrError};
+use crate::file_utils::{project_file_path, write_project};
+use crate::jobs::run_blocking;
+use crate::models::Project;
+use crate::paths::{is_within, long_path, normalize, same_path};
+use crate::temp_files::TempFile;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use log::debug;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Start of every encrypted file. It is followed by the key's salt, the
+/// nonce and the AES-256-GCM ciphertext.
+const MAGIC: &[u8] = b"RECORDR-ENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// Key for an encrypted project, derived from its passphrase. The salt is
+/// stored in every file of the project so any of them can be unlocked.
+struct ProjectKey {
+    salt: [u8; SALT_LEN],
+    cipher: Aes256Gcm,
+}
+
+impl ProjectKey {
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Self, RecordrError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| RecordrError::new(ErrorKind::Other, e.to_string()))?;
+        Ok(Self {
+            salt,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    fn generate(passphrase: &str) -> Result<Self, RecordrError> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt)
+    }
+
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, RecordrError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, data)
+            .map_err(|_| RecordrError::new(ErrorKind::Other, "Encryption failed"))?;
+        let mut output = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&self.salt);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    /// Decrypts a whole encrypted file, header included.
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, RecordrError> {
+        if file_salt(data) != Some(&self.salt[..]) {
+            return Err(RecordrError::invalid_input(
+                "File was encrypted with a different passphrase",
+            ));
+        }
+        let nonce = Nonce::from_slice(&data[MAGIC.len() + SALT_LEN..HEADER_LEN]);
+        self.cipher
+            .decrypt(nonce, &data[HEADER_LEN..])
+            .map_err(|_| RecordrError::invalid_input("Incorrect passphrase or corrupted file"))
+    }
+}
+
+/// Keys of the projects unlocked in this session, by project directory.
+/// Keys are held in memory only and are gone once the app exits.
+static KEYS: Mutex<Vec<(PathBuf, ProjectKey)>> = Mutex::new(Vec::new());
+
+fn is_encrypted_data(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+fn file_salt(data: &[u8]) -> Option<&[u8]> {
+    is_encrypted_data(data).then(|| &data[MAGIC.len()..MAGIC.len() + SALT_LEN])
+}
+
+/// Runs `f` with the key of the unlocked project containing `path`.
+fn with_key<T>(path: &Path, f: impl FnOnce(Option<&ProjectKey>) -> T) -> T {
+    let keys = KEYS.lock().unwrap();
+    let key = keys
+        .iter()
+        .find(|(directory, _)| is_within(path, directory))
+        .map(|(_, key)| key);
+    f(key)
+}
+
+fn set_key(directory: &Path, key: ProjectKey) {
+    let mut keys = KEYS.lock().unwrap();
+    keys.retain(|(existing, _)| !same_path(existing, directory));
+    keys.push((normalize(directory), key));
+}
+
+/// Whether files written under `directory` are encrypted.
+pub fn has_key(directory: &Path) -> bool {
+    with_key(directory, |key| key.is_some())
+}
+
+/// Whether the file at `path` is encrypted.
+pub fn is_encrypted(path: &Path) -> Result<bool, RecordrError> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(long_path(path))
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(is_encrypted_data(&header))
+}
+
+/// Reads a file, decrypting it if it is encrypted. Reading an encrypted
+/// file of a project that has not been unlocked fails with `KeyRequired`.
+pub fn read(path: &Path) -> Result<Vec<u8>, RecordrError> {
+    let data = fs::read(long_path(path))
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    if !is_encrypted_data(&data) {
+        return Ok(data);
+    }
+    with_key(path, |key| match key {
+        Some(key) => key.decrypt(&data),
+        None => Err(RecordrError::new(
+            ErrorKind::KeyRequired,
+            "File is encrypted; enter the project's passphrase to open it",
+        )),
+    })
+    .map_err(|e| e.with_context(path.display().to_string()))
+}
+
+/// Readable and seekable file contents.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opens a file for reading. Encrypted files are decrypted into memory,
+/// plain files are read from disk as needed.
+pub fn open(path: &Path) -> Result<Box<dyn ReadSeek>, RecordrError> {
+    if is_encrypted(path)? {
+        Ok(Box::new(Cursor::new(read(path)?)))
+    } else {
+        let file = File::open(long_path(path))
+            .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Writes a file, encrypting it if it belongs to an unlocked project. The
+/// data goes to a temp file that then replaces the target, so a failed
+/// write never leaves the target truncated.
+pub fn write(path: &Path, data: &[u8]) -> Result<(), RecordrError> {
+    write_temp(path, data)?.persist(path)
+}
+
+/// Writes `data`, encrypted if needed, to a temp file beside `path`. The
+/// file is synced before it is returned, so a crash after it replaces the
+/// target cannot leave the target empty.
+fn write_temp(path: &Path, data: &[u8]) -> Result<TempFile, RecordrError> {
+    let encrypted = with_key(path, |key| key.map(|key| key.encrypt(data)).transpose())?;
+    let tmp = TempFile::new(path);
+    File::create(long_path(tmp.path()))
+        .and_then(|mut file| {
+            file.write_all(encrypted.as_deref().unwrap_or(data))?;
+            file.sync_all()
+        })
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    Ok(tmp)
+}
+
+/// File being streamed to, such as a take while it is recorded. Files of an
+/// unlocked project are held in memory and only written, encrypted, when
+/// the sink is finished, so their plain contents never reach the disk.
+/// Others are written to disk as they go. Flushing finishes the file, so
+/// finalizing a WAV writer writes it out and reports any failure; a sealed
+/// file dropped unfinished is never written.
+pub enum FileSink {
+    Disk(BufWriter<File>),
+    Sealed {
+        path: PathBuf,
+        data: Cursor<Vec<u8>>,
+    },
+}
+
+/// Creates a file to stream to, encrypted like `write` if it belongs to an
+/// unlocked project.
+pub fn create(path: &Path) -> Result<FileSink, RecordrError> {
+    if has_key(path) {
+        return Ok(FileSink::Sealed {
+            path: path.to_path_buf(),
+            data: Cursor::new(Vec::new()),
+        });
+    }
+    let file = File::create(long_path(path))
+        .map_err(|e| RecordrError::from(e).with_context(path.display().to_string()))?;
+    Ok(FileSink::Disk(BufWriter::new(file)))
+}
+
+impl FileSink {
+    /// Writes out what the file holds so far: flushes a file on disk, or
+    /// encrypts and writes a sealed one, replacing what an earlier call
+    /// wrote.
+    pub fn finish(&mut self) -> Result<(), RecordrError> {
+        match self {
+            FileSink::Disk(file) => Ok(file.flush()?),
+            FileSink::Sealed { path, data } => write(path, data.get_ref()),
+        }
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileSink::Disk(file) => file.write(buf),
+            FileSink::Sealed { data, .. } => data.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileSink::Disk(file) => file.flush(),
+            FileSink::Sealed { .. } => self.finish().map_err(io::Error::other),
+        }
+    }
+}
+
+impl Seek for FileSink {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            FileSink::Disk(file) => file.seek(pos),
+            FileSink::Sealed { data, .. } => data.seek(pos),
+        }
+    }
+}
+
+/// Path of the copy of `path` kept from before its last checked write.
+pub fn previous_path(path: &Path) -> PathBuf {
+    let mut previous = path.as_os_str().to_owned();
+    previous.push(".prev");
+    PathBuf::from(previous)
+}
+
+/// Writes a file like `write`, but first reads the temp file back and has
+/// `verify` check it, so a short or garbled write never replaces the target.
+/// The target's previous contents are kept as `<file>.prev`.
+pub fn write_checked(
+    path: &Path,
+    data: &[u8],
+    verify: impl FnOnce(&[u8]) -> Result<(), RecordrError>,
+) -> Result<(), RecordrError> {
+    let tmp = write_temp(path, data)?;
+    verify(&read(tmp.path())?).map_err(|e| e.with_context(path.display().to_string()))?;
+
+    if path.exists() {
+        let previous = previous_path(path);
+        fs::copy(long_path(path), long_path(&previous))
+            .map_err(|e| RecordrError::from(e).with_context(previous.display().to_string()))?;
+    }
+    tmp.persist(path)
+}
+
+/// Encrypts a file written in plain form, such as a take recorded before
+/// the project was encrypted, if it belongs to an unlocked project.
+pub fn seal(path: &Path) -> Result<(), RecordrError> {
+    if !has_key(path) || is_encrypted(path)? {
+        return Ok(());
+    }
+    let data = fs::read(long_path(path))?;
+    write(path, &data)
+}
+
+/// Seals the files under `directory` that `include` picks, in its
+/// subfolders too. Symlinks are left alone.
+fn seal_tree(directory: &Path, include: &dyn Fn(&Path) -> bool) -> Result<(), RecordrError> {
+    let entries = match fs::read_dir(long_path(directory)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(RecordrError::from(e).with_context(directory.display().to_string())),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = directory.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            seal_tree(&path, include)?;
+        } else if file_type.is_file() && include(&path) {
+            seal(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file in the recordings folder holds audio or what was said:
+/// takes and older takes with their backups and sidecars, session
+/// recordings with their markers, and the segments of long recordings.
+fn is_recording(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    [".wav", ".wav.bak", ".wav.json", ".markers.json"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+/// Unlocks the encrypted project whose JSON is at `file_path`, so its files
+/// can be read and new files are encrypted. Plain projects are left as they
+/// are.
+pub fn unlock(file_path: &Path, passphrase: &str) -> Result<(), RecordrError> {
+    let data = fs::read(file_path)
+        .map_err(|e| RecordrError::from(e).with_context(file_path.display().to_string()))?;
+    let Some(salt) = file_salt(&data) else {
+        return Ok(());
+    };
+    let key = ProjectKey::derive(passphrase, salt.try_into().unwrap())?;
+    key.decrypt(&data)
+        .map_err(|e| e.with_context(file_path.display().to_string()))?;
+    let directory = file_path.parent().unwrap_or(Path::new(""));
+    debug!("Unlocked encrypted project in {}", directory.display());
+    set_key(directory, key);
+    Ok(())
+}
+
+/// Encrypts a project with a passphrase: the project JSON and the copy kept
+/// of its previous version, each take with its backup and sidecar, the
+/// other recordings, and the raw takes, board tapes and imported originals.
+/// The `approved/` tree is removed, as encrypted projects have none. Files
+/// written later are encrypted too. The passphrase must be entered again
+/// whenever the project is opened.
+#[tauri::command]
+pub async fn encrypt_project(
+    mut project: Project,
+    passphrase: String,
+) -> Result<Project, RecordrError> {
+    run_blocking(move || {
+        if project.metadata.encrypted {
+            return Err(RecordrError::invalid_state("Project is already encrypted"));
+        }
+        if passphrase.is_empty() {
+            return Err(RecordrError::invalid_input("Passphrase must not be empty"));
+        }
+
+        // The project JSON goes first: a take left unencrypted by a failure
+        // below can still be read, while a plain project JSON would lose
+        // track of the key.
+        let directory = PathBuf::from(&project.metadata.directory);
+        set_key(&directory, ProjectKey::generate(&passphrase)?);
+        project.metadata.encrypted = true;
+        write_project(&project)?;
+        // The checked write kept the plain project JSON as its previous
+        // version.
+        let previous = previous_path(&project_file_path(&project));
+        if previous.exists() {
+            seal(&previous)?;
+        }
+        remove_approved_tree(&directory)?;
+
+        for sentence in &project.sentences {
+            let Some(audio_file_path) = &sentence.audio_file_path else {
+                continue;
+            };
+            let wav_path = directory.join(audio_file_path);
+            let mut backup = wav_path.clone().into_os_string();
+            backup.push(".bak");
+            for path in [sidecar_path(&wav_path), PathBuf::from(backup), wav_path] {
+                if path.exists() {
+                    seal(&path)?;
+                }
+            }
+        }
+        seal_tree(
+            &project.metadata.layout.recordings_path(&directory),
+            &is_recording,
+        )?;
+        for folder in [RAW_DIRECTORY, TAPE_DIRECTORY, ORIGINALS_DIRECTORY] {
+            seal_tree(&directory.join(folder), &|_| true)?;
+        }
+        Ok(project)
+    })
+    .await
+}
@@ -0,0 +1,198 @@
+use crate::errors::RecordrError;
+use crate::file_utils::app_data_file_path;
+use crate::paths::long_path;
+use fs2::FileExt;
+use log::{debug, info, warn};
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Suffix of a file being written in place of its target.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Temporary files this process has not finished with yet. The list is
+/// mirrored to a file of the process's own, locked for as long as the
+/// process runs, so files left behind by a crash can be removed by the next
+/// process without touching those of one still running.
+struct Manifest {
+    file: Option<File>,
+    pending: Vec<PathBuf>,
+}
+
+static MANIFEST: Mutex<Manifest> = Mutex::new(Manifest {
+    file: None,
+    pending: Vec::new(),
+});
+
+/// Folder of the temp file lists, one per process.
+fn get_manifest_directory() -> PathBuf {
+    app_data_file_path("temp_files")
+}
+
+/// Creates and locks this process's list.
+fn open_own_manifest() -> Result<File, RecordrError> {
+    let directory = get_manifest_directory();
+    fs::create_dir_all(&directory)?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(directory.join(format!("{}.json", std::process::id())))?;
+    file.try_lock_exclusive()?;
+    Ok(file)
+}
+
+impl Manifest {
+    fn save(&mut self) {
+        let result = (|| {
+            if self.file.is_none() {
+                self.file = Some(open_own_manifest()?);
+            }
+            let file = self.file.as_mut().unwrap();
+            let json = serde_json::to_vec(&self.pending)?;
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&json)?;
+            Ok::<_, RecordrError>(())
+        })();
+        if let Err(e) = result {
+            warn!("Failed to save temp file list: {}", e);
+        }
+    }
+}
+
+fn remove(path: &Path) {
+    match fs::remove_file(long_path(path)) {
+        Ok(()) => debug!("Removed temp file {}", path.display()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove temp file {}: {}", path.display(), e),
+    }
+}
+
+/// File written at `<target>.partial` and deleted when dropped unless it is
+/// moved into place. Work that fails, is cancelled or is stopped part way
+/// through therefore leaves nothing behind once it unwinds. Only the partial
+/// path is registered, so cleaning up after a crash never removes the
+/// target.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    target: PathBuf,
+    done: bool,
+}
+
+impl TempFile {
+    /// Registers a temporary file to be written in place of `target`.
+    pub fn new(target: impl Into<PathBuf>) -> Self {
+        let target = target.into();
+        let mut path = target.as_os_str().to_owned();
+        path.push(PARTIAL_SUFFIX);
+        let path = PathBuf::from(path);
+
+        let mut manifest = MANIFEST.lock().unwrap();
+        manifest.pending.push(path.clone());
+        manifest.save();
+        Self {
+            path,
+            target,
+            done: false,
+        }
+    }
+
+    /// Path the file is written to until it is kept.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the file to its target, returning the target's path.
+    pub fn keep(self) -> Result<PathBuf, RecordrError> {
+        let target = self.target.clone();
+        self.persist(&target)?;
+        Ok(target)
+    }
+
+    /// Moves the file over `target`.
+    pub fn persist(mut self, target: &Path) -> Result<(), RecordrError> {
+        fs::rename(long_path(&self.path), long_path(target))
+            .map_err(|e| RecordrError::from(e).with_context(target.display().to_string()))?;
+        self.release();
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.done = true;
+        let mut manifest = MANIFEST.lock().unwrap();
+        manifest.pending.retain(|path| path != &self.path);
+        manifest.save();
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.done {
+            remove(&self.path);
+            self.release();
+        }
+    }
+}
+
+/// Reads a temp file list, logging rather than failing on a bad one.
+fn read_manifest(mut file: &File) -> Vec<PathBuf> {
+    let mut contents = String::new();
+    if let Err(e) = file.read_to_string(&mut contents) {
+        warn!("Failed to read temp file list: {}", e);
+        return Vec::new();
+    }
+    serde_json::from_str(&contents)
+        .map_err(|e| warn!("Invalid temp file list: {}", e))
+        .unwrap_or_default()
+}
+
+/// Removes the temp files that processes which have since exited left
+/// behind, such as the take being recorded when the app crashed. Lists
+/// still locked belong to running processes and are left alone. Called on
+/// startup.
+pub fn clean_up_temp_files() {
+    // Older versions kept one list for every process, which also named
+    // finished files, so only its temp files are removed.
+    let legacy = app_data_file_path("temp_files.json");
+    if let Ok(contents) = fs::read_to_string(&legacy) {
+        let leftover: Vec<PathBuf> = serde_json::from_str(&contents).unwrap_or_default();
+        for path in leftover
+            .iter()
+            .filter(|path| path.extension() == Some(OsStr::new("tmp")))
+        {
+            remove(path);
+        }
+        remove(&legacy);
+    }
+
+    let Ok(entries) = fs::read_dir(get_manifest_directory()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let list_path = entry.path();
+        if list_path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+        let Ok(file) = OpenOptions::new().read(true).write(true).open(&list_path) else {
+            continue;
+        };
+        if file.try_lock_exclusive().is_err() {
+            continue;
+        }
+        let leftover = read_manifest(&file);
+        if !leftover.is_empty() {
+            info!("Removing {} leftover temp files", leftover.len());
+        }
+        for path in &leftover {
+            remove(path);
+        }
+        // Unlocked by closing, which has to come first on Windows.
+        drop(file);
+        remove(&list_path);
+    }
+}
@@ -1,10 +1,12 @@
 import { writable } from 'svelte/store';
-import { invoke } from '@tauri-apps/api/tauri';
+import { invoke } from '@tauri-apps/api/core';
 
 export interface RecentProject {
   path: string;
   name: string;
   last_accessed: string; // ISO timestamp
+  missing?: boolean;
+  pinned?: boolean;
 }
 
 export interface RecentProjectsData {
@@ -1,14 +1,74 @@
-// Define the event payload types
-export type AutoRecordStartSentenceEvent = {
-  payload: number; // Sentence index
-};
+// Payload of the 'auto-record-event' window event
+export type AutoRecordEventKind =
+  | 'sentence_started'
+  | 'countdown_tick'
+  | 'sentence_finished'
+  | 'sentence_skipped'
+  | 'take_rejected'
+  | 'empty_take'
+  | 'take_timeout'
+  | 'session_progress'
+  | 'awaiting_confirmation'
+  | 'paused'
+  | 'rest_break'
+  | 'session_quota_reached'
+  | 'resumed'
+  | 'suspended'
+  | 'device_lost'
+  | 'low_disk_space'
+  | 'level_drift'
+  | 'duplicate_speech'
+  | 'cue'
+  | 'voice_detected' // Monitor mode only
+  | 'silence_detected' // Monitor mode only
+  | 'level' // Monitor mode only
+  | 'complete';
 
-export type AutoRecordFinishSentenceEvent = {
-  payload: {
-    id: number;
-    audioFilePath: string;
-  };
-};
+export interface AutoRecordEvent {
+  kind: AutoRecordEventKind;
+  sessionId: string;
+  sentenceId: number | null;
+  text?: string; // Only present when emit_prompt_text is enabled
+  timestamp: string;
+  remainingMs?: number; // Only present on countdown ticks and rest breaks
+  takeNumber?: number; // Only present when several takes are recorded per sentence
+  audioFilePath: string | null;
+  durationMs: number | null;
+  peakLevel: number | null;
+  stats: SentenceStats | null;
+  cue?: Cue; // Only present on cue events
+  freeSpaceMb?: number; // Only present on low_disk_space events
+  levelDriftDb?: number; // Only present on level_drift events, positive when louder
+  duplicateOf?: number; // Only present on duplicate_speech events
+  similarity?: number; // 0 to 1, only present on duplicate_speech events
+  progress?: SessionProgress; // Only present on session_progress and session_quota_reached events
+  status?: SentenceStatus; // Only present on sentence_finished events
+  notes?: string; // Reviewer notes of the finished sentence, when set
+  tags?: string[];
+}
+
+// Payload of the 'external-edit' event, once an edited take is imported or
+// rejected
+export interface ExternalEditEvent {
+  id: number;
+  audioFilePath: string | null;
+  error: string | null;
+}
+
+// Payload of the 'external-file-added', 'external-file-changed' and
+// 'external-file-removed' events of a watched recordings folder
+export interface ExternalFileEvent {
+  path: string;
+  sentenceId: number | null; // Sentence the file is named after, if any
+}
+
+export interface SessionProgress {
+  completed: number;
+  remaining: number;
+  averageTakeMs: number;
+  averageSentenceMs: number;
+  etaMs: number;
+}
 
 export interface ProjectMetadata {
   name: string;
@@ -17,9 +77,245 @@ export interface ProjectMetadata {
   created_at: string;
   last_modified: string;
   directory: string;
+  recording_settings?: RecordingSettings;
+  language?: string | null; // BCP 47 tag, e.g. 'en-US'
+  encrypted?: boolean;
+  file_name?: string | null; // Project JSON file name; older projects use `${name}.json`
+  approved_links?: boolean; // Keep an approved/ directory of links to approved takes
+  layout?: ProjectLayout; // Missing in older projects, which record next to the project file
+}
+
+// Where a project keeps its audio; change it with migrateProjectLayout
+export interface ProjectLayout {
+  recordings: string; // Folder within the project directory, e.g. 'recordings'; '' for none
+}
+
+export type OutputFormat = 'wav16' | 'wav24' | 'wav32_float';
+
+export interface RecordingSettings {
+  input_source: InputSource;
+  device_name: string | null;
+  sample_rate: number | null;
+  voice_probability: number; // VAD speech probability (0-1) that counts as voice when loud enough
+  silence_threshold: number; // dBFS; quieter audio counts as silence whatever the VAD hears
+  silence_duration_ms: number;
+  silence_padding_ms: number;
+  silence_confirmation_ms: number;
+  pre_sentence_delay_ms: number;
+  output_format: OutputFormat;
+  channel_mode: ChannelMode;
+  emit_prompt_text: boolean;
+  noise_reduction: NoiseReductionSettings;
+  filters: FilterSettings;
+  transients: TransientSettings;
+  duplicate_speech: DuplicateSpeechSettings; // Holds takes that sound like another sentence's
+  input_gain: InputGainSettings;
+  vad_preprocessing: VadPreprocessingSettings;
+  write_sidecar: boolean;
+  keep_raw_takes: boolean; // Untrimmed, unprocessed copy of each take in raw/
+  write_session_log: boolean; // JSON-lines audit trail in the project's logs folder
+  auto_advance: boolean;
+  requeue_skipped: boolean;
+  recording_order: RecordingOrder;
+  overwrite_recorded: boolean;
+  takes_per_sentence: number;
+  min_take_duration_ms: number;
+  min_voiced_ms: number; // Takes with less voice are deleted and re-armed
+  pause_policy: PausePolicy;
+  low_latency: boolean;
+  max_take_duration_ms: number; // 0 disables the limit
+  take_timeout_action: TakeTimeoutAction;
+  sprint_minutes: number; // 0 disables sprints
+  rest_minutes: number;
+  max_session_minutes: number; // 0 sets no limit
+  max_session_sentences: number; // 0 sets no limit
+  level_baseline_dbfs: number | null; // Else the last monitor session's, else the first takes
+  level_drift_threshold_db: number; // 0 disables the check
+  cues: CueSettings;
+  board_tape: BoardTapeSettings;
+  continuous_session: boolean; // One session WAV plus markers; split afterwards
+  min_free_space_mb: number; // 0 disables the disk space checks
+}
+
+export type TakeTimeoutAction = 'keep' | 'discard';
+
+export type PausePolicy = 'discard' | 'keep_partial' | 'auto_retry';
+
+// Order an auto-record session works through its sentences; a shuffle with
+// the same seed always gives the same order
+export type RecordingOrder =
+  | { strategy: 'sequential' }
+  | { strategy: 'shuffled'; seed: number }
+  | { strategy: 'shortest_first' }
+  | { strategy: 'unrecorded_first' };
+
+export type ChannelMode = 'input' | 'mono' | 'stereo';
+
+export type InputSource =
+  | { kind: 'device' }
+  | { kind: 'rtp'; address: string; channels: number; sample_rate: number };
+
+export type Cue = 'armed' | 'captured';
+
+export interface CueSettings {
+  enabled: boolean;
+  volume: number; // 0 leaves only the cue event
+}
+
+export interface BoardTapeSettings {
+  enabled: boolean; // Opus tape of the whole session in board_tape/
+  bitrate_kbps: number;
+}
+
+export interface NoiseReductionSettings {
+  enabled: boolean;
+  strength: number;
+}
+
+// Filters applied to each take before it is written
+export interface FilterSettings {
+  high_pass: boolean;
+  high_pass_hz: number; // 80 to 120
+  remove_dc_offset: boolean;
+}
+
+// Turns down the breaths, clicks and pops found in each take before writing it
+export interface TransientSettings {
+  attenuate: boolean;
+  attenuation_db: number; // 0 to 40
+}
+
+export interface DuplicateSpeechSettings {
+  enabled: boolean;
+  threshold: number; // 0 to 1
+}
+
+export interface InputGainSettings {
+  gain_db: number; // -24 to 24
+  agc: boolean; // Slowly adjusts the gain towards a steady speech level
+}
+
+export interface VadPreprocessingSettings {
+  high_pass_hz: number | null;
+  gain_db: number;
+}
+
+// Input latency of a running auto-record session
+export interface InputLatency {
+  low_latency: boolean; // false when the device refused the low-latency buffer
+  buffer_frames: number | null;
+  buffer_latency_ms: number | null;
+  capture_latency_ms: number | null; // Only when the host reports timestamps
+}
+
+// Hands-free control of auto-record sessions by pedal or MIDI controller
+export type ControlAction = 'pause' | 'resume' | 'toggle_pause' | 'skip' | 'retry';
+
+export type MidiTrigger =
+  | { kind: 'note'; number: number }
+  | { kind: 'control_change'; number: number } // Fires at values of 64 and above
+  | { kind: 'program_change'; number: number };
+
+export type MidiBinding = MidiTrigger & {
+  channel?: number | null; // 0-15, any channel when null
+  action: ControlAction;
+};
+
+export interface HidDeviceId {
+  vendor_id: number;
+  product_id: number;
+}
+
+export interface HidBinding {
+  byte: number; // Index into the input report
+  value: number;
+  action: ControlAction;
+}
+
+export interface ControlSettings {
+  midi_port: string | null;
+  midi_bindings: MidiBinding[];
+  hid_device: HidDeviceId | null;
+  hid_bindings: HidBinding[];
+  media_keys: boolean; // Play/pause media key pauses and resumes a running session
+}
+
+export interface ControlDevice {
+  name: string;
+  id: HidDeviceId | null; // Only set for HID devices
+}
+
+// App-wide defaults, used when a command is not given a value
+export interface AppSettings {
+  settings_version?: number; // Set by the backend when the settings are saved
+  default_device: string | null;
+  silence_threshold: number; // dBFS
+  silence_duration_ms: number;
+  silence_padding_ms: number;
+  default_export_preset: string | null;
+  recordings_root: string | null;
+  project_backups: number; // Backups kept per project file; 0 turns them off
+  stable_project_file: boolean; // New projects are saved as project.recordr.json
+  session_analytics: boolean; // Opt-in; kept locally and never sent anywhere
+}
+
+// One auto-record session in the local analytics store
+export interface SessionRecord {
+  session_id: string;
+  project_directory: string;
+  started_at: string;
+  ended_at: string;
+  duration_ms: number; // Pauses included
+  paused_ms: number;
+  take_ms: number;
+  takes: number; // Kept takes, retakes included
+  sentences_recorded: number;
+  rejected_takes: number;
+  take_timeouts: number;
+  skipped: number;
+  duplicate_takes: number;
+  device_errors: number;
+  low_disk_space: number;
+}
+
+export interface SessionTotals {
+  sessions: number;
+  duration_ms: number;
+  active_ms: number; // Pauses left out
+  take_ms: number;
+  takes: number;
+  sentences_recorded: number;
+  rejected_takes: number;
+  retake_rate: number; // 0 to 1
+  rejection_rate: number; // 0 to 1
+  average_session_ms: number;
+  sentences_per_hour: number;
+  errors: number;
+}
+
+export interface SessionAnalytics {
+  totals: SessionTotals;
+  projects: Record<string, SessionTotals>; // Keyed by project directory
+  sessions: SessionRecord[]; // Most recent first
+}
+
+// Payload of 'log-event': a warning or error logged by the backend
+export interface LogEvent {
+  level: 'warn' | 'error';
+  target: string; // Rust module, e.g. recordr_lib::audio::stream
+  message: string;
+  timestamp: string;
+}
+
+// Copy of a project file taken before a save replaced it
+export interface ProjectBackup {
+  file_name: string; // In the project's .backups/ folder
+  created_at: string;
+  size: number;
 }
 
 export interface Project {
+  schema_version?: number; // Set by the backend; older files are upgraded on open
   metadata: ProjectMetadata;
   sentences: Sentence[];
 }
@@ -29,4 +325,202 @@ export interface Sentence {
   text: string;
   recorded: boolean;
   audio_file_path: string | null;
+  qc_flags?: QcFlag[];
+  stats?: SentenceStats | null;
+  skipped?: boolean;
+  notes?: string | null;
+  tags?: string[];
+  status?: SentenceStatus;
+  assets?: string[]; // File names in the project's assets/ folder
+  character?: string | null;
+  rerecord?: RerecordFlag | null; // Cleared by the next take
+  file_stem?: string | null; // Set when the text alone would name another sentence's takes
+}
+
+// Set by flagForRerecord; queued sentences are revisited at the end of the
+// next auto-record session with requeue_skipped on
+export interface RerecordFlag {
+  reason: string | null;
+  flagged_at: string;
+  queued: boolean;
+}
+
+// Column of the metadata CSV written by exportLocalization
+export type LocalizationColumn = 'filename' | 'text' | 'character' | 'language' | 'duration';
+
+// Training toolchain whose manifest exportDataset writes
+export type DatasetFormat = 'ljspeech' | 'coqui' | 'piper' | 'espnet';
+
+// Review state of a sentence; recording a take sets it to 'recorded'
+export type SentenceStatus = 'unrecorded' | 'recorded' | 'approved' | 'rejected';
+
+export interface SentenceStats {
+  duration_ms: number;
+  peak_dbfs: number;
+  rms_dbfs: number;
+  clipped_samples: number;
+  leading_silence_ms: number;
+  trailing_silence_ms: number;
+  transients: Transient[];
+}
+
+export type TransientKind = 'breath' | 'click' | 'pop';
+
+// Likely breath, mouth click or plosive pop in a take, for targeted cleanup
+export interface Transient {
+  kind: TransientKind;
+  start_ms: number;
+  end_ms: number;
+  peak_dbfs: number;
+}
+
+// Existing recording imported as a sentence's take
+export interface AudioImport {
+  sentence_id: number;
+  file_path: string; // WAV file; the original is kept under originals/
+}
+
+// Metadata embedded in a WAV file's BWF and LIST-INFO chunks
+export interface AudioMetadata {
+  sentence_id: number | null;
+  text: string | null;
+  project_name: string | null;
+  recorded_at: string | null; // RFC 3339 for files written by recordr
+  device: string | null;
+  software: string | null;
+}
+
+// Format of an audio file as read by probeAudio
+export interface AudioProbe {
+  encoding: string; // e.g. pcm_s16le, flac or mp3
+  sample_rate: number | null;
+  channels: number | null;
+  bits_per_sample: number | null;
+  duration_ms: number | null; // Unknown for some MP3s
+  tags: Record<string, string>;
+}
+
+// Compressed format of review copies: Ogg Opus or Ogg Vorbis
+export type ReviewFormat = 'opus' | 'ogg';
+
+// One take of a sentence, for choosing the best take
+export interface TakeMetrics {
+  audio_file_path: string;
+  take: number | null; // Only set for numbered takes
+  current: boolean; // The take the sentence currently uses
+  duration_ms: number;
+  peak_dbfs: number;
+  rms_dbfs: number;
+  clipped_samples: number;
+  word_error_rate: number | null; // Only when a transcript was given
+}
+
+// Sentences sharing the same normalized text
+export interface DuplicateGroup {
+  text: string;
+  ids: number[]; // Import file rows, or sentence IDs for a project audit
+}
+
+export type TakeIssue =
+  | { kind: 'no_audio_path' }
+  | { kind: 'missing' }
+  | { kind: 'empty_file' }
+  | { kind: 'unreadable'; message: string }
+  | { kind: 'no_audio' };
+
+export interface TakeProblem {
+  sentence_id: number;
+  audio_file_path: string | null;
+  issue: TakeIssue;
+}
+
+export interface IntegrityReport {
+  checked: number; // Sentences marked recorded
+  problems: TakeProblem[];
+  orphans: string[]; // WAVs in the recordings folder no sentence owns
+}
+
+export interface ImportedSentences {
+  sentences: Sentence[];
+  duplicates: DuplicateGroup[];
+}
+
+export type QcFlag = { kind: 'language_mismatch'; expected: string; detected: string };
+
+export interface CoverageStats {
+  total: number;
+  counts: Record<string, number>;
+  missing: string[];
+  coverage_percent: number | null;
+}
+
+export interface CoverageReport {
+  language: string;
+  characters: CoverageStats;
+  phonemes: CoverageStats | null; // null without a grapheme-to-phoneme table
+  diphones: CoverageStats | null;
+  unmapped_characters: Record<string, number>;
+}
+
+export interface HistogramBucket {
+  start_ms: number;
+  end_ms: number;
+  count: number;
+}
+
+export interface RecentTake {
+  sentence_id: number;
+  text: string;
+  recorded_at: string;
+}
+
+export interface DashboardData {
+  total_sentences: number;
+  recorded_sentences: number;
+  completion_percent: number;
+  total_duration_ms: number;
+  recorded_per_day: Record<string, number>; // Keyed by local date, YYYY-MM-DD
+  duration_histogram: HistogramBucket[];
+  flag_counts: Record<string, number>;
+  recent_activity: RecentTake[];
+}
+
+// Library versions and processing parameters, for dataset documentation
+export interface EngineInfo {
+  app_version: string;
+  libraries: Record<string, string | null>;
+  vad_model: string;
+  vad_sample_rate: number;
+  vad_chunk_size: number;
+  vad_voice_probability: number;
+  vad_resampler: string;
+  conversion_resampler: string;
+  denoise_resampler: string;
+  denoise_sample_rate: number;
+  playback_resampler: string;
+}
+
+export type ErrorKind =
+  | 'no_input_device'
+  | 'device_error'
+  | 'disk_full'
+  | 'not_found'
+  | 'permission_denied'
+  | 'invalid_state'
+  | 'invalid_input'
+  | 'unsupported_format'
+  | 'file_too_large'
+  | 'io'
+  | 'parse'
+  | 'audio'
+  | 'cancelled'
+  | 'key_required'
+  | 'other';
+
+// Error payload rejected by every backend command
+export interface RecordrError {
+  kind: ErrorKind;
+  message: string;
+  context: string | null;
+  recoverable: boolean;
 }
@@ -1,24 +1,56 @@
-import { invoke } from '@tauri-apps/api/tauri';
-import type { Sentence } from '../types';
+import { invoke } from '@tauri-apps/api/core';
+import type { ProjectLayout, RecordingOrder, RecordingSettings, Sentence } from '../types';
 
+// Silence settings left undefined come from recordingSettings, then the app
+// settings
 export async function startAutoRecord(
   sentences: Sentence[],
   projectDirectory: string,
-  silenceThreshold: number,
-  silenceDuration: number,
-  silencePadding: number,
-  window: Window
-) {
-  await invoke('start_auto_record', {
+  silenceThreshold: number | undefined,
+  silenceDuration: number | undefined,
+  silencePadding: number | undefined,
+  window: Window,
+  recordingSettings?: RecordingSettings,
+  layout?: ProjectLayout,
+  overwrite?: boolean,
+  startSentenceId?: number,
+  sessionId?: string // Carried by the session's events; generated if not given
+): Promise<string> {
+  return await invoke('start_auto_record', {
     sentences,
+    startSentenceId,
+    sessionId,
     projectDirectory,
+    layout,
+    recordingSettings,
     silenceThreshold,
     silenceDuration,
     silencePadding,
+    overwrite,
     window,
   });
 }
 
+// Listens like auto-record and emits voice_detected, silence_detected and
+// level events without writing any files
+export async function startMonitorMode(
+  silenceThreshold: number,
+  silenceDuration: number,
+  silencePadding: number,
+  recordingSettings?: RecordingSettings
+): Promise<string> {
+  return await invoke('start_monitor_mode', {
+    recordingSettings,
+    silenceThreshold,
+    silenceDuration,
+    silencePadding,
+  });
+}
+
+export async function stopMonitorMode(): Promise<boolean> {
+  return await invoke('stop_monitor_mode');
+}
+
 export async function stopAutoRecord() {
   await invoke('stop_auto_record');
 }
@@ -30,3 +62,51 @@ export async function pauseAutoRecord() {
 export async function resumeAutoRecord() {
   await invoke('resume_auto_record');
 }
+
+export async function reconnectAudioDevice(deviceName?: string) {
+  await invoke('reconnect_audio_device', { deviceName });
+}
+
+export async function confirmSentence() {
+  await invoke('confirm_sentence');
+}
+
+export async function retrySentence() {
+  await invoke('retry_sentence');
+}
+
+export async function skipSentence() {
+  await invoke('skip_sentence');
+}
+
+// Software input gain applied before voice detection and writing; takes
+// effect at once in a running session
+export async function setInputGain(gainDb: number, agc?: boolean) {
+  await invoke('set_input_gain', { gainDb, agc });
+}
+
+// Changes the silence threshold (dBFS), duration and padding (ms) and the VAD
+// voice probability of the running session or monitor without stopping it;
+// values left out are kept
+export async function updateAutoRecordSettings(
+  silenceThreshold?: number,
+  silenceDuration?: number,
+  silencePadding?: number,
+  voiceProbability?: number
+) {
+  await invoke('update_auto_record_settings', {
+    voiceProbability,
+    silenceThreshold,
+    silenceDuration,
+    silencePadding,
+  });
+}
+
+// Reorders the sentences after the current one in the running session only
+export async function setRecordingOrder(strategy: RecordingOrder) {
+  await invoke('set_recording_order', { strategy });
+}
+
+export async function setCurrentSentence(sentenceId: number) {
+  await invoke('set_current_sentence', { sentenceId });
+}
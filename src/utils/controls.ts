@@ -0,0 +1,14 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { ControlDevice, ControlSettings } from '../types';
+
+export async function getInputControls(): Promise<ControlSettings> {
+  return await invoke('get_input_controls');
+}
+
+export async function setInputControls(settings: ControlSettings): Promise<ControlSettings> {
+  return await invoke('set_input_controls', { settings });
+}
+
+export async function listControlDevices(): Promise<ControlDevice[]> {
+  return await invoke('list_control_devices');
+}
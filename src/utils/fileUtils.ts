@@ -1,6 +1,6 @@
-import { invoke } from '@tauri-apps/api/tauri';
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
 import { getVersion } from '@tauri-apps/api/app';
-import { open } from '@tauri-apps/api/dialog';
+import { open } from '@tauri-apps/plugin-dialog';
 import { join, homeDir } from '@tauri-apps/api/path';
 import { get } from 'svelte/store';
 import {
@@ -11,12 +11,26 @@ import {
   selectedSentence,
   isRecording,
 } from '../stores/projectStore';
-import type { Project, Sentence } from '../types';
-import { appWindow } from '@tauri-apps/api/window';
+import type {
+  AudioImport,
+  AudioMetadata,
+  AudioProbe,
+  DatasetFormat,
+  ImportedSentences,
+  LocalizationColumn,
+  Project,
+  ProjectBackup,
+  ProjectLayout,
+  RecordrError,
+  ReviewFormat,
+  Sentence,
+  TakeMetrics,
+} from '../types';
+import { getCurrentWindow } from '@tauri-apps/api/window';
 import type { ModalSettings, ModalStore } from '@skeletonlabs/skeleton';
 
 async function setWindowTitle(name: string) {
-  await appWindow.setTitle(`Recordr - ${name}`);
+  await getCurrentWindow().setTitle(`Recordr - ${name}`);
 }
 
 export async function newProject(modalStore: ModalStore) {
@@ -59,7 +73,7 @@ export async function newProject(modalStore: ModalStore) {
             isProjectLoaded.set(true);
             await setWindowTitle(value);
             // Build a recent project object with required schema
-            const projectFilePath = `${savedProject.metadata.directory}/${savedProject.metadata.name}.json`;
+            const projectFilePath = await projectFilePathOf(savedProject);
             const recentProject = {
               path: projectFilePath,
               name: savedProject.metadata.name,
@@ -91,7 +105,10 @@ export async function openProject(path?: string) {
     })) as string);
 
   if (selected) {
-    const loadedProject: Project = await invoke('open_project', { filePath: selected });
+    const loadedProject = await openProjectFile(selected);
+    if (!loadedProject) {
+      return;
+    }
     project.set(loadedProject);
     sentences.set(loadedProject.sentences);
     isProjectLoaded.set(true);
@@ -110,6 +127,80 @@ export async function openProject(path?: string) {
   }
 }
 
+// Opens a project file, asking for the passphrase if the project is
+// encrypted. Returns null if the user cancels the prompt.
+async function openProjectFile(filePath: string): Promise<Project | null> {
+  try {
+    return await invoke('open_project', { filePath });
+  } catch (error) {
+    if ((error as RecordrError).kind !== 'key_required') {
+      throw error;
+    }
+  }
+  const passphrase = prompt('This project is encrypted. Enter its passphrase:');
+  if (passphrase === null) {
+    return null;
+  }
+  return await invoke('open_project', { filePath, passphrase });
+}
+
+// Path of the project's JSON file, which older projects name after the
+// project.
+export async function projectFilePathOf(target: Project): Promise<string> {
+  const { directory, name, file_name } = target.metadata;
+  return await join(directory, file_name ?? `${name}.json`);
+}
+
+// Renames the current project. The backend moves its JSON file if it was
+// named after the project and updates the recent projects list.
+export async function renameProject(name: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const renamed: Project = await invoke('rename_project', { project: currentProject, name });
+  project.set(renamed);
+  await setWindowTitle(renamed.metadata.name);
+}
+
+// Lists the backups of a project file, newest first. Works for project files
+// that no longer open.
+export async function listProjectBackups(filePath: string): Promise<ProjectBackup[]> {
+  return await invoke('list_project_backups', { filePath });
+}
+
+// Replaces a project file with one of its backups and loads it. The file
+// being replaced is backed up first.
+export async function restoreProjectBackup(
+  filePath: string,
+  backup: string,
+  passphrase?: string
+) {
+  const restored: Project = await invoke('restore_project_backup', {
+    filePath,
+    backup,
+    passphrase,
+  });
+  project.set(restored);
+  sentences.set(restored.sentences);
+  isProjectLoaded.set(true);
+  await setWindowTitle(restored.metadata.name);
+}
+
+// Encrypts the current project's files with a passphrase, which is then
+// needed every time the project is opened.
+export async function encryptProject(passphrase: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const encrypted: Project = await invoke('encrypt_project', {
+    project: currentProject,
+    passphrase,
+  });
+  project.set(encrypted);
+}
+
 export async function saveProject() {
   const currentProject = get(project);
   if (!currentProject) {
@@ -134,24 +225,45 @@ export async function saveProject() {
   }
 }
 
+// Sentence and file of the manual take being recorded, so stopping links
+// the file that was written even if another sentence has been selected since
+let manualTake: { sentence: Sentence; filename: string } | null = null;
+
 export async function toggleRecording() {
+  if (get(isRecording)) {
+    const take = manualTake;
+    manualTake = null;
+    invoke('stop_recording').then(() => {
+      if (!take) {
+        return;
+      }
+      take.sentence.recorded = true;
+      take.sentence.status = 'recorded';
+      take.sentence.audio_file_path = take.filename;
+      saveProject();
+    });
+    return;
+  }
+
   const sentence = get(selectedSentence);
   if (!sentence) {
     alert('Select a sentence to record.');
     return;
   }
-
   const filename = await generateFilename(sentence);
-  if (get(isRecording)) {
-    invoke('stop_recording').then(() => {
-      sentence.recorded = true;
-      saveProject();
-    });
-  } else {
-    invoke('start_recording', { filename });
-  }
+  const metadata = get(project)?.metadata;
+  invoke('start_recording', {
+    filename,
+    minFreeSpaceMb: metadata?.recording_settings?.min_free_space_mb,
+    sentence,
+    projectDirectory: metadata?.directory,
+  })
+    .then(() => (manualTake = { sentence, filename }))
+    .catch((error) => alert(`Recording could not start: ${(error as RecordrError).message}`));
 }
 
+// Path of a manual take of `sentence`, in the recordings folder that
+// auto-record writes its takes to
 export async function generateFilename(sentence: Sentence) {
   const currentProject = get(project);
   if (!currentProject) {
@@ -159,19 +271,355 @@ export async function generateFilename(sentence: Sentence) {
   }
   return await join(
     currentProject.metadata.directory,
-    `${sentence.text.trim().replace(/\s+/g, '_')}.wav`
+    currentProject.metadata.layout?.recordings ?? '',
+    `${sentence.file_stem ?? sentence.text.trim().replace(/\s+/g, '_')}.wav`
   );
 }
 
+// Imports existing recordings as takes, converted to the project's audio
+// settings. Progress is reported through 'import-progress' events.
+export async function importSentenceAudio(imports: AudioImport[], jobId?: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke('import_sentence_audio', {
+    project: currentProject,
+    imports,
+    jobId,
+  });
+  project.set(updated);
+  sentences.set(updated.sentences);
+}
+
+// Flags a sentence to be recorded again, e.g. while reviewing takes. With
+// enqueue, it is revisited at the end of the next auto-record session.
+export async function flagForRerecord(sentenceId: number, reason?: string, enqueue?: boolean) {
+  await editSentences('flag_for_rerecord', { sentenceId, reason, enqueue });
+}
+
+// Clears the re-record flag of the given sentences, or of all of them.
+export async function clearRerecordFlags(sentenceIds?: number[]) {
+  await editSentences('clear_rerecord_flags', { sentenceIds });
+}
+
+// Re-trims the silence around a take's speech to the given padding, or the
+// project's padding. The original is kept as a .bak file.
+export async function retrimSentence(sentenceId: number, silencePaddingMs?: number) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke('retrim_sentence', {
+    project: currentProject,
+    sentenceId,
+    silencePaddingMs,
+  });
+  project.set(updated);
+  sentences.set(updated.sentences);
+}
+
+// Re-trims every take of the project. Progress is reported through
+// 'retrim-progress' events.
+export async function retrimProject(silencePaddingMs?: number, jobId?: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke('retrim_project', {
+    project: currentProject,
+    silencePaddingMs,
+    jobId,
+  });
+  project.set(updated);
+  sentences.set(updated.sentences);
+}
+
+// Looks for breaths, clicks and pops in every take of the project that has
+// stats. Progress is reported through 'transients-progress' events.
+export async function detectProjectTransients(jobId?: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke('detect_project_transients', {
+    project: currentProject,
+    jobId,
+  });
+  project.set(updated);
+  sentences.set(updated.sentences);
+}
+
+// Copies a take to the project's external_edit/ folder for editing in a DAW.
+// Saving over the copy imports it as a new take, reported through
+// 'external-edit' events. Returns the copy's path.
+export async function sendToExternalEditor(sentenceId: number): Promise<string> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  return await invoke('send_to_external_editor', { project: currentProject, sentenceId });
+}
+
+// Watches the project's recordings folder for WAV files saved, changed or
+// deleted by other apps, reported through 'external-file-added',
+// 'external-file-changed' and 'external-file-removed' events.
+export async function watchProjectFolder() {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  await invoke('watch_project_folder', { project: currentProject });
+}
+
+export async function unwatchProjectFolder() {
+  await invoke('unwatch_project_folder');
+}
+
+// Encodes compressed review copies of every take into the project's review/
+// folder, leaving the WAV masters untouched. Progress is reported through
+// 'review-export-progress' events. Returns the paths of the copies written.
+export async function exportReviewAudio(
+  format: ReviewFormat,
+  bitrateKbps: number,
+  jobId?: string
+): Promise<string[]> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  return await invoke('export_review_audio', {
+    project: currentProject,
+    format,
+    bitrateKbps,
+    jobId,
+  });
+}
+
+// Runs a sentence list edit against the loaded project. The backend saves
+// the project and returns it with the sentences renumbered.
+async function editSentences(command: string, args: Record<string, unknown>) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke(command, { project: currentProject, ...args });
+  project.set(updated);
+  sentences.set(updated.sentences);
+}
+
+// Inserts a sentence at `position` (0-based), or at the end.
+export async function addSentence(text: string, position?: number) {
+  await editSentences('add_sentence', { text, position });
+}
+
+// Corrects a sentence's text, renaming its takes to match.
+export async function updateSentenceText(sentenceId: number, text: string) {
+  await editSentences('update_sentence_text', { sentenceId, text });
+}
+
+// Moves the project's takes into the recordings folder of `layout` and
+// records takes there from then on
+export async function migrateProjectLayout(layout: ProjectLayout) {
+  await editSentences('migrate_project_layout', { layout });
+}
+
+// Points sentences whose take has gone missing at files of the same name
+// found under `searchDir`
+export async function relinkMissingAudio(searchDir: string) {
+  await editSentences('relink_missing_audio', { searchDir });
+}
+
+// Links a file recorded elsewhere, e.g. in a DAW, as a sentence's take.
+// With `copy`, it is converted into the project's recordings folder;
+// without, a WAV file already in the project's format is linked in place.
+export async function attachExternalAudio(sentenceId: number, path: string, copy: boolean) {
+  await editSentences('attach_external_audio', { sentenceId, path, copy });
+}
+
+// Asks for a file recorded elsewhere and adds a converted copy of it as the
+// sentence's next take.
+export async function chooseExternalAudio(sentenceId: number) {
+  const selected = await open({
+    multiple: false,
+    filters: [{ name: 'Audio Files', extensions: ['wav', 'flac', 'mp3'] }],
+    title: 'Select Audio to Attach',
+  });
+  if (Array.isArray(selected) || !selected) {
+    return;
+  }
+  await attachExternalAudio(sentenceId, selected, true);
+}
+
+// Removes a sentence from the script. Its takes stay on disk.
+export async function deleteSentence(sentenceId: number) {
+  await editSentences('delete_sentence', { sentenceId });
+}
+
+// Puts the sentences in the order of `sentenceIds`, which lists each once.
+export async function reorderSentences(sentenceIds: number[]) {
+  await editSentences('reorder_sentences', { sentenceIds });
+}
+
+// Copies an image or other context file into the project and attaches it to
+// a sentence's prompt.
+export async function attachSentenceAsset(sentenceId: number, filePath: string) {
+  await editSentences('attach_sentence_asset', { sentenceId, filePath });
+}
+
+// Detaches an asset; the file is deleted once no sentence uses it.
+export async function removeSentenceAsset(sentenceId: number, asset: string) {
+  await editSentences('remove_sentence_asset', { sentenceId, asset });
+}
+
+// Loads an attached asset as an object URL for display.
+export async function loadSentenceAssetUrl(asset: string): Promise<string> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const data: number[] = await invoke('load_sentence_asset', {
+    project: currentProject,
+    asset,
+  });
+  return URL.createObjectURL(new Blob([new Uint8Array(data)]));
+}
+
+// Plays an attached WAV asset, such as a reference read, on the output
+// device, resampled as it plays when its sample rate differs.
+export async function playSentenceAsset(asset: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  await invoke('play_sentence_asset', { project: currentProject, asset });
+}
+
+export async function stopSentenceAsset() {
+  await invoke('stop_sentence_asset');
+}
+
+// Writes the sentences with their status, notes and tags to a CSV or TSV
+// file, chosen by the file's extension.
+export async function exportSentences(filePath: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  await invoke('export_sentences', { project: currentProject, filePath });
+}
+
+// Writes a self-contained HTML QC report of the project, with each
+// sentence's status, flags, measurements and a spectrogram thumbnail.
+// `filePath` must end in .html; there is no PDF output.
+// Progress is reported through 'qc-report-progress' events.
+export async function exportQcReport(filePath: string, jobId?: string) {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  await invoke('export_qc_report', { project: currentProject, filePath, jobId });
+}
+
+// Cuts the takes of a continuous session recording into a file per take,
+// using the .markers.json file written next to it, and saves the project.
+export async function splitSessionRecording(markersPath: string): Promise<Project> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  const updated: Project = await invoke('split_session_recording', {
+    project: currentProject,
+    markersPath,
+  });
+  project.set(updated);
+  sentences.set(updated.sentences);
+  return updated;
+}
+
+// Copies the recorded takes into outputDir with a metadata.csv for
+// localization vendors; columns default to
+// filename,text,character,language,duration. Returns the CSV path.
+export async function exportLocalization(
+  outputDir: string,
+  columns?: LocalizationColumn[],
+  delimiter?: string,
+  jobId?: string
+): Promise<string> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  return await invoke('export_localization', {
+    project: currentProject,
+    outputDir,
+    columns,
+    delimiter,
+    jobId,
+  });
+}
+
+// Copies the recorded takes into outputDir/wavs and writes the manifest of
+// a TTS training toolchain beside them. The speaker defaults to the project
+// name. Returns the paths of the manifest files.
+export async function exportDataset(
+  outputDir: string,
+  format: DatasetFormat,
+  speaker?: string,
+  jobId?: string
+): Promise<string[]> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  return await invoke('export_dataset', {
+    project: currentProject,
+    outputDir,
+    format,
+    speaker,
+    jobId,
+  });
+}
+
+// Reads the sentence, project, recording time and device embedded in a WAV
+// file.
+export async function readAudioMetadata(path: string): Promise<AudioMetadata> {
+  return await invoke('read_audio_metadata', { path });
+}
+
+// Reads the encoding, sample rate, channels, bit depth, length and tags of
+// a WAV, FLAC or MP3 file
+export async function probeAudio(path: string): Promise<AudioProbe> {
+  return await invoke('probe_audio', { path });
+}
+
+// Measures every take of a sentence. `transcripts` maps take file paths to
+// speech recognizer output, adding a word error rate to those takes.
+export async function compareTakesMetrics(
+  sentenceId: number,
+  transcripts?: Record<string, string>
+): Promise<TakeMetrics[]> {
+  const currentProject = get(project);
+  if (!currentProject) {
+    throw new Error('No project loaded');
+  }
+  return await invoke('compare_takes_metrics', {
+    project: currentProject,
+    sentenceId,
+    transcripts,
+  });
+}
+
 export async function playSentence(sentence: Sentence) {
-  const fullPath = await generateFilename(sentence);
+  if (!sentence.audio_file_path) {
+    console.error('Sentence has no take to play:', sentence.id);
+    return;
+  }
   try {
-    const audioData: number[] = await invoke('load_audio_file', { filePath: fullPath });
-    const uint8Array = new Uint8Array(audioData);
-    const blob = new Blob([uint8Array], { type: 'audio/wav' });
-    const audio = new Audio();
-    audio.src = URL.createObjectURL(blob);
-    audio.play();
+    // Streamed in ranges through the audio scheme rather than copied
+    // across IPC whole
+    const audio = new Audio(convertFileSrc(sentence.audio_file_path, 'audio'));
+    await audio.play();
   } catch (error) {
     console.error('Error playing audio:', error);
   }
@@ -181,7 +629,12 @@ export async function handleFileImport() {
   try {
     const selected = await open({
       multiple: false,
-      filters: [{ name: 'Text Files', extensions: ['txt', 'csv', 'tsv'] }],
+      filters: [
+        {
+          name: 'Prompt Files',
+          extensions: ['txt', 'csv', 'tsv', 'xlsx', 'xls', 'ods', 'json', 'fountain'],
+        },
+      ],
     });
 
     if (Array.isArray(selected) || !selected) {
@@ -194,10 +647,17 @@ export async function handleFileImport() {
       return;
     }
 
-    const newSentences: Sentence[] = await invoke('import_sentences', {
-      filePath: selected,
-      projectDir: currentProject.metadata.directory,
-    });
+    const { sentences: newSentences, duplicates }: ImportedSentences = await invoke(
+      'import_sentences',
+      {
+        filePath: selected,
+        projectDir: currentProject.metadata.directory,
+      }
+    );
+
+    for (const duplicate of duplicates) {
+      console.warn(`Duplicate sentence "${duplicate.text}" on rows ${duplicate.ids.join(', ')}`);
+    }
 
     sentences.update((currentSentences) => {
       const maxId = Math.max(0, ...currentSentences.map((s) => s.id));
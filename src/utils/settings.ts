@@ -0,0 +1,34 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { AppSettings, SessionAnalytics } from '../types';
+
+export async function getSettings(): Promise<AppSettings> {
+  return await invoke('get_settings');
+}
+
+export async function updateSettings(newSettings: AppSettings): Promise<AppSettings> {
+  return await invoke('update_settings', { newSettings });
+}
+
+// Only sessions recorded with the session_analytics setting on are counted
+export async function getSessionAnalytics(
+  projectDirectory?: string,
+  since?: string
+): Promise<SessionAnalytics> {
+  return await invoke('get_session_analytics', { projectDirectory, since });
+}
+
+export async function clearSessionAnalytics(): Promise<void> {
+  await invoke('clear_session_analytics');
+}
+
+// Takes a level such as 'debug' or RUST_LOG-style directives; lasts until
+// the app exits. Warnings and errors also arrive as 'log-event'.
+export async function setLogLevel(level: string): Promise<void> {
+  await invoke('set_log_level', { level });
+}
+
+// Turns 'log-event' on or off until the app exits. Forwarded records are
+// limited to a few a second either way.
+export async function setLogForwarding(enabled: boolean): Promise<void> {
+  await invoke('set_log_forwarding', { enabled });
+}